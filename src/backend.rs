@@ -0,0 +1,283 @@
+// Where the UI draws: the terminal, or a grid of cells to check what it drew
+
+use crate::shared::{self, Style};
+use crossterm::style::{Attribute, Color};
+use std::{
+    cell::RefCell,
+    fmt,
+    fs::File,
+    io::{self, Write},
+    rc::Rc,
+};
+use unicode_width::UnicodeWidthChar;
+use vte::{Params, Parser, Perform};
+
+/// What [`crate::writer::UiContext`] writes its escape sequences to
+pub trait Backend: Write {
+    /// (columns, rows) to draw in
+    fn size(&self) -> io::Result<(u16, u16)>;
+
+    /// whether the programs rp runs, the shell and the editor, and Ctrl-Z can take over the
+    /// terminal drawn to, none of them run otherwise
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+/// The terminal rp runs on, even when stdout is redirected
+pub struct Terminal(File);
+
+impl Terminal {
+    #[cfg(unix)]
+    pub fn open() -> io::Result<Self> {
+        File::create("/dev/tty").map(Self)
+    }
+
+    #[cfg(windows)]
+    pub fn open() -> io::Result<Self> {
+        File::create("CON:").map(Self)
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Backend for Terminal {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        crossterm::terminal::size()
+    }
+
+    fn is_terminal(&self) -> bool {
+        true
+    }
+}
+
+/// A char of the screen with its colors, the right half of a wide char is `'\0'`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub style: Style,
+}
+
+impl Cell {
+    fn blank(background: Color) -> Self {
+        Self {
+            ch: ' ',
+            style: Style {
+                background,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+/// A screen of a fixed size without a terminal, the escape sequences written to it are applied
+/// to its cells like a terminal would.
+///
+/// Clones share the cells, one is given to [`crate::writer::UiContext::headless`] and the other
+/// one tells what it drew:
+///
+/// ```
+/// use rust_pager::backend::Headless;
+/// use std::io::Write;
+///
+/// let mut screen = Headless::new(20, 2);
+/// write!(screen, "\x1b[2;1Hsecond\x1b[1;3H\x1b[7mfirst")?;
+/// assert_eq!(screen.text(), "  first\nsecond");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Headless {
+    grid: Rc<RefCell<Grid>>,
+    parser: Rc<RefCell<Parser>>,
+}
+
+impl Headless {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            grid: Rc::new(RefCell::new(Grid::new(width as usize, height as usize))),
+            parser: Rc::new(RefCell::new(Parser::new())),
+        }
+    }
+
+    /// blank cells of the new size, a terminal resized keeps its cells but the UI redraws them
+    pub fn resize(&mut self, width: u16, height: u16) {
+        *self.grid.borrow_mut() = Grid::new(width as usize, height as usize);
+    }
+
+    /// the cell at column `x` of row `y`, both from 0
+    pub fn cell(&self, x: usize, y: usize) -> Cell {
+        let grid = self.grid.borrow();
+        grid.cells[y * grid.width + x]
+    }
+
+    /// the chars of row `y`, without the spaces ending it
+    pub fn row(&self, y: usize) -> String {
+        let grid = self.grid.borrow();
+        let cells = &grid.cells[y * grid.width..(y + 1) * grid.width];
+        let row = cells.iter().map(|cell| cell.ch).filter(|ch| *ch != '\0');
+        row.collect::<String>().trim_end().to_string()
+    }
+
+    /// every row, like [`Self::row`], without the empty rows ending the screen
+    pub fn text(&self) -> String {
+        let height = self.grid.borrow().height;
+        let rows = (0..height).map(|y| self.row(y)).collect::<Vec<_>>();
+        rows.join("\n").trim_end().to_string()
+    }
+
+    /// columns of row `y` drawn with `attribute`, like the search matches in reverse video
+    pub fn with_attribute(&self, y: usize, attribute: Attribute) -> Vec<usize> {
+        let width = self.grid.borrow().width;
+        (0..width)
+            .filter(|x| self.cell(*x, y).style.attribute.has(attribute))
+            .collect()
+    }
+
+    /// (column, row) the next char would be written at
+    pub fn cursor(&self) -> (usize, usize) {
+        let grid = self.grid.borrow();
+        (grid.x, grid.y)
+    }
+}
+
+impl fmt::Debug for Headless {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text())
+    }
+}
+
+impl Write for Headless {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (mut parser, mut grid) = (self.parser.borrow_mut(), self.grid.borrow_mut());
+        for b in buf {
+            parser.advance(&mut *grid, *b);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend for Headless {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        let grid = self.grid.borrow();
+        Ok((grid.width as u16, grid.height as u16))
+    }
+}
+
+struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    x: usize,
+    y: usize,
+    style: Style,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::blank(Color::Reset); width * height],
+            x: 0,
+            y: 0,
+            style: Style::default(),
+        }
+    }
+
+    // cells `from..to` of the screen, in order of the rows
+    fn clear(&mut self, from: usize, to: usize) {
+        let blank = Cell::blank(self.style.background);
+        let to = to.min(self.cells.len());
+        self.cells[from.min(to)..to].fill(blank);
+    }
+
+    fn line_feed(&mut self) {
+        if self.y + 1 < self.height {
+            self.y += 1;
+        } else if self.height > 0 {
+            self.cells.drain(..self.width);
+            let blank = Cell::blank(self.style.background);
+            self.cells.resize(self.width * self.height, blank);
+        }
+    }
+}
+
+impl Perform for Grid {
+    fn print(&mut self, ch: char) {
+        let width = ch.width().unwrap_or(0);
+        // line wrapping is off, like the UI sets it
+        if width == 0 || self.x + width > self.width || self.y >= self.height {
+            return;
+        }
+
+        let idx = self.y * self.width + self.x;
+        self.cells[idx] = Cell {
+            ch,
+            style: self.style,
+        };
+        if width == 2 {
+            self.cells[idx + 1] = Cell {
+                ch: '\0',
+                style: self.style,
+            };
+        }
+        self.x += width;
+    }
+
+    fn execute(&mut self, b: u8) {
+        match b {
+            b'\r' => self.x = 0,
+            b'\n' => self.line_feed(),
+            8 => self.x = self.x.saturating_sub(1),
+            b'\t' => self.x = ((self.x / 8 + 1) * 8).min(self.width.saturating_sub(1)),
+            _ => {}
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        // the private modes, like the alternate screen, don't change the cells
+        if !intermediates.is_empty() {
+            return;
+        }
+
+        let arg = |idx: usize| params.iter().nth(idx).map_or(0, |param| param[0] as usize);
+        let row = self.y * self.width;
+        let cursor = row + self.x;
+
+        match action {
+            'H' | 'f' => {
+                // a screen without rows or columns keeps the cursor at 0
+                self.y = arg(0).max(1).min(self.height).saturating_sub(1);
+                self.x = arg(1).max(1).min(self.width).saturating_sub(1);
+            }
+            'K' => match arg(0) {
+                0 => self.clear(cursor, row + self.width),
+                1 => self.clear(row, cursor + 1),
+                _ => self.clear(row, row + self.width),
+            },
+            'J' => match arg(0) {
+                0 => self.clear(cursor, self.cells.len()),
+                1 => self.clear(0, cursor + 1),
+                _ => self.clear(0, self.cells.len()),
+            },
+            'm' => {
+                self.style.sgr(params);
+                // the attributes turned off aren't kept, only the ones drawn
+                self.style.attribute = shared::drawn_attributes(self.style.attribute);
+            }
+            _ => {}
+        }
+    }
+}
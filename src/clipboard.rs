@@ -0,0 +1,25 @@
+// Copying to the clipboard of the terminal, which works over ssh too
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// OSC 52 sequence setting the clipboard to `text`, for terminals that allow it
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+        for i in 0..4 {
+            // padded after the last byte
+            if i > chunk.len() {
+                out.push('=');
+            } else {
+                out.push(BASE64[(n >> (18 - 6 * i)) & 63] as char);
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,277 @@
+// Colors of the input fitted to what the terminal shows
+
+use crossterm::style::Color;
+
+/// Colors a terminal can show
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorDepth {
+    /// 24-bit colors
+    True,
+    /// the 256 color palette
+    Ansi256,
+    /// the 16 colors of the theme of the terminal
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// `True` when `COLORTERM` says so, then `Ansi256` for a `TERM` with 256 colors
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        // the Windows console takes them all
+        if colorterm == "truecolor" || colorterm == "24bit" || cfg!(windows) {
+            Self::True
+        } else if term.contains("256") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+}
+
+/// xterm's values of the 16 colors, the terminal may show others
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// levels of each channel in the 6x6x6 cube of the 256 color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// `color` as close as `depth` gets to it
+pub fn fit(color: Color, depth: ColorDepth) -> Color {
+    match (color, depth) {
+        (_, ColorDepth::True) => color,
+        (Color::Rgb { r, g, b }, ColorDepth::Ansi256) => Color::AnsiValue(to_256(r, g, b)),
+        (Color::Rgb { r, g, b }, ColorDepth::Ansi16) => ansi16(nearest_16(r, g, b)),
+        (Color::AnsiValue(n), ColorDepth::Ansi16) if n >= 16 => {
+            let (r, g, b) = rgb_of_256(n);
+            ansi16(nearest_16(r, g, b))
+        }
+        _ => color,
+    }
+}
+
+// the closer of the nearest cube color and the nearest grey
+fn to_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| {
+        (0..CUBE.len())
+            .min_by_key(|i| (CUBE[*i] as i32 - v as i32).abs())
+            .unwrap_or(0)
+    };
+    let (lr, lg, lb) = (level(r), level(g), level(b));
+    let cube = 16 + 36 * lr + 6 * lg + lb;
+
+    let average = (r as usize + g as usize + b as usize) / 3;
+    let grey = (average.saturating_sub(3) / 10).min(23);
+    let grey_value = (8 + 10 * grey) as u8;
+
+    let cube_rgb = (CUBE[lr], CUBE[lg], CUBE[lb]);
+    let grey_rgb = (grey_value, grey_value, grey_value);
+    if distance((r, g, b), grey_rgb) < distance((r, g, b), cube_rgb) {
+        (232 + grey) as u8
+    } else {
+        cube as u8
+    }
+}
+
+fn rgb_of_256(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => ANSI16[n as usize],
+        16..=231 => {
+            let n = n as usize - 16;
+            (CUBE[n / 36], CUBE[n / 6 % 6], CUBE[n % 6])
+        }
+        _ => {
+            let v = 8 + 10 * (n - 232);
+            (v, v, v)
+        }
+    }
+}
+
+fn nearest_16(r: u8, g: u8, b: u8) -> usize {
+    (0..ANSI16.len())
+        .min_by_key(|i| distance((r, g, b), ANSI16[*i]))
+        .unwrap_or(0)
+}
+
+// squared, weighted a bit like the eye does
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    2 * d(a.0, b.0) + 4 * d(a.1, b.1) + 3 * d(a.2, b.2)
+}
+
+fn ansi16(n: usize) -> Color {
+    [
+        Color::Black,
+        Color::DarkRed,
+        Color::DarkGreen,
+        Color::DarkYellow,
+        Color::DarkBlue,
+        Color::DarkMagenta,
+        Color::DarkCyan,
+        Color::Grey,
+        Color::DarkGrey,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::White,
+    ][n]
+}
+
+/// What the terminal answered about itself, see [`query_terminal`]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Reply {
+    pub background: Option<(u8, u8, u8)>,
+    /// its device attributes have sixel graphics
+    pub sixel: bool,
+    /// pixels of a row, for the images sized in pixels
+    pub cell_height: Option<u16>,
+}
+
+/// Background color of the terminal, see [`query_terminal`]
+pub fn query_background() -> Option<(u8, u8, u8)> {
+    query_terminal().background
+}
+
+/// Background color of the terminal asked with OSC 11 while in raw mode, with the size of its
+/// cells and its device attributes.
+///
+/// The device attributes query comes last, every terminal answers that one, so those
+/// ignoring the others don't make it wait for the whole timeout
+pub fn query_terminal() -> Reply {
+    match ask() {
+        Some(reply) => Reply {
+            background: parse_background(&reply),
+            sixel: parse_sixel(&reply),
+            cell_height: parse_cell_height(&reply),
+        },
+        None => Reply::default(),
+    }
+}
+
+#[cfg(unix)]
+fn ask() -> Option<String> {
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::time::{Duration, Instant};
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    let mut tty = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    tty.write_all(b"\x1b]11;?\x07\x1b[16t\x1b[c").ok()?;
+    tty.flush().ok()?;
+
+    let deadline = Instant::now() + TIMEOUT;
+    let mut reply = Vec::new();
+    let mut buf = [0; 64];
+    // the device attributes end with `c`
+    while !reply.ends_with(b"c") {
+        let left = deadline.checked_duration_since(Instant::now())?;
+        let mut fds = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut fds, 1, left.as_millis() as libc::c_int) } <= 0 {
+            return None;
+        }
+        let len = tty.read(&mut buf).ok()?;
+        reply.extend_from_slice(&buf[..len]);
+    }
+
+    Some(String::from_utf8_lossy(&reply).into_owned())
+}
+
+#[cfg(windows)]
+fn ask() -> Option<String> {
+    None
+}
+
+// `rgb:RRRR/GGGG/BBBB` in the reply, each one of 1 to 4 hex digits
+fn parse_background(reply: &str) -> Option<(u8, u8, u8)> {
+    let start = reply.find("]11;rgb:")? + "]11;rgb:".len();
+    let end = reply[start..].find(['\x07', '\x1b'])? + start;
+    let mut channels = reply[start..end].split('/').map(|hex| {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len().clamp(1, 4))) - 1;
+        Some((value * 255 / max) as u8)
+    });
+
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+// `ESC [ ? 62 ; 4 ; ... c`, 4 for sixel
+fn parse_sixel(reply: &str) -> bool {
+    let start = match reply.rfind("\x1b[?") {
+        Some(start) => start + "\x1b[?".len(),
+        None => return false,
+    };
+    let attributes = reply[start..].trim_end_matches('c');
+    let mut attributes = attributes.split(';').skip(1);
+    attributes.any(|attribute| attribute == "4")
+}
+
+// `ESC [ 6 ; height ; width t`, the size of a cell in pixels
+fn parse_cell_height(reply: &str) -> Option<u16> {
+    let start = reply.find("\x1b[6;")? + "\x1b[6;".len();
+    let end = reply[start..].find('t')? + start;
+    reply[start..end].split(';').next()?.parse().ok()
+}
+
+/// a background a dark text reads on
+pub fn is_light((r, g, b): (u8, u8, u8)) -> bool {
+    let luminance = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+    luminance > 128.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backgrounds() {
+        let reply = "\x1b]11;rgb:ffff/8080/0000\x1b\\";
+        assert_eq!(parse_background(reply), Some((255, 128, 0)));
+        let reply = "\x1b]11;rgb:ff/00/80\x07";
+        assert_eq!(parse_background(reply), Some((255, 0, 128)));
+        // after the replies to the other queries
+        let reply = "\x1b[?62;4c\x1b]11;rgb:1e1e/1e1e/1e1e\x1b\\";
+        assert_eq!(parse_background(reply), Some((30, 30, 30)));
+    }
+
+    #[test]
+    fn no_backgrounds() {
+        for reply in [
+            "",
+            "\x1b[?62;4c",
+            "\x1b]11;rgb:ffff/ffff\x1b\\",
+            "\x1b]11;rgb:ffff/zzzz/ffff\x1b\\",
+            // cut before its end
+            "\x1b]11;rgb:ffff/ffff/ffff",
+        ] {
+            assert_eq!(parse_background(reply), None, "{:?}", reply);
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use std::{hash::BuildHasher, ops::Range};
+use unicode_width::UnicodeWidthChar;
+
+/// background of a line only in the first file, or its side of a changed pair
+const REMOVED_BACKGROUND: u8 = 52;
+/// background of a line only in the second file, or its side of a changed pair
+const ADDED_BACKGROUND: u8 = 22;
+
+/// One step from the lines of `a` to the lines of `b`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edit {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// `a` and `b` side by side, like `sdiff`: each line holds a line of `a`, a marker
+/// and the matching line of `b`, the whole line fitting in `columns`.
+///
+/// The marker is ` | ` for a changed line, ` < ` for a line only in `a`,
+/// ` > ` for a line only in `b` and ` │ ` for a line in both.
+pub fn side_by_side(a: &[u8], b: &[u8], columns: usize) -> Vec<u8> {
+    let a = split_lines(a);
+    let b = split_lines(b);
+    let half = columns.saturating_sub(3) / 2;
+    let mut out = Vec::with_capacity((a.len().max(b.len()) + 1) * (half * 2 + 16));
+
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    // a last pair past the ends flushes the changes before it
+    let end = Edit::Same(a.len(), b.len());
+    for edit in diff_lines(&a, &b).into_iter().chain(std::iter::once(end)) {
+        match edit {
+            Edit::Removed(i) => removed.push(i),
+            Edit::Added(j) => added.push(j),
+            Edit::Same(i, j) => {
+                // removed and added lines in a row are changes of one another
+                for k in 0..removed.len().max(added.len()) {
+                    let left = removed.get(k).map(|i| a[*i].as_str());
+                    let right = added.get(k).map(|j| b[*j].as_str());
+                    let marker = match (left, right) {
+                        (Some(_), Some(_)) => " | ",
+                        (Some(_), None) => " < ",
+                        _ => " > ",
+                    };
+                    push_side(&mut out, left, half, REMOVED_BACKGROUND);
+                    out.extend_from_slice(marker.as_bytes());
+                    push_side(&mut out, right, half, ADDED_BACKGROUND);
+                    out.push(b'\n');
+                }
+                removed.clear();
+                added.clear();
+
+                if i < a.len() && j < b.len() {
+                    push_side(&mut out, Some(&a[i]), half, 0);
+                    out.extend_from_slice(" │ ".as_bytes());
+                    push_side(&mut out, Some(&b[j]), half, 0);
+                    out.push(b'\n');
+                }
+            }
+        }
+    }
+
+    out
+}
+
+// lines as shown, tabs expanded and other control chars left out
+fn split_lines(data: &[u8]) -> Vec<String> {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    if data.is_empty() {
+        return Vec::new();
+    }
+
+    data.split(|b| *b == b'\n')
+        .map(|line| {
+            let mut text = String::with_capacity(line.len());
+            let mut column = 0;
+            for ch in String::from_utf8_lossy(line).chars() {
+                match ch {
+                    '\t' => {
+                        let spaces = 8 - column % 8;
+                        text.extend(std::iter::repeat_n(' ', spaces));
+                        column += spaces;
+                    }
+                    _ if ch.is_control() => {}
+                    _ => {
+                        text.push(ch);
+                        column += ch.width().unwrap_or(0);
+                    }
+                }
+            }
+            text
+        })
+        .collect()
+}
+
+// `text` cut or padded to `width` columns, on a colored background unless 0
+fn push_side(out: &mut Vec<u8>, text: Option<&str>, width: usize, background: u8) {
+    let text = match text {
+        Some(text) => text,
+        None => return out.extend(std::iter::repeat_n(b' ', width)),
+    };
+
+    if background != 0 {
+        out.extend_from_slice(format!("\x1b[48;5;{}m", background).as_bytes());
+    }
+    let mut used = 0;
+    for ch in text.chars() {
+        let w = ch.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        let mut buf = [0; 4];
+        out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+    }
+    out.extend(std::iter::repeat_n(b' ', width - used));
+    if background != 0 {
+        out.extend_from_slice(b"\x1b[49m");
+    }
+}
+
+/// Shortest edit script from `a` to `b`, with the O(ND) algorithm of Myers in its linear
+/// space version: the middle of the path is found from both ends, then each half the same way
+pub fn diff_lines<T: AsRef<str>>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let hasher = ahash::RandomState::new();
+    let hashes = |lines: &[T]| {
+        lines
+            .iter()
+            .map(|l| hasher.hash_one(l.as_ref()))
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (hashes(a), hashes(b));
+
+    let mut v = Diagonals::new(a.len() + b.len());
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    conquer(&a, 0..a.len(), &b, 0..b.len(), &mut v, &mut edits);
+    edits
+}
+
+/// furthest x on each diagonal k = x - y of the paths from the start and from the end,
+/// reused by each part of the path
+struct Diagonals {
+    forward: Vec<usize>,
+    backward: Vec<usize>,
+    offset: isize,
+}
+
+impl Diagonals {
+    fn new(len: usize) -> Self {
+        let max = len / 2 + 2;
+        Self {
+            forward: vec![0; 2 * max + 1],
+            backward: vec![0; 2 * max + 1],
+            offset: max as isize,
+        }
+    }
+
+    fn at(&self, k: isize) -> usize {
+        (k + self.offset) as usize
+    }
+}
+
+// the edits of `a[a_range]` to `b[b_range]`, in order
+fn conquer(
+    a: &[u64],
+    mut a_range: Range<usize>,
+    b: &[u64],
+    mut b_range: Range<usize>,
+    v: &mut Diagonals,
+    edits: &mut Vec<Edit>,
+) {
+    while !a_range.is_empty() && !b_range.is_empty() && a[a_range.start] == b[b_range.start] {
+        edits.push(Edit::Same(a_range.start, b_range.start));
+        a_range.start += 1;
+        b_range.start += 1;
+    }
+    let mut same_after = 0;
+    while !a_range.is_empty() && !b_range.is_empty() && a[a_range.end - 1] == b[b_range.end - 1] {
+        a_range.end -= 1;
+        b_range.end -= 1;
+        same_after += 1;
+    }
+
+    if a_range.is_empty() {
+        edits.extend(b_range.clone().map(Edit::Added));
+    } else if b_range.is_empty() {
+        edits.extend(a_range.clone().map(Edit::Removed));
+    } else {
+        let (x, y) = middle_snake(a, a_range.clone(), b, b_range.clone(), v);
+        conquer(a, a_range.start..x, b, b_range.start..y, v, edits);
+        conquer(a, x..a_range.end, b, y..b_range.end, v, edits);
+    }
+
+    for i in 0..same_after {
+        edits.push(Edit::Same(a_range.end + i, b_range.end + i));
+    }
+}
+
+// a point of a shortest path, strictly between its ends since the ranges don't start or end
+// with the same line and neither is empty
+fn middle_snake(
+    a: &[u64],
+    a_range: Range<usize>,
+    b: &[u64],
+    b_range: Range<usize>,
+    v: &mut Diagonals,
+) -> (usize, usize) {
+    let (n, m) = (a_range.len(), b_range.len());
+    let delta = n as isize - m as isize;
+    let odd = delta & 1 == 1;
+    let (a, b) = (&a[a_range.clone()], &b[b_range.clone()]);
+    let one = v.at(1);
+    v.forward[one] = 0;
+    v.backward[one] = 0;
+
+    for d in 0..=((n + m).div_ceil(2) as isize) {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && v.forward[v.at(k - 1)] < v.forward[v.at(k + 1)]) {
+                v.forward[v.at(k + 1)]
+            } else {
+                v.forward[v.at(k - 1)] + 1
+            };
+            let (start_x, start_y) = (x, (x as isize - k) as usize);
+            let mut y = start_y;
+            while x < n && y < m && a[x] == b[y] {
+                x += 1;
+                y += 1;
+            }
+            let at = v.at(k);
+            v.forward[at] = x;
+            // the path from the end on the same diagonal, one step shorter
+            if odd && (k - delta).abs() < d && x + v.backward[v.at(delta - k)] >= n {
+                return (a_range.start + start_x, b_range.start + start_y);
+            }
+        }
+
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && v.backward[v.at(k - 1)] < v.backward[v.at(k + 1)])
+            {
+                v.backward[v.at(k + 1)]
+            } else {
+                v.backward[v.at(k - 1)] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            while x < n && y < m && a[n - x - 1] == b[m - y - 1] {
+                x += 1;
+                y += 1;
+            }
+            let at = v.at(k);
+            v.backward[at] = x;
+            // the path from the start on the same diagonal, as long
+            if !odd && (k - delta).abs() <= d && x + v.forward[v.at(delta - k)] >= n {
+                return (a_range.start + n - x, b_range.start + m - y);
+            }
+        }
+    }
+
+    unreachable!("the paths from both ends meet by half of n + m")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `a` edited into `b`, checking each edit is in order and the lines kept are the same
+    fn apply(a: &[&str], b: &[&str]) -> Vec<Edit> {
+        let edits = diff_lines(a, b);
+        let (mut i, mut j) = (0, 0);
+        for edit in &edits {
+            match *edit {
+                Edit::Same(x, y) => {
+                    assert_eq!((x, y, a[x]), (i, j, b[y]));
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Removed(x) => {
+                    assert_eq!(x, i);
+                    i += 1;
+                }
+                Edit::Added(y) => {
+                    assert_eq!(y, j);
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (a.len(), b.len()));
+        edits
+    }
+
+    fn changes(edits: &[Edit]) -> usize {
+        edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Same(..)))
+            .count()
+    }
+
+    #[test]
+    fn an_empty_side_is_all_added_or_removed() {
+        assert_eq!(apply(&[], &["a", "b"]), [Edit::Added(0), Edit::Added(1)]);
+        assert_eq!(apply(&["a"], &[]), [Edit::Removed(0)]);
+        assert_eq!(apply(&[], &[]), []);
+    }
+
+    #[test]
+    fn identical_lines_are_all_the_same() {
+        let lines = ["a", "b", "a"];
+        assert_eq!(
+            apply(&lines, &lines),
+            [Edit::Same(0, 0), Edit::Same(1, 1), Edit::Same(2, 2)]
+        );
+    }
+
+    #[test]
+    fn a_single_insert_or_delete() {
+        let edits = apply(&["a", "c"], &["a", "b", "c"]);
+        assert_eq!(edits, [Edit::Same(0, 0), Edit::Added(1), Edit::Same(1, 2)]);
+        let edits = apply(&["a", "b", "c"], &["a", "c"]);
+        assert_eq!(edits, [Edit::Same(0, 0), Edit::Removed(1), Edit::Same(2, 1)]);
+    }
+
+    #[test]
+    fn edit_scripts_are_the_shortest() {
+        // the example of Myers' paper, D = 5
+        let a = ["a", "b", "c", "a", "b", "b", "a"];
+        let b = ["c", "b", "a", "b", "a", "c"];
+        assert_eq!(changes(&apply(&a, &b)), 5);
+        let a = ["x", "a", "b", "y", "c", "z"];
+        let b = ["a", "q", "b", "c", "r", "s", "z"];
+        assert_eq!(changes(&apply(&a, &b)), 5);
+
+        // against the longest common subsequence, on lines of a few letters
+        let mut seed = 1u32;
+        let mut lines = |len: usize| {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    ["a", "b", "c"][(seed >> 16) as usize % 3]
+                })
+                .collect::<Vec<_>>()
+        };
+        for len in 0..40 {
+            let (a, b) = (lines(len), lines(40 - len));
+            let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in 0..a.len() {
+                for j in 0..b.len() {
+                    lcs[i + 1][j + 1] = match a[i] == b[j] {
+                        true => lcs[i][j] + 1,
+                        false => lcs[i][j + 1].max(lcs[i + 1][j]),
+                    };
+                }
+            }
+            let shortest = a.len() + b.len() - 2 * lcs[a.len()][b.len()];
+            assert_eq!(changes(&apply(&a, &b)), shortest);
+        }
+    }
+
+    #[test]
+    fn changed_lines_are_side_by_side() {
+        let out = side_by_side(b"same
+old
+only a
+", b"same
+new
+", 23);
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "same       │ same      ");
+        assert!(lines[1].contains("old") && lines[1].contains(" | ") && lines[1].contains("new"));
+        assert!(lines[2].contains("only a") && lines[2].contains(" < "));
+    }
+
+    #[test]
+    fn sides_of_empty_files() {
+        assert!(side_by_side(b"", b"", 20).is_empty());
+        let out = String::from_utf8(side_by_side(b"", b"b\n", 21)).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.contains(" > "));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::keys::Keymap;
+use crate::ui_theme::UiTheme;
+use crossterm::style::Color;
+use regex::Regex;
+use std::{io::Write, path::PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    /// keep the colors of the input
+    Colorful,
+    /// attributes only, no colors
+    Plain,
+    /// neither colors nor attributes, only the highlights of rp
+    Mono,
+}
+
+impl Theme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "colorful" => Some(Self::Colorful),
+            "plain" => Some(Self::Plain),
+            "mono" => Some(Self::Mono),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Colorful => "colorful",
+            Self::Plain => "plain",
+            Self::Mono => "mono",
+        }
+    }
+}
+
+/// How a failed action, like a search without a match, is told
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bell {
+    /// flash the prompt line
+    Visual,
+    /// the bell of the terminal
+    Audible,
+    Off,
+}
+
+impl Bell {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "visual" => Some(Self::Visual),
+            "audible" => Some(Self::Audible),
+            "off" => Some(Self::Off),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Visual => "visual",
+            Self::Audible => "audible",
+            Self::Off => "off",
+        }
+    }
+}
+
+/// Lines matching `pattern` get `color` when the input has no colors of its own,
+/// from `level = <color> <pattern>` lines
+#[derive(Clone)]
+pub struct LogLevel {
+    /// as written in the config
+    pub name: String,
+    pub color: Color,
+    pub pattern: Regex,
+}
+
+impl LogLevel {
+    pub fn new(name: &str, pattern: &str) -> Option<Self> {
+        Some(Self {
+            name: name.to_string(),
+            color: parse_color(name)?,
+            // an empty one would match every line
+            pattern: Regex::new(pattern).ok().filter(|_| !pattern.is_empty())?,
+        })
+    }
+}
+
+fn default_levels() -> Vec<LogLevel> {
+    [
+        ("red", r"\b(?:ERROR|FATAL|CRITICAL)\b"),
+        ("yellow", r"\bWARN(?:ING)?\b"),
+        ("green", r"\bINFO\b"),
+        ("grey", r"\b(?:DEBUG|TRACE)\b"),
+    ]
+    .iter()
+    .filter_map(|(name, pattern)| LogLevel::new(name, pattern))
+    .collect()
+}
+
+/// Preferences read from `<config dir>/rp/config`, in `key = value` lines
+#[derive(Clone)]
+pub struct Config {
+    pub mouse: bool,
+    pub wrap: bool,
+    pub theme: Theme,
+    /// colors of what rp draws, picked for the background of the terminal when None
+    pub ui_theme: Option<UiTheme>,
+    pub bell: Bell,
+    /// scroll steps of a held Up/Down key, the next one is used every few repeats
+    pub acceleration: Vec<usize>,
+    /// lines scrolled by a notch of the mouse wheel
+    pub wheel_lines: usize,
+    /// color plain lines by their log level
+    pub log_colors: bool,
+    /// the first matching one applies
+    pub log_levels: Vec<LogLevel>,
+    /// bindings the `bind` lines change
+    pub keymap: Keymap,
+    /// `(keys, action)` of each `bind = <keys> <action>` line, checked once applied
+    pub bindings: Vec<(String, String)>,
+    /// template of the prompt, see `-P`
+    pub prompt: Option<String>,
+    /// lines starting the sections, see `--section`
+    pub section: Option<Regex>,
+    pub scrollbar: bool,
+    pub minimap: bool,
+    pub ruler: bool,
+    pub title: bool,
+    /// underline the urls of the lines, see `--urls`
+    pub urls: bool,
+    /// underline the file positions of the lines, see `--file-links`
+    pub file_links: bool,
+    /// columns of the guides, see `--guides`
+    pub guides: Vec<usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mouse: true,
+            wrap: true,
+            theme: Theme::Colorful,
+            ui_theme: None,
+            bell: Bell::Visual,
+            acceleration: vec![1, 2, 4],
+            wheel_lines: 3,
+            log_colors: false,
+            log_levels: default_levels(),
+            keymap: Keymap::Default,
+            bindings: Vec::new(),
+            prompt: None,
+            section: None,
+            scrollbar: false,
+            minimap: false,
+            ruler: false,
+            title: false,
+            urls: false,
+            file_links: false,
+            guides: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        #[cfg(unix)]
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+        #[cfg(windows)]
+        let dir = std::env::var_os("APPDATA").map(PathBuf::from);
+
+        Some(dir?.join("rp").join("config"))
+    }
+
+    /// returns None when there is no config file yet
+    pub fn load() -> Option<Self> {
+        let text = std::fs::read_to_string(Self::path()?).ok()?;
+        let mut config = Self::default();
+        let mut levels = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => continue,
+            };
+
+            // unknown keys and invalid values are ignored
+            match key {
+                "mouse" => config.mouse = parse_bool(value).unwrap_or(config.mouse),
+                "wrap" => config.wrap = parse_bool(value).unwrap_or(config.wrap),
+                "theme" => config.theme = Theme::from_name(value).unwrap_or(config.theme),
+                "ui_theme" => config.ui_theme = UiTheme::from_name(value),
+                "bell" => config.bell = Bell::from_name(value).unwrap_or(config.bell),
+                "acceleration" => {
+                    if let Some(steps) = parse_steps(value) {
+                        config.acceleration = steps;
+                    }
+                }
+                "wheel_lines" => match value.parse() {
+                    Ok(lines) if lines > 0 => config.wheel_lines = lines,
+                    _ => {}
+                },
+                "log_colors" => config.log_colors = parse_bool(value).unwrap_or(config.log_colors),
+                "level" => {
+                    let (name, pattern) = value.split_once(' ').unwrap_or((value, ""));
+                    levels.extend(LogLevel::new(name, pattern.trim()));
+                }
+                "keymap" => config.keymap = Keymap::from_name(value).unwrap_or(config.keymap),
+                "bind" => {
+                    // the keys of a sequence are separated by spaces, the action is last
+                    let (key, action) = value.rsplit_once(' ').unwrap_or((value, ""));
+                    config
+                        .bindings
+                        .push((key.to_string(), action.trim().to_string()));
+                }
+                "scrollbar" => config.scrollbar = parse_bool(value).unwrap_or(config.scrollbar),
+                "minimap" => config.minimap = parse_bool(value).unwrap_or(config.minimap),
+                "ruler" => config.ruler = parse_bool(value).unwrap_or(config.ruler),
+                "title" => config.title = parse_bool(value).unwrap_or(config.title),
+                "urls" => config.urls = parse_bool(value).unwrap_or(config.urls),
+                "file_links" => config.file_links = parse_bool(value).unwrap_or(config.file_links),
+                "guides" => config.guides = parse_columns(value).unwrap_or_default(),
+                "prompt" => config.prompt = Some(value.to_string()).filter(|v| !v.is_empty()),
+                "section" => config.section = Regex::new(value).ok().filter(|_| !value.is_empty()),
+                _ => {}
+            }
+        }
+
+        // the defaults are replaced, not added to
+        if !levels.is_empty() {
+            config.log_levels = levels;
+        }
+
+        Some(config)
+    }
+
+    /// write every preference, creating the directory if needed
+    pub fn save(&self) -> std::io::Result<PathBuf> {
+        let path = Self::path().ok_or(std::io::ErrorKind::NotFound)?;
+        // never left half written by a crash or a full disk
+        crate::history::write_file(&path, |file| {
+            writeln!(file, "# rp preferences")?;
+            writeln!(file, "mouse = {}", self.mouse)?;
+            writeln!(file, "wrap = {}", self.wrap)?;
+            writeln!(file, "theme = {}", self.theme.name())?;
+            let ui_theme = self.ui_theme.map_or("auto", |theme| theme.name);
+            writeln!(file, "ui_theme = {}", ui_theme)?;
+            writeln!(file, "bell = {}", self.bell.name())?;
+            let steps = self
+                .acceleration
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            writeln!(file, "acceleration = {}", steps.join(", "))?;
+            writeln!(file, "wheel_lines = {}", self.wheel_lines)?;
+            writeln!(file, "log_colors = {}", self.log_colors)?;
+            for level in self.log_levels.iter() {
+                writeln!(file, "level = {} {}", level.name, level.pattern)?;
+            }
+            writeln!(file, "keymap = {}", self.keymap.name())?;
+            for (key, action) in self.bindings.iter() {
+                writeln!(file, "bind = {} {}", key, action)?;
+            }
+            writeln!(file, "scrollbar = {}", self.scrollbar)?;
+            writeln!(file, "minimap = {}", self.minimap)?;
+            writeln!(file, "ruler = {}", self.ruler)?;
+            writeln!(file, "title = {}", self.title)?;
+            writeln!(file, "urls = {}", self.urls)?;
+            writeln!(file, "file_links = {}", self.file_links)?;
+            if !self.guides.is_empty() {
+                let columns = self
+                    .guides
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>();
+                writeln!(file, "guides = {}", columns.join(", "))?;
+            }
+            if let Some(prompt) = &self.prompt {
+                writeln!(file, "prompt = {}", prompt)?;
+            }
+            if let Some(section) = &self.section {
+                writeln!(file, "section = {}", section)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(path)
+    }
+}
+
+// a color name, `grey` for the dark one, or a number of the 256 color palette
+fn parse_color(name: &str) -> Option<Color> {
+    let color = match name {
+        "black" => Color::Black,
+        "red" => Color::DarkRed,
+        "green" => Color::DarkGreen,
+        "yellow" => Color::DarkYellow,
+        "blue" => Color::DarkBlue,
+        "magenta" => Color::DarkMagenta,
+        "cyan" => Color::DarkCyan,
+        "white" => Color::Grey,
+        "grey" | "gray" => Color::DarkGrey,
+        _ => Color::AnsiValue(name.parse().ok()?),
+    };
+    Some(color)
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// columns like `80, 120`, from 1
+pub fn parse_columns(value: &str) -> Option<Vec<usize>> {
+    parse_steps(value)
+}
+
+// `1, 2, 4`, every step must be positive
+fn parse_steps(value: &str) -> Option<Vec<usize>> {
+    let steps = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().ok().filter(|n| *n > 0))
+        .collect::<Option<Vec<usize>>>()?;
+
+    if steps.is_empty() {
+        None
+    } else {
+        Some(steps)
+    }
+}
@@ -0,0 +1,277 @@
+// Conversions of numeric tokens (hex numbers, epoch timestamps, byte sizes)
+
+/// describe every conversion that makes sense for the token
+pub fn describe(token: &str) -> Option<String> {
+    let mut parts = Vec::new();
+
+    if let Some(n) = parse_hex(token) {
+        parts.push(format!("{}", n));
+        parts.push(human_size(n));
+    } else if let Ok(n) = token.parse::<u64>() {
+        parts.push(format!("{:#x}", n));
+        if let Some(time) = epoch_time(n) {
+            parts.push(time);
+        }
+        parts.push(human_size(n));
+    } else if let Some(n) = parse_size(token) {
+        parts.push(format!("{} bytes", n));
+        parts.push(format!("{:#x}", n));
+    } else {
+        return None;
+    }
+
+    Some(format!("{}: {}", token, parts.join(" | ")))
+}
+
+/// find the token containing `idx`-th char of the text
+pub fn token_at(text: &str, idx: usize) -> Option<String> {
+    let is_token_char = |c: char| c.is_ascii_alphanumeric() || c == '.';
+    let chars = text.chars().collect::<Vec<_>>();
+
+    if !chars.get(idx).copied().is_some_and(is_token_char) {
+        return None;
+    }
+
+    let start = chars[..idx]
+        .iter()
+        .rposition(|c| !is_token_char(*c))
+        .map_or(0, |i| i + 1);
+    let end = chars[idx..]
+        .iter()
+        .position(|c| !is_token_char(*c))
+        .map_or(chars.len(), |i| i + idx);
+
+    Some(chars[start..end].iter().collect())
+}
+
+fn parse_hex(token: &str) -> Option<u64> {
+    let digits = if let Some(digits) = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        digits
+    } else if let Some(digits) = token
+        .strip_suffix('h')
+        .filter(|digits| digits.starts_with(|c: char| c.is_ascii_digit()))
+    {
+        // like `0FFh`, a word ending in `h` isn't one
+        digits
+    } else if token.chars().any(|c| c.is_ascii_digit())
+        && token.chars().any(|c| c.is_ascii_alphabetic())
+    {
+        // plain hex has both digits and letters, otherwise it's decimal or a word like `add`
+        token
+    } else {
+        return None;
+    };
+
+    u64::from_str_radix(digits, 16).ok()
+}
+
+/// parse sizes like `4KiB`, `10M` or `3GB`
+pub fn parse_size(token: &str) -> Option<u64> {
+    const UNITS: &[(&str, u64)] = &[
+        ("KiB", 1 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("TiB", 1 << 40),
+        ("KB", 1_000),
+        ("MB", 1_000_000),
+        ("GB", 1_000_000_000),
+        ("TB", 1_000_000_000_000),
+        ("K", 1 << 10),
+        ("M", 1 << 20),
+        ("G", 1 << 30),
+        ("T", 1 << 40),
+        ("B", 1),
+    ];
+
+    UNITS.iter().find_map(|(unit, scale)| {
+        let n = token.strip_suffix(unit)?.parse::<f64>().ok()?;
+        Some((n * *scale as f64) as u64)
+    })
+}
+
+/// `n` bytes in the largest binary unit, like `1.5 MiB` or `4.0 GiB`
+pub fn human_size(n: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    let mut size = n as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", n)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// guess the unit from the magnitude, only years 2001..2286 are considered
+fn epoch_time(n: u64) -> Option<String> {
+    let (secs, nanos, precision) = match n {
+        1_000_000_000..=9_999_999_999 => (n, 0, 0),
+        1_000_000_000_000..=9_999_999_999_999 => (n / 1_000, (n % 1_000) * 1_000_000, 3),
+        1_000_000_000_000_000..=9_999_999_999_999_999 => (n / 1_000_000, (n % 1_000_000) * 1_000, 6),
+        1_000_000_000_000_000_000..=9_999_999_999_999_999_999 => {
+            (n / 1_000_000_000, n % 1_000_000_000, 9)
+        }
+        _ => return None,
+    };
+
+    let (date, offset) = local_time(secs as i64)?;
+    let frac = match precision {
+        0 => String::new(),
+        p => format!(".{:09}", nanos)[..p + 1].to_string(),
+    };
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{}{:02}:{:02}",
+        date.0,
+        date.1,
+        date.2,
+        date.3,
+        date.4,
+        date.5,
+        frac,
+        sign,
+        offset / 3600,
+        offset % 3600 / 60
+    ))
+}
+
+type DateTime = (i64, u32, u32, u32, u32, u32);
+
+#[cfg(unix)]
+fn local_time(secs: i64) -> Option<(DateTime, i64)> {
+    let time = secs as libc::time_t;
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return None;
+    }
+
+    Some((
+        (
+            tm.tm_year as i64 + 1900,
+            tm.tm_mon as u32 + 1,
+            tm.tm_mday as u32,
+            tm.tm_hour as u32,
+            tm.tm_min as u32,
+            tm.tm_sec as u32,
+        ),
+        tm.tm_gmtoff as i64,
+    ))
+}
+
+#[cfg(windows)]
+fn local_time(secs: i64) -> Option<(DateTime, i64)> {
+    let time = secs as libc::time_t;
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+
+    if unsafe { libc::localtime_s(&mut tm, &time) } != 0 {
+        return None;
+    }
+
+    let date = (
+        tm.tm_year as i64 + 1900,
+        tm.tm_mon as u32 + 1,
+        tm.tm_mday as u32,
+        tm.tm_hour as u32,
+        tm.tm_min as u32,
+        tm.tm_sec as u32,
+    );
+    // the tm of Windows has no offset, it's how far the local time is from UTC
+    let local = days_from_civil(date.0, date.1, date.2) * 86400
+        + (date.3 * 3600 + date.4 * 60 + date.5) as i64;
+    Some((date, local - secs))
+}
+
+// days since 1970-01-01, from http://howardhinnant.github.io/date_algorithms.html
+#[cfg(windows)]
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_numbers() {
+        let cases = [
+            ("0x1f", Some(31)),
+            ("0XFF", Some(255)),
+            ("0FFh", Some(255)),
+            ("1e", Some(30)),
+            ("deadbeef1", Some(0xdeadbeef1)),
+            ("add", None),
+            ("face", None),
+            ("each", None),
+            ("1234", None),
+            ("0xg", None),
+            ("", None),
+        ];
+        for (token, n) in cases {
+            assert_eq!(parse_hex(token), n, "{:?}", token);
+        }
+    }
+
+    // the date depends on the local time zone, not the precision
+    #[test]
+    fn epoch_times_by_magnitude() {
+        let cases = [
+            (999_999_999, None),
+            (1_700_000_000, Some(0)),
+            (1_700_000_000_123, Some(4)),
+            (1_700_000_000_123_456, Some(7)),
+            (1_700_000_000_123_456_789, Some(10)),
+            (10_000_000_000, None),
+            (99_999_999_999_999, None),
+        ];
+        for (n, frac) in cases {
+            let time = epoch_time(n);
+            assert_eq!(time.is_some(), frac.is_some(), "{}", n);
+            if let (Some(time), Some(frac)) = (time, frac) {
+                // `YYYY-MM-DDThh:mm:ss`, the fraction, then the offset
+                assert_eq!(time.len(), 19 + frac + 6, "{}", time);
+                assert!(time.starts_with("2023-11-1"), "{}", time);
+            }
+        }
+    }
+
+    #[test]
+    fn human_sizes() {
+        let cases = [
+            (0, "0 B"),
+            (1023, "1023 B"),
+            (1024, "1.0 KiB"),
+            (1536, "1.5 KiB"),
+            (5 << 30, "5.0 GiB"),
+            (3 << 50, "3.0 PiB"),
+            (u64::MAX, "16.0 EiB"),
+        ];
+        for (n, size) in cases {
+            assert_eq!(human_size(n), size);
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("4KiB"), Some(4096));
+        assert_eq!(parse_size("1.5M"), Some(3 << 19));
+        assert_eq!(parse_size("3GB"), Some(3_000_000_000));
+        assert_eq!(parse_size("12"), None);
+    }
+}
@@ -0,0 +1,187 @@
+use ruzstd::decoding::{errors::FrameDecoderError, FrameDecoder, StreamingDecoder};
+use std::io::{self, BufRead, BufReader, Read};
+
+/// Compressed formats recognized by their first bytes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Format {
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Self::Xz)
+        } else if magic.starts_with(b"BZh") {
+            Some(Self::Bzip2)
+        } else {
+            None
+        }
+    }
+}
+
+/// `input` decompressed if its first bytes tell a known format, as is otherwise
+pub fn auto<'a>(input: impl Read + Send + 'a) -> io::Result<Box<dyn Read + Send + 'a>> {
+    let mut input = BufReader::new(input);
+    // a short first read is enough for every magic number
+    let format = Format::detect(input.fill_buf()?);
+
+    Ok(match format {
+        Some(format) => decoder(format, input)?,
+        None => Box::new(input),
+    })
+}
+
+pub fn decoder<'a>(
+    format: Format,
+    input: impl BufRead + Send + 'a,
+) -> io::Result<Box<dyn Read + Send + 'a>> {
+    // concatenated streams, like from `cat a.gz b.gz`, are read to the end
+    Ok(match format {
+        Format::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(input)),
+        Format::Zstd => Box::new(ZstdFrames::new(input)?),
+        Format::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(input)),
+        Format::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(input)),
+    })
+}
+
+/// zstd decoder going on with the next frame at the end of each one
+struct ZstdFrames<R: BufRead> {
+    decoder: Option<StreamingDecoder<R, FrameDecoder>>,
+}
+
+impl<R: BufRead> ZstdFrames<R> {
+    fn new(input: R) -> io::Result<Self> {
+        let decoder = next_frame(input, FrameDecoder::new())?;
+        Ok(Self { decoder })
+    }
+}
+
+impl<R: BufRead> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let decoder = match &mut self.decoder {
+                Some(decoder) => decoder,
+                None => return Ok(0),
+            };
+
+            let len = decoder.read(buf)?;
+            if len > 0 || buf.is_empty() {
+                return Ok(len);
+            }
+
+            let (input, frame_decoder) = self.decoder.take().unwrap().into_parts();
+            self.decoder = next_frame(input, frame_decoder)?;
+        }
+    }
+}
+
+// None at the end of the input, skippable frames are skipped
+fn next_frame<R: BufRead>(
+    mut input: R,
+    frame_decoder: FrameDecoder,
+) -> io::Result<Option<StreamingDecoder<R, FrameDecoder>>> {
+    loop {
+        let header = input.fill_buf()?;
+        if header.is_empty() {
+            return Ok(None);
+        }
+
+        // magic 0x184d2a5? then the length of the frame, both little endian
+        match header {
+            [0x50..=0x5f, 0x2a, 0x4d, 0x18, a, b, c, d, ..] => {
+                let length = u32::from_le_bytes([*a, *b, *c, *d]) as u64;
+                input.consume(8);
+                io::copy(&mut (&mut input).take(length), &mut io::sink())?;
+            }
+            _ => {
+                return StreamingDecoder::new_with_decoder(input, frame_decoder)
+                    .map(Some)
+                    .map_err(invalid_data)
+            }
+        }
+    }
+}
+
+fn invalid_data(e: FrameDecoderError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn formats_by_their_magic_bytes() {
+        let cases: [(&[u8], Option<Format>); 9] = [
+            (&[0x1f, 0x8b, 0x08, 0x00], Some(Format::Gzip)),
+            (&[0x28, 0xb5, 0x2f, 0xfd, 0x24], Some(Format::Zstd)),
+            (&[0xfd, b'7', b'z', b'X', b'Z', 0x00, 0x00], Some(Format::Xz)),
+            (b"BZh91AY&SY", Some(Format::Bzip2)),
+            // cut short before the end of the magic
+            (&[0x1f], None),
+            (&[0x28, 0xb5, 0x2f], None),
+            (&[0xfd, b'7', b'z', b'X', b'Z'], None),
+            (b"plain text", None),
+            (b"", None),
+        ];
+        for (magic, format) in cases {
+            assert_eq!(Format::detect(magic), format, "{:?}", magic);
+        }
+    }
+
+    fn read(input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        auto(input).unwrap().read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn each_format_is_decompressed() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(b"gzip\n").unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 1);
+        xz.write_all(b"xz\n").unwrap();
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        bzip2.write_all(b"bzip2\n").unwrap();
+
+        assert_eq!(read(&gzip.finish().unwrap()), b"gzip\n");
+        assert_eq!(read(&xz.finish().unwrap()), b"xz\n");
+        assert_eq!(read(&bzip2.finish().unwrap()), b"bzip2\n");
+        assert_eq!(read(b"plain\n"), b"plain\n");
+    }
+
+    // a frame holding `text` in a single raw block
+    fn zstd_frame(text: &[u8]) -> Vec<u8> {
+        let block = (text.len() as u32) << 3 | 1;
+        let mut frame = vec![0x28, 0xb5, 0x2f, 0xfd, 0x20, text.len() as u8];
+        frame.extend_from_slice(&block.to_le_bytes()[..3]);
+        frame.extend_from_slice(text);
+        frame
+    }
+
+    #[test]
+    fn zstd_frames_are_read_past_the_skippable_ones() {
+        let skippable = [0x50, 0x2a, 0x4d, 0x18, 2, 0, 0, 0, b'?', b'?'];
+        let input = [zstd_frame(b"one\n"), skippable.to_vec(), zstd_frame(b"two\n")].concat();
+        assert_eq!(read(&input), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn concatenated_streams_are_read_to_the_end() {
+        let stream = |text: &[u8]| {
+            let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+            gzip.write_all(text).unwrap();
+            gzip.finish().unwrap()
+        };
+        let input = [stream(b"one\n"), stream(b"two\n")].concat();
+        assert_eq!(read(&input), b"one\ntwo\n");
+    }
+}
@@ -0,0 +1,128 @@
+/// Kind of a line of a unified diff
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiffLine {
+    /// not part of a diff, like the commit messages of `git log -p`
+    Other,
+    /// the first header of a file, `diff --git` or the `---` of `diff -u`
+    File,
+    /// `index`, `---`, `+++` and the like after it
+    Header,
+    /// `@@ -1,2 +1,3 @@`
+    Hunk,
+    Added,
+    Removed,
+    Context,
+}
+
+/// Tells the kind of each line of a unified diff from its bytes, in order, as the reader
+/// scans them
+#[derive(Default)]
+pub struct Classifier {
+    /// old and new lines left in the current hunk
+    old: usize,
+    new: usize,
+    /// a `diff` line started the file, so its `---` line doesn't start another one
+    header: bool,
+}
+
+impl Classifier {
+    /// `bytes` of the next line, its escape sequences included
+    pub fn classify(&mut self, bytes: &[u8]) -> DiffLine {
+        let bytes = skip_escapes(bytes);
+
+        if self.old > 0 || self.new > 0 {
+            // the hunk header tells how many lines follow, a `---` in there is a removed line
+            match bytes.first() {
+                Some(b'+') => {
+                    self.new = self.new.saturating_sub(1);
+                    DiffLine::Added
+                }
+                Some(b'-') => {
+                    self.old = self.old.saturating_sub(1);
+                    DiffLine::Removed
+                }
+                // `\ No newline at end of file`
+                Some(b'\\') => DiffLine::Context,
+                _ => {
+                    self.old = self.old.saturating_sub(1);
+                    self.new = self.new.saturating_sub(1);
+                    DiffLine::Context
+                }
+            }
+        } else if let Some((old, new)) = parse_hunk(bytes) {
+            self.old = old;
+            self.new = new;
+            self.header = false;
+            DiffLine::Hunk
+        } else if bytes.starts_with(b"diff ") {
+            self.header = true;
+            DiffLine::File
+        } else if bytes.starts_with(b"--- ") {
+            // `diff -u` output has no `diff` line
+            match std::mem::replace(&mut self.header, true) {
+                true => DiffLine::Header,
+                false => DiffLine::File,
+            }
+        } else if self.header || bytes.starts_with(b"+++ ") {
+            DiffLine::Header
+        } else {
+            DiffLine::Other
+        }
+    }
+}
+
+/// Files and hunks of a unified diff, found as its lines arrive
+#[derive(Default)]
+pub struct DiffIndex {
+    kinds: Vec<DiffLine>,
+    /// first line of each file
+    pub files: Vec<usize>,
+    /// line of each hunk header
+    pub hunks: Vec<usize>,
+}
+
+impl DiffIndex {
+    /// at least one hunk was seen
+    pub fn is_diff(&self) -> bool {
+        !self.hunks.is_empty()
+    }
+
+    pub fn kind(&self, idx: usize) -> DiffLine {
+        self.kinds.get(idx).copied().unwrap_or(DiffLine::Other)
+    }
+
+    /// the next line is of `kind`, see [`Classifier`]
+    pub fn push(&mut self, kind: DiffLine) {
+        let idx = self.kinds.len();
+        match kind {
+            DiffLine::File => self.files.push(idx),
+            DiffLine::Hunk => self.hunks.push(idx),
+            _ => {}
+        }
+        self.kinds.push(kind);
+    }
+}
+
+// the bytes after the escape sequences starting them, like the colors of `git diff --color`
+fn skip_escapes(mut bytes: &[u8]) -> &[u8] {
+    while let Some(rest) = bytes.strip_prefix(b"\x1b[") {
+        match rest.iter().position(|b| (0x40..=0x7e).contains(b)) {
+            Some(end) => bytes = &rest[end + 1..],
+            None => return &[],
+        }
+    }
+    bytes
+}
+
+// (old, new) line counts of `@@ -l[,s] +l[,s] @@`
+fn parse_hunk(bytes: &[u8]) -> Option<(usize, usize)> {
+    let rest = bytes.strip_prefix(b"@@ -")?;
+    let end = rest.windows(3).position(|w| w == b" @@")?;
+    let (old, new) = std::str::from_utf8(&rest[..end]).ok()?.split_once(" +")?;
+
+    let count = |range: &str| match range.split_once(',') {
+        Some((_, count)) => count.parse().ok(),
+        None => range.parse::<usize>().ok().map(|_| 1),
+    };
+    Some((count(old)?, count(new)?))
+}
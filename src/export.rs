@@ -0,0 +1,95 @@
+use std::{
+    borrow::Cow,
+    io::{Result, Write},
+};
+use unicode_width::UnicodeWidthChar;
+
+/// Indentation added to continuation lines, on top of the line's own indentation
+const CONTINUATION_INDENT: &str = "  ";
+
+pub fn write_plain<'a>(out: &mut impl Write, lines: impl Iterator<Item = &'a str>) -> Result<()> {
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+
+    Ok(())
+}
+
+/// write lines as they were read, their escape sequences included
+pub fn write_raw<'a>(
+    out: &mut impl Write,
+    lines: impl Iterator<Item = Cow<'a, [u8]>>,
+) -> Result<()> {
+    for line in lines {
+        out.write_all(&line)?;
+        out.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// write lines word-wrapped at `column`, continuation lines keep the indentation of the line
+pub fn write_wrapped<'a>(
+    out: &mut impl Write,
+    lines: impl Iterator<Item = &'a str>,
+    column: usize,
+) -> Result<()> {
+    for line in lines {
+        for piece in wrap(line, column) {
+            writeln!(out, "{}", piece)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn wrap(line: &str, column: usize) -> Vec<String> {
+    let indent_len = line.len() - line.trim_start().len();
+    let indent = format!("{}{}", &line[..indent_len], CONTINUATION_INDENT);
+    // never let the indentation eat the whole column
+    let indent = if str_width(&indent) * 2 > column {
+        String::new()
+    } else {
+        indent
+    };
+
+    let mut pieces = Vec::new();
+    let mut current = line[..indent_len].to_string();
+    let mut current_width = str_width(&current);
+    let mut has_word = false;
+
+    for word in line[indent_len..].split(' ') {
+        let word_width = str_width(word);
+
+        if has_word && current_width + 1 + word_width > column {
+            pieces.push(std::mem::replace(&mut current, indent.clone()));
+            current_width = str_width(&indent);
+            has_word = false;
+        }
+
+        if has_word {
+            current.push(' ');
+            current_width += 1;
+        }
+
+        // hard break words longer than a whole line
+        for ch in word.chars() {
+            let width = ch.width().unwrap_or(0);
+            if current_width + width > column && current_width > str_width(&indent) {
+                pieces.push(std::mem::replace(&mut current, indent.clone()));
+                current_width = str_width(&indent);
+            }
+            current.push(ch);
+            current_width += width;
+        }
+
+        has_word = true;
+    }
+
+    pieces.push(current);
+    pieces
+}
+
+fn str_width(s: &str) -> usize {
+    s.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
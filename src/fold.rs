@@ -0,0 +1,186 @@
+use std::borrow::Cow;
+
+/// Last line of the block starting at `start`, out of `len` lines given by `line`:
+///
+/// - up to the matching bracket for a line opening a JSON object or array
+/// - the more indented lines after it for a YAML key or list item, or any other line
+///
+/// None if the line starts no block.
+pub fn region<'t>(line: impl Fn(usize) -> Cow<'t, str>, start: usize, len: usize) -> Option<usize> {
+    let first = line(start);
+    let trimmed = first.trim_end();
+
+    let mut depth = open_brackets(&first, 0);
+    if trimmed.ends_with(['{', '[']) && depth > 0 {
+        for idx in start + 1..len {
+            depth = open_brackets(&line(idx), depth);
+            if depth <= 0 {
+                return Some(idx);
+            }
+        }
+        // the end is still coming
+        return Some(len - 1).filter(|end| *end > start);
+    }
+
+    // the items of a YAML list can be as indented as its key
+    let key = trimmed.ends_with(':');
+    indented(&line, start, len, key)
+}
+
+/// `(first, last)` of each block of lines indented more than `indent`, the first line
+/// being the one before the block, like for a stack trace under its exception
+pub fn deeper_than<'t>(
+    line: impl Fn(usize) -> Cow<'t, str>,
+    len: usize,
+    indent: usize,
+) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    // line before the current block, and the last line of it so far
+    let mut block: Option<(usize, usize)> = None;
+    let mut previous = None;
+
+    for idx in 0..len {
+        let text = line(idx);
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        if indent_of(&text) > indent {
+            match &mut block {
+                Some((_, end)) => *end = idx,
+                None => block = previous.map(|first| (first, idx)),
+            }
+        } else {
+            blocks.extend(block.take());
+            previous = Some(idx);
+        }
+    }
+    blocks.extend(block);
+
+    blocks
+}
+
+// lines after `start` indented more than it, trailing blank lines left out;
+// with `list`, items of a list at the same indent belong to the key too
+fn indented<'t>(
+    line: &impl Fn(usize) -> Cow<'t, str>,
+    start: usize,
+    len: usize,
+    list: bool,
+) -> Option<usize> {
+    let indent = indent_of(&line(start));
+    let mut end = start;
+
+    for idx in start + 1..len {
+        let text = line(idx);
+        if text.trim().is_empty() {
+            continue;
+        }
+        let deeper = indent_of(&text) > indent;
+        let item = list && indent_of(&text) == indent && text.trim_start().starts_with("- ");
+        if !deeper && !item {
+            break;
+        }
+        end = idx;
+    }
+
+    Some(end).filter(|end| *end > start)
+}
+
+/// columns of leading whitespace, a tab counted as one
+pub fn indent_of(text: &str) -> usize {
+    text.len() - text.trim_start().len()
+}
+
+// `depth` after the brackets of `text`, those in strings don't count
+fn open_brackets(text: &str, mut depth: isize) -> isize {
+    let mut string = false;
+    let mut escaped = false;
+
+    for ch in text.chars() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if string => escaped = true,
+            '"' => string = !string,
+            '{' | '[' if !string => depth += 1,
+            '}' | ']' if !string => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &'static str) -> (impl Fn(usize) -> Cow<'static, str>, usize) {
+        let lines = text.lines().collect::<Vec<_>>();
+        let len = lines.len();
+        (move |idx| Cow::Borrowed(lines[idx]), len)
+    }
+
+    #[test]
+    fn json_regions_end_at_their_bracket() {
+        let (line, len) = lines(
+            "{\n\
+             \x20 \"name\": \"}\",\n\
+             \x20 \"list\": [\n\
+             \x20   1, [2, 3]\n\
+             \x20 ],\n\
+             \x20 \"empty\": {}\n\
+             }\n\
+             after\n",
+        );
+        assert_eq!(region(&line, 0, len), Some(6));
+        assert_eq!(region(&line, 2, len), Some(4));
+        // opened and closed on the same line
+        assert_eq!(region(&line, 5, len), None);
+    }
+
+    #[test]
+    fn unfinished_json_regions_go_to_the_last_line() {
+        let (line, len) = lines("[\n  1,\n  2,\n");
+        assert_eq!(region(&line, 0, len), Some(2));
+        let (line, len) = lines("{\n");
+        assert_eq!(region(&line, 0, len), None);
+    }
+
+    #[test]
+    fn indented_regions() {
+        let (line, len) = lines(
+            "server:\n\
+             \x20 port: 80\n\
+             \n\
+             \x20 hosts:\n\
+             \x20 - a\n\
+             \x20 - b\n\
+             \n\
+             client: {}\n",
+        );
+        // trailing blank lines left out
+        assert_eq!(region(&line, 0, len), Some(5));
+        // list items as indented as their key
+        assert_eq!(region(&line, 3, len), Some(5));
+        assert_eq!(region(&line, 1, len), None);
+        assert_eq!(region(&line, 7, len), None);
+    }
+
+    #[test]
+    fn blocks_deeper_than_an_indent() {
+        let (line, len) = lines(
+            "  leading\n\
+             Exception: boom\n\
+             \x20   at one\n\
+             \n\
+             \x20   at two\n\
+             next\n\
+             other\n\
+             \tat three\n",
+        );
+        assert_eq!(deeper_than(&line, len, 0), [(1, 4), (6, 7)]);
+        assert_eq!(deeper_than(&line, len, 4), []);
+        assert_eq!(deeper_than(&line, 0, 0), []);
+    }
+}
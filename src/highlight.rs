@@ -0,0 +1,149 @@
+use std::{
+    io::{self, BufRead, Read, Write},
+    path::Path,
+    sync::OnceLock,
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Style, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+
+/// Theme used unless `--syntax-theme` asks for another
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+
+// loading them takes a while, only done once a file needs them
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Names `--syntax-theme` accepts
+pub fn theme_names() -> impl Iterator<Item = &'static str> {
+    themes().themes.keys().map(String::as_str)
+}
+
+/// Input with the escape sequences for the style of each token added, line by line
+pub struct Highlighted<R> {
+    input: R,
+    lines: HighlightLines<'static>,
+    line: Vec<u8>,
+    out: Vec<u8>,
+    pos: usize,
+}
+
+/// `input` highlighted if the name of `path` or the first line of `sample` tells its syntax,
+/// None for plain text, already colored text or an unknown `theme`
+pub fn highlight<R: BufRead>(
+    path: &Path,
+    sample: &[u8],
+    theme: &str,
+    input: R,
+) -> Option<Highlighted<R>> {
+    // the escape sequences already there would be mixed with the new ones
+    if sample.contains(&0x1b) {
+        return None;
+    }
+
+    let theme = themes().themes.get(theme)?;
+    let syntax = find_syntax(path, sample)?;
+
+    Some(Highlighted {
+        input,
+        lines: HighlightLines::new(syntax, theme),
+        line: Vec::with_capacity(256),
+        out: Vec::with_capacity(1024),
+        pos: 0,
+    })
+}
+
+// by file name first for `Makefile` and the like, then by extension, then by shebang
+fn find_syntax(path: &Path, sample: &[u8]) -> Option<&'static SyntaxReference> {
+    let syntaxes = syntaxes();
+    let by_name = |name: Option<&std::ffi::OsStr>| {
+        name.and_then(|name| name.to_str())
+            .and_then(|name| syntaxes.find_syntax_by_extension(name))
+    };
+    let first_line = || {
+        let end = sample
+            .iter()
+            .position(|b| *b == b'\n')
+            .unwrap_or(sample.len());
+        let line = std::str::from_utf8(&sample[..end]).ok()?;
+        syntaxes.find_syntax_by_first_line(line)
+    };
+
+    by_name(path.file_name())
+        .or_else(|| by_name(path.extension()))
+        .or_else(first_line)
+        .filter(|syntax| syntax.name != "Plain Text")
+}
+
+impl<R: BufRead> Highlighted<R> {
+    fn next_line(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        self.out.clear();
+        self.pos = 0;
+        if self.input.read_until(b'\n', &mut self.line)? == 0 {
+            return Ok(false);
+        }
+
+        let line = String::from_utf8_lossy(&self.line);
+        match self.lines.highlight_line(&line, syntaxes()) {
+            Ok(regions) => {
+                for (style, text) in regions {
+                    write_style(&mut self.out, style)?;
+                    self.out.extend_from_slice(text.as_bytes());
+                }
+                // the next line starts from no style, like in the file
+                let newline = self.out.last() == Some(&b'\n');
+                if newline {
+                    self.out.pop();
+                }
+                self.out.extend_from_slice(b"\x1b[0m");
+                if newline {
+                    self.out.push(b'\n');
+                }
+            }
+            // a grammar that can't handle the line leaves it as is
+            Err(_) => self.out.extend_from_slice(&self.line),
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Read for Highlighted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.out.len() && !self.next_line()? {
+            return Ok(0);
+        }
+
+        let len = (self.out.len() - self.pos).min(buf.len());
+        buf[..len].copy_from_slice(&self.out[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+// the theme's background is left out, the terminal's own is kept
+fn write_style(out: &mut Vec<u8>, style: Style) -> io::Result<()> {
+    let color = style.foreground;
+    write!(out, "\x1b[0;38;2;{};{};{}", color.r, color.g, color.b)?;
+    if style.font_style.contains(FontStyle::BOLD) {
+        out.extend_from_slice(b";1");
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        out.extend_from_slice(b";3");
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        out.extend_from_slice(b";4");
+    }
+    out.push(b'm');
+    Ok(())
+}
@@ -0,0 +1,560 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// files remembered, the least recently shown are forgotten first
+const MAX_POSITIONS: usize = 1000;
+/// searches remembered, the oldest are forgotten first
+pub const MAX_SEARCHES: usize = 100;
+
+/// Last line on top of the view for each file, in `<state dir>/rp/positions`
+#[derive(Default)]
+pub struct Positions {
+    /// most recent last
+    entries: Vec<(PathBuf, usize)>,
+    /// files set since loaded, the others are saved as another rp may have left them
+    changed: Vec<PathBuf>,
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    #[cfg(unix)]
+    let dir = std::env::var_os("XDG_STATE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        });
+    #[cfg(windows)]
+    let dir = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+
+    Some(dir?.join("rp"))
+}
+
+// write the file at `path` to a temporary file next to it, renamed over it once complete so that
+// it's never left half written, and never read so by another rp
+pub(crate) fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    let temp = path.with_file_name(name);
+    let mut out = BufWriter::new(File::create(&temp)?);
+    let written = write(&mut out).and_then(|_| out.flush());
+    drop(out);
+
+    match written.and_then(|_| std::fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            std::fs::remove_file(&temp).ok();
+            Err(e)
+        }
+    }
+}
+
+impl Positions {
+    fn path() -> Option<PathBuf> {
+        Some(state_dir()?.join("positions"))
+    }
+
+    /// `<line>\t<path>` lines, an unreadable file is the same as no file
+    pub fn load() -> Self {
+        let text = match Self::path().and_then(|path| std::fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return Self::default(),
+        };
+
+        let entries = text
+            .lines()
+            .filter_map(|line| {
+                let (number, path) = line.split_at(line.find('\t')?);
+                Some((PathBuf::from(&path[1..]), number.parse().ok()?))
+            })
+            .collect();
+
+        Self {
+            entries,
+            changed: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> Option<usize> {
+        let path = std::fs::canonicalize(path).ok()?;
+        self.entries
+            .iter()
+            .rev()
+            .find(|(p, _)| *p == path)
+            .map(|(_, line)| *line)
+    }
+
+    /// remember `line` for the file, forget it when back on top
+    pub fn set(&mut self, path: &Path, line: usize) {
+        let path = match std::fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+
+        self.entries.retain(|(p, _)| *p != path);
+        if !self.changed.contains(&path) {
+            self.changed.push(path.clone());
+        }
+        if line > 0 {
+            self.entries.push((path, line));
+        }
+
+        let extra = self.entries.len().saturating_sub(MAX_POSITIONS);
+        self.entries.drain(..extra);
+    }
+
+    /// the files set here over those saved meanwhile, like by another rp that quit since
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::path().ok_or(std::io::ErrorKind::NotFound)?;
+        let mut entries = Self::load().entries;
+        entries.retain(|(p, _)| !self.changed.contains(p));
+        let changed = self
+            .entries
+            .iter()
+            .filter(|(p, _)| self.changed.contains(p));
+        entries.extend(changed.cloned());
+        let extra = entries.len().saturating_sub(MAX_POSITIONS);
+
+        write_file(&path, |out| {
+            for (path, line) in entries.iter().skip(extra) {
+                // a name with a line break can't be read back
+                match path.to_str() {
+                    Some(path) if !path.contains('\n') => writeln!(out, "{}\t{}", line, path)?,
+                    _ => {}
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+fn searches_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("searches"))
+}
+
+/// previous searches, oldest first
+pub fn load_searches() -> Vec<String> {
+    searches_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|text| text.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+pub fn save_searches(searches: &[String]) -> std::io::Result<()> {
+    let path = searches_path().ok_or(std::io::ErrorKind::NotFound)?;
+    let skip = searches.len().saturating_sub(MAX_SEARCHES);
+    write_file(&path, |out| {
+        for search in searches.iter().skip(skip) {
+            writeln!(out, "{}", search)?;
+        }
+        Ok(())
+    })
+}
+
+/// A line of a file named to come back to it
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Bookmark {
+    pub line: usize,
+    pub name: String,
+    /// what was found there, can be empty
+    pub note: String,
+}
+
+/// Bookmarks of each file, in `<state dir>/rp/bookmarks`
+#[derive(Default)]
+pub struct Bookmarks {
+    entries: Vec<(PathBuf, Bookmark)>,
+    /// files whose bookmarks changed since loaded, the others are saved as another rp may have
+    /// left them
+    changed: Vec<PathBuf>,
+}
+
+impl Bookmarks {
+    fn path() -> Option<PathBuf> {
+        Some(state_dir()?.join("bookmarks"))
+    }
+
+    /// `<line>\t<name>\t<note>\t<path>` lines, an unreadable file is the same as no file
+    pub fn load() -> Self {
+        let text = match Self::path().and_then(|path| std::fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return Self::default(),
+        };
+
+        Self {
+            entries: Self::parse(&text),
+            changed: Vec::new(),
+        }
+    }
+
+    // the name and the note have their tabs, line breaks and backslashes escaped
+    fn parse(text: &str) -> Vec<(PathBuf, Bookmark)> {
+        text.lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(4, '\t');
+                let line = fields.next()?.parse().ok()?;
+                let (name, note) = (unescape(fields.next()?), unescape(fields.next()?));
+                let path = PathBuf::from(fields.next()?);
+                Some((path, Bookmark { line, name, note }))
+            })
+            .collect()
+    }
+
+    fn write(out: &mut impl Write, entries: &[(PathBuf, Bookmark)]) -> std::io::Result<()> {
+        for (path, bookmark) in entries.iter() {
+            let Bookmark { line, name, note } = bookmark;
+            match path.to_str() {
+                Some(path) if !path.contains('\n') => {
+                    let (name, note) = (escape(name), escape(note));
+                    writeln!(out, "{}\t{}\t{}\t{}", line, name, note, path)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// the bookmarks of the file, by line
+    pub fn get(&self, path: &Path) -> Vec<Bookmark> {
+        let path = match std::fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => return Vec::new(),
+        };
+        let mut bookmarks = self
+            .entries
+            .iter()
+            .filter(|(p, _)| *p == path)
+            .map(|(_, bookmark)| bookmark.clone())
+            .collect::<Vec<_>>();
+        bookmarks.sort_by_key(|bookmark| bookmark.line);
+        bookmarks
+    }
+
+    /// replace the bookmarks of the file
+    pub fn set(&mut self, path: &Path, bookmarks: &[Bookmark]) {
+        let path = match std::fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+
+        let mut old = self.get(&path);
+        let mut new = bookmarks.to_vec();
+        old.sort_by_key(|bookmark| bookmark.line);
+        new.sort_by_key(|bookmark| bookmark.line);
+        if old == new {
+            return;
+        }
+
+        self.entries.retain(|(p, _)| *p != path);
+        let entries = bookmarks.iter().map(|b| (path.clone(), b.clone()));
+        self.entries.extend(entries);
+        if !self.changed.contains(&path) {
+            self.changed.push(path);
+        }
+    }
+
+    /// the files changed here over those saved meanwhile, nothing written if none changed
+    pub fn save(&self) -> std::io::Result<()> {
+        if self.changed.is_empty() {
+            return Ok(());
+        }
+
+        let path = Self::path().ok_or(std::io::ErrorKind::NotFound)?;
+        let mut entries = Self::load().entries;
+        entries.retain(|(p, _)| !self.changed.contains(p));
+        let changed = self
+            .entries
+            .iter()
+            .filter(|(p, _)| self.changed.contains(p));
+        entries.extend(changed.cloned());
+
+        write_file(&path, |out| Self::write(out, &entries))
+    }
+}
+
+// `field` on one line and without tabs, for the fields of the state files
+fn escape(field: &str) -> Cow<'_, str> {
+    if !field.contains(['\\', '\t', '\n']) {
+        return Cow::Borrowed(field);
+    }
+
+    let mut escaped = String::with_capacity(field.len() + 2);
+    for ch in field.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(ch),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+// the field `escape` wrote, a backslash before another char is kept as it is
+fn unescape(field: &str) -> String {
+    let mut text = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            text.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => text.push('\t'),
+            Some('n') => text.push('\n'),
+            Some('\\') => text.push('\\'),
+            Some(other) => {
+                text.push('\\');
+                text.push(other);
+            }
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// What a session keeps of an input
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SavedView {
+    /// line on top of the view, None if at the end
+    pub line: Option<usize>,
+    pub needle: String,
+    pub reverse: bool,
+    pub dedup: bool,
+    pub squeeze: bool,
+    pub only_stderr: bool,
+    pub ignore_case: bool,
+}
+
+impl Default for SavedView {
+    /// at the top, without search or filter
+    fn default() -> Self {
+        Self {
+            line: Some(0),
+            needle: String::new(),
+            reverse: false,
+            dedup: false,
+            squeeze: false,
+            only_stderr: false,
+            ignore_case: false,
+        }
+    }
+}
+
+/// The files of a `--session` and where each was left, in `<state dir>/rp/sessions/<name>`
+#[derive(Default, Debug)]
+pub struct SavedSession {
+    pub inputs: Vec<(PathBuf, SavedView)>,
+    /// index of the input shown last
+    pub current: usize,
+}
+
+impl SavedSession {
+    /// a name for a file of the sessions directory: no path separator, and no dot first
+    /// like `..`
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+    }
+
+    fn path(name: &str) -> Option<PathBuf> {
+        let name = Some(name).filter(|name| Self::is_valid_name(name))?;
+        Some(state_dir()?.join("sessions").join(name))
+    }
+
+    /// `key = value` lines, each `file` starting an input, None if never saved
+    pub fn load(name: &str) -> Option<Self> {
+        let text = std::fs::read_to_string(Self::path(name)?).ok()?;
+        Some(Self::parse(&text))
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut session = Self::default();
+
+        for line in text.lines() {
+            // only the space `save` puts after the `=`, a search can start with spaces
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.strip_prefix(' ').unwrap_or(value)),
+                None => continue,
+            };
+            if key == "file" {
+                let input = (PathBuf::from(value), SavedView::default());
+                session.inputs.push(input);
+                continue;
+            }
+            if key == "current" {
+                session.current = value.parse().unwrap_or(0);
+                continue;
+            }
+
+            let view = match session.inputs.last_mut() {
+                Some((_, view)) => view,
+                None => continue,
+            };
+            match key {
+                "line" => view.line = value.parse().ok(),
+                "search" => view.needle = unescape(value),
+                "filters" => {
+                    let filters = value.split_whitespace().collect::<Vec<_>>();
+                    view.reverse = filters.contains(&"reverse");
+                    view.dedup = filters.contains(&"dedup");
+                    view.squeeze = filters.contains(&"squeeze");
+                    view.only_stderr = filters.contains(&"stderr");
+                    view.ignore_case = filters.contains(&"ignorecase");
+                }
+                _ => {}
+            }
+        }
+
+        session.current = session.current.min(session.inputs.len().saturating_sub(1));
+        session
+    }
+
+    /// the view saved for `path`, if it's one of the inputs
+    pub fn view(&self, path: &Path) -> Option<SavedView> {
+        let path = std::fs::canonicalize(path).ok()?;
+        self.inputs
+            .iter()
+            .find(|(p, _)| *p == path)
+            .map(|(_, view)| view.clone())
+    }
+
+    /// remember the view of `path`, added after the other inputs if it's not one yet
+    pub fn set(&mut self, path: &Path, view: SavedView) {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        match self.inputs.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, saved)) => *saved = view,
+            None => self.inputs.push((path, view)),
+        }
+    }
+
+    pub fn save(&self, name: &str) -> std::io::Result<()> {
+        if !Self::is_valid_name(name) {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        }
+        let path = Self::path(name).ok_or(std::io::ErrorKind::NotFound)?;
+        write_file(&path, |out| self.write(out))
+    }
+
+    fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        writeln!(out, "# rp session")?;
+        writeln!(out, "current = {}", self.current)?;
+        for (path, view) in self.inputs.iter() {
+            // found again from another directory
+            let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            let path = match path.to_str() {
+                Some(path) if !path.contains('\n') => path.to_string(),
+                _ => continue,
+            };
+            writeln!(out, "file = {}", path)?;
+            match view.line {
+                Some(line) => writeln!(out, "line = {}", line)?,
+                None => writeln!(out, "line = end")?,
+            }
+            if !view.needle.is_empty() {
+                writeln!(out, "search = {}", escape(&view.needle))?;
+            }
+            let filters = [
+                (view.reverse, "reverse"),
+                (view.dedup, "dedup"),
+                (view.squeeze, "squeeze"),
+                (view.only_stderr, "stderr"),
+                (view.ignore_case, "ignorecase"),
+            ];
+            let filters = filters
+                .iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, name)| *name)
+                .collect::<Vec<_>>();
+            if !filters.is_empty() {
+                writeln!(out, "filters = {}", filters.join(" "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(line: usize, name: &str, note: &str) -> Bookmark {
+        Bookmark {
+            line,
+            name: name.to_string(),
+            note: note.to_string(),
+        }
+    }
+
+    #[test]
+    fn bookmarks_are_read_back() {
+        let entries = vec![
+            (PathBuf::from("/a"), bookmark(3, "start", "")),
+            (PathBuf::from("/a"), bookmark(10, "tab\there", "two\nlines")),
+            (PathBuf::from("/b c"), bookmark(0, "back\\slash", "\\n as typed")),
+        ];
+        let mut out = Vec::new();
+        Bookmarks::write(&mut out, &entries).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(Bookmarks::parse(&text), entries);
+    }
+
+    #[test]
+    fn bookmarks_of_a_path_with_a_line_break_are_left_out() {
+        let entries = vec![(PathBuf::from("/a\nb"), bookmark(1, "name", "note"))];
+        let mut out = Vec::new();
+        Bookmarks::write(&mut out, &entries).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn unknown_escapes_are_kept() {
+        assert_eq!(unescape("a\\qb\\"), "a\\qb\\");
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn sessions_are_read_back() {
+        let view = SavedView {
+            line: None,
+            needle: "two\nlines\\d".to_string(),
+            ignore_case: true,
+            ..SavedView::default()
+        };
+        let session = SavedSession {
+            inputs: vec![
+                (PathBuf::from("/a"), view),
+                (PathBuf::from("/b"), SavedView::default()),
+            ],
+            current: 1,
+        };
+        let mut out = Vec::new();
+        session.write(&mut out).unwrap();
+        let read = SavedSession::parse(&String::from_utf8(out).unwrap());
+        assert_eq!(read.inputs, session.inputs);
+        assert_eq!(read.current, 1);
+    }
+
+    #[test]
+    fn session_names_stay_in_the_sessions_directory() {
+        for name in ["work", "a.b", "x-1"] {
+            assert!(SavedSession::is_valid_name(name), "{}", name);
+        }
+        for name in ["", "..", "../../x", "a/b", "a\\b", ".hidden"] {
+            assert!(!SavedSession::is_valid_name(name), "{}", name);
+            assert!(SavedSession::path(name).is_none());
+        }
+        let saved = SavedSession::default().save("../x");
+        assert_eq!(saved.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
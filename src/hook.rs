@@ -0,0 +1,47 @@
+use crate::shared::Style;
+use crossterm::event::KeyEvent;
+
+/// Something that happened in the pager, see [`crate::writer::UiContext::on_event`]
+#[derive(Clone, Copy, Debug)]
+pub enum PagerEvent<'a> {
+    /// the user asked to quit
+    Quit,
+    /// the view reached the last line while input is still coming
+    ReachedEnd,
+    /// a search ran, `matches` is the number of lines containing the needle
+    Search { needle: &'a str, matches: usize },
+    /// a key without binding in the keymap
+    UnhandledKey(KeyEvent),
+    /// the line on top of the view is now `top`, from 0
+    Scroll { top: usize },
+}
+
+pub type Hook<'h> = Box<dyn FnMut(&PagerEvent) + 'h>;
+
+/// What the pager does with a key a [`KeyFilter`] saw, see [`crate::writer::UiContext::on_key`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum KeyAction {
+    /// handle it like without the filter
+    Pass,
+    /// drop it, the filter did what the key is for
+    Consume,
+    /// handle this key instead, the next filters see it too
+    Replace(KeyEvent),
+    /// run a command of the `:` prompt instead, like `export report.txt`
+    Command(String),
+}
+
+pub type KeyFilter<'h> = Box<dyn FnMut(&KeyEvent) -> KeyAction + 'h>;
+
+/// What an embedder adds to a line as it's drawn, see [`crate::writer::UiContext::decorate`]
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Decoration {
+    /// before the first row of the line, like `✔` or `E`, cut to the width of the gutter
+    pub gutter: String,
+    /// of the gutter text and the spaces after it
+    pub gutter_style: Style,
+    /// over the colors of the line, except `Color::Reset` ones, its attributes added to the line's
+    pub style: Style,
+}
+
+pub type Decorator<'h> = Box<dyn FnMut(usize, &str) -> Option<Decoration> + 'h>;
@@ -0,0 +1,326 @@
+// Images of the input, in the escape sequences of sixel, iTerm2 and kitty, drawn in place on the
+// terminals that know them
+
+use std::env;
+
+/// bytes kept of the start of a sequence, its params and the header of the image after them
+const HEAD: usize = 1024;
+
+/// How an image is sent to the terminal
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    /// `ESC P q ... ESC \`, drawn by xterm, foot, mlterm and WezTerm
+    Sixel,
+    /// `ESC ] 1337 ; File = ... BEL`, drawn by iTerm2 and WezTerm
+    Iterm,
+    /// `ESC _ G ... ESC \`, drawn by kitty, WezTerm and Ghostty
+    Kitty,
+}
+
+/// Height of an image, in pixels until the height of a row is known
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Height {
+    Rows(u32),
+    Pixels(u32),
+}
+
+/// The first image drawn by a line
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Image {
+    pub protocol: Protocol,
+    pub height: Height,
+}
+
+impl Image {
+    /// rows taken by the image, none when it's given in pixels and `cell_height` isn't known
+    pub fn rows(&self, cell_height: Option<u16>) -> Option<usize> {
+        match self.height {
+            Height::Rows(rows) => Some(rows as usize),
+            Height::Pixels(pixels) => {
+                let cell_height = cell_height.filter(|height| *height > 0)? as u32;
+                Some(pixels.div_ceil(cell_height) as usize)
+            }
+        }
+    }
+}
+
+/// the protocols of the terminal rp runs on, from its environment and whether its device
+/// attributes have sixel, see [`crate::colors::query_terminal`]
+pub fn supported(sixel: bool) -> Vec<Protocol> {
+    let var = |name| env::var(name).unwrap_or_default();
+    let program = var("TERM_PROGRAM");
+    let mut protocols = Vec::new();
+    if sixel {
+        protocols.push(Protocol::Sixel);
+    }
+    if program == "iTerm.app" || program == "WezTerm" || var("LC_TERMINAL") == "iTerm2" {
+        protocols.push(Protocol::Iterm);
+    }
+    let kitty = env::var_os("KITTY_WINDOW_ID").is_some() || var("TERM") == "xterm-kitty";
+    if kitty || program == "WezTerm" || program == "ghostty" {
+        protocols.push(Protocol::Kitty);
+    }
+    protocols
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    /// the params of a DCS, up to its final byte
+    Dcs,
+    Sixel,
+    Osc,
+    Apc,
+    /// in a string of no image
+    Ignore,
+}
+
+/// Finds the first image of a line as its bytes go by, a sequence left open at the end of the
+/// line is none
+pub struct Detector {
+    state: State,
+    head: Vec<u8>,
+    /// graphics new lines of a sixel, each one 6 pixels below the one before
+    bands: u32,
+    found: Option<Image>,
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self {
+            state: State::Ground,
+            head: Vec::new(),
+            bands: 0,
+            found: None,
+        }
+    }
+}
+
+impl Detector {
+    pub fn advance(&mut self, b: u8) {
+        match self.state {
+            State::Ground => {
+                if b == 0x1b {
+                    self.state = State::Escape;
+                }
+            }
+            State::Escape => {
+                self.head.clear();
+                self.bands = 0;
+                self.state = match b {
+                    b'P' => State::Dcs,
+                    b']' => State::Osc,
+                    b'_' => State::Apc,
+                    0x1b => State::Escape,
+                    _ => State::Ground,
+                };
+            }
+            // ST is `ESC \`, the escape ends the string whatever follows
+            _ if b == 0x1b => {
+                self.end();
+                self.state = State::Escape;
+            }
+            State::Osc if b == 7 => {
+                self.end();
+                self.state = State::Ground;
+            }
+            State::Dcs => match b {
+                b'q' => {
+                    self.head.clear();
+                    self.state = State::Sixel;
+                }
+                0x40..=0x7e => self.state = State::Ignore,
+                _ => {}
+            },
+            State::Sixel => {
+                if b == b'-' {
+                    self.bands += 1;
+                }
+                self.push(b);
+            }
+            State::Osc | State::Apc => self.push(b),
+            State::Ignore => {}
+        }
+    }
+
+    /// the image of the line that ended, ready for the next one
+    pub fn take(&mut self) -> Option<Image> {
+        self.state = State::Ground;
+        self.head.clear();
+        self.found.take()
+    }
+
+    fn push(&mut self, b: u8) {
+        if self.head.len() < HEAD {
+            self.head.push(b);
+        }
+    }
+
+    fn end(&mut self) {
+        let image = match self.state {
+            State::Sixel => sixel(&self.head, self.bands),
+            State::Osc => iterm(&self.head),
+            State::Apc => kitty(&self.head),
+            _ => None,
+        };
+        if self.found.is_none() {
+            self.found = image;
+        }
+    }
+}
+
+// the raster attributes `"Pan;Pad;Ph;Pv` give the height, the bands do without them
+fn sixel(data: &[u8], bands: u32) -> Option<Image> {
+    let raster = data.strip_prefix(b"\"").and_then(|raster| {
+        let end = raster.iter().position(|b| !matches!(b, b'0'..=b'9' | b';'));
+        let raster = std::str::from_utf8(&raster[..end.unwrap_or(raster.len())]).ok()?;
+        raster.split(';').nth(3)?.parse::<u32>().ok()
+    });
+    let height = raster.unwrap_or((bands + 1) * 6);
+    Some(Image {
+        protocol: Protocol::Sixel,
+        height: Height::Pixels(height),
+    })
+}
+
+// `1337;File=inline=1;height=N:base64`, the height in rows, `Npx` or that of the PNG
+fn iterm(head: &[u8]) -> Option<Image> {
+    let head = head.strip_prefix(b"1337;File=")?;
+    let colon = head.iter().position(|b| *b == b':').unwrap_or(head.len());
+    let params = String::from_utf8_lossy(&head[..colon]);
+    let param = |name| {
+        let mut params = params.split(';').filter_map(|param| param.split_once('='));
+        params.find(|(key, _)| *key == name).map(|(_, value)| value)
+    };
+    // a file to download, not to show
+    if param("inline") != Some("1") {
+        return None;
+    }
+
+    let height = match param("height") {
+        Some(rows) if rows.bytes().all(|b| b.is_ascii_digit()) => Height::Rows(rows.parse().ok()?),
+        Some(pixels) if pixels.ends_with("px") => {
+            Height::Pixels(pixels.trim_end_matches("px").parse().ok()?)
+        }
+        // the terminal fits a percentage to the window and `auto` to the picture
+        Some(height) if height != "auto" => return None,
+        _ => Height::Pixels(png_height(head.get(colon + 1..)?)?),
+    };
+    Some(Image {
+        protocol: Protocol::Iterm,
+        height,
+    })
+}
+
+// `Ga=T,r=N,...;base64`, only shown with `a=T` or `a=p`, rows `r`, or pixels `v` of raw
+// pixels or those of the PNG
+fn kitty(head: &[u8]) -> Option<Image> {
+    let head = head.strip_prefix(b"G")?;
+    let semicolon = head.iter().position(|b| *b == b';').unwrap_or(head.len());
+    let control = String::from_utf8_lossy(&head[..semicolon]);
+    let key = |name| {
+        let mut keys = control.split(',').filter_map(|key| key.split_once('='));
+        keys.find(|(key, _)| *key == name).map(|(_, value)| value)
+    };
+    if !matches!(key("a"), Some("T") | Some("p")) {
+        return None;
+    }
+
+    let height = match (key("r"), key("v"), key("f")) {
+        (Some(rows), _, _) => Height::Rows(rows.parse().ok()?),
+        (None, Some(pixels), Some("24") | Some("32") | None) => {
+            Height::Pixels(pixels.parse().ok()?)
+        }
+        (None, _, Some("100")) => Height::Pixels(png_height(head.get(semicolon + 1..)?)?),
+        _ => return None,
+    };
+    Some(Image {
+        protocol: Protocol::Kitty,
+        height,
+    })
+}
+
+// the height in the IHDR chunk of a PNG, 24 bytes in, in base64
+fn png_height(data: &[u8]) -> Option<u32> {
+    let bytes = base64(data.get(..32)?)?;
+    if !bytes.starts_with(b"\x89PNG") || &bytes[12..16] != b"IHDR" {
+        return None;
+    }
+    Some(u32::from_be_bytes([
+        bytes[20], bytes[21], bytes[22], bytes[23],
+    ]))
+}
+
+fn base64(data: &[u8]) -> Option<Vec<u8>> {
+    let value = |b: u8| match b {
+        b'A'..=b'Z' => Some(b - b'A'),
+        b'a'..=b'z' => Some(b - b'a' + 26),
+        b'0'..=b'9' => Some(b - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let mut bytes = Vec::with_capacity(data.len() / 4 * 3);
+    for quad in data.chunks_exact(4) {
+        let n = quad
+            .iter()
+            .try_fold(0u32, |n, b| Some(n << 6 | value(*b)? as u32))?;
+        bytes.extend_from_slice(&n.to_be_bytes()[1..]);
+    }
+    Some(bytes)
+}
+
+/// the sequences of `protocol` in the bytes of a line, to write them as they are; kitty is told
+/// not to answer them, its answers would be read as keys
+pub fn sequences(bytes: &[u8], protocol: Protocol) -> Vec<u8> {
+    let start: &[u8] = match protocol {
+        Protocol::Sixel => b"\x1bP",
+        Protocol::Iterm => b"\x1b]1337;File=",
+        Protocol::Kitty => b"\x1b_G",
+    };
+    let mut out = Vec::new();
+    let mut idx = 0;
+
+    while let Some(found) = bytes[idx..].windows(start.len()).position(|w| w == start) {
+        let from = idx + found;
+        let data = from + start.len();
+        // ST or the BEL ending an OSC
+        let end = match bytes[data..].iter().position(|b| *b == 0x1b || *b == 7) {
+            Some(end) if bytes[data + end] == 0x1b => (data + end + 2).min(bytes.len()),
+            Some(end) => data + end + 1,
+            None => break,
+        };
+        idx = end;
+
+        let sequence = &bytes[from..end];
+        if protocol == Protocol::Sixel && !is_sixel(&bytes[data..end]) {
+            continue;
+        }
+        out.extend_from_slice(start);
+        if protocol == Protocol::Kitty && !has_quiet(&bytes[data..end]) {
+            let keys = !matches!(bytes.get(data), Some(b';') | Some(0x1b));
+            out.extend_from_slice(if keys { b"q=2," } else { b"q=2" });
+        }
+        out.extend_from_slice(&sequence[start.len()..]);
+    }
+
+    out
+}
+
+// a DCS of params then `q`
+fn is_sixel(dcs: &[u8]) -> bool {
+    let params = dcs
+        .iter()
+        .take_while(|b| matches!(b, b'0'..=b'9' | b';'))
+        .count();
+    dcs.get(params) == Some(&b'q')
+}
+
+// a `q` key in the control data of kitty
+fn has_quiet(apc: &[u8]) -> bool {
+    let control = apc.split(|b| *b == b';').next().unwrap_or_default();
+    control
+        .split(|b| *b == b',')
+        .any(|key| key.starts_with(b"q="))
+}
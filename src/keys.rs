@@ -0,0 +1,495 @@
+// Key bindings of the config file, like `bind = ctrl-g top` or `bind = g g top`
+
+use crate::writer::{KeyBehavior, ScrollSize};
+use ahash::AHashMap;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Bindings the `bind` lines of the config start from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Keymap {
+    Default,
+    /// the keys of less only, no sequences but `ZZ`
+    Less,
+    /// `gg` without waiting as `g` does nothing alone, `h` and `l` pan
+    Vim,
+    /// `C-n`, `C-p`, `C-v`, `M-v`, `M-<`, `M->`, `C-s`, `M-x`, `C-x C-c` and the like
+    Emacs,
+    /// only the keys bound in the config
+    Empty,
+}
+
+impl Keymap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::Default),
+            "less" => Some(Self::Less),
+            "vim" => Some(Self::Vim),
+            "emacs" => Some(Self::Emacs),
+            "empty" => Some(Self::Empty),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Less => "less",
+            Self::Vim => "vim",
+            Self::Emacs => "emacs",
+            Self::Empty => "empty",
+        }
+    }
+}
+
+/// Names of the actions a key can be bound to
+const ACTIONS: &[(&str, KeyBehavior)] = &[
+    ("quit", KeyBehavior::Quit),
+    ("down", KeyBehavior::Down(ScrollSize::One)),
+    ("up", KeyBehavior::Up(ScrollSize::One)),
+    ("half-page-down", KeyBehavior::Down(ScrollSize::HalfPage)),
+    ("half-page-up", KeyBehavior::Up(ScrollSize::HalfPage)),
+    ("page-down", KeyBehavior::Down(ScrollSize::Page)),
+    ("page-up", KeyBehavior::Up(ScrollSize::Page)),
+    ("bottom", KeyBehavior::Down(ScrollSize::End)),
+    ("top", KeyBehavior::Up(ScrollSize::End)),
+    ("left", KeyBehavior::Left),
+    ("right", KeyBehavior::Right),
+    ("search", KeyBehavior::Search),
+    ("search-next", KeyBehavior::SearchNext),
+    ("search-prev", KeyBehavior::SearchPrev),
+    ("section-next", KeyBehavior::SectionNext),
+    ("section-prev", KeyBehavior::SectionPrev),
+    ("normal", KeyBehavior::NormalMode),
+    ("command", KeyBehavior::Command),
+    ("convert", KeyBehavior::Convert),
+    ("dedup", KeyBehavior::Dedup),
+    ("follow", KeyBehavior::Follow),
+    ("reload", KeyBehavior::Reload),
+    ("pause", KeyBehavior::Pause),
+    ("buffers", KeyBehavior::Buffers),
+    ("stderr-only", KeyBehavior::StderrOnly),
+    ("hex", KeyBehavior::Hex),
+    ("dump-quit", KeyBehavior::DumpQuit),
+    ("hunk-next", KeyBehavior::HunkNext),
+    ("hunk-prev", KeyBehavior::HunkPrev),
+    ("file-next", KeyBehavior::FileNext),
+    ("file-prev", KeyBehavior::FilePrev),
+    ("fold-toggle", KeyBehavior::Fold('a')),
+    ("fold-open", KeyBehavior::Fold('o')),
+    ("fold-close", KeyBehavior::Fold('c')),
+    ("fold-all", KeyBehavior::Fold('M')),
+    ("unfold-all", KeyBehavior::Fold('R')),
+    ("fold-deeper", KeyBehavior::Fold('i')),
+    ("line-top", KeyBehavior::LineTop),
+    ("line-center", KeyBehavior::LineCenter),
+    ("line-bottom", KeyBehavior::LineBottom),
+    ("toggle-option", KeyBehavior::Toggle),
+    ("help", KeyBehavior::Help),
+    ("palette", KeyBehavior::Palette),
+    ("record-macro", KeyBehavior::Record),
+    ("replay-macro", KeyBehavior::Replay),
+    ("cursor-line", KeyBehavior::CursorLine),
+    ("visual", KeyBehavior::Visual),
+    ("mouse", KeyBehavior::Mouse),
+    ("suspend", KeyBehavior::Suspend),
+    ("open-link", KeyBehavior::OpenLink),
+    ("edit", KeyBehavior::Edit),
+    ("save", KeyBehavior::Save),
+    ("pipe", KeyBehavior::Pipe),
+    ("shell", KeyBehavior::Shell),
+    ("matches", KeyBehavior::Matches),
+    ("outline", KeyBehavior::Outline),
+    ("bookmark", KeyBehavior::Bookmark),
+    ("bookmarks", KeyBehavior::Bookmarks),
+];
+
+/// Actions listed by the help, under their heading
+const HELP: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Moving",
+        &[
+            ("down", "one line down"),
+            ("up", "one line up"),
+            ("half-page-down", "half a page down"),
+            ("half-page-up", "half a page up"),
+            ("page-down", "one page down"),
+            ("page-up", "one page up"),
+            ("top", "first line"),
+            ("bottom", "last line"),
+            ("left", "pan left, half a page up when wrapping"),
+            ("right", "pan right, half a page down when wrapping"),
+            ("line-top", "clicked or top line to the top"),
+            ("line-center", "clicked or top line to the center"),
+            ("line-bottom", "clicked or top line to the bottom"),
+        ],
+    ),
+    (
+        "Searching",
+        &[
+            ("search", "search forward"),
+            ("search-next", "next match"),
+            ("search-prev", "previous match"),
+            ("normal", "clear the search or the prompt"),
+            ("matches", "list the matching lines, Enter jumps to one"),
+        ],
+    ),
+    (
+        "Jumping",
+        &[
+            ("section-next", "next section"),
+            ("section-prev", "previous section"),
+            ("outline", "list the sections, Enter jumps to one"),
+            ("bookmark", "name the cursor or top line, with a note"),
+            ("bookmarks", "list the bookmarks, d deletes one"),
+            ("hunk-next", "next hunk of a diff"),
+            ("hunk-prev", "previous hunk of a diff"),
+            ("file-next", "next file of a diff"),
+            ("file-prev", "previous file of a diff"),
+        ],
+    ),
+    (
+        "Folding",
+        &[
+            ("fold-toggle", "toggle the fold of the clicked or top line"),
+            ("fold-open", "open it"),
+            ("fold-close", "close it"),
+            ("fold-all", "close all folds"),
+            ("unfold-all", "open all folds"),
+            ("fold-deeper", "fold what's more indented than the line"),
+        ],
+    ),
+    (
+        "Viewing",
+        &[
+            ("toggle-option", "then S chop, N numbers, i case, s squeeze"),
+            ("cursor-line", "a line cursor j and k move"),
+            ("visual", "select lines, then y copy, w write, | pipe"),
+            ("dedup", "collapse repeated lines"),
+            ("stderr-only", "only the lines written to stderr"),
+            ("hex", "switch between text and hex dump"),
+            ("convert", "conversions of the clicked or searched token"),
+            ("follow", "follow new lines, any key to stop"),
+            ("pause", "pause and resume the input"),
+            ("mouse", "capture the mouse, or let the terminal select"),
+        ],
+    ),
+    (
+        "Files",
+        &[
+            ("reload", "load the file again"),
+            ("buffers", "list of the inputs"),
+            ("open-link", "open the url or file:line nearest the click"),
+            ("edit", "the file in $EDITOR at the cursor or top line, v in less"),
+            ("save", "write the lines or the selection, -r with escapes"),
+            ("pipe", "pipe them to a command and show what it prints"),
+            ("shell", "run a shell command, % for the file name"),
+        ],
+    ),
+    (
+        "Other",
+        &[
+            ("command", "command, like :wrap or :n"),
+            ("help", "this help"),
+            ("palette", "run any action by its name"),
+            ("record-macro", "record keys into a register, again to stop"),
+            ("replay-macro", "replay a register, @ for the last one"),
+            ("suspend", "stop, back with fg"),
+            ("quit", "quit"),
+            ("dump-quit", "quit and write the input to stdout"),
+        ],
+    ),
+];
+
+/// Keys named in `bind` lines, the others are written as the char they type
+const KEYS: &[(&str, KeyCode)] = &[
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("space", KeyCode::Char(' ')),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+/// Lines of the help: a heading for each group of actions, then the keys bound to each of them
+/// and what it does, the actions without any key left out
+pub fn help(
+    keymap: &AHashMap<KeyEvent, KeyBehavior>,
+    sequences: &AHashMap<Vec<KeyEvent>, KeyBehavior>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for (heading, actions) in HELP {
+        let mut group = Vec::new();
+        for (name, description) in actions.iter() {
+            let behavior = match ACTIONS.iter().find(|(action, _)| action == name) {
+                Some((_, behavior)) => *behavior,
+                None => continue,
+            };
+
+            let names = keys_of(keymap, sequences, behavior);
+            if names.is_empty() {
+                continue;
+            }
+            group.push(format!("  {:<20} {}", names.join(", "), description));
+        }
+
+        if !group.is_empty() {
+            lines.push(heading.to_string());
+            lines.append(&mut group);
+            lines.push(String::new());
+        }
+    }
+
+    if keymap.values().any(|b| matches!(b, KeyBehavior::Number(_))) {
+        lines.push("A number typed before a move repeats it, like 5j".to_string());
+    }
+    lines
+}
+
+/// Names of the keys and sequences bound to `behavior`, the keys typing a char first
+pub fn keys_of(
+    keymap: &AHashMap<KeyEvent, KeyBehavior>,
+    sequences: &AHashMap<Vec<KeyEvent>, KeyBehavior>,
+    behavior: KeyBehavior,
+) -> Vec<String> {
+    let singles = keymap
+        .iter()
+        .filter(|(_, b)| **b == behavior)
+        .map(|(key, _)| key_name(*key));
+    let multiple = sequences
+        .iter()
+        .filter(|(_, b)| **b == behavior)
+        .map(|(keys, _)| {
+            let names = keys.iter().map(|key| key_name(*key)).collect::<Vec<_>>();
+            names.join(" ")
+        });
+
+    let mut names = singles.chain(multiple).collect::<Vec<_>>();
+    names.sort_by(|a, b| (a.chars().count() > 1, a).cmp(&(b.chars().count() > 1, b)));
+    names.dedup();
+    names
+}
+
+/// Actions whose name or description has the chars of `query` in that order, as
+/// `(name, description, action)`, the best matches first and all of them for an empty query
+pub fn palette(query: &str) -> Vec<(&'static str, &'static str, KeyBehavior)> {
+    let mut matches = Vec::new();
+    for (_, actions) in HELP {
+        for (name, description) in actions.iter() {
+            let behavior = match ACTIONS.iter().find(|(action, _)| action == name) {
+                Some((_, behavior)) => *behavior,
+                None => continue,
+            };
+            // a match in the name beats one in the description
+            let score = match (fuzzy_score(query, name), fuzzy_score(query, description)) {
+                (Some(score), _) => score + 100_000,
+                (None, Some(score)) => score,
+                (None, None) => continue,
+            };
+            matches.push((score, (*name, *description, behavior)));
+        }
+    }
+
+    // stable, the order of the help for the same score
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches.into_iter().map(|(_, action)| action).collect()
+}
+
+// the chars of `query` found in order in `text`, ignoring case and spaces: more points for
+// those following one another or starting a word, the text itself most, a shorter text winning
+// between the same points
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut from = 0;
+
+    for ch in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let idx = from + text[from..].iter().position(|c| *c == ch)?;
+        score += 1;
+        if idx > 0 && idx == from {
+            score += 4;
+        }
+        if idx == 0 {
+            score += 5;
+        } else if !text[idx - 1].is_alphanumeric() {
+            score += 3;
+        }
+        from = idx + 1;
+    }
+
+    Some(score * 100 - text.len() as i32)
+}
+
+/// The name of `key` in `bind` lines, see [`parse_key`]
+pub fn key_name(key: KeyEvent) -> String {
+    let mut name = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        name.push_str("ctrl-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        name.push_str("alt-");
+    }
+    // `G` is written as is
+    let char = matches!(key.code, KeyCode::Char(ch) if ch != ' ');
+    if key.modifiers.contains(KeyModifiers::SHIFT) && !char {
+        name.push_str("shift-");
+    }
+
+    match KEYS.iter().find(|(_, code)| *code == key.code) {
+        Some((key, _)) => name.push_str(key),
+        None => match key.code {
+            KeyCode::Char(ch) => name.push(ch),
+            KeyCode::F(n) => name.push_str(&format!("f{}", n)),
+            code => name.push_str(&format!("{:?}", code).to_lowercase()),
+        },
+    }
+    name
+}
+
+/// The action called `name`, None for `none` which unbinds the key
+pub fn parse_action(name: &str) -> Result<Option<KeyBehavior>, String> {
+    if name == "none" {
+        return Ok(None);
+    }
+
+    match ACTIONS.iter().find(|(action, _)| *action == name) {
+        Some((_, behavior)) => Ok(Some(*behavior)),
+        None => {
+            let names = ACTIONS.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            Err(format!(
+                "Unknown action {:?}, expected none or one of {}",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// Keys separated by spaces, one for a single key binding and more for a sequence like `g g`,
+/// each one given in all the ways of [`parse_key`]
+pub fn parse_keys(spec: &str) -> Result<Vec<Vec<KeyEvent>>, String> {
+    let keys = spec
+        .split_whitespace()
+        .map(parse_key)
+        .collect::<Result<Vec<_>, _>>()?;
+    match keys.is_empty() {
+        true => Err("Missing key".to_string()),
+        false => Ok(keys),
+    }
+}
+
+/// `j`, `G`, `ctrl-g`, `alt-left`, `f5` and the like.
+///
+/// A char typed with Shift, like `G` or `:`, comes with the Shift modifier on some terminals
+/// and not on others, so all of them are given
+pub fn parse_key(spec: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+    // `-` alone or `ctrl--` is the key itself
+    while rest.len() > 1 {
+        let (modifier, after) = match rest.split_once('-') {
+            Some((modifier, after)) if !after.is_empty() => (modifier, after),
+            _ => break,
+        };
+        modifiers |= match modifier {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("Unknown modifier {:?} in key {:?}", modifier, spec)),
+        };
+        rest = after;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(ch), None) => KeyCode::Char(ch),
+        _ => match KEYS.iter().find(|(name, _)| *name == rest) {
+            Some((_, code)) => *code,
+            None => match rest.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n @ 1..=12) => KeyCode::F(n),
+                _ => return Err(format!("Unknown key {:?}", spec)),
+            },
+        },
+    };
+
+    Ok(match code {
+        KeyCode::Char(ch) if ch.is_ascii_uppercase() => {
+            vec![KeyEvent::new(code, modifiers | KeyModifiers::SHIFT)]
+        }
+        KeyCode::Char(ch) if !ch.is_alphanumeric() && !modifiers.contains(KeyModifiers::SHIFT) => {
+            vec![
+                KeyEvent::new(code, modifiers),
+                KeyEvent::new(code, modifiers | KeyModifiers::SHIFT),
+            ]
+        }
+        _ => vec![KeyEvent::new(code, modifiers)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn keys_with_modifiers() {
+        let (none, ctrl, alt, shift) = (
+            KeyModifiers::NONE,
+            KeyModifiers::CONTROL,
+            KeyModifiers::ALT,
+            KeyModifiers::SHIFT,
+        );
+        let cases = [
+            ("j", vec![key(KeyCode::Char('j'), none)]),
+            ("G", vec![key(KeyCode::Char('G'), shift)]),
+            ("ctrl-g", vec![key(KeyCode::Char('g'), ctrl)]),
+            ("alt-left", vec![key(KeyCode::Left, alt)]),
+            ("ctrl-alt-x", vec![key(KeyCode::Char('x'), ctrl | alt)]),
+            ("shift-tab", vec![key(KeyCode::Tab, shift)]),
+            ("f5", vec![key(KeyCode::F(5), none)]),
+            ("space", vec![key(KeyCode::Char(' '), none), key(KeyCode::Char(' '), shift)]),
+            // with Shift or not depending on the terminal
+            (":", vec![key(KeyCode::Char(':'), none), key(KeyCode::Char(':'), shift)]),
+            ("-", vec![key(KeyCode::Char('-'), none), key(KeyCode::Char('-'), shift)]),
+            (
+                "ctrl--",
+                vec![key(KeyCode::Char('-'), ctrl), key(KeyCode::Char('-'), ctrl | shift)],
+            ),
+        ];
+        for (spec, keys) in cases {
+            assert_eq!(parse_key(spec), Ok(keys), "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn bad_keys() {
+        for spec in ["", "meta-x", "ctrl-", "ctrl-nope", "f0", "f13", "fx", "jk"] {
+            assert!(parse_key(spec).is_err(), "{:?}", spec);
+        }
+        assert!(parse_key("hyper-x").unwrap_err().contains("modifier"));
+    }
+
+    #[test]
+    fn key_sequences() {
+        let keys = parse_keys("g  g").unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0], keys[1]);
+        assert_eq!(parse_keys("ctrl-x ctrl-c").unwrap().len(), 2);
+
+        assert!(parse_keys("").is_err());
+        assert!(parse_keys("   ").is_err());
+        assert!(parse_keys("g nope").is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Internals of the `rp` pager, usable to embed it in other applications.
+//!
+//! [`pager::Pager`] pages text a program already has, [`writer::UiContext`] fed by one of the
+//! [`reader`] functions does the rest, like the `rp` binary does. Given a
+//! [`backend::Headless`] screen instead of the terminal, it draws to a grid of cells, to test
+//! what it shows
+
+pub mod backend;
+mod clipboard;
+pub mod colors;
+pub mod compare;
+pub mod config;
+pub mod convert;
+pub mod decompress;
+pub mod diff;
+mod export;
+pub mod fold;
+#[cfg(feature = "highlight")]
+pub mod highlight;
+pub mod history;
+pub mod hook;
+pub mod image;
+pub mod keys;
+pub mod links;
+pub mod mmap;
+pub mod pager;
+pub mod preprocess;
+pub mod process;
+pub mod prompt;
+pub mod reader;
+pub mod shared;
+pub mod source;
+pub mod spill;
+pub mod timestamp;
+pub mod ui_theme;
+pub mod watch;
+#[cfg(feature = "ratatui")]
+pub mod widget;
+pub mod writer;
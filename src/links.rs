@@ -0,0 +1,177 @@
+// Links written as plain text in the lines, to open them
+
+use regex::Regex;
+use std::{ops::Range, path::Path, sync::OnceLock};
+
+const SCHEMES: &[&str] = &["https://", "http://"];
+
+/// What a link of a line opens
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Target {
+    Url(String),
+    /// a file at a line, written like `src/main.rs:12:5` by compilers and grep
+    File {
+        path: String,
+        line: usize,
+    },
+}
+
+/// char ranges of the `http://` and `https://` urls in `text`
+pub fn urls(text: &str) -> Vec<Range<usize>> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut urls = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        // not the end of a word, like `xhttp://`
+        let starts_word = idx == 0 || !chars[idx - 1].is_alphanumeric();
+        let scheme = SCHEMES
+            .iter()
+            .find(|scheme| starts_word && starts_with(&chars[idx..], scheme));
+
+        match scheme {
+            Some(scheme) => {
+                let host = idx + scheme.len();
+                let end = url_end(&chars, idx, host);
+                if end > host {
+                    urls.push(idx..end);
+                }
+                idx = end.max(host);
+            }
+            None => idx += 1,
+        }
+    }
+
+    urls
+}
+
+// `prefix` is ASCII, one char per byte
+fn starts_with(chars: &[char], prefix: &str) -> bool {
+    chars.len() >= prefix.len() && chars[..prefix.len()].iter().copied().eq(prefix.chars())
+}
+
+// the url goes on up to a space or a quote, without the punctuation ending a sentence
+// or the brackets it's written between
+fn url_end(chars: &[char], start: usize, from: usize) -> usize {
+    let is_url_char = |c: &char| !c.is_whitespace() && !c.is_control() && !"<>\"'`".contains(*c);
+    let mut end = from + chars[from..].iter().take_while(|c| is_url_char(c)).count();
+
+    while end > from {
+        let url = &chars[start..end];
+        let unbalanced = |open, close| {
+            let count = |c| url.iter().filter(|ch| **ch == c).count();
+            url[url.len() - 1] == close && count(open) < count(close)
+        };
+        if ".,;:!?".contains(chars[end - 1]) || unbalanced('(', ')') || unbalanced('[', ']') {
+            end -= 1;
+        } else {
+            break;
+        }
+    }
+
+    end
+}
+
+// `path:line` or `path:line:column`, a path with a `.` or a `/` only, see `is_path`
+fn file_position() -> &'static Regex {
+    static FILE_POSITION: OnceLock<Regex> = OnceLock::new();
+    FILE_POSITION.get_or_init(|| Regex::new(r"[\w.~+/-]*[./][\w.~+/-]*:(\d+)(?::\d+)?").unwrap())
+}
+
+/// char ranges of the urls and file positions in `text`, in order, with what they open
+pub fn targets(text: &str) -> Vec<(Range<usize>, Target)> {
+    let chars = |range: Range<usize>| {
+        let start = text[..range.start].chars().count();
+        start..start + text[range].chars().count()
+    };
+    let url_text = |url: &Range<usize>| text.chars().skip(url.start).take(url.len()).collect();
+
+    let urls = urls(text);
+    let mut targets = urls
+        .iter()
+        .map(|url| (url.clone(), Target::Url(url_text(url))))
+        .collect::<Vec<_>>();
+
+    for found in file_position().captures_iter(text) {
+        let (whole, line) = (found.get(0).unwrap(), found.get(1).unwrap());
+        let range = chars(whole.range());
+        // the port of a url isn't a line
+        let overlaps = |url: &Range<usize>| url.start < range.end && range.start < url.end;
+        if urls.iter().any(overlaps) {
+            continue;
+        }
+        let path = text[whole.start()..line.start() - 1].to_string();
+        if !is_path(&path) {
+            continue;
+        }
+        let line = line.as_str().parse().unwrap_or(1);
+        targets.push((range, Target::File { path, line }));
+    }
+
+    targets.sort_by_key(|(range, _)| range.start);
+    targets
+}
+
+// a path written with a `/`, or a file name with an extension that is there, not an address
+// and port like `10.0.0.1:8080` or `example.com:443`
+fn is_path(path: &str) -> bool {
+    if path.contains('/') {
+        return true;
+    }
+    let extension = path.rsplit_once('.').map_or("", |(_, extension)| extension);
+    !extension.is_empty()
+        && !extension.bytes().all(|b| b.is_ascii_digit())
+        && Path::new(path).is_file()
+}
+
+/// target of `text` nearest to char `idx`, the one it's in if any
+pub fn nearest(text: &str, idx: usize) -> Option<Target> {
+    let distance = |range: &Range<usize>| match idx {
+        _ if range.contains(&idx) => 0,
+        _ if idx < range.start => range.start - idx,
+        _ => idx + 1 - range.end,
+    };
+    let targets = targets(text).into_iter();
+    targets
+        .min_by_key(|(range, _)| distance(range))
+        .map(|(_, target)| target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(text: &str) -> Vec<Target> {
+        targets(text).into_iter().map(|(_, target)| target).collect()
+    }
+
+    fn file(path: &str, line: usize) -> Target {
+        Target::File {
+            path: path.into(),
+            line,
+        }
+    }
+
+    #[test]
+    fn positions_of_compilers_and_grep_are_files() {
+        assert_eq!(files("error at src/main.rs:12:5"), [file("src/main.rs", 12)]);
+        assert_eq!(files("./notes:3: todo"), [file("./notes", 3)]);
+        // in the current directory, the manifest of the crate
+        assert_eq!(files("Cargo.toml:1"), [file("Cargo.toml", 1)]);
+    }
+
+    #[test]
+    fn addresses_and_ports_are_not_files() {
+        assert_eq!(files("listening on 10.0.0.1:8080"), []);
+        assert_eq!(files("connect example.com:443 failed"), []);
+        assert_eq!(files("version 1.2:3"), []);
+    }
+
+    #[test]
+    fn ports_of_urls_are_not_lines() {
+        assert_eq!(
+            files("see https://example.com:8443/x"),
+            [Target::Url("https://example.com:8443/x".into())]
+        );
+    }
+}
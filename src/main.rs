@@ -1,52 +1,467 @@
-mod reader;
-mod shared;
-mod writer;
-
 use bumpalo::Bump;
 use crossbeam_queue::ArrayQueue;
 use crossbeam_utils::thread::scope;
+use clap::Parser;
 use crossterm::{tty::IsTty, Result};
+use rust_pager::{
+    compare,
+    config::{Bell, Config, Theme},
+    convert, decompress,
+    history::{self, Bookmarks, Positions, SavedSession},
+    keys::Keymap,
+    mmap,
+    preprocess::{self, Preprocessed},
+    process::{self, Command},
+    reader,
+    shared::{ReaderState, StyleTable},
+    spill::Spill,
+    ui_theme::UiTheme,
+    watch::Watcher,
+    writer::{self, Dump, Freeze, StartupCommand},
+};
+#[cfg(feature = "highlight")]
+use rust_pager::highlight;
+use std::ffi::OsString;
 use std::fs::File;
-use std::path::PathBuf;
-use std::sync::{atomic, Arc};
-
-static RUN: atomic::AtomicBool = atomic::AtomicBool::new(true);
+use std::path::{Path, PathBuf};
+use std::sync::{atomic, Arc, Mutex};
+use std::time::SystemTime;
 
+/// Yet another pager in Rust
+#[derive(Parser)]
+#[command(
+    name = "rp",
+    version,
+    override_usage = "<command> | rp [OPTIONS] [+COMMAND]...\n       rp [OPTIONS] [+COMMAND]... <PATH>...\n       rp [OPTIONS] [+COMMAND]... -- <COMMAND>...",
+    after_help = "Startup commands:\n  +G           Start at the end\n  +/<PATTERN>  Start at the first line matching the pattern\n  +<N>         Start at line N"
+)]
 struct Args {
-    path: Option<PathBuf>,
+    /// Files to show, one at a time
+    #[arg(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+    /// Display lines in reverse order (newest first)
+    #[arg(long)]
+    reverse: bool,
+    /// Collapse consecutive identical lines
+    #[arg(long)]
+    dedup: bool,
+    /// Keep reading the file as it grows, reopen it when rotated or truncated
+    #[arg(long)]
+    follow_name: bool,
+    /// Reload the file when it changes, keeping the position
+    #[arg(long)]
+    watch: bool,
+    /// Print the input and quit if it fits on one screen
+    #[arg(short = 'F', long)]
+    quit_if_one_screen: bool,
+    /// Quit when scrolling forward at the end of the input
+    #[arg(short = 'e', long)]
+    quit_at_eof: bool,
+    /// Quit as soon as scrolling forward reaches the end of the input
+    #[arg(short = 'E', long = "QUIT-AT-EOF")]
+    quit_at_first_eof: bool,
+    /// Write the input to stdout on quit, as received or as text only [styled, plain]
+    #[arg(
+        long,
+        value_name = "STYLE",
+        value_parser = parse_dump_arg,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "styled"
+    )]
+    dump: Option<Dump>,
+    /// Don't remember where each file was left
+    #[arg(long)]
+    no_save_position: bool,
+    /// Reopen the files of the session NAME where they were left, with their searches and
+    /// filters, and save it again on quit; the files given start it over
+    #[arg(long, value_name = "NAME", value_parser = parse_session_name)]
+    session: Option<String>,
+    /// Memory used for piped input before spilling to a temporary file [default: 256MiB]
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    spill_threshold: Option<usize>,
+    /// Drop the lines far from the view past this size, piped input spills at half of it
+    #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+    max_memory: Option<usize>,
+    /// Convert each file with a LESSOPEN style command before showing it,
+    /// `|CMD %s` pages its output, `CMD %s` prints the name of a file to show
+    #[arg(long, value_name = "CMD")]
+    preprocess: Option<String>,
+    /// Ignore the LESSOPEN environment variable
+    #[arg(short = 'L', long)]
+    no_lessopen: bool,
+    /// Give the command a pipe instead of a terminal
+    #[arg(long)]
+    no_pty: bool,
+    /// Don't tint the lines the command writes to stderr
+    #[arg(long)]
+    no_stderr_tint: bool,
+    /// Show two files side by side, with the lines that differ highlighted
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["paths", "command"])]
+    diff: Vec<PathBuf>,
+    /// Lines starting the sections `{` and `}` move between and `T` lists, man page
+    /// headings, diff files, Markdown headings and `== name ==` markers by default
+    #[arg(long, value_name = "REGEX", value_parser = regex::Regex::new)]
+    section: Option<regex::Regex>,
+    /// Keep the first N lines on top while the rest scrolls, like column names
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    header: usize,
+    /// Keep the first N columns in view when panning unwrapped lines, or the first N fields
+    /// with `Nf` for fields separated by spaces and `NfC` for fields separated by C
+    #[arg(long, value_name = "SPEC", value_parser = parse_freeze)]
+    freeze: Option<Freeze>,
+    /// Key bindings to start from: default, less, vim, emacs or empty, before the `bind` lines
+    /// of the config
+    #[arg(long, value_name = "PRESET", value_parser = parse_keymap)]
+    keys: Option<Keymap>,
+    /// What the prompt shows instead of the line range, like `%f %lt-%lb/%L %p%%`, see
+    /// `prompt` in the config
+    #[arg(short = 'P', long, value_name = "FORMAT")]
+    prompt: Option<String>,
+    /// Capture the mouse whatever the config says, `M` toggles it
+    #[arg(long, conflicts_with = "no_mouse")]
+    mouse: bool,
+    /// Leave the mouse to the terminal to select text, the wheel still scrolls
+    #[arg(long)]
+    no_mouse: bool,
+    /// Show the input without its colors and attributes, like `theme = mono`
+    #[arg(long)]
+    mono: bool,
+    /// Colors of the prompt, the search matches and the rest drawn by rp: default (reverse
+    /// video), dark or light, picked for the background of the terminal unless given
+    #[arg(long, value_name = "THEME", value_parser = parse_ui_theme)]
+    ui_theme: Option<UiTheme>,
+    /// Tell failed actions, like a search without a match, by flashing the prompt line
+    /// (visual), with the bell of the terminal (audible) or not at all (off)
+    #[arg(long, value_name = "BELL", value_parser = parse_bell)]
+    bell: Option<Bell>,
+    /// Show a scrollbar on the right edge, with the search matches marked on it
+    #[arg(long)]
+    scrollbar: bool,
+    /// Show where the search matches and stderr lines are in the whole input, in a column
+    /// left of the scrollbar
+    #[arg(long)]
+    minimap: bool,
+    /// Show a row of column numbers on top, panned along with the lines
+    #[arg(long)]
+    ruler: bool,
+    /// Show the file name and the percentage in the title of the window
+    #[arg(long)]
+    title: bool,
+    /// Underline the http(s) urls of the lines, Ctrl-click or `gx` opens them
+    #[arg(long)]
+    urls: bool,
+    /// Underline the `path:line` positions of compilers and grep, opened in `$EDITOR` the same way
+    #[arg(long)]
+    file_links: bool,
+    /// Show the escape sequences of the input as caret text, like `^[[31m`, instead of their
+    /// colors; `-r` toggles it
+    #[arg(long)]
+    escapes: bool,
+    /// Columns to draw guides at, like `80,120`, counted from 1
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    guides: Vec<usize>,
+    /// Color lines without colors by their log level, see `level` in the config
+    #[arg(long)]
+    log_colors: bool,
+    /// Don't color the added and removed lines of a diff without colors
+    #[arg(long)]
+    no_diff_colors: bool,
+    /// Show source files as they are, without syntax highlighting
+    #[cfg(feature = "highlight")]
+    #[arg(long)]
+    no_highlight: bool,
+    /// Colors of the syntax highlighting
+    #[cfg(feature = "highlight")]
+    #[arg(
+        long,
+        value_name = "THEME",
+        value_parser = parse_theme_arg,
+        default_value = rust_pager::highlight::DEFAULT_THEME
+    )]
+    syntax_theme: String,
+    /// Command to run and page the output of, its exit status is rp's
+    #[arg(last = true, value_name = "COMMAND")]
+    command: Vec<OsString>,
+    #[arg(skip)]
+    startup: Vec<StartupCommand>,
+}
+
+/// Reader of the input shown, stopped on ctrl-c
+static READER: Mutex<Option<Arc<ReaderState>>> = Mutex::new(None);
+
+/// What is kept from one input to the next
+struct Session {
+    /// the files given and the ones opened with `:e` since
+    paths: Vec<PathBuf>,
+    states: Vec<Option<writer::ViewState>>,
+    positions: Option<Positions>,
+    searches: Vec<String>,
+    bookmarks: Bookmarks,
+    /// where each file of `--session` was left
+    views: SavedSession,
+    command: Option<Command>,
+    /// outputs of the command, read once
+    command_output: Option<process::Output>,
+    /// the files shown before, as they were read
+    buffers: Vec<Option<Buffer>>,
+}
+
+/// A file as it was read, shown again when switching back to it unless it changed since
+struct Buffer {
+    file: File,
+    /// the preprocessor's output, or else the mapping of the file
+    output: Option<Vec<u8>>,
+    mapped: Option<mmap::Mmap>,
+    /// the file the preprocessor replaced it with, its name tells the syntax
+    replacement: Option<PathBuf>,
+    /// length and modification time of the file when it was read
+    stamp: Option<(u64, SystemTime)>,
+}
+
+impl Buffer {
+    // the file at `path` is still the one read, or is gone and only the buffer is left of it
+    fn is_current(&self, path: &Path) -> bool {
+        match std::fs::metadata(path) {
+            Ok(metadata) => stamp(&metadata) == self.stamp,
+            Err(_) => true,
+        }
+    }
+}
+
+fn stamp(metadata: &std::fs::Metadata) -> Option<(u64, SystemTime)> {
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+impl Session {
+    /// index of `path` in the inputs, added after them if it's not one yet
+    fn open(&mut self, path: &Path) -> usize {
+        match self.paths.iter().position(|p| p == path) {
+            Some(index) => index,
+            None => {
+                self.paths.push(path.to_path_buf());
+                self.states.push(None);
+                self.buffers.push(None);
+                self.paths.len() - 1
+            }
+        }
+    }
+}
+
+fn parse_size_arg(s: &str) -> std::result::Result<usize, String> {
+    s.parse()
+        .ok()
+        .or_else(|| convert::parse_size(s).map(|n| n as usize))
+        .ok_or_else(|| format!("invalid size: {}", s))
+}
+
+fn parse_dump_arg(s: &str) -> std::result::Result<Dump, String> {
+    Dump::from_name(s).ok_or_else(|| format!("expected styled or plain: {}", s))
+}
+
+#[cfg(feature = "highlight")]
+fn parse_theme_arg(s: &str) -> std::result::Result<String, String> {
+    match highlight::theme_names().any(|name| name == s) {
+        true => Ok(s.to_string()),
+        false => {
+            let mut names = highlight::theme_names().collect::<Vec<_>>();
+            names.sort_unstable();
+            Err(format!("expected one of {}: {}", names.join(", "), s))
+        }
+    }
+}
+
+// the name of a file of the sessions directory
+fn parse_session_name(name: &str) -> std::result::Result<String, String> {
+    match SavedSession::is_valid_name(name) {
+        true => Ok(name.to_string()),
+        false => Err(format!("expected a name without slashes: {}", name)),
+    }
+}
+
+fn parse_keymap(name: &str) -> std::result::Result<Keymap, String> {
+    Keymap::from_name(name)
+        .ok_or_else(|| format!("expected default, less, vim, emacs or empty: {}", name))
+}
+
+// `--mono`, or the config theme without colors when `NO_COLOR` is set, see no-color.org
+fn theme(args: &Args, config: &Config) -> Theme {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    match config.theme {
+        _ if args.mono => Theme::Mono,
+        Theme::Colorful if no_color => Theme::Plain,
+        theme => theme,
+    }
+}
+
+fn parse_ui_theme(name: &str) -> std::result::Result<UiTheme, String> {
+    UiTheme::from_name(name).ok_or_else(|| format!("expected default, dark or light: {}", name))
+}
+
+fn parse_bell(name: &str) -> std::result::Result<Bell, String> {
+    Bell::from_name(name).ok_or_else(|| format!("expected visual, audible or off: {}", name))
+}
+
+fn parse_freeze(spec: &str) -> std::result::Result<Freeze, String> {
+    Freeze::parse(spec).ok_or_else(|| format!("expected N, Nf or NfC: {}", spec))
+}
+
+// the `+` commands where a path would go, apart from the other args, or the unknown one.
+// The values of the options and the files named like a command, those `exists` tells, are left
+// to clap
+fn split_startup(
+    args: impl IntoIterator<Item = OsString>,
+    exists: impl Fn(&Path) -> bool,
+) -> std::result::Result<(Vec<OsString>, Vec<StartupCommand>), String> {
+    use clap::CommandFactory;
+
+    let mut cli = Args::command();
+    cli.build();
+    // the options followed by a value, and how many
+    let mut valued = Vec::new();
+    for arg in cli.get_arguments().filter(|arg| !arg.is_positional()) {
+        if !arg.get_action().takes_values() {
+            continue;
+        }
+        let count = arg.get_num_args().map_or(1, |range| range.min_values());
+        let names = arg.get_long().map(|long| format!("--{}", long));
+        let names = names.into_iter().chain(arg.get_short().map(|s| format!("-{}", s)));
+        valued.extend(names.map(|name| (name, count)));
+    }
+
+    let mut rest = Vec::new();
+    let mut startup = Vec::new();
+    let mut options = true;
+    // values of the option before still to come
+    let mut values = 0;
+
+    for arg in args {
+        let command = arg.to_str().and_then(|a| a.strip_prefix('+'));
+        match command {
+            Some(command)
+                if options && values == 0 && !rest.is_empty() && !exists(Path::new(&arg)) =>
+            {
+                startup.push(parse_startup(command).ok_or_else(|| command.to_string())?);
+            }
+            _ => {
+                if values > 0 {
+                    values -= 1;
+                } else if options {
+                    options = arg != "--";
+                    values = valued
+                        .iter()
+                        .find(|(name, _)| arg.to_str() == Some(name.as_str()))
+                        .map_or(0, |(_, count)| *count);
+                }
+                rest.push(arg);
+            }
+        }
+    }
+
+    Ok((rest, startup))
+}
+
+fn parse_startup(arg: &str) -> Option<StartupCommand> {
+    match arg {
+        "G" => Some(StartupCommand::End),
+        _ if arg.starts_with('/') && arg.len() > 1 => {
+            Some(StartupCommand::Search(arg[1..].to_string()))
+        }
+        _ => arg.parse().ok().map(StartupCommand::Line),
+    }
 }
 
 impl Args {
-    pub fn parse() -> Option<Self> {
-        let mut args = pico_args::Arguments::from_env();
+    /// `+` commands are taken out first, anywhere before a `--`
+    pub fn from_env() -> Self {
+        let (rest, startup) = match split_startup(std::env::args_os(), Path::exists) {
+            Ok(split) => split,
+            Err(command) => {
+                eprintln!("rp: unknown startup command: +{}", command);
+                std::process::exit(2);
+            }
+        };
 
-        if args.contains(["-h", "--help"]) {
-            println!("rp 0.1.0");
-            println!("USAGE: `<command> | rp` or `rp <path>`");
-            return None;
+        let mut args = Self::parse_from(rest);
+        args.startup = startup;
+        args
+    }
+
+    /// only one input, that can't be reopened
+    fn piped(&self) -> bool {
+        !self.command.is_empty() || !self.diff.is_empty() || !std::io::stdin().is_tty()
+    }
+
+    /// `--preprocess`, or LESSOPEN like less
+    fn preprocessor(&self) -> Option<String> {
+        let lessopen = std::env::var("LESSOPEN").ok().filter(|_| !self.no_lessopen);
+        self.preprocess.clone().or(lessopen).filter(|t| !t.is_empty())
+    }
+
+    fn quit_at_end(&self) -> usize {
+        match (self.quit_at_first_eof, self.quit_at_eof) {
+            (true, _) => 1,
+            (false, true) => 2,
+            (false, false) => 0,
         }
+    }
 
-        Some(Self {
-            path: args.free_from_str().ok(),
-        })
+    fn spill_threshold(&self) -> usize {
+        self.spill_threshold
+            .or_else(|| self.max_memory.map(|max| max / 2))
+            .unwrap_or(256 << 20)
     }
 }
 
+// as wide as the view, less the column the wrapping keeps free
+fn compare_files(a: &Path, b: &Path) -> Result<Vec<u8>> {
+    let (columns, _) = crossterm::terminal::size()?;
+    let columns = (columns as usize).saturating_sub(1);
+    let (a, b) = (std::fs::read(a)?, std::fs::read(b)?);
+    Ok(compare::side_by_side(&a, &b, columns))
+}
+
+/// a file of a known syntax, highlighted unless turned off
+#[cfg(feature = "highlight")]
+fn highlighted<'a>(
+    args: &Args,
+    path: Option<&Path>,
+    data: &'a [u8],
+) -> Option<Box<dyn std::io::Read + Send + 'a>> {
+    let path = path.filter(|_| !args.no_highlight)?;
+    let input = highlight::highlight(path, data, &args.syntax_theme, data)?;
+    Some(Box::new(input))
+}
+
+#[cfg(not(feature = "highlight"))]
+fn highlighted<'a>(
+    _args: &Args,
+    _path: Option<&Path>,
+    _data: &'a [u8],
+) -> Option<Box<dyn std::io::Read + Send + 'a>> {
+    None
+}
+
 #[cfg(unix)]
-fn get_input(args: &crate::Args) -> Result<File> {
+fn get_input(path: Option<&Path>) -> Result<File> {
     if !std::io::stdin().is_tty() {
         unsafe {
             use std::os::unix::prelude::FromRawFd;
-            let stdin = File::from_raw_fd(libc::STDIN_FILENO);
+            // duplicate so closing it never frees fd 0 for the tty opened later
+            let fd = libc::dup(libc::STDIN_FILENO);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let stdin = File::from_raw_fd(fd);
             Ok(stdin)
         }
     } else {
-        Ok(File::open(args.path.as_deref().expect("No given path"))?)
+        Ok(File::open(path.expect("No given path"))?)
     }
 }
 
 #[cfg(windows)]
-fn get_input(args: &crate::Args) -> Result<File> {
+fn get_input(path: Option<&Path>) -> Result<File> {
     if !std::io::stdin().is_tty() {
         unsafe {
             use std::os::windows::prelude::{FromRawHandle, AsRawHandle};
@@ -54,7 +469,7 @@ fn get_input(args: &crate::Args) -> Result<File> {
             Ok(stdin)
         }
     } else {
-        Ok(File::open(args.path.as_deref().expect("No given path"))?)
+        Ok(File::open(path.expect("No given path"))?)
     }
 }
 
@@ -72,27 +487,502 @@ fn main() -> Result<()> {
     }
 
     ctrlc::set_handler(|| {
-        crate::RUN.store(false, atomic::Ordering::Release);
+        if let Some(input_state) = &*READER.lock().unwrap() {
+            input_state.run.store(false, atomic::Ordering::Release);
+        }
     })
     .expect("Set ctrlc handler");
+    mmap::handle_shrinking();
 
-    let args = match Args::parse() {
-        Some(args) => args,
-        None => return Ok(()),
+    let mut args = Args::from_env();
+    let saved_config = Config::load();
+    // ask for the preferences once, until there is a config file
+    let first_run = saved_config.is_none() && Config::path().is_some();
+    let config = saved_config.unwrap_or_default();
+
+    let (command, command_output) = if args.command.is_empty() {
+        (None, None)
+    } else {
+        match Command::spawn(&args.command, !args.no_pty) {
+            Ok((command, output)) => (Some(command), Some(output)),
+            Err(e) => {
+                eprintln!("rp: can't run {}: {}", args.command[0].to_string_lossy(), e);
+                std::process::exit(127);
+            }
+        }
+    };
+
+    // the files of the session unless others are given
+    let views = args.session.as_deref().and_then(SavedSession::load);
+    let views = views.unwrap_or_default();
+    let mut current = 0;
+    if args.paths.is_empty() && !args.piped() && !views.inputs.is_empty() {
+        args.paths = views.inputs.iter().map(|(path, _)| path.clone()).collect();
+        current = views.current;
+    }
+
+    // piped input is the only one, otherwise the files are shown one at a time
+    let inputs = if args.piped() {
+        1
+    } else {
+        args.paths.len().max(1)
+    };
+    let mut session = Session {
+        paths: args.paths.clone(),
+        states: (0..inputs).map(|_| None).collect(),
+        positions: if !args.no_save_position {
+            Some(Positions::load())
+        } else {
+            None
+        },
+        searches: history::load_searches(),
+        bookmarks: Bookmarks::load(),
+        views,
+        command,
+        command_output,
+        buffers: (0..inputs).map(|_| None).collect(),
+    };
+    let mut first_run = first_run;
+
+    while let Some((state, next)) = page(&args, &config, first_run, current, &mut session)? {
+        first_run = false;
+
+        // an input shown for the first time starts at the top, or where the session left it
+        let saved = session.paths.get(next).and_then(|path| session.views.view(path));
+        if session.states[next].is_none() && next != current && saved.is_none() {
+            session.states[next] = Some(state.for_other_input());
+        }
+        session.states[current] = Some(state);
+        current = next;
+    }
+
+    if let Some(Err(e)) = session.positions.map(|positions| positions.save()) {
+        eprintln!("rp: can't save the positions: {}", e);
+    }
+    if let Err(e) = history::save_searches(&session.searches) {
+        eprintln!("rp: can't save the searches: {}", e);
+    }
+    if let Err(e) = session.bookmarks.save() {
+        eprintln!("rp: can't save the bookmarks: {}", e);
+    }
+    // piped input can't be reopened
+    if let (Some(name), false) = (&args.session, args.piped()) {
+        let views = &session.views;
+        let inputs = session.paths.iter().map(|path| {
+            let view = views.view(path).unwrap_or_default();
+            (path.clone(), view)
+        });
+        let saved = SavedSession {
+            inputs: inputs.collect(),
+            current,
+        };
+        if let Err(e) = saved.save(name) {
+            eprintln!("rp: can't save the session {}: {}", name, e);
+        }
+    }
+
+    if let Some(command) = session.command {
+        std::process::exit(command.wait()?);
+    }
+
+    Ok(())
+}
+
+/// read the `index`-th input and show it until quit, returns the state to restore
+/// and the input to show next if a reload or another input was asked
+fn page(
+    args: &Args,
+    config: &Config,
+    first_run: bool,
+    index: usize,
+    session: &mut Session,
+) -> Result<Option<(writer::ViewState, usize)>> {
+    let state = session.states[index].take();
+    // detected unless toggled for this input
+    let hex = state.as_ref().and_then(|state| state.hex());
+    // piped input can't be reopened
+    let path = if args.piped() {
+        None
+    } else {
+        session.paths.get(index).cloned()
+    };
+    let path = path.as_deref();
+    let stamp = path
+        .and_then(|path| std::fs::metadata(path).ok())
+        .and_then(|m| stamp(&m));
+    // shown before and not changed since, it's not read again
+    let mut kept = session.buffers[index]
+        .take()
+        .filter(|buffer| path.is_some_and(|path| buffer.is_current(path)));
+    // a followed file is read as it grows, not converted once
+    let preprocessed = match (path, args.preprocessor()) {
+        (Some(path), Some(template)) if !args.follow_name && kept.is_none() => {
+            preprocess::run(&template, path)
+        }
+        _ => None,
+    };
+    let (output, replacement) = match preprocessed {
+        Some(Preprocessed::Output(output)) => (Some(output), None),
+        Some(Preprocessed::Replacement(replacement)) => (None, Some(replacement)),
+        None => (None, None),
+    };
+    // the comparison is paged like the preprocessor's output
+    let output = match args.diff.as_slice() {
+        [a, b] => Some(compare_files(a, b)?),
+        _ => output.or_else(|| kept.as_mut().and_then(|buffer| buffer.output.take())),
+    };
+    let (stdin, stderr) = match session.command_output.take() {
+        Some(output) => (output.stdout, output.stderr),
+        // the first file only stands for the input, the comparison is read instead
+        None if !args.diff.is_empty() => (File::open(&args.diff[0])?, None),
+        None => match &kept {
+            Some(buffer) => (buffer.file.try_clone()?, None),
+            None => (get_input(replacement.as_deref().or(path))?, None),
+        },
+    };
+    // files shown earlier in this run have their whole state already,
+    // the startup commands only apply to the first one
+    let startup = match state {
+        None => args.startup.clone(),
+        Some(_) => Vec::new(),
+    };
+    let quit_if_one_screen = args.quit_if_one_screen && state.is_none() && session.states.len() == 1;
+    // a file of the session, shown for the first time
+    let saved = match path {
+        Some(path) if state.is_none() && startup.is_empty() => session.views.view(path),
+        _ => None,
+    };
+    let start_line = match (&session.positions, path, &saved) {
+        (Some(positions), Some(path), None) if state.is_none() && startup.is_empty() => {
+            positions.get(path)
+        }
+        _ => None,
+    };
+    let follow_path = path.filter(|_| args.follow_name);
+    // the file the bytes come from, its name tells the syntax,
+    // the preprocessor's output has its own colors if any
+    let kept_replacement = kept.as_mut().and_then(|buffer| buffer.replacement.take());
+    let replaced = replacement.as_deref().or(kept_replacement.as_deref());
+    let source = match &output {
+        Some(_) => None,
+        None => replaced.or(path),
+    };
+    // a followed file keeps growing past the mapping
+    let mapped = match (kept.and_then(|buffer| buffer.mapped), follow_path, &output) {
+        (Some(mapped), _, _) => Some(mapped),
+        (None, None, None) => mmap::Mmap::new(&stdin),
+        _ => None,
     };
-    let stdin = get_input(&args)?;
+    // to keep the buffer once switching to another input
+    let file = match (path, &output, &mapped) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => stdin.try_clone().ok(),
+        _ => None,
+    };
+    // the preprocessor's output is read like a mapped file
+    let data = output.as_deref().or(mapped.as_ref().map(|m| m.as_slice()));
+    let compressed = data.and_then(decompress::Format::detect);
+    let plain = data.filter(|_| compressed.is_none());
+    let dump_hex = plain.is_some_and(|data| hex.unwrap_or_else(|| reader::looks_binary(data)));
+    let highlighted = plain
+        .filter(|_| !dump_hex)
+        .and_then(|data| highlighted(args, source, data));
+    let size = match data {
+        // only the decompressed or highlighted size would tell the progress
+        Some(_) if compressed.is_some() || highlighted.is_some() => 0,
+        Some(data) => data.len() as u64,
+        None => stdin
+            .metadata()
+            .ok()
+            .filter(|m| m.is_file())
+            .map_or(0, |m| m.len()),
+    };
+    let mut input_state = ReaderState::new(size);
+    if let Some(mapped) = &mapped {
+        input_state.shrunk = mapped.shrunk();
+    }
+    let input_state = Arc::new(input_state);
+    *READER.lock().unwrap() = Some(input_state.clone());
+    let watcher = path.filter(|_| args.watch).and_then(Watcher::new);
     let rx = Arc::new(ArrayQueue::new(1024 * 16));
+    let styles = Arc::new(StyleTable::new());
     let mut b = Bump::with_capacity(1024 * 1024);
+    let spill = Spill::new(args.spill_threshold());
 
-    scope(|s| {
+    let ret = scope(|s| {
         let tx = rx.clone();
+        let reader_styles = styles.clone();
+        let reader_state = input_state.clone();
         s.builder()
             .name("stdin".into())
-            .spawn(|_| reader::read_from_stdin(stdin, &mut b, tx))?;
+            .spawn(|_| {
+                let ret = match (data, follow_path, stderr) {
+                    (Some(data), _, _) => match compressed {
+                        Some(format) => decompress::decoder(format, data).and_then(|input| {
+                            reader::read_input(
+                                input,
+                                hex,
+                                &mut b,
+                                &spill,
+                                tx,
+                                reader_styles,
+                                reader_state,
+                            )
+                        }),
+                        None if dump_hex => {
+                            reader::read_hex(data, &mut b, &spill, tx, reader_styles, reader_state)
+                        }
+                        None => match highlighted {
+                            Some(input) => reader::read_input(
+                                input,
+                                Some(false),
+                                &mut b,
+                                &spill,
+                                tx,
+                                reader_styles,
+                                reader_state,
+                            ),
+                            None => reader::read_from_mmap(data, tx, reader_styles, reader_state),
+                        },
+                    },
+                    (None, Some(path), _) => reader::follow_file(
+                        path,
+                        stdin,
+                        &mut b,
+                        &spill,
+                        tx,
+                        reader_styles,
+                        reader_state,
+                    ),
+                    #[cfg(unix)]
+                    (None, None, Some(stderr)) => reader::read_from_command(
+                        stdin,
+                        stderr,
+                        &mut b,
+                        &spill,
+                        tx,
+                        reader_styles,
+                        reader_state,
+                    ),
+                    (None, None, _) => decompress::auto(stdin).and_then(|input| {
+                        reader::read_input(
+                            input,
+                            hex,
+                            &mut b,
+                            &spill,
+                            tx,
+                            reader_styles,
+                            reader_state,
+                        )
+                    }),
+                };
+                input_state.done.store(true, atomic::Ordering::Release);
+                ret
+            })?;
 
-        writer::UiContext::new(rx)?.run()?;
+        let ret = writer::UiContext::new(rx, styles, input_state.clone())
+            .and_then(|ui| ui.mouse(!args.no_mouse && (args.mouse || config.mouse)))
+            .and_then(|ui| {
+                let ui = ui
+                    .wrap(config.wrap)
+                    .theme(theme(args, config))
+                    .ui_theme(args.ui_theme.or(config.ui_theme))
+                    .bell(args.bell.unwrap_or(config.bell))
+                    .acceleration(config.acceleration.clone())
+                    .wheel_lines(config.wheel_lines)
+                    .log_colors(args.log_colors || config.log_colors)
+                    .log_levels(config.log_levels.clone())
+                    .keymap(args.keys.unwrap_or(config.keymap))
+                    .bind(&config.bindings)
+                    .setup(first_run)
+                    .reverse(args.reverse)
+                    .dedup(args.dedup)
+                    .max_memory(args.max_memory)
+                    .reloadable(path.is_some())
+                    .watch(watcher)
+                    .inputs(index, input_list(args, &session.paths, &session.states))
+                    .start_at(start_line)
+                    .startup(startup)
+                    .quit_if_one_screen(quit_if_one_screen)
+                    .dump_on_quit(args.dump)
+                    .quit_at_end(args.quit_at_end())
+                    .tint_stderr(!args.no_stderr_tint)
+                    .diff_colors(!args.no_diff_colors)
+                    .section(args.section.clone().or(config.section.clone()))
+                    .header(args.header)
+                    .freeze(args.freeze)
+                    .scrollbar(args.scrollbar || config.scrollbar)
+                    .minimap(args.minimap || config.minimap)
+                    .ruler(args.ruler || config.ruler)
+                    .title(args.title || config.title)
+                    .urls(args.urls || config.urls)
+                    .file_links(args.file_links || config.file_links)
+                    .escapes(args.escapes)
+                    .guides(match args.guides.is_empty() {
+                        true => config.guides.clone(),
+                        false => args.guides.clone(),
+                    })
+                    .prompt_format(args.prompt.clone().or_else(|| config.prompt.clone()))
+                    .search_history(session.searches.clone())
+                    .bookmarks(path.map_or_else(Vec::new, |path| session.bookmarks.get(path)));
+                match (state, saved) {
+                    (Some(state), _) => ui.restore(state),
+                    (None, Some(view)) => Ok(ui.restore_saved(view)),
+                    (None, None) => Ok(ui),
+                }
+            })
+            .and_then(|mut ui| {
+                ui.run()?;
+                // a file that can't be opened anymore leaves this input shown
+                while let Some(e) = next_error(&ui, index, session) {
+                    ui.stay(e);
+                    ui.run()?;
+                }
+                session.searches = ui.searches().to_vec();
+                if let Some(path) = path {
+                    session.bookmarks.set(path, ui.bookmark_list());
+                    session.views.set(path, ui.saved_view());
+                }
+                if let (Some(positions), Some(path)) = (&mut session.positions, path) {
+                    positions.set(path, ui.top_line());
+                }
+                let next = ui.reload_state().map(|state| {
+                    let next = match ui.next_path() {
+                        Some(path) => session.open(path),
+                        None => ui.next_input().unwrap_or(index),
+                    };
+                    (state, next)
+                });
+                if let (None, Some(dump)) = (&next, ui.dump_mode()) {
+                    ui.dump(&mut std::io::stdout().lock(), dump)?;
+                }
+                Ok(next)
+            });
 
-        Ok(())
+        // stop the reader too, a command still writing would keep it waiting
+        input_state.run.store(false, atomic::Ordering::Release);
+        if let Some(command) = &mut session.command {
+            command.stop();
+        }
+        ret
     })
-    .unwrap()
+    .unwrap();
+
+    if let (Some(path), Some(replacement), Some(template)) =
+        (path, &replacement, std::env::var_os("LESSCLOSE"))
+    {
+        preprocess::close(&template.to_string_lossy(), path, replacement);
+    }
+
+    // kept to switch back to, a reload reads the file again
+    if let (Ok(Some((_, next))), Some(file)) = (&ret, file) {
+        if *next != index {
+            session.buffers[index] = Some(Buffer {
+                file,
+                output,
+                mapped,
+                replacement: replacement.or(kept_replacement),
+                stamp,
+            });
+        }
+    }
+
+    ret
+}
+
+// why the input `ui` switches to can't be shown, if it can't
+fn next_error(ui: &writer::UiContext, index: usize, session: &Session) -> Option<String> {
+    ui.reload_state()?;
+    let next = ui.next_input().filter(|next| *next != index)?;
+    let path = session.paths.get(next)?;
+    let kept = session.buffers[next].as_ref();
+    if kept.is_some_and(|buffer| buffer.is_current(path)) {
+        return None;
+    }
+
+    let e = File::open(path).err()?;
+    Some(format!("Can't open {}: {}", path.display(), e))
+}
+
+fn input_list(
+    args: &Args,
+    paths: &[PathBuf],
+    states: &[Option<writer::ViewState>],
+) -> Vec<writer::InputInfo> {
+    if args.piped() {
+        let name = if let [a, b] = args.diff.as_slice() {
+            format!("{} {}", a.display(), b.display())
+        } else if args.command.is_empty() {
+            "-".into()
+        } else {
+            let args = args.command.iter().map(|arg| arg.to_string_lossy());
+            args.collect::<Vec<_>>().join(" ")
+        };
+        return vec![writer::InputInfo {
+            name,
+            size: None,
+            line: None,
+        }];
+    }
+
+    paths
+        .iter()
+        .zip(states)
+        .map(|(path, state)| writer::InputInfo {
+            name: path.display().to_string(),
+            size: std::fs::metadata(path).ok().map(|m| m.len()),
+            line: state.as_ref().and_then(|state| state.line()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_commands() {
+        assert!(matches!(parse_startup("G"), Some(StartupCommand::End)));
+        assert!(matches!(
+            parse_startup("42"),
+            Some(StartupCommand::Line(42))
+        ));
+        assert!(matches!(
+            parse_startup("/a b"),
+            Some(StartupCommand::Search(pattern)) if pattern == "a b"
+        ));
+        for arg in ["", "/", "g", "-1", "4x"] {
+            assert!(parse_startup(arg).is_none(), "{:?}", arg);
+        }
+    }
+
+    // with `+notes.txt` a file
+    fn split(args: &[&str]) -> std::result::Result<(Vec<OsString>, usize), String> {
+        let args = args.iter().map(OsString::from);
+        let exists = |path: &Path| path == Path::new("+notes.txt");
+        split_startup(args, exists).map(|(rest, startup)| (rest, startup.len()))
+    }
+
+    #[test]
+    fn startup_commands_only_where_a_path_goes() {
+        let (rest, startup) = split(&["rp", "+G", "-P", "+%f", "file", "+/x"]).unwrap();
+        assert_eq!(rest, ["rp", "-P", "+%f", "file"]);
+        assert_eq!(startup, 2);
+
+        let (rest, startup) = split(&["rp", "--diff", "+a", "+b", "+10"]).unwrap();
+        assert_eq!(rest, ["rp", "--diff", "+a", "+b"]);
+        assert_eq!(startup, 1);
+
+        // after `--`, the command's args
+        let (rest, startup) = split(&["rp", "--", "cmd", "+1"]).unwrap();
+        assert_eq!((rest.len(), startup), (4, 0));
+
+        assert_eq!(split(&["rp", "+nope"]), Err("nope".to_string()));
+    }
+
+    #[test]
+    fn files_named_like_a_startup_command() {
+        let (rest, startup) = split(&["rp", "+notes.txt", "+G"]).unwrap();
+        assert_eq!(rest, ["rp", "+notes.txt"]);
+        assert_eq!(startup, 1);
+    }
 }
@@ -0,0 +1,370 @@
+use std::{
+    convert::TryFrom,
+    fs::File,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+#[cfg(unix)]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Once, OnceLock,
+};
+
+/// mappings the bus error handler knows about, the other files are read like a pipe
+#[cfg(unix)]
+const MAX_MAPPINGS: usize = 64;
+
+/// Start and length of a mapping, 0 for a free one, and the flag set once it shrank
+#[cfg(unix)]
+struct Slot {
+    start: AtomicUsize,
+    len: AtomicUsize,
+    shrunk: AtomicUsize,
+}
+
+#[cfg(unix)]
+#[allow(clippy::declare_interior_mutable_const)]
+const FREE: Slot = Slot {
+    start: AtomicUsize::new(0),
+    len: AtomicUsize::new(0),
+    shrunk: AtomicUsize::new(0),
+};
+
+// the handler only reads these, nothing it does may allocate or lock
+#[cfg(unix)]
+static SLOTS: [Slot; MAX_MAPPINGS] = [FREE; MAX_MAPPINGS];
+#[cfg(unix)]
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+#[cfg(unix)]
+static HANDLER: Once = Once::new();
+/// the handler of SIGBUS before [`handle_shrinking`], given the bus errors not ours
+#[cfg(unix)]
+static PREVIOUS: OnceLock<libc::sigaction> = OnceLock::new();
+
+/// Read-only mapping of a whole regular file
+///
+/// A file cut shorter while mapped, like by `logrotate` or `> file`, kills the process with a
+/// bus error on the next read past its new end, unless [`handle_shrinking`] was called: the
+/// pages past it read as zeros then, and [`Self::shrunk`] is set
+pub struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+    shrunk: Arc<AtomicBool>,
+    #[cfg(unix)]
+    slot: usize,
+}
+
+// the mapping is never written
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// map the file if it's a non-empty regular file, and there's room for one more mapping
+    #[cfg(unix)]
+    pub fn new(file: &File) -> Option<Self> {
+        use std::os::unix::prelude::AsRawFd;
+
+        let metadata = file.metadata().ok()?;
+        if !metadata.is_file() || metadata.len() == 0 {
+            return None;
+        }
+
+        // taken with a start of 1 until mapped
+        let slot = SLOTS.iter().position(|slot| {
+            slot.start
+                .compare_exchange(0, 1, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        })?;
+        let release = || SLOTS[slot].start.store(0, Ordering::Release);
+
+        let len = match usize::try_from(metadata.len()) {
+            Ok(len) => len,
+            Err(_) => {
+                release();
+                return None;
+            }
+        };
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            release();
+            return None;
+        }
+
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+
+        let shrunk = Arc::new(AtomicBool::new(false));
+        SLOTS[slot]
+            .shrunk
+            .store(Arc::as_ptr(&shrunk) as usize, Ordering::Release);
+        SLOTS[slot].len.store(len, Ordering::Release);
+        SLOTS[slot].start.store(ptr as usize, Ordering::Release);
+        Some(Self {
+            ptr,
+            len,
+            shrunk,
+            slot,
+        })
+    }
+
+    #[cfg(windows)]
+    pub fn new(_file: &File) -> Option<Self> {
+        None
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        // SAFETY: the mapping lives as long as self, and stays readable if the file shrinks
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    /// set once the file was cut shorter, shared with [`crate::shared::ReaderState::shrunk`]
+    pub fn shrunk(&self) -> Arc<AtomicBool> {
+        self.shrunk.clone()
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            SLOTS[self.slot].len.store(0, Ordering::Release);
+            SLOTS[self.slot].start.store(0, Ordering::Release);
+            SLOTS[self.slot].shrunk.store(0, Ordering::Release);
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// Read zeros past the end of a mapped file cut shorter instead of dying of a bus error.
+///
+/// Installs a handler of SIGBUS for the whole process, the bus errors outside of the mappings
+/// go to the handler there was before, if any. Called once by `rp`, an application embedding
+/// the pager calls it if its own handling of SIGBUS allows it
+pub fn handle_shrinking() {
+    #[cfg(unix)]
+    HANDLER.call_once(install_handler);
+}
+
+/// Drop the pages of a mapping from memory, but those from the first to the last byte of `a`
+/// and `b`. The dropped ones are read from the file again when next touched. Does nothing unless
+/// both are in the same mapping, like the lines of a mapped file near the view
+pub fn evict_around(a: &[u8], b: &[u8]) {
+    #[cfg(unix)]
+    {
+        let keep_start = (a.as_ptr() as usize).min(b.as_ptr() as usize);
+        let keep_end = (a.as_ptr() as usize + a.len()).max(b.as_ptr() as usize + b.len());
+        let mapping = SLOTS.iter().find_map(|slot| {
+            let start = slot.start.load(Ordering::Acquire);
+            let len = slot.len.load(Ordering::Acquire);
+            let end = start + len;
+            Some((start, end)).filter(|_| len > 0 && start <= keep_start && keep_end <= end)
+        });
+        let (start, end) = match mapping {
+            Some(mapping) => mapping,
+            None => return,
+        };
+
+        let page_size = page_size();
+        let from = keep_start & !(page_size - 1);
+        let to = ((keep_end + page_size - 1) & !(page_size - 1)).min(end);
+        // the pages of a file are never written, they come back from it as they were
+        unsafe {
+            libc::madvise(start as *mut libc::c_void, from - start, libc::MADV_DONTNEED);
+            libc::madvise(to as *mut libc::c_void, end - to, libc::MADV_DONTNEED);
+        }
+    }
+}
+
+/// Bytes of the process in memory, the pages of the mappings read so far included; on Linux only
+pub fn resident() -> Option<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+        let pages = statm.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+        Some(pages * page_size())
+    }
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    match PAGE_SIZE.load(Ordering::Relaxed) {
+        0 => {
+            let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+            let page_size = usize::try_from(page_size).unwrap_or(4096);
+            PAGE_SIZE.store(page_size, Ordering::Relaxed);
+            page_size
+        }
+        page_size => page_size,
+    }
+}
+
+#[cfg(unix)]
+fn install_handler() {
+    // known before the handler may need it
+    page_size();
+
+    unsafe {
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        libc::sigaction(libc::SIGBUS, std::ptr::null(), &mut previous);
+        PREVIOUS.set(previous).ok();
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+        let handler: extern "C" fn(_, _, _) = on_bus_error;
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut());
+    }
+}
+
+// a read past the end of a file that shrank: zeros from that page to the end of the mapping,
+// and the read is done again; any other bus error goes to the handler before
+#[cfg(unix)]
+extern "C" fn on_bus_error(sig: libc::c_int, info: *mut libc::siginfo_t, ctx: *mut libc::c_void) {
+    let addr = unsafe { (*info).si_addr() } as usize;
+    let page_size = PAGE_SIZE.load(Ordering::Relaxed);
+
+    for slot in SLOTS.iter() {
+        let start = slot.start.load(Ordering::Acquire);
+        let len = slot.len.load(Ordering::Acquire);
+        if len == 0 || addr < start || addr >= start + len {
+            continue;
+        }
+
+        let from = addr & !(page_size - 1);
+        let zeros = unsafe {
+            libc::mmap(
+                from as *mut libc::c_void,
+                start + len - from,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if zeros != libc::MAP_FAILED {
+            // the mapping owns the flag until its slot is free
+            let shrunk = slot.shrunk.load(Ordering::Acquire) as *const AtomicBool;
+            if let Some(shrunk) = unsafe { shrunk.as_ref() } {
+                shrunk.store(true, Ordering::Release);
+            }
+            return;
+        }
+    }
+
+    match PREVIOUS.get() {
+        Some(previous) if previous.sa_flags & libc::SA_SIGINFO != 0 => {
+            let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+                unsafe { std::mem::transmute(previous.sa_sigaction) };
+            handler(sig, info, ctx);
+        }
+        Some(previous)
+            if previous.sa_sigaction != libc::SIG_DFL && previous.sa_sigaction != libc::SIG_IGN =>
+        {
+            let handler: extern "C" fn(libc::c_int) =
+                unsafe { std::mem::transmute(previous.sa_sigaction) };
+            handler(sig);
+        }
+        // the read is done again, and fatal this time as without the handler
+        _ => unsafe {
+            libc::signal(libc::SIGBUS, libc::SIG_DFL);
+        },
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    static CHAINED: AtomicBool = AtomicBool::new(false);
+    static SETUP: Once = Once::new();
+
+    extern "C" fn previous(_: libc::c_int, _: *mut libc::siginfo_t, _: *mut libc::c_void) {
+        CHAINED.store(true, Ordering::Release);
+    }
+
+    // a handler of the application's own, then the one of the mappings over it
+    fn setup() {
+        SETUP.call_once(|| unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            let handler: extern "C" fn(_, _, _) = previous;
+            action.sa_sigaction = handler as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGBUS, &action, std::ptr::null_mut());
+            handle_shrinking();
+        });
+    }
+
+    #[test]
+    fn a_file_cut_shorter_reads_as_zeros() {
+        setup();
+        let page = PAGE_SIZE.load(Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("rp-mmap-{}", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&vec![b'a'; 3 * page + 10]).unwrap();
+
+        let mapped = Mmap::new(&File::open(&path).unwrap()).unwrap();
+        let shrunk = mapped.shrunk();
+        file.set_len(100).unwrap();
+        let data = mapped.as_slice();
+        assert_eq!(data[50], b'a');
+        assert_eq!((data[2 * page], data[3 * page + 5]), (0, 0));
+        assert!(shrunk.load(Ordering::Acquire));
+
+        drop(mapped);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn other_bus_errors_go_to_the_handler_before() {
+        setup();
+        unsafe {
+            libc::raise(libc::SIGBUS);
+        }
+        assert!(CHAINED.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn evicted_pages_are_read_again() {
+        let page = page_size();
+        let path = std::env::temp_dir().join(format!("rp-mmap-evict-{}", std::process::id()));
+        let text = (0..4 * page).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        std::fs::write(&path, &text).unwrap();
+
+        let mapped = Mmap::new(&File::open(&path).unwrap()).unwrap();
+        let data = mapped.as_slice();
+        assert_eq!(data, &text[..]);
+        evict_around(&data[2 * page..2 * page + 10], &data[page + 5..page + 6]);
+        assert_eq!(data, &text[..]);
+        // bytes of no mapping are left alone
+        let copy = text.clone();
+        evict_around(&copy[..10], &copy[page..]);
+        assert_eq!(copy, text);
+
+        drop(mapped);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_files_are_not_mapped() {
+        let path = std::env::temp_dir().join(format!("rp-mmap-empty-{}", std::process::id()));
+        let file = File::create(&path).unwrap();
+        assert!(Mmap::new(&file).is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
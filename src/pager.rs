@@ -0,0 +1,232 @@
+// Running rp from another program on text it already has
+
+use crate::config::Theme;
+use crate::hook::{Decoration, Decorator, Hook, KeyAction, KeyFilter, PagerEvent};
+use crate::keys::Keymap;
+use crate::reader;
+use crate::shared::{ReaderState, StyleTable};
+use crate::source::LineSource;
+use crate::spill::Spill;
+use crate::writer::{InputInfo, UiContext};
+use bumpalo::Bump;
+use crossbeam_queue::ArrayQueue;
+use crossbeam_utils::thread::scope;
+use crossterm::{event::KeyEvent, Result};
+use std::sync::{atomic::Ordering, Arc};
+
+/// Pages text on the terminal, like piping it to `rp`.
+///
+/// ```no_run
+/// use rust_pager::{keys::Keymap, pager::Pager};
+///
+/// Pager::new()
+///     .push_text("\x1b[1mtitle\x1b[0m\nthe rest\n")
+///     .keymap(Keymap::Less)
+///     .run()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Pager<'h> {
+    text: Vec<u8>,
+    source: Option<Box<dyn LineSource + Send + 'h>>,
+    #[cfg(feature = "tokio")]
+    async_input: Option<Box<dyn tokio::io::AsyncRead + Unpin + Send + 'h>>,
+    name: Option<String>,
+    keymap: Keymap,
+    bindings: Vec<(String, String)>,
+    wrap: bool,
+    mouse: bool,
+    theme: Theme,
+    hooks: Vec<Hook<'h>>,
+    key_filters: Vec<KeyFilter<'h>>,
+    decorator: Option<(usize, Decorator<'h>)>,
+}
+
+impl<'h> Default for Pager<'h> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'h> Pager<'h> {
+    /// no text yet, with the defaults of rp without a config
+    pub fn new() -> Self {
+        Self {
+            text: Vec::new(),
+            source: None,
+            #[cfg(feature = "tokio")]
+            async_input: None,
+            name: None,
+            keymap: Keymap::Default,
+            bindings: Vec::new(),
+            wrap: true,
+            mouse: true,
+            theme: Theme::Colorful,
+            hooks: Vec::new(),
+            key_filters: Vec::new(),
+            decorator: None,
+        }
+    }
+
+    /// add `text` after the text so far, its escape sequences color it like they would
+    /// on the terminal
+    pub fn push_text(self, text: impl AsRef<str>) -> Self {
+        self.push_bytes(text.as_ref().as_bytes())
+    }
+
+    /// add bytes that aren't UTF-8, shown like `rp` shows such a file
+    pub fn push_bytes(mut self, bytes: impl AsRef<[u8]>) -> Self {
+        self.text.extend_from_slice(bytes.as_ref());
+        self
+    }
+
+    /// fetch the lines from `source` as the view gets near them, instead of the text pushed,
+    /// see [`LineSource`]
+    pub fn source(mut self, source: impl LineSource + Send + 'h) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// read the lines from `input` as they come, like the output of an async command, see
+    /// [`reader::read_async`]
+    #[cfg(feature = "tokio")]
+    pub fn async_input(mut self, input: impl tokio::io::AsyncRead + Unpin + Send + 'h) -> Self {
+        self.async_input = Some(Box::new(input));
+        self
+    }
+
+    /// shown in the status bar instead of `stdin`
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// bindings to start from, see `--keys`
+    pub fn keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    /// bind `keys`, like `ctrl-g` or `g t`, to an action of the config like `top`, or `none`
+    pub fn bind(mut self, keys: &str, action: &str) -> Self {
+        self.bindings.push((keys.to_string(), action.to_string()));
+        self
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// capture the mouse to scroll with the wheel
+    pub fn mouse(mut self, mouse: bool) -> Self {
+        self.mouse = mouse;
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// call `hook` for every [`PagerEvent`], see [`UiContext::on_event`]
+    pub fn on_event(mut self, hook: impl FnMut(&PagerEvent) + 'h) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// give the keys to `filter` before the keymap, see [`UiContext::on_key`]
+    pub fn on_key(mut self, filter: impl FnMut(&KeyEvent) -> KeyAction + 'h) -> Self {
+        self.key_filters.push(Box::new(filter));
+        self
+    }
+
+    /// a gutter of `width` columns and colors for the lines, see [`UiContext::decorate`]
+    pub fn decorate(
+        mut self,
+        width: usize,
+        decorator: impl FnMut(usize, &str) -> Option<Decoration> + 'h,
+    ) -> Self {
+        self.decorator = Some((width, Box::new(decorator)));
+        self
+    }
+
+    /// take over the terminal until quit, it's restored after
+    pub fn run(self) -> Result<()> {
+        #[cfg(feature = "tokio")]
+        let async_input = self.async_input;
+        let Self {
+            text,
+            source,
+            name,
+            keymap,
+            bindings,
+            wrap,
+            mouse,
+            theme,
+            hooks,
+            key_filters,
+            decorator,
+            ..
+        } = self;
+
+        let rx = Arc::new(ArrayQueue::new(1024 * 16));
+        let styles = Arc::new(StyleTable::new());
+        let input_state = Arc::new(ReaderState::new(text.len() as u64));
+        let mut b = Bump::new();
+        let spill = Spill::new(usize::MAX);
+        let input = InputInfo {
+            name: name.unwrap_or_else(|| "-".to_string()),
+            size: Some(text.len() as u64).filter(|_| source.is_none()),
+            line: None,
+        };
+
+        let ret = scope(|s| {
+            let (tx, reader_styles, reader_state) =
+                (rx.clone(), styles.clone(), input_state.clone());
+            let (text, b, spill) = (&text, &mut b, &spill);
+            s.spawn(move |_| {
+                let state = reader_state.clone();
+                #[cfg(feature = "tokio")]
+                if let Some(input) = async_input {
+                    let ret = reader::read_async(input, b, spill, tx, reader_styles, state);
+                    reader_state.done.store(true, Ordering::Release);
+                    return ret;
+                }
+                let ret = match source {
+                    Some(source) => reader::read_source(source, b, spill, tx, reader_styles, state),
+                    None => reader::read_from_mmap(text, tx, reader_styles, state),
+                };
+                reader_state.done.store(true, Ordering::Release);
+                ret
+            });
+
+            let ret = UiContext::new(rx, styles, input_state.clone())
+                .and_then(|ui| ui.mouse(mouse))
+                .and_then(|ui| {
+                    let mut ui = ui
+                        .wrap(wrap)
+                        .theme(theme)
+                        .keymap(keymap)
+                        .bind(&bindings)
+                        .inputs(0, vec![input]);
+                    for mut hook in hooks {
+                        ui = ui.on_event(move |event| hook(event));
+                    }
+                    for mut filter in key_filters {
+                        ui = ui.on_key(move |key| filter(key));
+                    }
+                    if let Some((width, mut decorator)) = decorator {
+                        ui = ui.decorate(width, move |line, text| decorator(line, text));
+                    }
+                    ui.run()
+                });
+
+            // the reader waits while the queue is full
+            input_state.run.store(false, Ordering::Release);
+            ret
+        })
+        .unwrap();
+
+        ret
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+/// What an input preprocessor gave for a file, see [`run`]
+pub enum Preprocessed {
+    /// output of a `|command`, paged instead of the file
+    Output(Vec<u8>),
+    /// file to page instead, see [`close`]
+    Replacement(PathBuf),
+}
+
+/// Run a `LESSOPEN` style `template` on `path`, `%s` standing for the file name:
+///
+/// - `|command` pages the output of the command, or the file itself if there is none
+/// - `||command` pages the output even when empty
+/// - `command` prints the name of a file to page instead, if any
+pub fn run(template: &str, path: &Path) -> Option<Preprocessed> {
+    let (pipe, keep_empty, command) = match template.strip_prefix("||") {
+        Some(command) => (true, true, command),
+        None => match template.strip_prefix('|') {
+            Some(command) => (true, false, command),
+            None => (false, false, template),
+        },
+    };
+
+    let output = shell(&expand(command, &[path])).ok()?;
+
+    if pipe {
+        let keep = keep_empty || !output.is_empty();
+        Some(Preprocessed::Output(output)).filter(|_| keep)
+    } else {
+        let name = String::from_utf8(output).ok()?;
+        let name = name.trim_end_matches('\n');
+        Some(Preprocessed::Replacement(PathBuf::from(name))).filter(|_| !name.is_empty())
+    }
+}
+
+/// Run a `LESSCLOSE` style `template` once the replacement file isn't needed,
+/// the first `%s` is the file and the second the replacement
+pub fn close(template: &str, path: &Path, replacement: &Path) {
+    shell(&expand(template, &[path, replacement])).ok();
+}
+
+// replace each `%s` with the next path, quoted for the shell
+fn expand(template: &str, paths: &[&Path]) -> String {
+    let mut paths = paths.iter();
+    let mut command = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(i) = rest.find("%s") {
+        command.push_str(&rest[..i]);
+        if let Some(path) = paths.next() {
+            command.push_str(&crate::process::quote(&path.to_string_lossy()));
+        }
+        rest = &rest[i + 2..];
+    }
+    command.push_str(rest);
+
+    command
+}
+
+// stdout of the command, its stderr goes nowhere to keep the screen clean
+fn shell(command: &str) -> std::io::Result<Vec<u8>> {
+    let output = crate::process::shell(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+
+    Ok(output.stdout)
+}
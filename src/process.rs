@@ -0,0 +1,351 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    process::{ExitStatus, Stdio},
+};
+
+/// `command` run by the shell, `sh -c` or `cmd /C` on Windows
+pub fn shell(command: &str) -> std::process::Command {
+    #[cfg(unix)]
+    let mut shell = std::process::Command::new("sh");
+    #[cfg(unix)]
+    shell.arg("-c");
+    #[cfg(windows)]
+    let mut shell = std::process::Command::new("cmd");
+    #[cfg(windows)]
+    shell.arg("/C");
+
+    shell.arg(command);
+    shell
+}
+
+/// open `target`, a url or a path, with the program the system has for it
+pub fn open(target: &str) -> io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut opener = std::process::Command::new("open");
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut opener = std::process::Command::new("xdg-open");
+    #[cfg(windows)]
+    let mut opener = std::process::Command::new("cmd");
+    // the empty title, `start` would take a quoted url for it
+    #[cfg(windows)]
+    opener.args(["/C", "start", ""]);
+
+    // a browser starting in the background must not write over the view
+    let mut child = opener
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // reaped, the opener usually exits once it handed the url over
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// bytes kept of each of stdout and stderr of a command run by [`pipe`], it's killed past them
+pub const MAX_PIPED: usize = 4 << 20;
+
+/// What a command run by [`pipe`] printed
+pub struct Piped {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub status: ExitStatus,
+    /// it printed more than [`MAX_PIPED`] bytes to one of them and was killed
+    pub truncated: bool,
+}
+
+/// what `command` run by the shell prints with `input` on its stdin
+pub fn pipe(command: &str, input: Vec<u8>) -> io::Result<Piped> {
+    let mut shell = shell(command);
+    shell
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // in its own group, so killing it reaches the commands of a pipeline too
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+    let mut child = shell.spawn()?;
+
+    // written meanwhile, the command may print before it read all of it, or not read it at all
+    let mut stdin = child.stdin.take().ok_or(io::ErrorKind::BrokenPipe)?;
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            let mut printed = Vec::new();
+            let over = pipe.is_some_and(|pipe| read_up_to_max(pipe, &mut printed));
+            tx.send(over).ok();
+            printed
+        })
+    };
+    let stdout = read(child.stdout.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    let stderr = read(child.stderr.take().map(|p| Box::new(p) as Box<dyn Read + Send>));
+    drop(tx);
+
+    // killed as soon as one of them is over, the other one may not end without it
+    let truncated = rx.iter().any(|over| over);
+    if truncated {
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+        #[cfg(windows)]
+        child.kill().ok();
+    }
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let status = child.wait()?;
+    writer.join().ok();
+    Ok(Piped {
+        stdout,
+        stderr,
+        status,
+        truncated,
+    })
+}
+
+// true once more than `MAX_PIPED` bytes came, only those are kept
+fn read_up_to_max(mut pipe: impl Read, printed: &mut Vec<u8>) -> bool {
+    let mut buf = [0; 8192];
+    loop {
+        let n = match pipe.read(&mut buf) {
+            Ok(0) => return false,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return false,
+        };
+        let room = MAX_PIPED - printed.len();
+        printed.extend_from_slice(&buf[..n.min(room)]);
+        if n > room {
+            return true;
+        }
+    }
+}
+
+/// `path` opened at `line` by `$VISUAL` or `$EDITOR`, `vi` without them, on the terminal
+pub fn edit(path: &Path, line: usize, input: File, output: File) -> io::Result<ExitStatus> {
+    // set but empty is taken as unset, as the shell would run the path
+    let set = |name| env::var(name).ok().filter(|e: &String| !e.is_empty());
+    let editor = set("VISUAL")
+        .or_else(|| set("EDITOR"))
+        .unwrap_or_else(|| "vi".into());
+
+    // given to the shell, the editor may come with arguments like `code -w`
+    let command = format!("{} +{} {}", editor, line, quote(&path.to_string_lossy()));
+    let errors = output.try_clone()?;
+    shell(&command)
+        .stdin(input)
+        .stdout(output)
+        .stderr(errors)
+        .status()
+}
+
+/// `arg` as a single word of the shell [`shell`] runs, whatever quotes or spaces it has
+#[cfg(unix)]
+pub fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(windows)]
+pub fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg)
+}
+
+/// A command run by the pager, its output is read like piped input
+pub struct Command {
+    child: std::process::Child,
+}
+
+/// Read ends of the outputs of a [`Command`]
+pub struct Output {
+    pub stdout: File,
+    /// mixed into stdout where it can't be told apart
+    pub stderr: Option<File>,
+}
+
+impl Command {
+    /// start `args[0]` with the rest as arguments, with `pty` the command writes
+    /// to terminals where possible
+    pub fn spawn(args: &[OsString], pty: bool) -> io::Result<(Self, Output)> {
+        let (program, rest) = args.split_first().ok_or(io::ErrorKind::InvalidInput)?;
+
+        // piped input goes to the command, the terminal is the pager's
+        let stdin = if crossterm::tty::IsTty::is_tty(&io::stdin()) {
+            Stdio::null()
+        } else {
+            Stdio::inherit()
+        };
+
+        let mut command = std::process::Command::new(program);
+        command.args(rest).stdin(stdin);
+        // in its own group, so stopping it reaches what it started too
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let (child, output) = spawn_piped(command, pty)?;
+
+        Ok((Self { child }, output))
+    }
+
+    /// terminate the command if it's still running, the pager quit before reading all of it
+    pub fn stop(&mut self) {
+        if let Ok(Some(_)) = self.child.try_wait() {
+            return;
+        }
+
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(self.child.id() as libc::pid_t), libc::SIGTERM);
+        }
+        #[cfg(windows)]
+        self.child.kill().ok();
+    }
+
+    /// wait for the command to exit, returns the status rp should exit with
+    pub fn wait(mut self) -> io::Result<i32> {
+        let status = self.child.wait()?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            // like a shell, 128 + the signal that killed it
+            if let Some(signal) = status.signal() {
+                return Ok(128 + signal);
+            }
+        }
+
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+// stdout and stderr each get a pipe or a terminal, read together in arrival order
+#[cfg(unix)]
+fn spawn_piped(
+    mut command: std::process::Command,
+    pty: bool,
+) -> io::Result<(std::process::Child, Output)> {
+    let open = || match pty.then(open_pty) {
+        Some(Ok(pty)) => Ok(pty),
+        _ => open_pipe(),
+    };
+    let (stdout, stdout_write) = open()?;
+    let (stderr, stderr_write) = open()?;
+
+    command
+        .stdout(Stdio::from(stdout_write))
+        .stderr(Stdio::from(stderr_write));
+    let child = command.spawn()?;
+    // the write ends go away with the builder, the command's exit is the end of input
+    drop(command);
+
+    let stderr = Some(stderr);
+    Ok((child, Output { stdout, stderr }))
+}
+
+#[cfg(unix)]
+fn open_pipe() -> io::Result<(File, File)> {
+    use std::os::unix::prelude::FromRawFd;
+
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // the command only gets the duplicates made for its stdout and stderr
+    for fd in fds.iter() {
+        unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+// tools checking isatty keep their colors and progress output, returns (master, slave)
+#[cfg(unix)]
+fn open_pty() -> io::Result<(File, File)> {
+    use std::os::unix::prelude::FromRawFd;
+
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if master < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let master_file = File::from_raw_fd(master);
+        libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+
+        if libc::grantpt(master) < 0 || libc::unlockpt(master) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let name = libc::ptsname(master);
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let slave = libc::open(name, libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if slave < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let slave_file = File::from_raw_fd(slave);
+
+        // keep `\n` as is instead of `\r\n`
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(slave, &mut termios) == 0 {
+            termios.c_oflag &= !libc::OPOST;
+            libc::tcsetattr(slave, libc::TCSANOW, &termios);
+        }
+
+        // as wide as the view, for tools that fit their output
+        if let Ok((columns, rows)) = crossterm::terminal::size() {
+            let size = libc::winsize {
+                ws_row: rows,
+                ws_col: columns,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            };
+            libc::ioctl(slave, libc::TIOCSWINSZ, &size);
+        }
+
+        Ok((master_file, slave_file))
+    }
+}
+
+// stderr stays on the console, there is no terminal to give
+#[cfg(windows)]
+fn spawn_piped(
+    mut command: std::process::Command,
+    _pty: bool,
+) -> io::Result<(std::process::Child, Output)> {
+    use std::os::windows::io::OwnedHandle;
+
+    let mut child = command.stdout(Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take().ok_or(io::ErrorKind::BrokenPipe)?;
+    let stdout = File::from(OwnedHandle::from(stdout));
+
+    Ok((child, Output { stdout, stderr: None }))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipes_the_input_through() {
+        let piped = pipe("cat; echo oops >&2", b"line\n".to_vec()).unwrap();
+        assert_eq!((piped.stdout, piped.stderr), (b"line\n".to_vec(), b"oops\n".to_vec()));
+        assert!(piped.status.success() && !piped.truncated);
+    }
+
+    #[test]
+    fn endless_output_is_cut() {
+        let piped = pipe("yes", Vec::new()).unwrap();
+        assert!(piped.truncated);
+        assert_eq!(piped.stdout.len(), MAX_PIPED);
+
+        let piped = pipe("yes >&2", Vec::new()).unwrap();
+        assert!(piped.truncated && piped.stdout.is_empty());
+    }
+}
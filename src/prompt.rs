@@ -0,0 +1,85 @@
+// Prompt templates, like the `-P` option of less
+
+/// `format` with each `%x` spec replaced by `value("x")`.
+///
+/// A spec is one letter, or two when `l`, `p` or `b` is followed by `t` or `b` for the
+/// top or bottom line. `%%` is a `%`, and specs `value` doesn't know are kept as written
+pub fn expand(format: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(format.len() * 2);
+    let mut rest = format;
+
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let mut chars = after.chars();
+        let len = match (chars.next(), chars.next()) {
+            (None, _) => 0,
+            (Some('l' | 'p' | 'b'), Some('t' | 'b')) => 2,
+            (Some(ch), _) => ch.len_utf8(),
+        };
+        let spec = &after[..len];
+
+        match spec {
+            // a `%` ending the format is one too
+            "%" | "" => out.push('%'),
+            _ => match value(spec) {
+                Some(text) => out.push_str(&text),
+                None => {
+                    out.push('%');
+                    out.push_str(spec);
+                }
+            },
+        }
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(spec: &str) -> Option<String> {
+        match spec {
+            "f" => Some("file.txt".into()),
+            "lt" => Some("10".into()),
+            "lb" => Some("33".into()),
+            "l" => Some("l".into()),
+            "é" => Some("e".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn specs_are_replaced() {
+        let cases = [
+            ("%f", "file.txt"),
+            ("%f lines %lt-%lb", "file.txt lines 10-33"),
+            ("%lx", "lx"),
+            ("%%f is %f", "%f is file.txt"),
+            ("%é", "e"),
+            ("no specs", "no specs"),
+            ("", ""),
+        ];
+        for (format, expanded) in cases {
+            assert_eq!(expand(format, value), expanded, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn unknown_specs_are_kept() {
+        let cases = [
+            ("%q", "%q"),
+            ("%pt %bb", "%pt %bb"),
+            ("at 100%", "at 100%"),
+            ("%", "%"),
+            ("%f%", "file.txt%"),
+        ];
+        for (format, expanded) in cases {
+            assert_eq!(expand(format, value), expanded, "{:?}", format);
+        }
+    }
+}
@@ -1,49 +1,454 @@
-use crate::shared::{Buffer, RpLine};
+use crate::{
+    shared::{RawLine, ReaderState, Scanner, StyleTable},
+    source::LineSource,
+    spill::Spill,
+};
 use bumpalo::Bump;
 use crossbeam_queue::ArrayQueue;
 use crossterm::Result;
 use std::{
     fs::File,
-    io::{ErrorKind, Read},
+    fmt::Write,
+    io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
     sync::{atomic::Ordering, Arc},
+    time::Duration,
 };
 
+/// how often a followed file is checked once everything was read
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+/// bytes on each line of a hex dump
+pub const HEX_ROW: usize = 16;
+/// bytes looked at to tell if the input is binary
+const BINARY_SAMPLE: usize = 4096;
+/// lines fetched from a `LineSource` at once
+const FETCH_LINES: usize = 1024;
+/// how long a `LineSource` waits for new lines before the view is checked again
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// a NUL or many control chars, escape sequences and line breaks aside
+pub fn looks_binary(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(BINARY_SAMPLE)];
+    let control = sample
+        .iter()
+        .filter(|b| (**b < 0x20 && !(8..=13).contains(*b) && **b != 0x1b) || **b == 0x7f)
+        .count();
+
+    sample.contains(&0) || control * 10 > sample.len()
+}
+
+/// read `input` as text, or as a hex dump when `hex` says so or it looks binary
+pub fn read_input<'b>(
+    input: impl Read,
+    hex: Option<bool>,
+    b: &'b mut Bump,
+    spill: &'b Spill,
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
+) -> Result<()> {
+    let mut input = BufReader::new(input);
+    let hex = match hex {
+        Some(hex) => hex,
+        None => looks_binary(input.fill_buf()?),
+    };
+
+    if hex {
+        read_hex(input, b, spill, tx, styles, state)
+    } else {
+        read_from_stdin(input, b, spill, tx, styles, state)
+    }
+}
+
+/// show `input` as lines of `HEX_ROW` bytes, with their offset and ASCII
+pub fn read_hex<'b>(
+    input: impl Read,
+    b: &'b mut Bump,
+    spill: &'b Spill,
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
+) -> Result<()> {
+    state.hex.store(true, Ordering::Relaxed);
+
+    let mut input = BufReader::new(input);
+    let mut parser = vte::Parser::new();
+    let mut scanner = Scanner::new(b, spill, &tx, &styles, &state);
+    let mut row = [0; HEX_ROW];
+    let mut filled = 0;
+    let mut offset = 0;
+    let mut line = String::with_capacity(HEX_ROW * 5);
+
+    loop {
+        let l = match input.read(&mut row[filled..]) {
+            Ok(l) => l,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        if !state.run.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        filled += l;
+        state.read.fetch_add(l as u64, Ordering::Relaxed);
+
+        if filled == HEX_ROW || (l == 0 && filled > 0) {
+            hex_line(&mut line, offset, &row[..filled]);
+            line.bytes().for_each(|b| scanner.advance(&mut parser, b));
+            scanner.advance(&mut parser, b'\n');
+            offset += filled as u64;
+            filled = 0;
+        }
+
+        if l == 0 {
+            #[cfg(feature = "logging")]
+            log::info!("EOF");
+            return Ok(());
+        }
+    }
+}
+
+// `00000010  48 65 6c 6c 6f 0a              |Hello.|`, one space between bytes to search them
+fn hex_line(line: &mut String, offset: u64, bytes: &[u8]) {
+    line.clear();
+    write!(line, "{:08x}  ", offset).ok();
+    for b in bytes {
+        write!(line, "{:02x} ", b).ok();
+    }
+    for _ in bytes.len()..HEX_ROW {
+        line.push_str("   ");
+    }
+
+    line.push_str(" |");
+    line.extend(bytes.iter().map(|b| match b {
+        0x20..=0x7e => *b as char,
+        _ => '.',
+    }));
+    line.push('|');
+}
+
 pub fn read_from_stdin<'b>(
-    mut stdin: File,
+    mut stdin: impl Read,
     b: &'b mut Bump,
-    tx: Arc<ArrayQueue<RpLine<'b>>>,
+    spill: &'b Spill,
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
 ) -> Result<()> {
     let mut parser = vte::Parser::new();
-    let mut buffer = Buffer::new(b, &tx);
+    let mut scanner = Scanner::new(b, spill, &tx, &styles, &state);
     let mut stdin_buf = [0; 8196];
 
     loop {
         let buf = match stdin.read(&mut stdin_buf) {
             Ok(l) => &stdin_buf[..l],
             Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
-            Err(e) => return Err(From::from(e)),
+            // a terminal reports its other side closing as an error
+            #[cfg(unix)]
+            Err(ref e) if e.raw_os_error() == Some(libc::EIO) => &stdin_buf[..0],
+            Err(e) => return Err(e),
         };
 
         if buf.is_empty() {
             #[cfg(feature = "logging")]
             log::info!("EOF");
-            if !buffer.is_empty() {
-                buffer.flush();
+            if !scanner.is_empty() {
+                scanner.flush();
             }
             break Ok(());
         }
 
-        if !crate::RUN.load(Ordering::Acquire) {
+        if !state.run.load(Ordering::Acquire) {
             break Ok(());
         }
 
-        buf.iter().for_each(|b| parser.advance(&mut buffer, *b));
+        buf.iter().for_each(|b| scanner.advance(&mut parser, *b));
+        state.read.fetch_add(buf.len() as u64, Ordering::Relaxed);
+    }
+}
 
-        if buffer.is_full() {
-            #[cfg(feature = "logging")]
-            log::error!("Too long");
-            buffer.flush();
-            break Ok(());
+/// fetch the lines of `source` in order up to the ones the view needs, see
+/// [`ReaderState::wanted`], and send them like those of a file: the view keeps them all
+pub fn read_source<'b>(
+    mut source: impl LineSource,
+    b: &'b mut Bump,
+    spill: &'b Spill,
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
+) -> Result<()> {
+    let mut parser = vte::Parser::new();
+    let mut scanner = Scanner::new(b, spill, &tx, &styles, &state);
+    let mut lines = Vec::with_capacity(FETCH_LINES);
+    let mut fetched = 0;
+    state.lazy.store(true, Ordering::Relaxed);
+
+    while state.run.load(Ordering::Acquire) {
+        let end = source.len_hint().unwrap_or(usize::MAX);
+        if fetched >= end {
+            break;
+        }
+
+        // waiting like a followed file, not loading
+        let wanted = state.wanted.load(Ordering::Relaxed).min(end);
+        if fetched >= wanted {
+            state.waiting.store(true, Ordering::Relaxed);
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        let range = fetched..wanted.min(fetched + FETCH_LINES);
+        let asked = range.len();
+        lines.clear();
+        source.fetch(range, &mut lines)?;
+        state.waiting.store(false, Ordering::Relaxed);
+
+        for line in lines.iter().take(asked) {
+            let bytes = line.bytes().chain(std::iter::once(b'\n'));
+            bytes.for_each(|b| scanner.advance(&mut parser, b));
+            state
+                .read
+                .fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
+        }
+        fetched += lines.len().min(asked);
+
+        if lines.len() < asked {
+            state.waiting.store(true, Ordering::Relaxed);
+            if !source.poll(POLL_INTERVAL) {
+                break;
+            }
+        }
+    }
+
+    state.waiting.store(false, Ordering::Relaxed);
+    state.lazy.store(false, Ordering::Relaxed);
+    Ok(())
+}
+
+/// read `input` like [`read_from_stdin`] on a runtime of its own, a resource of another runtime,
+/// like the pipe of a `tokio::process::Child`, needs that one to keep running on other threads
+#[cfg(feature = "tokio")]
+pub fn read_async<'b>(
+    mut input: impl tokio::io::AsyncRead + Unpin,
+    b: &'b mut Bump,
+    spill: &'b Spill,
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
+) -> Result<()> {
+    use tokio::io::AsyncReadExt;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()?;
+    let mut parser = vte::Parser::new();
+    let mut scanner = Scanner::new(b, spill, &tx, &styles, &state);
+    let mut buf = [0; 8196];
+
+    runtime.block_on(async {
+        loop {
+            // the pager may quit while nothing comes
+            let read = tokio::time::timeout(POLL_INTERVAL, input.read(&mut buf)).await;
+            if !state.run.load(Ordering::Acquire) {
+                break Ok(());
+            }
+            let l = match read {
+                Ok(Ok(l)) => l,
+                Ok(Err(ref e)) if e.kind() == ErrorKind::Interrupted => continue,
+                Ok(Err(e)) => break Err(e),
+                Err(_) => continue,
+            };
+
+            if l == 0 {
+                if !scanner.is_empty() {
+                    scanner.flush();
+                }
+                break Ok(());
+            }
+
+            let chunk = &buf[..l];
+            chunk.iter().for_each(|b| scanner.advance(&mut parser, *b));
+            state.read.fetch_add(l as u64, Ordering::Relaxed);
         }
+    })
+}
+
+/// read a file mapped in memory, its lines borrow their bytes from the mapping instead of being
+/// copied; each line is still sent to the view, which keeps a [`RawLine`] for every one of them
+pub fn read_from_mmap<'b>(
+    data: &'b [u8],
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
+) -> Result<()> {
+    let mut parser = vte::Parser::new();
+    let mut scanner = Scanner::mapped(data, &tx, &styles, &state);
+
+    for chunk in data.chunks(8196) {
+        if !state.run.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        chunk.iter().for_each(|b| scanner.advance(&mut parser, *b));
+        state.read.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "logging")]
+    log::info!("EOF");
+    if !scanner.is_empty() {
+        scanner.flush();
     }
+
+    Ok(())
+}
+
+/// read both outputs of a command in arrival order, a line is only scanned once complete
+/// so that lines of stdout and stderr don't mix
+#[cfg(unix)]
+pub fn read_from_command<'b>(
+    stdout: File,
+    stderr: File,
+    b: &'b mut Bump,
+    spill: &'b Spill,
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
+) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut parser = vte::Parser::new();
+    let mut scanner = Scanner::new(b, spill, &tx, &styles, &state);
+    // (output, bytes of its incomplete line, still open)
+    let mut streams = [(stdout, Vec::new(), true), (stderr, Vec::new(), true)];
+    let mut buf = [0; 8196];
+
+    while streams.iter().any(|(_, _, open)| *open) {
+        if !state.run.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let mut fds = [0, 1].map(|i| libc::pollfd {
+            // negative fds are skipped
+            fd: if streams[i].2 { streams[i].0.as_raw_fd() } else { -1 },
+            events: libc::POLLIN,
+            revents: 0,
+        });
+        // wake up now and then to notice a quit
+        if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, 100) } < 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+
+        for (i, fd) in fds.iter().enumerate() {
+            if fd.revents == 0 {
+                continue;
+            }
+
+            let (output, pending, open) = &mut streams[i];
+            match output.read(&mut buf) {
+                Ok(0) => *open = false,
+                Ok(l) => {
+                    pending.extend_from_slice(&buf[..l]);
+                    state.read.fetch_add(l as u64, Ordering::Relaxed);
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(ref e) if e.raw_os_error() == Some(libc::EIO) => *open = false,
+                Err(e) => return Err(e),
+            }
+
+            // the incomplete line waits for the rest, or for the end of the output
+            let end = if *open {
+                pending.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1)
+            } else {
+                pending.len()
+            };
+            scanner.stderr = i == 1;
+            pending
+                .drain(..end)
+                .for_each(|b| scanner.advance(&mut parser, b));
+            if !*open && !scanner.is_empty() {
+                scanner.flush();
+            }
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    log::info!("EOF");
+    Ok(())
+}
+
+/// read the file at `path` like `tail -F`, waiting for more data at the end,
+/// starting over when it's truncated and reopening it when it's replaced
+pub fn follow_file<'b>(
+    path: &Path,
+    mut file: File,
+    b: &'b mut Bump,
+    spill: &'b Spill,
+    tx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    state: Arc<ReaderState>,
+) -> Result<()> {
+    let mut parser = vte::Parser::new();
+    let mut scanner = Scanner::new(b, spill, &tx, &styles, &state);
+    let mut file_buf = [0; 8196];
+    let mut offset = 0;
+
+    while state.run.load(Ordering::Acquire) {
+        let buf = match file.read(&mut file_buf) {
+            Ok(l) => &file_buf[..l],
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        if !buf.is_empty() {
+            state.waiting.store(false, Ordering::Relaxed);
+            offset += buf.len() as u64;
+            buf.iter().for_each(|b| scanner.advance(&mut parser, *b));
+            state.read.fetch_add(buf.len() as u64, Ordering::Relaxed);
+            continue;
+        }
+
+        state.waiting.store(true, Ordering::Relaxed);
+        std::thread::sleep(FOLLOW_INTERVAL);
+
+        // the rest of a rotated file was read already, a missing file may come back later
+        match std::fs::metadata(path) {
+            Ok(metadata) if !is_same_file(&file, &metadata) => {
+                if let Ok(new_file) = File::open(path) {
+                    #[cfg(feature = "logging")]
+                    log::info!("{} was replaced, reopening", path.display());
+                    file = new_file;
+                    offset = 0;
+                }
+            }
+            Ok(metadata) if metadata.len() < offset => {
+                #[cfg(feature = "logging")]
+                log::info!("{} was truncated", path.display());
+                file.seek(SeekFrom::Start(0))?;
+                offset = 0;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_same_file(file: &File, metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    file.metadata()
+        .map(|m| m.ino() == metadata.ino() && m.dev() == metadata.dev())
+        .unwrap_or(false)
+}
+
+// no stable file id, only truncation is detected
+#[cfg(windows)]
+fn is_same_file(_file: &File, _metadata: &std::fs::Metadata) -> bool {
+    true
 }
@@ -1,70 +1,268 @@
+use ahash::AHashMap;
 use bumpalo::Bump;
 use crossbeam_queue::ArrayQueue;
 use crossterm::style::{Attribute, Attributes, Color};
-use std::{convert::TryFrom, sync::atomic::Ordering, time::Duration};
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    hash::{BuildHasher, Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard,
+    },
+    time::Duration,
+};
 use unicode_width::UnicodeWidthChar;
 use vte::Params;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+use crate::diff::{Classifier, DiffLine};
+use crate::image::{Detector, Image};
+use crate::spill::Spill;
+
+/// What the reader of an input and the view showing it tell each other, each pager has its own
+pub struct ReaderState {
+    /// cleared to stop both the reader and the view
+    pub run: AtomicBool,
+    /// bytes of the input held by the reader's arena
+    pub memory: AtomicUsize,
+    /// bytes of the input read so far
+    pub read: AtomicU64,
+    /// size of the input if known beforehand, 0 otherwise
+    pub size: AtomicU64,
+    /// set once the reader stopped
+    pub done: AtomicBool,
+    /// set while the reader waits for a followed file to grow
+    pub waiting: AtomicBool,
+    /// set when the input is shown as a hex dump, see [`crate::reader::read_hex`]
+    pub hex: AtomicBool,
+    /// lines the view needs, a [`crate::source::LineSource`] isn't asked for more
+    pub wanted: AtomicUsize,
+    /// set while the lines come from a [`crate::source::LineSource`] that has more of them
+    pub lazy: AtomicBool,
+    /// why the lines over the memory budget couldn't be spilled to disk, told by the view
+    pub spill_error: Mutex<Option<String>>,
+    /// set when the mapped file read was cut shorter, the one of [`crate::mmap::Mmap::shrunk`]
+    /// when reading a mapping, cleared once told by the view
+    pub shrunk: Arc<AtomicBool>,
+}
+
+impl ReaderState {
+    /// for an input of `size` bytes, 0 if not known
+    pub fn new(size: u64) -> Self {
+        Self {
+            run: AtomicBool::new(true),
+            memory: AtomicUsize::new(0),
+            read: AtomicU64::new(0),
+            size: AtomicU64::new(size),
+            done: AtomicBool::new(false),
+            waiting: AtomicBool::new(false),
+            hex: AtomicBool::new(false),
+            wanted: AtomicUsize::new(0),
+            lazy: AtomicBool::new(false),
+            spill_error: Mutex::new(None),
+            shrunk: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// A char of a parsed line with its colors and attributes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct RpChar {
     pub ch: char,
     pub foreground: Color,
     pub background: Color,
+    /// color of the underline, the foreground's when `Reset`
+    pub underline: Color,
     pub attribute: Attributes,
 }
 
-pub type RpLine<'b> = &'b [RpChar];
+/// Colors and attributes set by the escape sequences of the input
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Style {
+    pub foreground: Color,
+    pub background: Color,
+    /// set by SGR 58, the foreground is used when `Reset`
+    pub underline: Color,
+    pub attribute: Attributes,
+    /// kept by SGR 0, only the escape sequences picking a charset change them
+    pub charsets: Charsets,
+}
 
-pub struct Buffer<'b, 'c> {
-    bump: &'b Bump,
-    cursor_column: usize,
-    tx: &'c ArrayQueue<RpLine<'b>>,
-    buf: Vec<RpChar>,
-    foreground: Color,
-    background: Color,
-    attribute: Attributes,
+/// The charsets of G0 and G1, the DEC special graphics when picked by `ESC ( 0` and
+/// `ESC ) 0`, and whether SO shifted to G1 until SI
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Charsets {
+    pub g0_graphics: bool,
+    pub g1_graphics: bool,
+    pub shifted: bool,
+}
+
+impl Charsets {
+    /// the line drawing char in place of `ch`, like `─` for `q`, while the graphics are in use
+    pub fn map(&self, ch: char) -> char {
+        let graphics = if self.shifted {
+            self.g1_graphics
+        } else {
+            self.g0_graphics
+        };
+        match graphics {
+            true => dec_graphics(ch),
+            false => ch,
+        }
+    }
+
+    // `ESC ( c` for G0 and `ESC ) c` for G1, `0` for the graphics and any other one for ASCII
+    fn designate(&mut self, intermediates: &[u8], byte: u8) {
+        match intermediates {
+            [b'('] => self.g0_graphics = byte == b'0',
+            [b')'] => self.g1_graphics = byte == b'0',
+            _ => {}
+        }
+    }
+
+    // SO and SI
+    fn shift(&mut self, b: u8) {
+        match b {
+            0x0e => self.shifted = true,
+            0x0f => self.shifted = false,
+            _ => {}
+        }
+    }
+
+    fn in_use(&self) -> bool {
+        self.map('q') != 'q'
+    }
+}
+
+// the DEC special graphics, from `_` to `~`
+fn dec_graphics(ch: char) -> char {
+    const GRAPHICS: [char; 32] = [
+        ' ', '◆', '▒', '␉', '␌', '␍', '␊', '°', '±', '␤', '␋', '┘', '┐', '┌', '└', '┼', '⎺', '⎻',
+        '─', '⎼', '⎽', '├', '┤', '┴', '┬', '│', '≤', '≥', 'π', '≠', '£', '·',
+    ];
+    match ch {
+        '_'..='~' => GRAPHICS[ch as usize - '_' as usize],
+        _ => ch,
+    }
 }
 
-impl<'b, 'c> Buffer<'b, 'c> {
-    pub fn new(bump: &'b Bump, tx: &'c ArrayQueue<RpLine<'b>>) -> Self {
+impl Default for Style {
+    fn default() -> Self {
         Self {
-            bump,
-            tx,
-            cursor_column: 0,
-            buf: Vec::with_capacity(64),
             foreground: Color::Reset,
             background: Color::Reset,
+            underline: Color::Reset,
             attribute: Attributes::default(),
+            charsets: Charsets::default(),
         }
     }
+}
 
-    pub fn is_empty(&self) -> bool {
-        self.buf.is_empty()
+// Attributes doesn't implement Hash
+impl Hash for Style {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.foreground.hash(state);
+        self.background.hash(state);
+        self.underline.hash(state);
+        self.charsets.hash(state);
+        Attribute::iterator()
+            .filter(|a| self.attribute.has(*a))
+            .for_each(|a| a.hash(state));
     }
+}
+
+/// the kinds of underline by their number in `4:n`, none for 0
+const UNDERLINES: [Option<Attribute>; 6] = [
+    None,
+    Some(Attribute::Underlined),
+    Some(Attribute::DoubleUnderlined),
+    Some(Attribute::Undercurled),
+    Some(Attribute::Underdotted),
+    Some(Attribute::Underdashed),
+];
+
+/// the attributes turned off together by one SGR, after the attribute turning them off
+const ATTRIBUTE_GROUPS: [(Attribute, &[Attribute]); 8] = [
+    (
+        Attribute::NormalIntensity,
+        &[Attribute::Bold, Attribute::Dim],
+    ),
+    (Attribute::NoItalic, &[Attribute::Italic]),
+    (
+        Attribute::NoUnderline,
+        &[
+            Attribute::Underlined,
+            Attribute::DoubleUnderlined,
+            Attribute::Undercurled,
+            Attribute::Underdotted,
+            Attribute::Underdashed,
+        ],
+    ),
+    (
+        Attribute::NoBlink,
+        &[Attribute::SlowBlink, Attribute::RapidBlink],
+    ),
+    (Attribute::NoReverse, &[Attribute::Reverse]),
+    (Attribute::NoHidden, &[Attribute::Hidden]),
+    (Attribute::NotCrossedOut, &[Attribute::CrossedOut]),
+    (Attribute::NotOverLined, &[Attribute::OverLined]),
+];
 
-    pub fn is_full(&self) -> bool {
-        self.buf.len() >= 512
+/// the attributes of `attributes` that are drawn, without the reset and the ones turning
+/// others off
+pub fn drawn_attributes(mut attributes: Attributes) -> Attributes {
+    attributes.unset(Attribute::Reset);
+    for (off, _) in ATTRIBUTE_GROUPS.iter() {
+        attributes.unset(*off);
     }
+    attributes
+}
 
-    pub fn flush(&mut self) {
-        let line = self.bump.alloc_slice_copy(&self.buf);
+impl Style {
+    // SGR 0, the colors go back to the terminal's too
+    fn reset(&mut self) {
+        *self = Self {
+            attribute: Attribute::Reset.into(),
+            charsets: self.charsets,
+            ..Self::default()
+        };
+    }
 
-        while self.tx.push(line).is_err() {
-            if !crate::RUN.load(Ordering::Acquire) {
-                return;
+    // `attribute` on, in place of the others of its group but for bold and dim that can
+    // go together
+    fn turn_on(&mut self, attribute: Attribute) {
+        for (off, group) in ATTRIBUTE_GROUPS.iter() {
+            if !group.contains(&attribute) {
+                continue;
             }
+            self.attribute.unset(*off);
+            if *off != Attribute::NormalIntensity {
+                group.iter().for_each(|a| self.attribute.unset(*a));
+            }
+        }
+        self.attribute.set(attribute);
+    }
 
-            std::thread::sleep(Duration::from_millis(50));
+    // the attributes of the group of `off` off, it's kept to turn them off on the terminal
+    fn turn_off(&mut self, off: Attribute) {
+        for (_, group) in ATTRIBUTE_GROUPS.iter().filter(|(o, _)| *o == off) {
+            group.iter().for_each(|a| self.attribute.unset(*a));
         }
+        self.attribute.set(off);
+    }
 
-        self.cursor_column = 0;
-        self.buf.clear();
+    // one kind of underline in place of the others, `NoUnderline` for none
+    fn set_underline(&mut self, kind: Option<Attribute>) {
+        match kind {
+            Some(kind) => self.turn_on(kind),
+            None => self.turn_off(Attribute::NoUnderline),
+        }
     }
 
     // Copied from vt100
-    fn sgr(&mut self, params: &Params) {
+    pub fn sgr(&mut self, params: &Params) {
         if params.is_empty() {
-            self.attribute = Attributes::default();
+            self.reset();
             return;
         }
 
@@ -81,7 +279,7 @@ impl<'b, 'c> Buffer<'b, 'c> {
 
         macro_rules! to_u8 {
             ($n:expr) => {
-                if let Some(n) = u8::try_from($n).ok() {
+                if let Ok(n) = u8::try_from($n) {
                     n
                 } else {
                     return;
@@ -100,76 +298,116 @@ impl<'b, 'c> Buffer<'b, 'c> {
         }
 
         loop {
-            match next_param!() {
-                &[0] => self.attribute = Attribute::Reset.into(),
-                &[1] => self.attribute.set(Attribute::Bold),
-                &[3] => self.attribute.set(Attribute::Italic),
-                &[4] => self.attribute.set(Attribute::Underlined),
-                &[7] => self.attribute.set(Attribute::Reverse),
-                &[22] => self.attribute.set(Attribute::NoBold),
-                &[23] => self.attribute.set(Attribute::NoItalic),
-                &[24] => self.attribute.set(Attribute::NoUnderline),
-                &[27] => self.attribute.set(Attribute::NoReverse),
-                &[n] if (30..=37).contains(&n) => {
-                    self.foreground = Color::AnsiValue(to_u8!(n) - 30);
+            match *next_param!() {
+                [0] => self.reset(),
+                [1] => self.turn_on(Attribute::Bold),
+                [2] => self.turn_on(Attribute::Dim),
+                [3] => self.turn_on(Attribute::Italic),
+                [4] => self.set_underline(Some(Attribute::Underlined)),
+                // `4:n`, the kind of underline of the terminals drawing the squiggles of
+                // compiler diagnostics
+                [4, n] => self.set_underline(UNDERLINES.get(n as usize).copied().flatten()),
+                [5] => self.turn_on(Attribute::SlowBlink),
+                [6] => self.turn_on(Attribute::RapidBlink),
+                [7] => self.turn_on(Attribute::Reverse),
+                [8] => self.turn_on(Attribute::Hidden),
+                [9] => self.turn_on(Attribute::CrossedOut),
+                [21] => self.set_underline(Some(Attribute::DoubleUnderlined)),
+                [22] => self.turn_off(Attribute::NormalIntensity),
+                [23] => self.turn_off(Attribute::NoItalic),
+                [24] => self.set_underline(None),
+                [25] => self.turn_off(Attribute::NoBlink),
+                [27] => self.turn_off(Attribute::NoReverse),
+                [28] => self.turn_off(Attribute::NoHidden),
+                [29] => self.turn_off(Attribute::NotCrossedOut),
+                [53] => self.turn_on(Attribute::OverLined),
+                [55] => self.turn_off(Attribute::NotOverLined),
+                [n] if (30..=37).contains(&n) => {
+                    self.foreground = idx_color(to_u8!(n) - 30);
                 }
-                &[38, 2, r, g, b] => {
+                // `38:2:r:g:b`, or `38:2:id:r:g:b` with the color space the terminals ignore
+                [38, 2, r, g, b] | [38, 2, _, r, g, b] => {
                     self.foreground = Color::Rgb {
                         r: to_u8!(r),
                         g: to_u8!(g),
                         b: to_u8!(b),
                     };
                 }
-                &[38, 5, i] => {
+                [38, 5, i] => {
                     self.foreground = idx_color(to_u8!(i));
                 }
-                &[38] => match next_param!() {
-                    &[2] => {
+                [38] => match *next_param!() {
+                    [2] => {
                         let r = next_param_u8!();
                         let g = next_param_u8!();
                         let b = next_param_u8!();
                         self.foreground = Color::Rgb { r, g, b };
                     }
-                    &[5] => {
+                    [5] => {
                         self.foreground = idx_color(next_param_u8!());
                     }
                     _ => {}
                 },
-                &[39] => {
+                [39] => {
                     self.foreground = Color::Reset;
                 }
-                &[n] if (40..=47).contains(&n) => {
+                [n] if (40..=47).contains(&n) => {
                     self.background = idx_color(to_u8!(n) - 40);
                 }
-                &[48, 2, r, g, b] => {
+                [48, 2, r, g, b] | [48, 2, _, r, g, b] => {
                     self.background = Color::Rgb {
                         r: to_u8!(r),
                         g: to_u8!(g),
                         b: to_u8!(b),
                     };
                 }
-                &[48, 5, i] => {
+                [48, 5, i] => {
                     self.background = idx_color(to_u8!(i));
                 }
-                &[48] => match next_param!() {
-                    &[2] => {
+                [48] => match *next_param!() {
+                    [2] => {
                         let r = next_param_u8!();
                         let g = next_param_u8!();
                         let b = next_param_u8!();
                         self.background = Color::Rgb { r, g, b };
                     }
-                    &[5] => {
+                    [5] => {
                         self.background = idx_color(next_param_u8!());
                     }
                     _ => {}
                 },
-                &[49] => {
+                [49] => {
                     self.background = Color::Reset;
                 }
-                &[n] if (90..=97).contains(&n) => {
+                [58, 2, r, g, b] | [58, 2, _, r, g, b] => {
+                    self.underline = Color::Rgb {
+                        r: to_u8!(r),
+                        g: to_u8!(g),
+                        b: to_u8!(b),
+                    };
+                }
+                [58, 5, i] => {
+                    self.underline = idx_color(to_u8!(i));
+                }
+                [58] => match *next_param!() {
+                    [2] => {
+                        let r = next_param_u8!();
+                        let g = next_param_u8!();
+                        let b = next_param_u8!();
+                        self.underline = Color::Rgb { r, g, b };
+                    }
+                    [5] => {
+                        self.underline = idx_color(next_param_u8!());
+                    }
+                    _ => {}
+                },
+                [59] => {
+                    self.underline = Color::Reset;
+                }
+                [n] if (90..=97).contains(&n) => {
                     self.foreground = idx_color(to_u8!(n) - 82);
                 }
-                &[n] if (100..=107).contains(&n) => {
+                [n] if (100..=107).contains(&n) => {
                     self.background = idx_color(to_u8!(n) - 92);
                 }
                 _ => {}
@@ -178,45 +416,594 @@ impl<'b, 'c> Buffer<'b, 'c> {
     }
 }
 
-fn idx_color(c: u8) -> Color {
-    use Color::*;
-    match c {
-        0 => Black,
-        1 => DarkRed,
-        2 => DarkGreen,
-        3 => DarkYellow,
-        4 => DarkBlue,
-        5 => DarkMagenta,
-        6 => DarkCyan,
-        7 => Grey,
-        8 => DarkGrey,
-        9 => Red,
-        10 => Green,
-        11 => Yellow,
-        12 => Blue,
-        13 => Magenta,
-        14 => Cyan,
-        15 => White,
-        n => Color::AnsiValue(n),
+/// Index of a style in the [`StyleTable`]
+pub type StyleId = u16;
+
+/// Styles seen in the input, interned so lines only need to store a `StyleId` per run
+pub struct StyleTable {
+    styles: RwLock<Vec<Style>>,
+    ids: Mutex<AHashMap<Style, StyleId>>,
+}
+
+impl Default for StyleTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StyleTable {
+    pub fn new() -> Self {
+        let mut ids = AHashMap::new();
+        ids.insert(Style::default(), 0);
+
+        Self {
+            styles: RwLock::new(vec![Style::default()]),
+            ids: Mutex::new(ids),
+        }
+    }
+
+    pub fn intern(&self, style: Style) -> StyleId {
+        let mut ids = self.ids.lock().unwrap();
+
+        if let Some(id) = ids.get(&style) {
+            return *id;
+        }
+
+        let mut styles = self.styles.write().unwrap();
+        match StyleId::try_from(styles.len()) {
+            Ok(id) => {
+                styles.push(style);
+                ids.insert(style, id);
+                id
+            }
+            // table is full, fallback to default style
+            Err(_) => 0,
+        }
+    }
+
+    pub fn styles(&self) -> RwLockReadGuard<'_, Vec<Style>> {
+        self.styles.read().unwrap()
+    }
+}
+
+/// Chars of a line sharing a style, from the end of the previous run
+#[derive(Clone, Copy)]
+pub struct StyleRun {
+    /// char index where this run ends (exclusive)
+    pub end: u32,
+    pub style: StyleId,
+}
+
+/// A parsed line borrowed from a [`ParsedLine`], its text and style runs
+#[derive(Clone, Copy)]
+pub struct RpLine<'b> {
+    pub text: &'b str,
+    pub runs: &'b [StyleRun],
+}
+
+impl<'b> RpLine<'b> {
+    /// expand chars in `start..end` into `out` with their styles
+    pub fn expand(&self, styles: &[Style], start: usize, end: usize, out: &mut Vec<RpChar>) {
+        out.clear();
+
+        let mut runs = self.runs.iter();
+        let mut run = runs.next();
+
+        for (i, ch) in self.text.chars().enumerate().take(end) {
+            while let Some(r) = run {
+                if (r.end as usize) > i {
+                    break;
+                }
+                run = runs.next();
+            }
+
+            if i < start {
+                continue;
+            }
+
+            let style = run
+                .and_then(|r| styles.get(r.style as usize))
+                .copied()
+                .unwrap_or_default();
+
+            out.push(RpChar {
+                ch,
+                foreground: style.foreground,
+                background: style.background,
+                underline: style.underline,
+                attribute: style.attribute,
+            });
+        }
+    }
+}
+
+/// An owned, parsed line
+#[derive(Default)]
+pub struct ParsedLine {
+    pub text: String,
+    pub runs: Vec<StyleRun>,
+    pub len: u32,
+    /// OSC 8 hyperlinks of the input, in order
+    pub links: Vec<Link>,
+}
+
+/// Chars `start..end` of a line linking to `url`
+pub struct Link {
+    pub start: u32,
+    pub end: u32,
+    pub url: String,
+}
+
+impl ParsedLine {
+    /// rough heap usage
+    pub fn memory(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.text.capacity()
+            + self.runs.capacity() * std::mem::size_of::<StyleRun>()
+            + self
+                .links
+                .iter()
+                .map(|link| std::mem::size_of::<Link>() + link.url.capacity())
+                .sum::<usize>()
+    }
+
+    /// url of the link at char `idx`
+    pub fn link_at(&self, idx: usize) -> Option<&str> {
+        let idx = idx as u32;
+        self.links
+            .iter()
+            .find(|link| (link.start..link.end).contains(&idx))
+            .map(|link| link.url.as_str())
+    }
+
+    pub fn as_line(&self) -> RpLine<'_> {
+        RpLine {
+            text: &self.text,
+            runs: &self.runs,
+        }
+    }
+
+    /// every char with its style, from `styles` the line was parsed with
+    pub fn chars(&self, styles: &StyleTable) -> Vec<RpChar> {
+        let mut chars = Vec::with_capacity(self.len as usize);
+        let line = self.as_line();
+        line.expand(&styles.styles(), 0, self.len as usize, &mut chars);
+        chars
+    }
+}
+
+/// Lines of `text` with their escape sequences parsed like the ones of the input, the styles
+/// interned in `styles`, [`ParsedLine::as_line`] is the [`RpLine`] the UI draws. A style goes
+/// on to the next lines until reset, like on the terminal
+pub fn parse_ansi(text: &str, styles: &StyleTable) -> Vec<ParsedLine> {
+    AnsiLines::new(text, styles).collect()
+}
+
+/// The lines of [`parse_ansi`] parsed as they are iterated
+pub struct AnsiLines<'a> {
+    lines: std::str::SplitInclusive<'a, char>,
+    styles: &'a StyleTable,
+    /// style the next line starts with
+    style: StyleId,
+}
+
+impl<'a> AnsiLines<'a> {
+    pub fn new(text: &'a str, styles: &'a StyleTable) -> Self {
+        Self {
+            lines: text.split_inclusive('\n'),
+            styles,
+            style: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for AnsiLines<'a> {
+    type Item = ParsedLine;
+
+    fn next(&mut self) -> Option<ParsedLine> {
+        let line = self.lines.next()?;
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line_parser = LineParser::new(Some(self.styles), self.style);
+        let (parsed, style) = parse_with_style(line.as_bytes(), line_parser);
+        self.style = self.styles.intern(style);
+        Some(parsed)
+    }
+}
+
+/// Where the bytes of a line are, in the reader's arena or the spill file of a large input
+#[derive(Clone, Copy)]
+pub enum RawBytes<'b> {
+    Memory(&'b [u8]),
+    Spilled {
+        spill: &'b Spill,
+        offset: u64,
+        len: u32,
+    },
+}
+
+/// Unparsed bytes of a line, escape sequences are only parsed once the line gets near the view
+#[derive(Clone, Copy)]
+pub struct RawLine<'b> {
+    pub bytes: RawBytes<'b>,
+    /// style active at the start of the line
+    pub style: StyleId,
+    /// length in chars
+    pub len: u32,
+    /// display width
+    pub width: u32,
+    /// written to stderr by a wrapped command
+    pub stderr: bool,
+    /// drawn in place of the line on the terminals that know how, see [`crate::image`]
+    pub image: Option<Image>,
+    /// shown as caret text, the escape sequences and other control chars as `^[` and the like,
+    /// `len` and `width` are those of the caret text then
+    pub escaped: bool,
+    /// hash of the bytes, to tell repeated lines apart without reading spilled ones back
+    pub hash: u64,
+    /// kind of the line if the input is a unified diff, see [`crate::diff`]
+    pub diff: DiffLine,
+}
+
+impl<'b> RawLine<'b> {
+    /// whether every char takes exactly one column
+    pub fn is_narrow(&self) -> bool {
+        self.len == self.width
+    }
+
+    pub fn bytes(&self) -> Cow<'b, [u8]> {
+        match self.bytes {
+            RawBytes::Memory(bytes) => Cow::Borrowed(bytes),
+            RawBytes::Spilled { spill, offset, len } => Cow::Owned(spill.read(offset, len)),
+        }
+    }
+
+    pub fn byte_len(&self) -> usize {
+        match self.bytes {
+            RawBytes::Memory(bytes) => bytes.len(),
+            RawBytes::Spilled { len, .. } => len as usize,
+        }
+    }
+
+    pub fn parse(&self, styles: &StyleTable) -> ParsedLine {
+        let bytes = self.bytes();
+        if self.escaped {
+            return caret_line(&bytes, Some(styles));
+        }
+        // the letters are lines in the DEC graphics picked by a line before
+        let graphics = styles.styles()[self.style as usize].charsets.in_use();
+
+        if let Some(text) = plain_text(&bytes).filter(|_| !graphics) {
+            let runs = if self.len > 0 {
+                vec![StyleRun {
+                    end: self.len,
+                    style: self.style,
+                }]
+            } else {
+                Vec::new()
+            };
+
+            return ParsedLine {
+                text: text.into(),
+                runs,
+                len: self.len,
+                links: Vec::new(),
+            };
+        }
+
+        run_parser(&bytes, LineParser::new(Some(styles), self.style))
+    }
+
+    /// text of the line without any style
+    pub fn text(&self) -> Cow<'b, str> {
+        match self.bytes() {
+            Cow::Borrowed(bytes) => match plain_text(bytes) {
+                Some(text) => Cow::Borrowed(text),
+                None if self.escaped => Cow::Owned(caret_line(bytes, None).text),
+                None => Cow::Owned(run_parser(bytes, LineParser::new(None, self.style)).text),
+            },
+            Cow::Owned(bytes) => match plain_text(&bytes) {
+                Some(text) => Cow::Owned(text.into()),
+                None if self.escaped => Cow::Owned(caret_line(&bytes, None).text),
+                None => Cow::Owned(run_parser(&bytes, LineParser::new(None, self.style)).text),
+            },
+        }
+    }
+
+    /// (length, width) of the line shown as caret text, none when it has no control char to show
+    pub fn escaped_size(&self) -> Option<(u32, u32)> {
+        let bytes = self.bytes();
+        if plain_text(&bytes).is_some() {
+            return None;
+        }
+        let text = caret_line(&bytes, None).text;
+        let width = text.chars().map(|ch| ch.width().unwrap_or(0) as u32).sum();
+        Some((text.chars().count() as u32, width))
+    }
+}
+
+// the line as it's written, the control chars but tabs as `^` and a letter and the ESC of the
+// escape sequences in reverse video, like less shows them; no style of the input is kept
+fn caret_line(bytes: &[u8], styles: Option<&StyleTable>) -> ParsedLine {
+    let caret = styles.map_or(0, |styles| {
+        styles.intern(Style {
+            attribute: Attribute::Reverse.into(),
+            ..Style::default()
+        })
+    });
+    let mut line = ParsedLine {
+        text: String::new(),
+        runs: Vec::new(),
+        len: 0,
+        links: Vec::new(),
+    };
+    let push = |line: &mut ParsedLine, ch: char, style: StyleId| {
+        line.text.push(ch);
+        line.len += 1;
+        match line.runs.last_mut() {
+            Some(run) if run.style == style => run.end = line.len,
+            _ => line.runs.push(StyleRun {
+                end: line.len,
+                style,
+            }),
+        }
+    };
+
+    let mut column = 0;
+    for ch in String::from_utf8_lossy(bytes).chars() {
+        match ch {
+            '\t' => {
+                let spaces = calculate_next_tab(column);
+                column += spaces;
+                (0..spaces).for_each(|_| push(&mut line, ' ', 0));
+            }
+            '\0'..='\x1f' | '\x7f' => {
+                column += 2;
+                push(&mut line, '^', caret);
+                push(&mut line, (ch as u8 ^ 0x40) as char, caret);
+            }
+            _ => {
+                column += ch.width().unwrap_or(0);
+                push(&mut line, ch, 0);
+            }
+        }
+    }
+    line
+}
+
+// no escape sequence or other control char, bytes can be used as is
+fn plain_text(bytes: &[u8]) -> Option<&str> {
+    if bytes.iter().any(|b| *b < 0x20 || *b == 0x7f) {
+        None
+    } else {
+        std::str::from_utf8(bytes).ok()
+    }
+}
+
+fn run_parser(bytes: &[u8], line_parser: LineParser) -> ParsedLine {
+    parse_with_style(bytes, line_parser).0
+}
+
+// the line and the style at its end
+fn parse_with_style(bytes: &[u8], mut line_parser: LineParser) -> (ParsedLine, Style) {
+    let mut parser = vte::Parser::new();
+
+    bytes
+        .iter()
+        .for_each(|b| parser.advance(&mut line_parser, *b));
+
+    // a link still open ends with the line
+    line_parser.end_link();
+    (line_parser.line, line_parser.style)
+}
+
+/// Where the scanner keeps bytes of lines
+enum Source<'b> {
+    /// copied into the arena, or into the spill file once the arena is over the threshold
+    Bump {
+        bump: &'b Bump,
+        raw: Vec<u8>,
+        spill: &'b Spill,
+        spilled: u64,
+        /// a write to the spill file failed, the view was told why
+        failed: bool,
+    },
+    /// the whole input is already in memory, lines borrow from it
+    Mapped(&'b [u8]),
+}
+
+/// Splits the input into `RawLine`s, only keeping track of the state needed to parse them later
+pub struct Scanner<'b, 'c> {
+    source: Source<'b>,
+    tx: &'c ArrayQueue<RawLine<'b>>,
+    styles: &'c StyleTable,
+    state: &'c ReaderState,
+    /// offset of the current byte in a mapped input
+    pos: usize,
+    line_start: usize,
+    cursor_column: usize,
+    len: u32,
+    width: u32,
+    style: Style,
+    start_style: StyleId,
+    style_outdated: bool,
+    images: Detector,
+    hasher: ahash::RandomState,
+    diff: Classifier,
+    /// the next lines come from stderr
+    pub stderr: bool,
+}
+
+impl<'b, 'c> Scanner<'b, 'c> {
+    pub fn new(
+        bump: &'b Bump,
+        spill: &'b Spill,
+        tx: &'c ArrayQueue<RawLine<'b>>,
+        styles: &'c StyleTable,
+        state: &'c ReaderState,
+    ) -> Self {
+        let source = Source::Bump {
+            bump,
+            raw: Vec::with_capacity(256),
+            spill,
+            spilled: 0,
+            failed: false,
+        };
+        Self::with_source(source, tx, styles, state)
+    }
+
+    pub fn mapped(
+        data: &'b [u8],
+        tx: &'c ArrayQueue<RawLine<'b>>,
+        styles: &'c StyleTable,
+        state: &'c ReaderState,
+    ) -> Self {
+        Self::with_source(Source::Mapped(data), tx, styles, state)
+    }
+
+    fn with_source(
+        source: Source<'b>,
+        tx: &'c ArrayQueue<RawLine<'b>>,
+        styles: &'c StyleTable,
+        state: &'c ReaderState,
+    ) -> Self {
+        Self {
+            source,
+            tx,
+            styles,
+            state,
+            pos: 0,
+            line_start: 0,
+            cursor_column: 0,
+            len: 0,
+            width: 0,
+            style: Style::default(),
+            start_style: 0,
+            style_outdated: false,
+            images: Detector::default(),
+            hasher: ahash::RandomState::new(),
+            diff: Classifier::default(),
+            stderr: false,
+        }
+    }
+
+    pub fn advance(&mut self, parser: &mut vte::Parser, b: u8) {
+        // line breaks are not part of the line
+        if let Source::Bump { raw, .. } = &mut self.source {
+            if !(10..=12).contains(&b) {
+                raw.push(b);
+            }
+        }
+        // a sequence left open, like a title cut short, ends at the line break instead of
+        // taking in the lines after it, each line is parsed on its own after that
+        if (10..=12).contains(&b) {
+            *parser = vte::Parser::new();
+        }
+        self.images.advance(b);
+        parser.advance(self, b);
+        self.pos += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match &self.source {
+            Source::Bump { raw, .. } => raw.is_empty(),
+            Source::Mapped(_) => self.pos <= self.line_start,
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let hasher = &self.hasher;
+        let diff = &mut self.diff;
+        // hashed and classified here as the bytes may be spilled to disk right after
+        let mut scan = |bytes: &[u8]| (hasher.hash_one(bytes), diff.classify(bytes));
+
+        let (bytes, (hash, diff)) = match &mut self.source {
+            Source::Bump {
+                bump,
+                raw,
+                spill,
+                spilled,
+                failed,
+            } => {
+                let scanned = scan(raw);
+                let over = bump.allocated_bytes() >= spill.threshold();
+                let written = over
+                    && match spill.write(*spilled, raw) {
+                        Ok(()) => true,
+                        // only once, the next lines are kept in memory too
+                        Err(e) if !*failed => {
+                            *failed = true;
+                            *self.state.spill_error.lock().unwrap() = Some(e);
+                            false
+                        }
+                        Err(_) => false,
+                    };
+
+                let bytes = if written {
+                    let offset = *spilled;
+                    *spilled += raw.len() as u64;
+                    RawBytes::Spilled {
+                        spill,
+                        offset,
+                        len: raw.len() as u32,
+                    }
+                } else {
+                    RawBytes::Memory(bump.alloc_slice_copy(raw))
+                };
+                self.state.memory.store(bump.allocated_bytes(), Ordering::Relaxed);
+                raw.clear();
+                (bytes, scanned)
+            }
+            Source::Mapped(data) => {
+                let bytes = &data[self.line_start..self.pos.min(data.len())];
+                (RawBytes::Memory(bytes), scan(bytes))
+            }
+        };
+        let line = RawLine {
+            bytes,
+            style: self.start_style,
+            len: self.len,
+            width: self.width,
+            stderr: self.stderr,
+            image: self.images.take(),
+            escaped: false,
+            hash,
+            diff,
+        };
+
+        while self.tx.push(line).is_err() {
+            if !self.state.run.load(Ordering::Acquire) {
+                return;
+            }
+
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        if self.style_outdated {
+            self.start_style = self.styles.intern(self.style);
+            self.style_outdated = false;
+        }
+
+        self.cursor_column = 0;
+        self.line_start = self.pos;
+        self.len = 0;
+        self.width = 0;
     }
 }
 
-impl vte::Perform for Buffer<'_, '_> {
+impl vte::Perform for Scanner<'_, '_> {
     fn print(&mut self, ch: char) {
-        self.cursor_column += ch.width().unwrap_or(0);
-        self.buf.push(RpChar {
-            ch,
-            foreground: self.foreground,
-            background: self.background,
-            attribute: self.attribute,
-        });
+        let width = ch.width().unwrap_or(0);
+        self.cursor_column += width;
+        self.len += 1;
+        self.width += width as u32;
     }
 
     fn execute(&mut self, b: u8) {
         match b {
-            // backspace
-            8 => {
-                self.buf.pop();
+            // backspace, assume the removed char was a narrow one
+            8 if self.len > 0 => {
+                self.len -= 1;
+                self.width = self.width.saturating_sub(1);
             }
             // tab
             9 => {
@@ -225,24 +1012,196 @@ impl vte::Perform for Buffer<'_, '_> {
                 }
             }
             // line break
-            10 | 11 | 12 => {
+            10..=12 => {
                 self.flush();
+                // skip the line break itself
+                self.line_start = self.pos + 1;
+            }
+            // the charsets go on to the next lines like the colors
+            0x0e | 0x0f => {
+                self.style.charsets.shift(b);
+                self.style_outdated = true;
             }
             _ => {}
         }
     }
 
     fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
-        match intermediates {
-            &[] => match action {
-                'm' => {
-                    self.sgr(params);
+        if intermediates.is_empty() && action == 'm' {
+            self.style.sgr(params);
+            self.style_outdated = true;
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        self.style.charsets.designate(intermediates, byte);
+        self.style_outdated = true;
+    }
+}
+
+struct LineParser<'c> {
+    /// styles are not tracked when missing
+    styles: Option<&'c StyleTable>,
+    line: ParsedLine,
+    cursor_column: usize,
+    style: Style,
+    style_id: StyleId,
+    style_outdated: bool,
+    /// (first char, url) of the link being printed
+    link: Option<(u32, String)>,
+}
+
+impl<'c> LineParser<'c> {
+    fn new(styles: Option<&'c StyleTable>, style_id: StyleId) -> Self {
+        let style = styles
+            .and_then(|styles| styles.styles().get(style_id as usize).copied())
+            .unwrap_or_default();
+
+        Self {
+            styles,
+            line: ParsedLine::default(),
+            cursor_column: 0,
+            style,
+            style_id,
+            style_outdated: false,
+            link: None,
+        }
+    }
+
+    fn end_link(&mut self) {
+        if let Some((start, url)) = self.link.take() {
+            let end = self.line.len;
+            if start < end {
+                self.line.links.push(Link { start, end, url });
+            }
+            self.style_outdated = true;
+        }
+    }
+
+    // links are underlined whatever the SGR inside them, the underline ends with them
+    fn link_style(&self) -> Style {
+        let mut attribute = self.style.attribute;
+        if self.link.is_some() {
+            attribute.unset(Attribute::NoUnderline);
+            attribute.set(Attribute::Underlined);
+        } else if !self.line.links.is_empty() && !attribute.has(Attribute::Underlined) {
+            attribute.set(Attribute::NoUnderline);
+        }
+        Style {
+            attribute,
+            ..self.style
+        }
+    }
+
+    fn pop(&mut self) {
+        let line = &mut self.line;
+
+        if line.text.pop().is_none() {
+            return;
+        }
+
+        line.len -= 1;
+
+        if let Some(run) = line.runs.last_mut() {
+            run.end -= 1;
+            let prev_end = match line.runs.len() {
+                1 => 0,
+                n => line.runs[n - 2].end,
+            };
+            if line.runs.last().is_some_and(|r| r.end == prev_end) {
+                line.runs.pop();
+            }
+        }
+    }
+}
+
+impl vte::Perform for LineParser<'_> {
+    fn print(&mut self, ch: char) {
+        if self.style_outdated {
+            if let Some(styles) = self.styles {
+                self.style_id = styles.intern(self.link_style());
+            }
+            self.style_outdated = false;
+        }
+
+        let ch = self.style.charsets.map(ch);
+        let line = &mut self.line;
+        self.cursor_column += ch.width().unwrap_or(0);
+        line.text.push(ch);
+        line.len += 1;
+
+        match line.runs.last_mut() {
+            Some(run) if run.style == self.style_id => run.end = line.len,
+            _ => line.runs.push(StyleRun {
+                end: line.len,
+                style: self.style_id,
+            }),
+        }
+    }
+
+    fn execute(&mut self, b: u8) {
+        match b {
+            // backspace
+            8 => {
+                self.pop();
+            }
+            // tab
+            9 => {
+                for _ in 0..calculate_next_tab(self.cursor_column) {
+                    self.print(' ');
                 }
-                _ => {}
-            },
+            }
+            0x0e | 0x0f => self.style.charsets.shift(b),
             _ => {}
         }
     }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        if intermediates.is_empty() && action == 'm' {
+            self.style.sgr(params);
+            self.style_outdated = true;
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
+        self.style.charsets.designate(intermediates, byte);
+    }
+
+    // `ESC ] 8 ; params ; url ST`, an empty url ends the link
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        if let [b"8", _, url @ ..] = params {
+            self.end_link();
+            // a `;` in the url splits it into more params
+            let url = String::from_utf8_lossy(&url.join(&b';')).into_owned();
+            if !url.is_empty() {
+                self.link = Some((self.line.len, url));
+                self.style_outdated = true;
+            }
+        }
+    }
+}
+
+fn idx_color(c: u8) -> Color {
+    use Color::*;
+    match c {
+        0 => Black,
+        1 => DarkRed,
+        2 => DarkGreen,
+        3 => DarkYellow,
+        4 => DarkBlue,
+        5 => DarkMagenta,
+        6 => DarkCyan,
+        7 => Grey,
+        8 => DarkGrey,
+        9 => Red,
+        10 => Green,
+        11 => Yellow,
+        12 => Blue,
+        13 => Magenta,
+        14 => Cyan,
+        15 => White,
+        n => Color::AnsiValue(n),
+    }
 }
 
 fn calculate_next_tab(cursor: usize) -> usize {
@@ -0,0 +1,53 @@
+// Lines a program makes as the pager needs them, instead of a file or a pipe
+
+use std::{io, ops::Range, time::Duration};
+
+/// Lines given on demand, like the rows of a query or generated text.
+///
+/// The view doesn't call it itself: [`crate::reader::read_source`] fetches the lines in order on
+/// the reader's thread and sends them to the view like those of any input. The view only tells
+/// how far to go, a few pages past the lines shown, or all of them once it's at the end, in
+/// reverse mode or following. The lines fetched are kept by the view and never asked for again,
+/// so only the ones past where the view went are left unfetched.
+pub trait LineSource {
+    /// how many lines there are, if known, they aren't fetched past it
+    fn len_hint(&self) -> Option<usize> {
+        None
+    }
+
+    /// push the lines `range` to `out`, fewer when the others don't exist yet.
+    /// A line can have escape sequences for its colors but no line break
+    fn fetch(&mut self, range: Range<usize>, out: &mut Vec<String>) -> io::Result<()>;
+
+    /// wait up to `timeout` for lines after the ones fetched, false when there won't be any
+    fn poll(&mut self, _timeout: Duration) -> bool {
+        false
+    }
+}
+
+/// every line at once, for content already made
+impl LineSource for Vec<String> {
+    fn len_hint(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn fetch(&mut self, range: Range<usize>, out: &mut Vec<String>) -> io::Result<()> {
+        let end = range.end.min(self.len());
+        out.extend_from_slice(self.get(range.start..end).unwrap_or_default());
+        Ok(())
+    }
+}
+
+impl<S: LineSource + ?Sized> LineSource for Box<S> {
+    fn len_hint(&self) -> Option<usize> {
+        (**self).len_hint()
+    }
+
+    fn fetch(&mut self, range: Range<usize>, out: &mut Vec<String>) -> io::Result<()> {
+        (**self).fetch(range, out)
+    }
+
+    fn poll(&mut self, timeout: Duration) -> bool {
+        (**self).poll(timeout)
+    }
+}
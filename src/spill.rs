@@ -0,0 +1,162 @@
+use std::{
+    collections::hash_map::RandomState,
+    fs::{File, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+/// names tried before giving up, another one is taken only if the name was
+const ATTEMPTS: usize = 16;
+
+/// Temporary file holding lines of a piped input once it outgrows the memory budget
+pub struct Spill {
+    threshold: usize,
+    // created on first use, why it couldn't be otherwise
+    file: OnceLock<Result<(File, PathBuf), String>>,
+}
+
+impl Spill {
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            file: OnceLock::new(),
+        }
+    }
+
+    /// bytes kept in memory before spilling
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn file(&self) -> Result<&File, &str> {
+        match self.file.get_or_init(create) {
+            Ok((file, _)) => Ok(file),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// why the bytes couldn't be written, they are kept in memory then
+    pub fn write(&self, offset: u64, bytes: &[u8]) -> Result<(), String> {
+        let file = self.file()?;
+        write_at(file, bytes, offset).map_err(|e| {
+            let dir = std::env::temp_dir();
+            format!("Can't spill to {}: {}", dir.display(), e)
+        })
+    }
+
+    pub fn read(&self, offset: u64, len: u32) -> Vec<u8> {
+        let mut buf = vec![0; len as usize];
+
+        match self.file() {
+            Ok(file) if read_at(file, &mut buf, offset).is_ok() => buf,
+            _ => {
+                #[cfg(feature = "logging")]
+                log::error!("Can't read spilled line at {}", offset);
+                Vec::new()
+            }
+        }
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        if let Some(Ok((_, path))) = self.file.take() {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+// a new file of a random name in the temporary directory, that's shared: a file of the same
+// name is left alone and another name is tried
+fn create() -> Result<(File, PathBuf), String> {
+    let dir = std::env::temp_dir();
+    let mut error = None;
+
+    for _ in 0..ATTEMPTS {
+        let random = RandomState::new().build_hasher().finish();
+        let path = dir.join(format!("rp-{}-{:016x}.spill", std::process::id(), random));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path);
+
+        match file {
+            Ok(file) => {
+                // nobody else needs the name, it's removed as soon as it's closed
+                #[cfg(unix)]
+                std::fs::remove_file(&path).ok();
+                return Ok((file, path));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => error = Some(e),
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
+
+    let error = error.map_or_else(String::new, |e| e.to_string());
+    Err(format!("Can't spill to {}: {}", dir.display(), error))
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, bytes: &[u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(bytes, offset)
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, mut bytes: &[u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !bytes.is_empty() {
+        let n = file.seek_write(bytes, offset)?;
+        bytes = &bytes[n..];
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        let n = file.seek_read(buf, offset)?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        buf = &mut buf[n..];
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spilled_bytes_are_read_back() {
+        let spill = Spill::new(0);
+        spill.write(0, b"one").unwrap();
+        spill.write(3, b"two").unwrap();
+        assert_eq!(spill.read(3, 3), b"two");
+        assert_eq!(spill.read(0, 6), b"onetwo");
+    }
+
+    #[test]
+    fn each_spill_has_its_own_file() {
+        let (a, b) = (Spill::new(0), Spill::new(0));
+        a.write(0, b"a").unwrap();
+        b.write(0, b"b").unwrap();
+        assert_eq!((a.read(0, 1), b.read(0, 1)), (b"a".to_vec(), b"b".to_vec()));
+    }
+}
@@ -0,0 +1,289 @@
+// Timestamps at the start of log lines, to jump to a time of day
+
+use std::borrow::Cow;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parts of a date and time, each one missing from some formats
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Timestamp {
+    pub year: Option<u32>,
+    /// (month, day)
+    pub day: Option<(u32, u32)>,
+    /// milliseconds since midnight
+    pub time: Option<u32>,
+}
+
+impl Timestamp {
+    /// `self` at or after `other`, comparing only the parts both have, the biggest first
+    pub fn at_or_after(&self, other: &Timestamp) -> bool {
+        let parts = [
+            (self.year, other.year),
+            (self.day.map(day_key), other.day.map(day_key)),
+            (self.time, other.time),
+        ];
+        for part in parts {
+            if let (Some(a), Some(b)) = part {
+                if a != b {
+                    return a > b;
+                }
+            }
+        }
+        true
+    }
+}
+
+fn day_key((month, day): (u32, u32)) -> u32 {
+    month * 32 + day
+}
+
+/// Timestamp starting `text`, after spaces and a `[`, in one of these formats:
+///
+/// - `2024-05-01 14:32:05.123`, `2024-05-01T14:32:05Z` or with `/` between the date parts
+/// - `May  1 14:32:05`, like syslog
+/// - `14:32:05`
+///
+/// Only a time of day counts, the lines starting with a number could be anything else
+pub fn at_line_start(text: &str) -> Option<Timestamp> {
+    let text = text.trim_start();
+    let text = text.strip_prefix('[').unwrap_or(text);
+    parse(text).filter(|stamp| stamp.time.is_some())
+}
+
+/// Timestamp `:time` was given, in the same formats, a date alone or `2024-05-01 14:32`
+pub fn parse_target(text: &str) -> Option<Timestamp> {
+    let stamp = parse(text.trim())?;
+    Some(stamp).filter(|stamp| stamp.day.is_some() || stamp.time.is_some())
+}
+
+fn parse(text: &str) -> Option<Timestamp> {
+    let mut stamp = Timestamp::default();
+    let mut rest = text;
+
+    if let Some((year, month, day, after)) = iso_date(rest) {
+        stamp.year = Some(year);
+        stamp.day = Some((month, day));
+        rest = after.strip_prefix(['T', ' ']).unwrap_or(after);
+    } else if let Some((month, day, after)) = syslog_date(rest) {
+        stamp.day = Some((month, day));
+        rest = after;
+    }
+
+    stamp.time = time_of_day(rest);
+    if stamp.day.is_none() && stamp.time.is_none() {
+        return None;
+    }
+    Some(stamp)
+}
+
+// `YYYY-MM-DD` or `YYYY/MM/DD`, and what follows
+fn iso_date(text: &str) -> Option<(u32, u32, u32, &str)> {
+    let (year, rest) = digits(text, 4)?;
+    let sep = rest.chars().next().filter(|c| *c == '-' || *c == '/')?;
+    let (month, rest) = digits(&rest[1..], 2)?;
+    let (day, rest) = digits(rest.strip_prefix(sep)?, 2)?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    Some((year, month, day, rest))
+}
+
+// `Mon DD `, the day padded with a space or not
+fn syslog_date(text: &str) -> Option<(u32, u32, &str)> {
+    let month = MONTHS.iter().position(|m| text.starts_with(m))? as u32 + 1;
+    let rest = text[3..].strip_prefix(' ')?.trim_start();
+    let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    if len == 0 || len > 2 {
+        return None;
+    }
+
+    let day = rest[..len].parse().ok()?;
+    let rest = rest[len..].strip_prefix(' ')?;
+    Some((month, day, rest))
+}
+
+// `HH:MM[:SS[.fff]]`, in milliseconds
+fn time_of_day(text: &str) -> Option<u32> {
+    let (hours, rest) = digits(text, 2)?;
+    let (minutes, rest) = digits(rest.strip_prefix(':')?, 2)?;
+    let (seconds, rest) = match rest.strip_prefix(':').and_then(|rest| digits(rest, 2)) {
+        Some((seconds, rest)) => (seconds, rest),
+        None => (0, rest),
+    };
+
+    // `14:32:05,123` from Java loggers too
+    let millis = match rest.strip_prefix(['.', ',']) {
+        Some(frac) => {
+            let len = frac.find(|c: char| !c.is_ascii_digit()).unwrap_or(frac.len());
+            let padded = format!("{:0<3}", &frac[..len.min(3)]);
+            padded.parse().unwrap_or(0)
+        }
+        None => 0,
+    };
+
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+// `len` ASCII digits and what follows
+fn digits(text: &str, len: usize) -> Option<(u32, &str)> {
+    let head = text.get(..len)?;
+    if !head.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((head.parse().ok()?, &text[len..]))
+}
+
+/// First line from `first` on with a timestamp, and that timestamp
+pub fn next_stamped<'t>(
+    line: &impl Fn(usize) -> Cow<'t, str>,
+    first: usize,
+    len: usize,
+) -> Option<(usize, Timestamp)> {
+    (first..len).find_map(|idx| at_line_start(&line(idx)).map(|stamp| (idx, stamp)))
+}
+
+/// First line with a timestamp at or after `target`, out of `len` lines given by `line`.
+///
+/// The lines are expected in order of time, so this is a binary search where the lines
+/// without a timestamp, like the rest of a stack trace, take the one of the next line having one
+pub fn first_at_or_after<'t>(
+    line: impl Fn(usize) -> Cow<'t, str>,
+    len: usize,
+    target: &Timestamp,
+) -> Option<usize> {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match next_stamped(&line, mid, high) {
+            Some((_, stamp)) if !stamp.at_or_after(target) => low = mid + 1,
+            _ => high = mid,
+        }
+    }
+
+    next_stamped(&line, low, len)
+        .filter(|(_, stamp)| stamp.at_or_after(target))
+        .map(|(idx, _)| idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(year: Option<u32>, day: Option<(u32, u32)>, time: u32) -> Option<Timestamp> {
+        Some(Timestamp {
+            year,
+            day,
+            time: Some(time),
+        })
+    }
+
+    // the lines given as `first_at_or_after` takes them
+    fn lines(text: &'static str) -> (impl Fn(usize) -> Cow<'static, str>, usize) {
+        let lines = text.lines().collect::<Vec<_>>();
+        let len = lines.len();
+        (move |idx| Cow::Borrowed(lines[idx]), len)
+    }
+
+    #[test]
+    fn syslog_stamps() {
+        let time = (14 * 3600 + 32 * 60 + 5) * 1000;
+        assert_eq!(
+            at_line_start("May  1 14:32:05 host sshd[42]: started"),
+            stamp(None, Some((5, 1)), time)
+        );
+        assert_eq!(
+            at_line_start("Dec 24 14:32:05 host"),
+            stamp(None, Some((12, 24)), time)
+        );
+    }
+
+    #[test]
+    fn iso_stamps() {
+        let time = (14 * 3600 + 32 * 60 + 5) * 1000;
+        assert_eq!(
+            at_line_start("2024-05-01T14:32:05.123Z GET /"),
+            stamp(Some(2024), Some((5, 1)), time + 123)
+        );
+        assert_eq!(
+            at_line_start("  [2024/05/01 14:32:05] done"),
+            stamp(Some(2024), Some((5, 1)), time)
+        );
+        assert_eq!(at_line_start("14:32 up"), stamp(None, None, time - 5000));
+    }
+
+    #[test]
+    fn java_stamps() {
+        let time = (9 * 3600 + 5 * 60 + 7) * 1000;
+        assert_eq!(
+            at_line_start("2024-05-01 09:05:07,5 INFO Main - started"),
+            stamp(Some(2024), Some((5, 1)), time + 500)
+        );
+        assert_eq!(
+            at_line_start("09:05:07,1234 WARN"),
+            stamp(None, None, time + 123)
+        );
+    }
+
+    #[test]
+    fn lines_without_stamps() {
+        for text in [
+            "",
+            "\tat com.example.Main.run(Main.java:10)",
+            "2024-05-01 has a date only",
+            "12345 lines read",
+            "25:00 isn't a time",
+            "Mayday 14:32:05",
+        ] {
+            assert_eq!(at_line_start(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn targets() {
+        assert_eq!(
+            parse_target(" 2024-05-01 "),
+            Some(Timestamp {
+                year: Some(2024),
+                day: Some((5, 1)),
+                time: None,
+            })
+        );
+        assert_eq!(parse_target("10:30"), stamp(None, None, 37_800_000));
+        assert_eq!(parse_target("tomorrow"), None);
+    }
+
+    #[test]
+    fn first_line_at_or_after_a_time() {
+        let (line, len) = lines(
+            "10:00:00 start\n\
+             10:05:00 error\n\
+             \tat one\n\
+             \tat two\n\
+             10:10:00 retry\n\
+             10:15:00 done\n",
+        );
+        let at = |target| first_at_or_after(&line, len, &parse_target(target).unwrap());
+
+        assert_eq!(at("09:00"), Some(0));
+        assert_eq!(at("10:05"), Some(1));
+        // the lines of the stack trace take the time of the next line
+        assert_eq!(at("10:07"), Some(4));
+        assert_eq!(at("10:15"), Some(5));
+        assert_eq!(at("10:16"), None);
+        assert_eq!(at("23:59"), None);
+    }
+
+    #[test]
+    fn no_stamped_lines() {
+        let (line, len) = lines("some\ntext\n");
+        let target = parse_target("10:00").unwrap();
+        assert_eq!(first_at_or_after(&line, len, &target), None);
+        assert_eq!(first_at_or_after(&line, 0, &target), None);
+    }
+}
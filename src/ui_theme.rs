@@ -0,0 +1,107 @@
+// Colors of what rp draws itself, apart from the input
+
+use crossterm::style::{Attribute, Color, SetAttribute, SetBackgroundColor, SetForegroundColor};
+
+/// How a part drawn by rp stands out
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Paint {
+    /// reverse video, readable whatever the colors of the terminal
+    Reverse,
+    /// (foreground, background)
+    Colors(Color, Color),
+}
+
+impl Paint {
+    /// escape sequence starting the part
+    pub fn start(self) -> String {
+        match self {
+            Self::Reverse => SetAttribute(Attribute::Reverse).to_string(),
+            Self::Colors(fg, bg) => format!("{}{}", SetForegroundColor(fg), SetBackgroundColor(bg)),
+        }
+    }
+
+    /// `text` the other way around inside the part, like the cursor of a prompt
+    pub fn inverted(self, text: &str) -> String {
+        match self {
+            Self::Reverse => format!(
+                "{}{}{}",
+                SetAttribute(Attribute::NoReverse),
+                text,
+                SetAttribute(Attribute::Reverse)
+            ),
+            Self::Colors(fg, bg) => format!(
+                "{}{}{}",
+                Self::Colors(bg, fg).start(),
+                text,
+                self.start()
+            ),
+        }
+    }
+}
+
+/// Colors of the prompt, the search matches and the other parts drawn by rp
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UiTheme {
+    pub name: &'static str,
+    /// the prompt line: the status bar, messages and the prompts of searches and commands
+    pub prompt: Paint,
+    /// the search matches in the lines
+    pub search: Paint,
+    /// the selected item of the lists over the view, like the buffers or the palette
+    pub selected: Paint,
+    /// the line numbers, dimmed with their color
+    pub gutter: Color,
+    /// background of the lines new in follow mode
+    pub new_line: Color,
+    /// the search matches on the scrollbar and the minimap
+    pub marks: Color,
+}
+
+/// reverse video, like most pagers
+pub const DEFAULT: UiTheme = UiTheme {
+    name: "default",
+    prompt: Paint::Reverse,
+    search: Paint::Reverse,
+    selected: Paint::Reverse,
+    gutter: Color::Reset,
+    new_line: Color::AnsiValue(236),
+    marks: Color::DarkYellow,
+};
+
+pub const DARK: UiTheme = UiTheme {
+    name: "dark",
+    prompt: Paint::Colors(Color::AnsiValue(252), Color::AnsiValue(238)),
+    search: Paint::Colors(Color::AnsiValue(16), Color::AnsiValue(178)),
+    selected: Paint::Colors(Color::AnsiValue(16), Color::AnsiValue(110)),
+    gutter: Color::AnsiValue(243),
+    new_line: Color::AnsiValue(236),
+    marks: Color::AnsiValue(178),
+};
+
+pub const LIGHT: UiTheme = UiTheme {
+    name: "light",
+    prompt: Paint::Colors(Color::AnsiValue(235), Color::AnsiValue(252)),
+    search: Paint::Colors(Color::AnsiValue(16), Color::AnsiValue(229)),
+    selected: Paint::Colors(Color::AnsiValue(16), Color::AnsiValue(153)),
+    gutter: Color::AnsiValue(245),
+    new_line: Color::AnsiValue(254),
+    marks: Color::AnsiValue(136),
+};
+
+/// the built-in themes
+pub const THEMES: &[UiTheme] = &[DEFAULT, DARK, LIGHT];
+
+impl UiTheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        THEMES.iter().find(|theme| theme.name == name).copied()
+    }
+
+    /// the one readable on the background of the terminal
+    pub fn for_background(light: bool) -> Self {
+        if light {
+            LIGHT
+        } else {
+            DARK
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+/// Tells when a file changed, with inotify on linux and by polling its metadata elsewhere
+pub struct Watcher {
+    #[cfg(target_os = "linux")]
+    fd: libc::c_int,
+    /// watched again after each change, a file replaced by a rename is another inode
+    #[cfg(target_os = "linux")]
+    path: std::ffi::CString,
+    #[cfg(not(target_os = "linux"))]
+    path: std::path::PathBuf,
+    #[cfg(not(target_os = "linux"))]
+    last: Option<(u64, std::time::SystemTime)>,
+}
+
+impl Watcher {
+    #[cfg(target_os = "linux")]
+    pub fn new(path: &Path) -> Option<Self> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+
+        let watcher = Self { fd, path };
+        if !watcher.add_watch() {
+            return None;
+        }
+        Some(watcher)
+    }
+
+    // the file now at the path, its link count changes when a rename replaces it
+    #[cfg(target_os = "linux")]
+    fn add_watch(&self) -> bool {
+        let mask = libc::IN_MODIFY
+            | libc::IN_CLOSE_WRITE
+            | libc::IN_ATTRIB
+            | libc::IN_MOVE_SELF
+            | libc::IN_DELETE_SELF;
+        unsafe { libc::inotify_add_watch(self.fd, self.path.as_ptr(), mask) >= 0 }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(path: &Path) -> Option<Self> {
+        let mut watcher = Self {
+            path: path.to_path_buf(),
+            last: None,
+        };
+        watcher.last = watcher.stat();
+        Some(watcher)
+    }
+
+    /// true if the file changed since the last call, never blocks
+    #[cfg(target_os = "linux")]
+    pub fn changed(&mut self) -> bool {
+        let mut buf = [0u8; 4096];
+        let mut changed = false;
+
+        // drain every pending event
+        while unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } > 0
+        {
+            changed = true;
+        }
+        // the same inode is watched once, a new one if it was replaced, none while it's gone
+        if changed {
+            self.add_watch();
+        }
+
+        changed
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn changed(&mut self) -> bool {
+        let current = self.stat();
+        if current != self.last {
+            self.last = current;
+            true
+        } else {
+            false
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn stat(&self) -> Option<(u64, std::time::SystemTime)> {
+        let metadata = std::fs::metadata(&self.path).ok()?;
+        Some((metadata.len(), metadata.modified().ok()?))
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_replaced_by_a_rename_are_seen() {
+        let dir = std::env::temp_dir().join(format!("rp-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (path, temp) = (dir.join("file"), dir.join("file.tmp"));
+        std::fs::write(&path, "one").unwrap();
+        let mut watcher = Watcher::new(&path).unwrap();
+        assert!(!watcher.changed());
+
+        // saved like an editor does, twice in a row
+        for text in ["two!", "three!!"] {
+            std::fs::write(&temp, text).unwrap();
+            std::fs::rename(&temp, &path).unwrap();
+            assert!(watcher.changed(), "{}", text);
+            assert!(!watcher.changed(), "{}", text);
+        }
+
+        std::fs::write(&path, "in place").unwrap();
+        assert!(watcher.changed());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
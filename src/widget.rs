@@ -0,0 +1,171 @@
+// The pager as a pane of a ratatui app
+
+use crate::backend::{Backend, Cell, Headless};
+use crate::shared::{RawLine, ReaderState, StyleTable};
+use crate::writer::UiContext;
+use crossbeam_queue::ArrayQueue;
+use crossterm::{
+    event::Event,
+    style::{Attribute, Color},
+    Result,
+};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{self, Modifier},
+    widgets::{StatefulWidget, Widget},
+};
+use std::{marker::PhantomData, sync::Arc};
+
+// ratatui has a single kind of underline
+const MODIFIERS: [(Attribute, Modifier); 13] = [
+    (Attribute::Bold, Modifier::BOLD),
+    (Attribute::Dim, Modifier::DIM),
+    (Attribute::Italic, Modifier::ITALIC),
+    (Attribute::Underlined, Modifier::UNDERLINED),
+    (Attribute::DoubleUnderlined, Modifier::UNDERLINED),
+    (Attribute::Undercurled, Modifier::UNDERLINED),
+    (Attribute::Underdotted, Modifier::UNDERLINED),
+    (Attribute::Underdashed, Modifier::UNDERLINED),
+    (Attribute::SlowBlink, Modifier::SLOW_BLINK),
+    (Attribute::RapidBlink, Modifier::RAPID_BLINK),
+    (Attribute::Reverse, Modifier::REVERSED),
+    (Attribute::Hidden, Modifier::HIDDEN),
+    (Attribute::CrossedOut, Modifier::CROSSED_OUT),
+];
+
+/// Draws the lines of a [`PagerState`] in its area, wrapped, highlighted and with the prompt
+/// on the last row, like `rp` draws them on the terminal.
+///
+/// ```no_run
+/// # fn frame(frame: &mut ratatui::Frame, state: &mut rust_pager::widget::PagerState) {
+/// use rust_pager::widget::PagerView;
+///
+/// frame.render_stateful_widget(PagerView::new(), frame.area(), state);
+/// # }
+/// ```
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PagerView<'b>(PhantomData<&'b ()>);
+
+impl<'b> PagerView<'b> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+/// The lines of a [`PagerView`], where it's scrolled to and what it searches.
+///
+/// The lines come from one of the [`crate::reader`] functions, run on another thread like
+/// [`crate::pager::Pager`] does with the same `rx`, `styles` and `reader`, those received
+/// meanwhile are shown each time it's drawn. The keys of the pane go to [`Self::handle_event`].
+pub struct PagerState<'b> {
+    ui: UiContext<'b>,
+    screen: Headless,
+}
+
+impl<'b> PagerState<'b> {
+    pub fn new(
+        rx: Arc<ArrayQueue<RawLine<'b>>>,
+        styles: Arc<StyleTable>,
+        reader: Arc<ReaderState>,
+    ) -> Result<Self> {
+        // resized to the area when first drawn
+        let screen = Headless::new(80, 24);
+        let ui = UiContext::with_backend(rx, styles, reader, screen.clone())?;
+        Ok(Self { ui, screen })
+    }
+
+    /// set the options of the pane, like `state.map(|ui| ui.wrap(false))`
+    pub fn map(mut self, f: impl FnOnce(UiContext<'b>) -> UiContext<'b>) -> Self {
+        self.ui = f(self.ui);
+        self
+    }
+
+    /// a key or a click on the pane, handled like `rp` does, true once it quits, like on `q`.
+    /// The events are those of `rust_pager`'s version of crossterm. The keys running programs
+    /// or writing files, like `!`, `|`, `V` and `s`, and Ctrl-Z only tell there's no terminal
+    pub fn handle_event(&mut self, event: Event) -> Result<bool> {
+        self.ui.handle_event(event)
+    }
+
+    pub fn ui(&mut self) -> &mut UiContext<'b> {
+        &mut self.ui
+    }
+}
+
+impl<'b> StatefulWidget for PagerView<'b> {
+    type State = PagerState<'b>;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        // nothing to draw in, like a pane squeezed out by the layout
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        if state.screen.size().ok() != Some((area.width, area.height)) {
+            state.screen.resize(area.width, area.height);
+            let resize = Event::Resize(area.width, area.height);
+            state.ui.handle_event(resize).ok();
+        }
+        // drawing to the headless screen can't fail
+        state.ui.render().ok();
+
+        for y in 0..area.height {
+            for x in 0..area.width {
+                let cell = state.screen.cell(x as usize, y as usize);
+                if let Some(target) = buf.cell_mut((area.x + x, area.y + y)) {
+                    set_cell(target, cell);
+                }
+            }
+        }
+    }
+}
+
+impl<'b> Widget for &mut PagerState<'b> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        StatefulWidget::render(PagerView::new(), area, buf, self);
+    }
+}
+
+fn set_cell(target: &mut ratatui::buffer::Cell, cell: Cell) {
+    target.reset();
+    // the right half of a wide char, drawn with the left one
+    if cell.ch == '\0' {
+        target.set_symbol("");
+        return;
+    }
+
+    let modifier = MODIFIERS
+        .iter()
+        .filter(|(attribute, _)| cell.style.attribute.has(*attribute))
+        .fold(Modifier::empty(), |modifier, (_, m)| modifier | *m);
+    let style = style::Style::default()
+        .fg(color(cell.style.foreground))
+        .bg(color(cell.style.background))
+        .add_modifier(modifier);
+    target.set_char(cell.ch).set_style(style);
+}
+
+// ratatui's names of the colors, the dark ones of crossterm are its normal ones
+fn color(color: Color) -> style::Color {
+    match color {
+        Color::Reset => style::Color::Reset,
+        Color::Black => style::Color::Black,
+        Color::DarkGrey => style::Color::DarkGray,
+        Color::Red => style::Color::LightRed,
+        Color::DarkRed => style::Color::Red,
+        Color::Green => style::Color::LightGreen,
+        Color::DarkGreen => style::Color::Green,
+        Color::Yellow => style::Color::LightYellow,
+        Color::DarkYellow => style::Color::Yellow,
+        Color::Blue => style::Color::LightBlue,
+        Color::DarkBlue => style::Color::Blue,
+        Color::Magenta => style::Color::LightMagenta,
+        Color::DarkMagenta => style::Color::Magenta,
+        Color::Cyan => style::Color::LightCyan,
+        Color::DarkCyan => style::Color::Cyan,
+        Color::White => style::Color::White,
+        Color::Grey => style::Color::Gray,
+        Color::Rgb { r, g, b } => style::Color::Rgb(r, g, b),
+        Color::AnsiValue(idx) => style::Color::Indexed(idx),
+    }
+}
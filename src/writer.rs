@@ -1,46 +1,151 @@
 use ahash::AHashMap;
 use crossbeam_queue::ArrayQueue;
 use crossterm::{
-    cursor::{Hide, MoveTo, MoveToNextLine, Show},
+    cursor::{Hide, MoveTo, Show},
     event::{
         poll, read, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent,
-        KeyModifiers, MouseEvent, MouseEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
     },
     execute, queue,
     style::{
         Attribute, Attributes, Color, SetAttribute, SetAttributes, SetBackgroundColor,
-        SetForegroundColor,
+        SetForegroundColor, SetUnderlineColor,
     },
     terminal::{
         disable_raw_mode, enable_raw_mode, Clear, ClearType, DisableLineWrap, EnableLineWrap,
         EnterAlternateScreen, LeaveAlternateScreen,
     },
+    tty::IsTty,
     Result,
 };
 use rayon::prelude::*;
+use regex::Regex;
 use smallvec::SmallVec;
 use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
     fs::File,
     io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
     sync::atomic::Ordering,
     sync::Arc,
     time::{Duration, Instant},
 };
 use unicode_width::UnicodeWidthChar;
 
-use crate::shared::{RpChar, RpLine};
+use crate::backend::{Backend, Terminal};
+use crate::colors::{self, ColorDepth};
+use crate::config::{self, Bell, Config, LogLevel, Theme};
+use crate::convert;
+use crate::diff::{DiffIndex, DiffLine};
+use crate::fold;
+use crate::history::{Bookmark, SavedView, MAX_SEARCHES};
+use crate::hook::{Decoration, Decorator, Hook, KeyAction, KeyFilter, PagerEvent};
+use crate::image::{self, Protocol};
+use crate::keys::{self, Keymap};
+use crate::links::{self, Target};
+use crate::mmap;
+use crate::prompt;
+use crate::reader::HEX_ROW;
+use crate::timestamp;
+use crate::ui_theme::{self, Paint, UiTheme};
+use crate::watch::Watcher;
+use crate::shared::{
+    drawn_attributes, ParsedLine, RawBytes, RawLine, ReaderState, RpChar, Style, StyleRun,
+    StyleTable,
+};
 
 type SearchPositionArr = SmallVec<[SearchPosition; 4]>;
 const OUTBUF_SIZE: usize = 1024 * 20;
+/// parsed lines further than this from the view are dropped
+const PARSE_MARGIN: usize = 1024;
+/// shown while the size of the input is unknown, turns every 64KiB read
+const SPINNER: &[char] = &['|', '/', '-', '\\'];
+/// lines that arrived more recently than this are highlighted while following
+const NEW_LINE_HIGHLIGHT: Duration = Duration::from_secs(3);
+/// background of the lines a wrapped command wrote to stderr
+const STDERR_BACKGROUND: Color = Color::AnsiValue(52);
+/// the pages of a mapped input far from the view are dropped at most this often
+const EVICT_INTERVAL: Duration = Duration::from_millis(250);
+/// how often the input rate is measured
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+/// changes of a watched file are gathered for this long before reloading it
+const WATCH_DELAY: Duration = Duration::from_millis(200);
+/// the visual bell flashes the prompt line this long
+const BELL_FLASH: Duration = Duration::from_millis(150);
+/// a second click on the same cell within this long is a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/// key presses closer than this are from a held key
+const REPEAT_GAP: Duration = Duration::from_millis(100);
+/// background of the line under the line cursor
+const CURSOR_BACKGROUND: Color = Color::AnsiValue(237);
+/// background of the lines selected in visual mode
+const SELECTION_BACKGROUND: Color = Color::AnsiValue(24);
+/// background of the guide columns
+const GUIDE_BACKGROUND: Color = Color::AnsiValue(236);
+/// held key repeats before moving to the next acceleration step
+const REPEATS_PER_STEP: usize = 8;
+/// wait for the next key of a sequence, like vim's `timeoutlen`
+const KEY_TIMEOUT: Duration = Duration::from_secs(1);
+/// macros replaying macros, replaying themselves at worst
+const MAX_MACRO_DEPTH: usize = 16;
+/// the terminal sends up and down keys for the wheel on the alternate screen, used while
+/// the mouse isn't captured
+const ALTERNATE_SCROLL_ON: &str = "\x1b[?1007h";
+const ALTERNATE_SCROLL_OFF: &str = "\x1b[?1007l";
+/// the title of the window is saved on the stack of the terminal, and restored from it
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+/// options of `:set`, the ones of `-` included
+const OPTIONS: &[&str] = &[
+    "wrap",
+    "number",
+    "ignorecase",
+    "squeeze",
+    "reverse",
+    "dedup",
+    "mouse",
+    "tint",
+    "scrollbar",
+    "minimap",
+    "ruler",
+    "cursorline",
+    "title",
+    "mono",
+    "urls",
+    "filelinks",
+    "escapes",
+    "memory",
+];
+/// lines `{` and `}` move between and the outline lists unless another pattern is given:
+/// man page headings, the files of a diff, Markdown headings and `== name ==` markers
+const DEFAULT_SECTION: &str = r"^(?:[A-Z][A-Z0-9 -]*[A-Z0-9]$|diff |#{1,6} |={2,} .* ={2,}$)";
+/// questions of the first-run prompt, all default to yes
+const SETUP_QUESTIONS: &[&str] = &[
+    "Capture the mouse? Scroll with the wheel, Shift+drag still selects text",
+    "Wrap long lines? Otherwise they are cut at the edge",
+    "Keep the colors of the input? Otherwise only bold, underline, etc. are shown",
+];
+
+#[cfg(unix)]
+fn get_output() -> Result<File> {
+    File::create("/dev/tty")
+}
 
 #[cfg(unix)]
-fn get_output() -> File {
-    File::create("/dev/tty").expect("Can't open tty")
+fn get_input() -> Result<File> {
+    File::open("/dev/tty")
 }
 
 #[cfg(windows)]
-fn get_output() -> File {
-    File::create("CON:").expect("Can't open con")
+fn get_output() -> Result<File> {
+    File::create("CON:")
+}
+
+#[cfg(windows)]
+fn get_input() -> Result<File> {
+    File::open("CONIN$")
 }
 
 #[derive(Clone, Copy)]
@@ -53,15 +158,44 @@ pub enum PromptState {
     Normal,
     Number(usize),
     Search(String),
+    Command(String),
+    /// index of the current first-run question
+    Setup(usize),
+    /// selected input of the list
+    Buffers(usize),
+    /// selected line of the panel of the search matches, see [`UiContext::panel_lines`]
+    Matches(usize),
+    /// selected heading of the outline panel, the lines matching [`UiContext::section`]
+    Outline(usize),
+    /// selected entry of the panel of the bookmarks
+    Bookmarks(usize),
+    /// keys of a sequence typed so far, like `]` of `]c`, and when the last one was
+    Keys(Vec<KeyEvent>, Instant),
+    /// waiting for the letter of the option to toggle, see [`UiContext::toggle_option`]
+    Toggle,
+    /// first line of the help shown
+    Help(usize),
+    /// first line shown of the output of the last `:pipe`
+    Output(usize),
+    /// query of the command palette and the selected action out of its matches
+    Palette(String, usize),
+    /// waiting for the register to record into or to replay, and the count of replays
+    Register(KeyBehavior, usize),
 }
 
 impl PromptState {
     pub fn take(&mut self) -> Self {
         std::mem::replace(self, Self::Normal)
     }
+
+    /// the keys go to the keymap, not to a prompt or a list
+    fn is_keymap(&self) -> bool {
+        matches!(self, Self::Normal | Self::Keys(..))
+    }
 }
 
-#[derive(Clone, Copy)]
+/// How far a key scrolls
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ScrollSize {
     One,
     HalfPage,
@@ -80,35 +214,104 @@ impl ScrollSize {
     }
 }
 
-#[derive(Clone, Copy)]
+/// What a key does, the actions of the keymap and of `bind` in the config
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum KeyBehavior {
     Quit,
 
     Down(ScrollSize),
     Up(ScrollSize),
+    /// pan the lines when they aren't wrapped, half a page up or down otherwise
+    Left,
+    Right,
 
     SearchNext,
     SearchPrev,
+    SectionNext,
+    SectionPrev,
 
     NormalMode,
     Number(u32),
     Search,
-}
+    Command,
 
-fn default_keymap() -> AHashMap<KeyEvent, KeyBehavior> {
-    let mut dict = AHashMap::new();
+    Convert,
+    Dedup,
+    Follow,
+    Reload,
+    Pause,
+    Buffers,
+    /// show only the lines a wrapped command wrote to stderr, or everything again
+    StderrOnly,
+    /// switch between the text and the hex dump of the input
+    Hex,
+    /// quit and write the input to stdout, see [`UiContext::dump`]
+    DumpQuit,
+    HunkNext,
+    HunkPrev,
+    FileNext,
+    FilePrev,
+    /// `a`, `o`, `c`, `M`, `R` or `i`, see [`UiContext::fold`]
+    Fold(char),
+    /// scroll to have the cursor line on top, at the center or at the bottom of the view
+    LineTop,
+    LineCenter,
+    LineBottom,
+    /// toggle the option of the next key, like `-S` of less
+    Toggle,
+    /// list the keys and what they do
+    Help,
+    /// pick an action by its name, see [`crate::keys::palette`]
+    Palette,
+    /// record the next keys into the register typed next, or stop recording
+    Record,
+    /// replay the keys of the register typed next, `@` for the last one replayed
+    Replay,
+    /// show a line cursor, moved by the one line scrolls instead of the view
+    CursorLine,
+    /// select the lines from the line cursor to where it's moved
+    Visual,
+    /// capture the mouse, or leave it to the terminal to select text
+    Mouse,
+    /// stop like any program on Ctrl-Z, the terminal restored until `fg`
+    Suspend,
+    /// open the hyperlink, url or file position nearest the click, or the first one of the cursor
+    /// line, with the system opener or the editor
+    OpenLink,
+    /// open the file at the cursor or top line in `$VISUAL` or `$EDITOR`, reloaded once it exits
+    Edit,
+    /// prompt for the path to write the lines to, the selected ones in visual mode
+    Save,
+    /// prompt for a command to pipe the lines to, the selected ones in visual mode
+    Pipe,
+    /// prompt for a shell command run on the terminal, `%` the name of the file
+    Shell,
+    /// list the lines matching the search in a panel, to jump to one of them
+    Matches,
+    /// list the lines starting the sections in a panel, to jump to one of them
+    Outline,
+    /// prompt for the name and note of a bookmark of the cursor or top line
+    Bookmark,
+    /// list the bookmarks in a panel, to jump to one of them
+    Bookmarks,
+}
 
-    macro_rules! keymap {
-        ($($modifier:expr => [$(($code:expr, $behavior:expr),)*],)*) => {
+// add the keys with each modifier to `dict`
+macro_rules! keymap {
+    ($dict:ident; $($modifier:expr => [$(($code:expr, $behavior:expr),)*],)*) => {
+        $(
             $(
-                $(
-                    dict.insert(KeyEvent::new($code, $modifier), $behavior);
-                )*
+                $dict.insert(KeyEvent::new($code, $modifier), $behavior);
             )*
-        }
+        )*
     }
+}
+
+fn default_keymap() -> AHashMap<KeyEvent, KeyBehavior> {
+    let mut dict = AHashMap::new();
 
     keymap! {
+        dict;
         KeyModifiers::NONE => [
             (KeyCode::Enter, KeyBehavior::Down(ScrollSize::One)),
             (KeyCode::Down, KeyBehavior::Down(ScrollSize::One)),
@@ -119,8 +322,8 @@ fn default_keymap() -> AHashMap<KeyEvent, KeyBehavior> {
 
             (KeyCode::Char('u'), KeyBehavior::Up(ScrollSize::HalfPage)),
             (KeyCode::Char('d'), KeyBehavior::Down(ScrollSize::HalfPage)),
-            (KeyCode::Left, KeyBehavior::Up(ScrollSize::HalfPage)),
-            (KeyCode::Right, KeyBehavior::Down(ScrollSize::HalfPage)),
+            (KeyCode::Left, KeyBehavior::Left),
+            (KeyCode::Right, KeyBehavior::Right),
 
             (KeyCode::Char('f'), KeyBehavior::Down(ScrollSize::Page)),
             (KeyCode::Char(' '), KeyBehavior::Down(ScrollSize::Page)),
@@ -137,6 +340,21 @@ fn default_keymap() -> AHashMap<KeyEvent, KeyBehavior> {
 
             (KeyCode::Char('/'), KeyBehavior::Search),
             (KeyCode::Char('n'), KeyBehavior::SearchNext),
+            (KeyCode::Char('}'), KeyBehavior::SectionNext),
+            (KeyCode::Char('{'), KeyBehavior::SectionPrev),
+            (KeyCode::Char(':'), KeyBehavior::Command),
+            (KeyCode::Char('c'), KeyBehavior::Convert),
+            (KeyCode::Char('p'), KeyBehavior::Pause),
+            (KeyCode::Char('-'), KeyBehavior::Toggle),
+            (KeyCode::Char('v'), KeyBehavior::Visual),
+            (KeyCode::Char('s'), KeyBehavior::Save),
+            (KeyCode::Char('|'), KeyBehavior::Pipe),
+            (KeyCode::Char('!'), KeyBehavior::Shell),
+            (KeyCode::Char('m'), KeyBehavior::Bookmark),
+            (KeyCode::Char('\''), KeyBehavior::Bookmarks),
+            (KeyCode::Char('@'), KeyBehavior::Replay),
+            (KeyCode::Char('h'), KeyBehavior::Help),
+            (KeyCode::F(1), KeyBehavior::Help),
 
             (KeyCode::Char('0'), KeyBehavior::Number(0)),
             (KeyCode::Char('1'), KeyBehavior::Number(1)),
@@ -151,10 +369,26 @@ fn default_keymap() -> AHashMap<KeyEvent, KeyBehavior> {
         ],
         KeyModifiers::SHIFT => [
             (KeyCode::Char('G'), KeyBehavior::Down(ScrollSize::End)),
+            (KeyCode::Char('D'), KeyBehavior::Dedup),
+            (KeyCode::Char('F'), KeyBehavior::Follow),
+            (KeyCode::Char('R'), KeyBehavior::Reload),
+            (KeyCode::Char('B'), KeyBehavior::Buffers),
             (KeyCode::Char('N'), KeyBehavior::SearchPrev),
             (KeyCode::Char('Q'), KeyBehavior::Quit),
+            (KeyCode::Char('O'), KeyBehavior::DumpQuit),
+            (KeyCode::Char('E'), KeyBehavior::StderrOnly),
+            (KeyCode::Char('H'), KeyBehavior::Hex),
+            (KeyCode::Char('C'), KeyBehavior::CursorLine),
+            (KeyCode::Char('M'), KeyBehavior::Mouse),
+            // not `v` like less, that's the visual mode
+            (KeyCode::Char('V'), KeyBehavior::Edit),
+            (KeyCode::Char('L'), KeyBehavior::Matches),
+            (KeyCode::Char('T'), KeyBehavior::Outline),
+            (KeyCode::Char(':'), KeyBehavior::Command),
+            (KeyCode::Char('@'), KeyBehavior::Replay),
         ],
         KeyModifiers::CONTROL => [
+            (KeyCode::Char('q'), KeyBehavior::Record),
             (KeyCode::Char('u'), KeyBehavior::Up(ScrollSize::HalfPage)),
             (KeyCode::Char('d'), KeyBehavior::Down(ScrollSize::HalfPage)),
             (KeyCode::Char('f'), KeyBehavior::Down(ScrollSize::Page)),
@@ -170,54 +404,582 @@ fn default_keymap() -> AHashMap<KeyEvent, KeyBehavior> {
 
             (KeyCode::Char('d'), KeyBehavior::Quit),
             (KeyCode::Char('c'), KeyBehavior::Quit),
+            (KeyCode::Char('z'), KeyBehavior::Suspend),
+        ],
+        KeyModifiers::ALT => [
+            (KeyCode::Char('x'), KeyBehavior::Palette),
         ],
     }
 
     dict
 }
 
+// bindings of more than one key
+fn default_sequences() -> AHashMap<Vec<KeyEvent>, KeyBehavior> {
+    let sequences = [
+        ("gg", KeyBehavior::Up(ScrollSize::End)),
+        ("]c", KeyBehavior::HunkNext),
+        ("[c", KeyBehavior::HunkPrev),
+        ("]f", KeyBehavior::FileNext),
+        ("[f", KeyBehavior::FilePrev),
+        ("za", KeyBehavior::Fold('a')),
+        ("zo", KeyBehavior::Fold('o')),
+        ("zc", KeyBehavior::Fold('c')),
+        ("zM", KeyBehavior::Fold('M')),
+        ("zR", KeyBehavior::Fold('R')),
+        ("zi", KeyBehavior::Fold('i')),
+        ("zt", KeyBehavior::LineTop),
+        ("zz", KeyBehavior::LineCenter),
+        ("zb", KeyBehavior::LineBottom),
+        ("gx", KeyBehavior::OpenLink),
+    ];
+
+    sequences
+        .iter()
+        .map(|(keys, behavior)| {
+            let keys = keys
+                .chars()
+                .map(|ch| KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+            (keys.collect(), *behavior)
+        })
+        .collect()
+}
+
+// keys and sequences of each preset, changes to the default ones
+fn preset_keymap(
+    keymap: Keymap,
+) -> (
+    AHashMap<KeyEvent, KeyBehavior>,
+    AHashMap<Vec<KeyEvent>, KeyBehavior>,
+) {
+    let mut dict = default_keymap();
+    let mut sequences = default_sequences();
+    let chars = |text: &str, modifiers| {
+        text.chars()
+            .map(|ch| KeyEvent::new(KeyCode::Char(ch), modifiers))
+            .collect::<Vec<_>>()
+    };
+
+    match keymap {
+        Keymap::Default => {}
+        Keymap::Less => {
+            sequences.clear();
+            sequences.insert(chars("ZZ", KeyModifiers::SHIFT), KeyBehavior::Quit);
+            keymap! {
+                dict;
+                KeyModifiers::NONE => [
+                    (KeyCode::Char('<'), KeyBehavior::Up(ScrollSize::End)),
+                    (KeyCode::Char('>'), KeyBehavior::Down(ScrollSize::End)),
+                    (KeyCode::Char('z'), KeyBehavior::Down(ScrollSize::Page)),
+                    (KeyCode::Char('w'), KeyBehavior::Up(ScrollSize::Page)),
+                    (KeyCode::Char('v'), KeyBehavior::Edit),
+                ],
+                // `v` edits like in less
+                KeyModifiers::SHIFT => [
+                    (KeyCode::Char('V'), KeyBehavior::Visual),
+                ],
+                KeyModifiers::ALT => [
+                    (KeyCode::Char('<'), KeyBehavior::Up(ScrollSize::End)),
+                    (KeyCode::Char('>'), KeyBehavior::Down(ScrollSize::End)),
+                ],
+            }
+        }
+        Keymap::Vim => {
+            // `gg` doesn't wait for a `g` alone
+            dict.remove(&KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE));
+            sequences.insert(chars("ZZ", KeyModifiers::SHIFT), KeyBehavior::Quit);
+            sequences.insert(chars("ZQ", KeyModifiers::SHIFT), KeyBehavior::Quit);
+            keymap! {
+                dict;
+                KeyModifiers::NONE => [
+                    (KeyCode::Char('h'), KeyBehavior::Left),
+                    (KeyCode::Char('l'), KeyBehavior::Right),
+                    // ZZ and ZQ quit instead
+                    (KeyCode::Char('q'), KeyBehavior::Record),
+                ],
+            }
+        }
+        Keymap::Emacs => {
+            dict.clear();
+            sequences.clear();
+            sequences.insert(chars("xc", KeyModifiers::CONTROL), KeyBehavior::Quit);
+            keymap! {
+                dict;
+                KeyModifiers::NONE => [
+                    (KeyCode::Enter, KeyBehavior::Down(ScrollSize::One)),
+                    (KeyCode::Down, KeyBehavior::Down(ScrollSize::One)),
+                    (KeyCode::Up, KeyBehavior::Up(ScrollSize::One)),
+                    (KeyCode::Left, KeyBehavior::Left),
+                    (KeyCode::Right, KeyBehavior::Right),
+                    (KeyCode::Char(' '), KeyBehavior::Down(ScrollSize::Page)),
+                    (KeyCode::PageDown, KeyBehavior::Down(ScrollSize::Page)),
+                    (KeyCode::PageUp, KeyBehavior::Up(ScrollSize::Page)),
+                    (KeyCode::Home, KeyBehavior::Up(ScrollSize::End)),
+                    (KeyCode::End, KeyBehavior::Down(ScrollSize::End)),
+                    (KeyCode::Esc, KeyBehavior::NormalMode),
+                    (KeyCode::Char('n'), KeyBehavior::SearchNext),
+                    (KeyCode::Char('q'), KeyBehavior::Quit),
+                    (KeyCode::F(1), KeyBehavior::Help),
+                ],
+                KeyModifiers::SHIFT => [
+                    (KeyCode::Char('N'), KeyBehavior::SearchPrev),
+                ],
+                KeyModifiers::CONTROL => [
+                    (KeyCode::Char('n'), KeyBehavior::Down(ScrollSize::One)),
+                    (KeyCode::Char('p'), KeyBehavior::Up(ScrollSize::One)),
+                    (KeyCode::Char('f'), KeyBehavior::Right),
+                    (KeyCode::Char('b'), KeyBehavior::Left),
+                    (KeyCode::Char('v'), KeyBehavior::Down(ScrollSize::Page)),
+                    (KeyCode::Char('s'), KeyBehavior::Search),
+                    (KeyCode::Char('r'), KeyBehavior::SearchPrev),
+                    (KeyCode::Char('g'), KeyBehavior::NormalMode),
+                    (KeyCode::Char('l'), KeyBehavior::LineCenter),
+                    (KeyCode::Char('h'), KeyBehavior::Help),
+                    (KeyCode::Char(' '), KeyBehavior::Visual),
+                    (KeyCode::Char('z'), KeyBehavior::Suspend),
+                ],
+                KeyModifiers::ALT => [
+                    (KeyCode::Char('v'), KeyBehavior::Up(ScrollSize::Page)),
+                    (KeyCode::Char('<'), KeyBehavior::Up(ScrollSize::End)),
+                    (KeyCode::Char('>'), KeyBehavior::Down(ScrollSize::End)),
+                    (KeyCode::Char('x'), KeyBehavior::Palette),
+                ],
+            }
+        }
+        Keymap::Empty => {
+            dict.clear();
+            sequences.clear();
+        }
+    }
+
+    (dict, sequences)
+}
+
+/// A key as compared with the keys of sequences: a typed char is the same with or without
+/// Shift, which terminals don't all report
+pub fn key_of_sequence(ke: KeyEvent) -> KeyEvent {
+    let modifiers = match ke.code {
+        KeyCode::Char(_) => ke.modifiers - KeyModifiers::SHIFT,
+        _ => ke.modifiers,
+    };
+    KeyEvent::new(ke.code, modifiers)
+}
+
+/// Where the view is, kept when the input is loaded again
+#[derive(Clone, Copy)]
+pub enum Position {
+    /// index of the line on top of the view
+    Line(usize),
+    End,
+}
+
+/// How [`UiContext::dump`] writes the lines
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dump {
+    /// as received, escape sequences included
+    Styled,
+    /// text only
+    Plain,
+}
+
+impl Dump {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "styled" => Some(Self::Styled),
+            "plain" => Some(Self::Plain),
+            _ => None,
+        }
+    }
+}
+
+/// Start of the lines kept in view while panning them, see [`UiContext::freeze`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Freeze {
+    /// the first N columns
+    Columns(usize),
+    /// the first N fields and the delimiter after them, fields are separated by
+    /// the char or by runs of spaces
+    Fields(usize, Option<char>),
+}
+
+impl Freeze {
+    /// `N` for columns, `Nf` for fields separated by spaces and `NfC` for fields separated by C,
+    /// like `1f` for the PID of `ps` or `2f,` for CSV
+    pub fn parse(spec: &str) -> Option<Self> {
+        let digits = spec
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(spec.len());
+        let n = spec[..digits].parse().ok()?;
+        let mut rest = spec[digits..].chars();
+
+        match (rest.next(), rest.next(), rest.next()) {
+            (None, _, _) => Some(Self::Columns(n)),
+            (Some('f'), delimiter, None) => Some(Self::Fields(n, delimiter)),
+            _ => None,
+        }
+    }
+
+    // chars of `text` kept, up to the end of the frozen columns or fields
+    fn len(self, text: &str) -> usize {
+        let (fields, delimiter) = match self {
+            Self::Columns(n) => return n.min(text.chars().count()),
+            Self::Fields(n, delimiter) => (n, delimiter),
+        };
+        if fields == 0 {
+            return 0;
+        }
+
+        let mut count = 0;
+        let mut in_field = false;
+        let mut chars = text.chars().enumerate().peekable();
+        while let Some((i, ch)) = chars.next() {
+            match delimiter {
+                Some(delimiter) if ch == delimiter => {
+                    count += 1;
+                    if count == fields {
+                        return i + 1;
+                    }
+                }
+                Some(_) => {}
+                // the spaces before the first field, like for right-aligned numbers, are part of it
+                None if ch == ' ' || ch == '\t' => {
+                    if in_field {
+                        count += 1;
+                        in_field = false;
+                    }
+                    let next_is_field = chars.peek().is_some_and(|(_, c)| *c != ' ' && *c != '\t');
+                    if count == fields && next_is_field {
+                        return i + 1;
+                    }
+                }
+                None => in_field = true,
+            }
+        }
+
+        text.chars().count()
+    }
+}
+
+/// Run once the first screenful is loaded, see [`UiContext::startup`]
+#[derive(Clone, Debug)]
+pub enum StartupCommand {
+    /// `+G`
+    End,
+    /// `+<N>`, counted from 1
+    Line(usize),
+    /// `+/<pattern>`
+    Search(String),
+}
+
+/// What survives a reload, see [`UiContext::reload_state`]
+pub struct ViewState {
+    position: Position,
+    needle: String,
+    reverse: bool,
+    dedup: bool,
+    squeeze: bool,
+    only_stderr: bool,
+    ignore_case: bool,
+    mouse: bool,
+    wrap: bool,
+    theme: Theme,
+    escapes: bool,
+    /// shown as a hex dump, None to tell from the input
+    hex: Option<bool>,
+}
+
+impl ViewState {
+    pub fn hex(&self) -> Option<bool> {
+        self.hex
+    }
+
+    /// line on top of the view, None if at the end
+    pub fn line(&self) -> Option<usize> {
+        match self.position {
+            Position::Line(line) => Some(line),
+            Position::End => None,
+        }
+    }
+
+    /// what a session keeps of it
+    pub fn saved(&self) -> SavedView {
+        SavedView {
+            line: self.line(),
+            needle: self.needle.clone(),
+            reverse: self.reverse,
+            dedup: self.dedup,
+            squeeze: self.squeeze,
+            only_stderr: self.only_stderr,
+            ignore_case: self.ignore_case,
+        }
+    }
+
+    /// same settings, at the top without search
+    pub fn for_other_input(&self) -> Self {
+        Self {
+            position: Position::Line(0),
+            needle: String::new(),
+            hex: None,
+            ..*self
+        }
+    }
+}
+
+/// An entry of the input list
+pub struct InputInfo {
+    pub name: String,
+    pub size: Option<u64>,
+    /// last line on top of the view, if it was shown before
+    pub line: Option<usize>,
+}
+
+/// A terminal-wide piece of a line, `start..end` in chars
+#[derive(Clone, Copy)]
+struct Row {
+    line: usize,
+    start: u32,
+    end: u32,
+    width: u32,
+}
+
+/// The pager: gets the lines the reader sends through `rx` and shows them until quit, what the
+/// reader is up to is shared through `reader`, its own for each pager.
+///
+/// Built with [`UiContext::new`] and the builder methods, then [`UiContext::run`];
+/// [`crate::pager::Pager`] does all of it for text given upfront
 pub struct UiContext<'b> {
-    rx: Arc<ArrayQueue<RpLine<'b>>>,
-    lines: Vec<RpLine<'b>>,
-    reflowed_lines: Vec<RpLine<'b>>,
-    reflowed_lines_associations: Vec<Vec<usize>>,
+    rx: Arc<ArrayQueue<RawLine<'b>>>,
+    styles: Arc<StyleTable>,
+    reader: Arc<ReaderState>,
+    lines: Vec<RawLine<'b>>,
+    /// first line of consecutive identical lines => count
+    repeats: AHashMap<usize, u32>,
+    repeat_leader: usize,
+    parsed_lines: AHashMap<usize, ParsedLine>,
+    parsed_memory: usize,
+    max_memory: Option<usize>,
+    /// when the pages of a mapped input far from the view were last dropped
+    evicted: Instant,
+    /// the memory used in the status
+    show_memory: bool,
+    reflowed_lines: Vec<Row>,
+    /// original line index => range of its rows
+    reflowed_lines_associations: Vec<Range<usize>>,
     search_positions: Vec<SearchPositionArr>,
     reflowed_search_positions: Vec<SearchPositionArr>,
     search_char_len: usize,
-    output: File,
+    needle: String,
+    /// previous searches, oldest first
+    search_history: Vec<String>,
+    /// entry recalled in the search prompt
+    history_index: Option<usize>,
+    output: Box<dyn Backend + 'b>,
     output_buf: Vec<u8>,
     scroll: usize,
     size_ctx: SizeContext,
     prev_wrap: usize,
     keymap: AHashMap<KeyEvent, KeyBehavior>,
+    /// keys compared with [`key_of_sequence`]
+    sequences: AHashMap<Vec<KeyEvent>, KeyBehavior>,
     need_redraw: bool,
     need_reflow: bool,
+    /// last lines that arrived in reverse mode since the last frame, their rows are put on top
+    /// at once
+    unreflowed: usize,
+    reverse: bool,
+    dedup: bool,
+    /// hide the lines not written to stderr
+    only_stderr: bool,
+    tint_stderr: bool,
+    diff: DiffIndex,
+    /// color the added and removed lines of a diff that has no colors
+    diff_colors: bool,
+    /// first line of each collapsed block => its last line, the others are hidden
+    folds: BTreeMap<usize, usize>,
+    /// headers of the sections `{` and `}` move between
+    section: Regex,
+    /// color plain lines with the first of the `log_levels` they match
+    log_colors: bool,
+    log_levels: Vec<LogLevel>,
+    /// command of the last `:pipe` and the lines it printed
+    pipe_output: (String, Vec<String>),
+    /// lines of the match, outline or bookmark panel, from when it was opened
+    panel_lines: Vec<usize>,
+    /// named lines of the input, by line
+    bookmarks: Vec<Bookmark>,
+    /// underline the `http(s)://` urls written in the lines
+    urls: bool,
+    /// underline the `path:line` positions written in the lines
+    file_links: bool,
+    /// show the escape sequences of the lines as caret text instead of their colors
+    escapes: bool,
+    /// line => (length, width) of the lines shown as caret text, as they are parsed, for those
+    /// reflowed since, the others still have their own
+    unescaped: AHashMap<usize, (u32, u32)>,
+    /// first lines of the input kept on top of the view, out of the scrolled rows
+    header: usize,
+    /// chars the unwrapped lines are panned to the right by
+    shift: usize,
+    freeze: Option<Freeze>,
+    /// chars of the longest frozen part
+    frozen_width: usize,
+    mouse: bool,
+    wrap: bool,
+    /// the number of each line before its first row
+    numbers: bool,
+    ignore_case: bool,
+    /// show only the first of consecutive blank lines
+    squeeze: bool,
+    bell: Bell,
+    /// what the terminal shows of the colors of the input
+    color_depth: ColorDepth,
+    /// the terminal answered what its background is, see [`colors::query_terminal`]
+    light_background: Option<bool>,
+    /// the images of the input drawn by the terminal, and the pixels of its rows
+    images: Vec<Protocol>,
+    cell_height: Option<u16>,
+    /// kitty keeps the images placed until they're deleted
+    kitty_placed: bool,
+    /// picked from `light_background` when None
+    ui_theme: Option<UiTheme>,
+    /// end of the flash of the visual bell
+    bell_until: Option<Instant>,
+    /// in the last column, where the lines never go
+    scrollbar: bool,
+    /// name and percentage in the title of the window, and the last title set
+    title: bool,
+    shown_title: Option<String>,
+    /// density of the search matches and stderr lines, left of the scrollbar
+    minimap: bool,
+    /// columns of the lines, from 1, drawn across the rows
+    guides: Vec<usize>,
+    theme: Theme,
+    loading: bool,
+    /// bytes and lines read per second, measured every RATE_INTERVAL
+    rate: usize,
+    line_rate: usize,
+    rate_sample: (Instant, u64, usize),
+    /// keep the view at the end as lines arrive, until the next key
+    follow: bool,
+    /// first line and arrival time of each batch received while following
+    new_lines: VecDeque<(usize, Instant)>,
+    /// the user scrolled to the bottom, new lines keep the view there
+    pinned: bool,
+    /// stop taking lines from `rx`, they are caught up on resume
+    paused: bool,
+    pending: usize,
+    acceleration: Vec<usize>,
+    /// lines scrolled by a notch of the wheel, half a page with Shift when wrapping
+    wheel_lines: usize,
+    /// direction, time and repeat count of the last one-line scroll
+    held: Option<(bool, Instant, usize)>,
+    /// wait for the next key of a sequence
+    key_timeout: Duration,
+    hooks: Vec<Hook<'b>>,
+    key_filters: Vec<KeyFilter<'b>>,
+    /// the width of its gutter, and what decorates the lines
+    decorator: Option<(usize, Decorator<'b>)>,
+    /// top line the hooks were last told about
+    hooked_top: usize,
+    /// the input can be loaded again, see [`ViewState`]
+    reloadable: bool,
+    reload: bool,
+    /// reload in the other of text and hex dump
+    toggle_hex: bool,
+    watcher: Option<Watcher>,
+    reload_at: Option<Instant>,
+    /// position to get back to as the reloaded lines arrive
+    resume: Option<Position>,
+    startup: Vec<StartupCommand>,
+    /// jump to the first match of the startup search once it arrives
+    search_pending: bool,
+    /// index of the shown input and all of them, for `:n`, `:p` and the list
+    input: usize,
+    inputs: Vec<InputInfo>,
+    next_input: Option<usize>,
+    next_path: Option<PathBuf>,
     prompt_outdated: bool,
     prompt_state: PromptState,
+    /// chars after the cursor in the search or command prompt, 0 at the end
+    prompt_cursor: usize,
     prompt: String,
+    /// shown instead of the normal prompt until the next key
+    message: Option<String>,
+    /// (line, char) of the last clicked position or search match
+    hint: Option<(usize, usize)>,
+    /// when and where the left button was last pressed
+    last_click: Option<(Instant, u16, u16)>,
+    /// the left button went down on the scrollbar or the minimap, dragging moves the view
+    dragging_bar: bool,
+    /// line of the line cursor when it's shown, kept in view
+    line_cursor: Option<usize>,
+    /// line the selection of visual mode started at, and whether the line cursor was
+    /// shown before
+    visual: Option<(usize, bool)>,
+    /// keys of each register
+    macros: AHashMap<char, Vec<KeyEvent>>,
+    /// register being recorded into and the keys so far
+    recording: Option<(char, Vec<KeyEvent>)>,
+    last_macro: Option<char>,
+    /// macros being replayed, one inside the other
+    replaying: usize,
+    /// print the input and quit instead of paging when it fits in the view
+    quit_if_one_screen: bool,
+    /// the alternate screen was entered, see [`UiContext::run`]
+    screen: bool,
+    /// raw mode was turned on by [`UiContext::new`], and is turned off once dropped
+    raw_mode: bool,
+    dump: Option<Dump>,
+    /// forward scrolls ending on the last line before quitting, 0 to never quit
+    quit_at_end: usize,
+    ends_reached: usize,
+    /// template of the prompt in place of the line range, see [`prompt::expand`]
+    prompt_format: Option<String>,
 }
 
 impl<'b> UiContext<'b> {
-    pub fn new(rx: Arc<ArrayQueue<RpLine<'b>>>) -> Result<Self> {
+    /// take over the terminal, in raw mode until dropped
+    pub fn new(
+        rx: Arc<ArrayQueue<RawLine<'b>>>,
+        styles: Arc<StyleTable>,
+        reader: Arc<ReaderState>,
+    ) -> Result<Self> {
         enable_raw_mode()?;
+        let reply = colors::query_terminal();
 
-        let mut output = get_output();
-
-        execute!(
-            output,
-            EnterAlternateScreen,
-            EnableMouseCapture,
-            DisableLineWrap,
-            Hide
-        )?;
+        let mut ui = Self::with_backend(rx, styles, reader, Terminal::open()?)?;
+        ui.light_background = reply.background.map(colors::is_light);
+        ui.images = image::supported(reply.sixel);
+        ui.cell_height = reply.cell_height;
+        ui.raw_mode = true;
+        Ok(ui)
+    }
 
+    /// draw to `output` without touching the terminal, like a [`Headless`](crate::backend::Headless) screen to check what
+    /// is drawn: [`Self::render`] draws the lines received and [`Self::handle_event`] takes the
+    /// keys
+    pub fn with_backend(
+        rx: Arc<ArrayQueue<RawLine<'b>>>,
+        styles: Arc<StyleTable>,
+        reader: Arc<ReaderState>,
+        output: impl Backend + 'b,
+    ) -> Result<Self> {
         let mut size_ctx = SizeContext::new();
-        let (x, y) = crossterm::terminal::size()?;
+        let (x, y) = output.size()?;
         size_ctx.resize(x as usize, y as usize);
 
         Ok(Self {
             rx,
+            styles,
+            reader,
             lines: Vec::with_capacity(1024),
+            repeats: AHashMap::new(),
+            repeat_leader: 0,
+            parsed_lines: AHashMap::new(),
+            parsed_memory: 0,
+            max_memory: None,
+            evicted: Instant::now(),
+            show_memory: false,
             reflowed_lines: Vec::with_capacity(1024),
             reflowed_lines_associations: Vec::new(),
             scroll: 0,
@@ -225,15 +987,107 @@ impl<'b> UiContext<'b> {
             search_positions: Vec::new(),
             reflowed_search_positions: Vec::new(),
             search_char_len: 0,
+            needle: String::new(),
+            search_history: Vec::new(),
+            history_index: None,
             size_ctx,
             keymap: default_keymap(),
+            sequences: default_sequences(),
             need_redraw: true,
             need_reflow: true,
+            unreflowed: 0,
+            reverse: false,
+            dedup: false,
+            only_stderr: false,
+            tint_stderr: true,
+            diff: DiffIndex::default(),
+            diff_colors: true,
+            folds: BTreeMap::new(),
+            section: Regex::new(DEFAULT_SECTION).unwrap(),
+            log_colors: false,
+            log_levels: Vec::new(),
+            pipe_output: (String::new(), Vec::new()),
+            panel_lines: Vec::new(),
+            bookmarks: Vec::new(),
+            urls: false,
+            file_links: false,
+            escapes: false,
+            unescaped: AHashMap::new(),
+            header: 0,
+            shift: 0,
+            freeze: None,
+            frozen_width: 0,
+            mouse: true,
+            wrap: true,
+            numbers: false,
+            ignore_case: false,
+            squeeze: false,
+            bell: Bell::Visual,
+            color_depth: ColorDepth::detect(),
+            light_background: None,
+            images: Vec::new(),
+            cell_height: None,
+            kitty_placed: false,
+            ui_theme: None,
+            bell_until: None,
+            scrollbar: false,
+            title: false,
+            shown_title: None,
+            minimap: false,
+            guides: Vec::new(),
+            theme: Theme::Colorful,
+            loading: true,
+            rate: 0,
+            line_rate: 0,
+            rate_sample: (Instant::now(), 0, 0),
+            follow: false,
+            new_lines: VecDeque::new(),
+            pinned: false,
+            paused: false,
+            pending: 0,
+            acceleration: vec![1],
+            wheel_lines: 1,
+            held: None,
+            key_timeout: KEY_TIMEOUT,
+            hooks: Vec::new(),
+            key_filters: Vec::new(),
+            decorator: None,
+            hooked_top: 0,
+            reloadable: false,
+            reload: false,
+            toggle_hex: false,
+            watcher: None,
+            reload_at: None,
+            resume: None,
+            startup: Vec::new(),
+            search_pending: false,
+            input: 0,
+            inputs: Vec::new(),
+            next_input: None,
+            next_path: None,
             prev_wrap: 0,
             prompt_state: PromptState::Normal,
+            prompt_cursor: 0,
             prompt_outdated: true,
             prompt: String::with_capacity(256),
-            output,
+            message: None,
+            hint: None,
+            last_click: None,
+            dragging_bar: false,
+            line_cursor: None,
+            visual: None,
+            macros: AHashMap::new(),
+            recording: None,
+            last_macro: None,
+            replaying: 0,
+            quit_if_one_screen: false,
+            screen: false,
+            raw_mode: false,
+            dump: None,
+            quit_at_end: 0,
+            ends_reached: 0,
+            prompt_format: None,
+            output: Box::new(output),
         })
     }
 
@@ -244,41 +1098,88 @@ impl<'b> UiContext<'b> {
     }
 
     pub fn update(&mut self) -> Result<()> {
-        if self.need_reflow {
-            self.reflowed_lines.clear();
-            self.reflowed_lines_associations.clear();
-            for line in &self.lines {
-                // if just line break
-                if line.len() < 1 {
-                    self.reflowed_lines.push(&line);
-                    self.reflowed_lines_associations.push(vec!{ self.reflowed_lines.len() - 1 });
-                    continue;
+        // the lines go on being kept in memory
+        let spill_error = self.reader.spill_error.lock().unwrap().take();
+        if let Some(e) = spill_error {
+            self.set_message(format!("{}, keeping the input in memory", e));
+        }
+        // the rest of a mapped file reads as zeros
+        if self.reader.shrunk.swap(false, Ordering::AcqRel) {
+            let keys = keys::keys_of(&self.keymap, &self.sequences, KeyBehavior::Reload);
+            let message = match keys.first() {
+                Some(key) if self.reloadable => {
+                    format!("The file got shorter while read, {} loads it again", key)
                 }
+                _ => "The file got shorter while read".to_string(),
+            };
+            self.set_message(message);
+        }
 
-                let mut takes: usize = 0;
-                let mut line_indexes = vec!{} as Vec<usize>;
-                loop {
-                    let start = takes * (self.size_ctx.terminal_column() - 1);
-                    let end = std::cmp::min(start + self.size_ctx.terminal_column() - 1, line.len());
+        if self.need_reflow {
+            let prev_len = self.reflowed_lines.len();
+            self.reflow();
 
-                    if start < line.len() {
-                        self.reflowed_lines.push(&line[start..end]);
-                        line_indexes.push(self.reflowed_lines.len() - 1);
-                        takes += 1;
-                        continue;
-                    }
+            // in reverse mode new lines arrive on top, keep the current view in place
+            if self.reverse && self.scroll > 0 {
+                self.scroll += self.reflowed_lines.len().saturating_sub(prev_len);
+            }
 
-                    break;
-                }
+            self.need_reflow = false;
+        } else if self.unreflowed > 0 {
+            let added = self.prepend_rows();
+            if self.scroll > 0 {
+                self.scroll += added;
+            }
+        }
 
-                self.reflowed_lines_associations.push(line_indexes);
+        let screenful = self.lines.len() >= self.size_ctx.terminal_line() || !self.loading;
+        if !self.startup.is_empty() && screenful && !self.need_reflow {
+            for command in std::mem::take(&mut self.startup) {
+                self.run_startup(command);
             }
+        }
+
+        if let Some(position) = self.resume {
+            self.resume_position(position);
+        }
 
-            if !self.reflowed_search_positions.is_empty() {
-                self.reflow_search();
+        if self.follow || self.pinned {
+            // new lines are on top in reverse mode
+            let end = if self.reverse { 0 } else { self.max_scroll() };
+            if self.scroll != end {
+                self.scroll = end;
+                self.need_redraw = true;
+                self.prompt_outdated = true;
             }
+        }
 
-            self.need_reflow = false;
+        if self.need_redraw {
+            self.clamp_line_cursor();
+        }
+
+        let top = self.top_line();
+        if top != self.hooked_top {
+            self.hooked_top = top;
+            self.emit(PagerEvent::Scroll { top });
+        }
+
+        if let (true, PromptState::Buffers(selected)) = (self.need_redraw, &self.prompt_state) {
+            return self.draw_buffers(*selected);
+        }
+        if let (true, PromptState::Help(first)) = (self.need_redraw, &self.prompt_state) {
+            return self.draw_help(*first);
+        }
+        if let (true, PromptState::Output(first)) = (self.need_redraw, &self.prompt_state) {
+            let (first, lines) = (*first, std::mem::take(&mut self.pipe_output.1));
+            let ret = self.draw_page(&lines, first, false);
+            self.pipe_output.1 = lines;
+            return ret;
+        }
+        if let (true, PromptState::Palette(query, selected)) =
+            (self.need_redraw, &self.prompt_state)
+        {
+            let (query, selected) = (query.clone(), *selected);
+            return self.draw_palette(&query, selected);
         }
 
         if self.need_redraw {
@@ -286,80 +1187,64 @@ impl<'b> UiContext<'b> {
             log::debug!("REDRAW");
 
             self.output_buf.clear();
+            if self.size_ctx.ruler_rows() > 0 {
+                self.write_ruler()?;
+            }
+            self.write_header()?;
 
-            queue!(self.output_buf, MoveTo(0, 0))?;
+            queue!(
+                self.output_buf,
+                MoveTo(self.size_ctx.left() as u16, self.size_ctx.top() as u16)
+            )?;
 
-            let mut ch_writer = ChWriter::new(self.size_ctx.terminal_column());
+            let mut row_buf = Vec::with_capacity(self.size_ctx.terminal_column());
+            let mut ch_writer = self.ch_writer();
+            ch_writer.guides = self.guide_columns();
             let (real, margin) = self
                 .size_ctx
                 .calculate_real_size(&self.reflowed_lines[self.scroll..]);
             let end = self.scroll + real;
+            let decorations = self.decorations(self.scroll, end);
+
+            self.parse_rows(self.scroll, end);
+            let styles = self.styles.clone();
+            let styles = styles.styles();
 
             #[cfg(feature = "logging")]
             log::debug!("margin: {}", margin);
             for _ in 0..margin {
-                queue!(
-                    self.output_buf,
-                    Clear(ClearType::CurrentLine),
-                    MoveToNextLine(1)
-                )?;
+                ch_writer.clear_line(&mut self.output_buf)?;
+                ch_writer.next_line(&mut self.output_buf)?;
             }
 
-            if self.reflowed_search_positions.is_empty() {
-                let mut iter = self.reflowed_lines[self.scroll..end].iter();
-                while let Some(line) = iter.next() {
-                    queue!(self.output_buf, Clear(ClearType::CurrentLine))?;
-                    ch_writer.write_slice(&mut self.output_buf, line)?;
-                    ch_writer.pos = 0;
-                    queue!(self.output_buf, MoveToNextLine(1))?;
-                }
-            } else {
-                let mut iter = self.reflowed_lines[self.scroll..end]
-                    .iter()
-                    .zip(self.reflowed_search_positions[self.scroll..end].iter());
-                
-                let mut overflow = 0 as usize;
-                while let Some((line, search)) = iter.next() {
-                    queue!(self.output_buf, Clear(ClearType::CurrentLine))?;
-                    
-                    let mut prev_pos = 0;
-                    
-                    if overflow > 0 {
-                        ch_writer.write_slice_reverse(&mut self.output_buf, &line[0..overflow])?;
-                        prev_pos = overflow;
-                        overflow = 0;
-                    }
-
-                    for pos in search.iter() {
-                        let start = pos.start as usize;
-                        let mut end = start + self.search_char_len;
-
-                        if end > line.len() {
-                            overflow = end - line.len();
-                            end = line.len();
-                        }
-
-                        if start > end {
-                            panic!("wtf is happening");
-                        }
-                        
-                        ch_writer.write_slice(&mut self.output_buf, &line[prev_pos..start])?;
-                        ch_writer.write_slice_reverse(&mut self.output_buf, &line[start..end])?;
-                        prev_pos = end;
-                    }
-                    ch_writer.write_slice(&mut self.output_buf, &line[prev_pos..])?;
-                    ch_writer.pos = 0;
-                    queue!(self.output_buf, MoveToNextLine(1))?;
-                }
+            let mut overflow = 0;
+            for (idx, decoration) in (self.scroll..end).zip(&decorations) {
+                let (buf, decoration) = (&mut row_buf, decoration.as_ref());
+                self.write_row(&mut ch_writer, buf, &styles, idx, decoration, &mut overflow)?;
             }
 
             self.prev_wrap = ch_writer.wrap;
             queue!(self.output_buf, SetAttribute(Attribute::Reset),)?;
+            if !self.images.is_empty() {
+                self.write_images(self.scroll..end, self.size_ctx.top() + margin)?;
+            }
+            if self.scrollbar {
+                self.write_scrollbar()?;
+            }
+            if self.minimap {
+                self.write_minimap()?;
+            }
+            if let PromptState::Matches(selected)
+            | PromptState::Outline(selected)
+            | PromptState::Bookmarks(selected) = self.prompt_state
+            {
+                self.write_line_panel(selected)?;
+            }
             self.update_prompt();
             self.write_prompt()?;
             #[cfg(feature = "logging")]
             log::trace!("Write {} bytes", self.output_buf.len());
-            self.output.write(&self.output_buf)?;
+            self.output.write_all(&self.output_buf)?;
             self.output.flush()?;
             self.need_redraw = false;
         } else if self.prompt_outdated {
@@ -370,14 +1255,110 @@ impl<'b> UiContext<'b> {
         Ok(())
     }
 
+    // the row `idx` of the view: its number and decoration in the gutter, the frozen columns, its
+    // text with the search matches reversed, and the notes after it; `overflow` is what's left of
+    // a match wrapped from the row before
+    fn write_row(
+        &mut self,
+        ch_writer: &mut ChWriter,
+        row_buf: &mut Vec<RpChar>,
+        styles: &[Style],
+        idx: usize,
+        decoration: Option<&Decoration>,
+        overflow: &mut usize,
+    ) -> Result<()> {
+        let row = self.reflowed_lines[idx];
+        let frozen = self.frozen_width();
+        let numbers = self.number_width();
+        let decorated = self.gutter_width() - numbers;
+
+        let line = self.parsed_lines[&row.line].as_line();
+        line.expand(styles, row.start as usize, row.end as usize, row_buf);
+        if let Some(decoration) = decoration {
+            restyle(row_buf, decoration.style);
+        }
+        ch_writer.clear_line(&mut self.output_buf)?;
+        ch_writer.background = self.row_background(row.line);
+        let first = self.is_first_row(idx);
+        if numbers > 0 {
+            let number = Some(row.line + 1).filter(|_| first);
+            ch_writer.write_number(&mut self.output_buf, number, numbers)?;
+        }
+        if decorated > 0 {
+            let decoration = decoration.filter(|_| first);
+            ch_writer.write_decoration(&mut self.output_buf, decoration, decorated)?;
+        }
+        if frozen > 0 {
+            let mut frozen_buf = Vec::new();
+            let end = self.frozen_len(row.line);
+            line.expand(styles, 0, end, &mut frozen_buf);
+            ch_writer.write_frozen(&mut self.output_buf, &frozen_buf, frozen)?;
+        }
+
+        let line = &row_buf[..];
+        let mut prev_pos = 0;
+        if *overflow > 0 {
+            let end = (*overflow).min(line.len());
+            ch_writer.write_slice_reverse(&mut self.output_buf, &line[0..end])?;
+            prev_pos = end;
+            *overflow = 0;
+        }
+        let search = self.reflowed_search_positions.get(idx);
+        for pos in search.into_iter().flatten() {
+            let start = pos.start as usize;
+            let mut end = start + self.search_char_len;
+            if end > line.len() {
+                *overflow = end - line.len();
+                end = line.len();
+            }
+
+            ch_writer.write_slice(&mut self.output_buf, &line[prev_pos..start])?;
+            ch_writer.write_slice_reverse(&mut self.output_buf, &line[start..end])?;
+            prev_pos = end;
+        }
+        ch_writer.write_slice(&mut self.output_buf, &line[prev_pos..])?;
+
+        if let Some(count) = self.repeat_count(row) {
+            ch_writer.write_count(&mut self.output_buf, count)?;
+        }
+        if let Some(summary) = self.fold_summary(row) {
+            ch_writer.write_note(&mut self.output_buf, &summary)?;
+        }
+        // the full width of the line cursor and the selection
+        if self.line_cursor == Some(row.line) || self.is_selected(row.line) {
+            ch_writer.fill_row(&mut self.output_buf)?;
+        }
+        ch_writer.write_guides(&mut self.output_buf)?;
+        ch_writer.next_line(&mut self.output_buf)
+    }
+
     fn write_prompt(&mut self) -> Result<()> {
-        let lines = self.size_ctx.terminal_line();
-        queue!(
-            self.output_buf,
-            MoveTo(0, lines as _),
-            Clear(ClearType::CurrentLine)
-        )?;
-        self.output_buf.extend_from_slice(self.prompt.as_bytes());
+        self.write_title()?;
+        let left = self.size_ctx.left() as u16;
+        let line = (self.size_ctx.top() + self.size_ctx.terminal_line()) as u16;
+        queue!(self.output_buf, MoveTo(left, line))?;
+
+        if self.bell_until.is_some() {
+            let width = self.size_ctx.terminal_column();
+            let paint = self.paint(self.ui().prompt);
+            write!(self.output_buf, "{}{:2$}", paint.start(), "", width)?;
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+            return Ok(());
+        }
+
+        if self.size_ctx.is_full_width() {
+            queue!(self.output_buf, Clear(ClearType::CurrentLine))?;
+            self.output_buf.extend_from_slice(self.prompt.as_bytes());
+        } else {
+            // don't draw over the panes beside the view
+            let width = self.size_ctx.terminal_column();
+            write!(self.output_buf, "{:1$}", "", width)?;
+            queue!(self.output_buf, MoveTo(left, line))?;
+            self.output_buf
+                .extend_from_slice(truncate_to_width(&self.prompt, width).as_bytes());
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
         Ok(())
     }
 
@@ -390,318 +1371,4441 @@ impl<'b> UiContext<'b> {
         Ok(())
     }
 
-    pub fn push_line(&mut self, line: RpLine<'b>) {
-        if self.lines.len() < self.size_ctx.terminal_line() {
-            self.need_redraw = true;
-        }
-        self.prompt_outdated = true;
-        self.lines.push(line);
-    }
-
-    fn update_prompt(&mut self) {
-        if self.prompt_outdated {
-            use std::fmt::Write;
-            self.prompt.clear();
-
-            match self.prompt_state {
-                PromptState::Normal => {
-                    write!(
-                        self.prompt,
-                        "{}lines {}-{}/{}",
-                        SetAttribute(Attribute::Reverse),
-                        self.scroll + 1,
-                        (self.scroll + self.size_ctx.terminal_line() - self.prev_wrap),
-                        self.reflowed_lines.len(),
-                    )
-                    .ok();
-
-                    if self.scroll == self.max_scroll() {
-                        self.prompt.push_str(" (END)");
-                    }
+    // make sure lines of rows in `start..end` are parsed, and drop the ones far from them
+    fn parse_rows(&mut self, start: usize, end: usize) {
+        let rows = &self.reflowed_lines[start..end];
+        let styles = &self.styles;
+        let lines = &self.lines;
+        let diff = Some(&self.diff).filter(|diff| diff.is_diff() && self.diff_colors);
+        let levels = if self.log_colors {
+            &self.log_levels[..]
+        } else {
+            &[]
+        };
+        let underlined = (self.urls, self.file_links);
+        let parsed_memory = &mut self.parsed_memory;
 
-                    write!(self.prompt, "{}", SetAttribute(Attribute::Reset),).ok();
-                }
-                PromptState::Number(n) => {
-                    write!(self.prompt, ":{}", n).ok();
+        for row in rows {
+            self.parsed_lines.entry(row.line).or_insert_with(|| {
+                let mut parsed = lines[row.line].parse(styles);
+                match diff {
+                    Some(diff) => color_diff_line(&mut parsed, diff.kind(row.line), styles),
+                    None => color_log_line(&mut parsed, levels, styles),
                 }
-                PromptState::Search(ref s) => {
-                    write!(
-                        self.prompt,
-                        "{}/{}{}",
-                        SetAttribute(Attribute::Reverse),
-                        s,
-                        SetAttribute(Attribute::Reset),
-                    )
-                    .ok();
+                if underlined != (false, false) {
+                    underline_links(&mut parsed, underlined, styles);
                 }
+                *parsed_memory += parsed.memory();
+                parsed
+            });
+        }
+
+        let first = rows.iter().map(|r| r.line).min().unwrap_or(0);
+        let last = rows.iter().map(|r| r.line).max().unwrap_or(0);
+        let near = first.saturating_sub(PARSE_MARGIN)..=last.saturating_add(PARSE_MARGIN);
+        self.parsed_lines.retain(|idx, parsed| {
+            let keep = near.contains(idx);
+            if !keep {
+                *parsed_memory -= parsed.memory();
             }
+            keep
+        });
 
-            self.prompt_outdated = false;
+        if let Some(max_memory) = self.max_memory {
+            let used = self.index_memory() + self.reader.memory.load(Ordering::Relaxed);
+            if used + self.parsed_memory > max_memory {
+                self.evict_parsed(first..=last, max_memory.saturating_sub(used));
+            }
+            // a mapped file is in memory as far as it was read or searched
+            let read = self.reader.read.load(Ordering::Relaxed) as usize;
+            if used + self.parsed_memory + read > max_memory {
+                self.evict_mapped(near);
+            }
         }
     }
 
-    fn goto_scroll(&mut self, idx: usize) {
-        let new_scroll = idx.min(self.max_scroll());
-        if new_scroll != self.scroll {
-            self.scroll = new_scroll;
-            self.need_redraw = true;
-            self.prompt_outdated = true;
+    // drop the pages of a mapped input but those of the lines near the view, they are read from
+    // the file again when needed
+    fn evict_mapped(&mut self, near: std::ops::RangeInclusive<usize>) {
+        if self.evicted.elapsed() < EVICT_INTERVAL {
+            return;
+        }
+        self.evicted = Instant::now();
+
+        let last = (*near.end()).min(self.lines.len().saturating_sub(1));
+        if let (Some(first), Some(last)) = (self.lines.get(*near.start()), self.lines.get(last)) {
+            // the other inputs are in the reader's arena or spilled, and bounded by then
+            if let (RawBytes::Memory(first), RawBytes::Memory(last)) = (first.bytes, last.bytes) {
+                mmap::evict_around(first, last);
+            }
         }
     }
 
-    fn scroll_down(&mut self, idx: usize) {
-        self.goto_scroll(self.scroll.saturating_add(idx));
+    // drop parsed lines from the farthest to the view, they are parsed again when needed
+    fn evict_parsed(&mut self, view: std::ops::RangeInclusive<usize>, budget: usize) {
+        let distance = |idx: usize| {
+            if idx < *view.start() {
+                view.start() - idx
+            } else {
+                idx.saturating_sub(*view.end())
+            }
+        };
+
+        let mut candidates = self
+            .parsed_lines
+            .keys()
+            .copied()
+            .filter(|idx| distance(*idx) > 0)
+            .collect::<Vec<_>>();
+        candidates.sort_unstable_by_key(|idx| std::cmp::Reverse(distance(*idx)));
+
+        for idx in candidates {
+            if self.parsed_memory <= budget {
+                break;
+            }
+            if let Some(parsed) = self.parsed_lines.remove(&idx) {
+                self.parsed_memory -= parsed.memory();
+            }
+        }
     }
 
-    fn scroll_up(&mut self, idx: usize) {
-        self.goto_scroll(self.scroll.saturating_sub(idx));
+    /// rough memory used to keep track of every line
+    fn index_memory(&self) -> usize {
+        use std::mem::size_of;
+
+        self.lines.capacity() * size_of::<RawLine>()
+            + self.reflowed_lines.capacity() * size_of::<Row>()
+            + self.reflowed_lines_associations.capacity() * size_of::<Range<usize>>()
+            + (self.search_positions.capacity() + self.reflowed_search_positions.capacity())
+                * size_of::<SearchPositionArr>()
     }
 
-    fn move_search(&mut self, forward: bool) {
-        let next = self.reflowed_search_positions[self.scroll..]
-            .iter()
-            .enumerate()
-            .skip(1)
-            .map(|(i, p)| (i + self.scroll, p));
+    // the memory of the process where it's known, that of the lines otherwise
+    fn memory_used(&self) -> usize {
+        mmap::resident().unwrap_or_else(|| {
+            self.index_memory() + self.reader.memory.load(Ordering::Relaxed) + self.parsed_memory
+        })
+    }
 
-        let prev = self.reflowed_search_positions[0..self.scroll].iter().enumerate();
+    fn memory_status(&self) -> String {
+        let index = self.index_memory();
+        let input = self.reader.memory.load(Ordering::Relaxed);
+        let styles = self.styles.styles().len();
 
-        let line = if forward {
-            next.chain(prev)
-                .find_map(|(line, p)| if !p.is_empty() { Some(line) } else { None })
+        let mut status = format!(
+            "memory: {} (index {}, input {}, parsed {} in {} lines, {} styles",
+            convert::human_size(self.memory_used() as u64),
+            convert::human_size(index as u64),
+            convert::human_size(input as u64),
+            convert::human_size(self.parsed_memory as u64),
+            self.parsed_lines.len(),
+            styles,
+        );
+        if let Some(max_memory) = self.max_memory {
+            let max_memory = convert::human_size(max_memory as u64);
+            status.push_str(&format!("), max {}", max_memory));
         } else {
-            prev.rev()
-                .chain(next.rev())
-                .find_map(|(line, p)| if !p.is_empty() { Some(line) } else { None })
-        };
-
-        if let Some(line) = line {
-            self.goto_scroll(line);
+            status.push(')');
         }
+        status
     }
 
-    fn search(&mut self, needle: &str) {
-        if !self.search_positions.is_empty() {
+    pub fn max_memory(mut self, max_memory: Option<usize>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    pub fn push_line(&mut self, mut line: RawLine<'b>) {
+        line.escaped = self.escapes;
+        if self.reverse || self.lines.len() < self.size_ctx.terminal_line() {
             self.need_redraw = true;
-            self.search_positions.clear();
         }
+        self.prompt_outdated = true;
 
-        let char_count = needle.chars().count();
-        self.search_char_len = char_count;
+        let repeat =
+            self.lines.last().map(|l| (l.hash, l.byte_len())) == Some((line.hash, line.byte_len()));
 
-        if char_count == 0 {
-            return;
+        if repeat {
+            *self.repeats.entry(self.repeat_leader).or_insert(1) += 1;
+        } else {
+            self.repeat_leader = self.lines.len();
         }
 
-        #[cfg(feature = "logging")]
-        log::debug!("Search: {:?}", needle);
+        if let Some(freeze) = self.freeze {
+            let frozen = freeze.len(&line.text());
+            if frozen > self.frozen_width {
+                // the rows after the frozen part get narrower
+                self.frozen_width = frozen;
+                self.need_reflow = !self.wrap;
+            }
+        }
 
-        self.need_redraw = true;
+        let numbers = self.number_width();
+        self.diff.push(line.diff);
+        self.lines.push(line);
+        if self.number_width() > numbers {
+            // one more digit, the rows get narrower
+            self.need_reflow = true;
+        }
 
+        if !self.needle.is_empty() {
+            self.search_positions
+                .push(find_matches(&line.text(), &self.needle, self.ignore_case));
+        }
+
+        if self.lines.len() <= self.header {
+            // drawn above the rows instead
+            self.size_escaped(self.lines.len() - 1);
+            self.size_ctx.header = self.lines.len();
+            self.need_redraw = true;
+        } else if self.dedup && repeat {
+            // only the counter changes
+            self.need_redraw = true;
+        } else if self.is_hidden(self.lines.len() - 1) {
+            // reflowed once shown again
+        } else if self.reverse {
+            self.unreflowed += 1;
+            self.need_redraw = true;
+        } else if !self.need_reflow {
+            self.reflow_line(self.lines.len() - 1);
+
+            let idx = self.lines.len() - 1;
+            if self.search_pending && !self.search_positions[idx].is_empty() {
+                self.search_pending = false;
+                self.hint = Some((idx, self.search_positions[idx][0].start as usize));
+                // wait for the rest of the screenful like after a reload
+                self.resume = Some(Position::Line(idx));
+            }
+        }
+    }
+
+    fn reflow(&mut self) {
+        self.unreflowed = 0;
+        self.reflowed_lines.clear();
+        self.reflowed_lines_associations.clear();
+        self.reflowed_lines_associations
+            .resize(self.lines.len(), 0..0);
+        self.reflowed_search_positions.clear();
+        for idx in 0..self.header.min(self.lines.len()) {
+            self.size_escaped(idx);
+        }
+
+        if self.reverse {
+            for idx in (self.header.min(self.lines.len())..self.lines.len()).rev() {
+                if !self.is_hidden(idx) {
+                    self.reflow_line(idx);
+                }
+            }
+        } else {
+            for idx in self.header.min(self.lines.len())..self.lines.len() {
+                if !self.is_hidden(idx) {
+                    self.reflow_line(idx);
+                }
+            }
+        }
+    }
+
+    // rows of the lines that arrived in reverse mode on top of the others, which move down by
+    // as many rows as are added
+    fn prepend_rows(&mut self) -> usize {
+        let first = self.lines.len() - self.unreflowed;
+        self.unreflowed = 0;
+        let rows = std::mem::take(&mut self.reflowed_lines);
+        let search_rows = std::mem::take(&mut self.reflowed_search_positions);
+
+        for idx in (first..self.lines.len()).rev() {
+            if !self.is_hidden(idx) {
+                self.reflow_line(idx);
+            }
+        }
+        let added = self.reflowed_lines.len();
+
+        for range in &mut self.reflowed_lines_associations[..first] {
+            if range.start < range.end {
+                *range = range.start + added..range.end + added;
+            }
+        }
+        self.reflowed_lines.extend(rows);
+        if !self.needle.is_empty() {
+            self.reflowed_search_positions
+                .resize(added, SmallVec::new());
+            self.reflowed_search_positions.extend(search_rows);
+        }
+        added
+    }
+
+    fn is_hidden(&self, idx: usize) -> bool {
+        (self.dedup && self.is_repeat(idx))
+            || (self.only_stderr && !self.lines[idx].stderr)
+            || self.folded_in(idx).is_some()
+            || self.is_squeezed(idx)
+    }
+
+    // a blank line after another one, hidden when squeezing them
+    fn is_squeezed(&self, idx: usize) -> bool {
+        let blank = |idx: usize| self.lines[idx].width == 0 && self.lines[idx].image.is_none();
+        self.squeeze && idx > 0 && blank(idx) && blank(idx - 1)
+    }
+
+    // first line of the collapsed block hiding `idx`
+    fn folded_in(&self, idx: usize) -> Option<usize> {
+        self.folds
+            .range(..idx)
+            .next_back()
+            .filter(|(_, end)| idx <= **end)
+            .map(|(start, _)| *start)
+    }
+
+    // shown after the last row of the first line of a collapsed block
+    fn fold_summary(&self, row: Row) -> Option<String> {
+        let end = *self.folds.get(&row.line)?;
+        if row.end != self.lines[row.line].len && self.wrap {
+            return None;
+        }
+
+        let hidden = match end - row.line {
+            1 => "1 line".to_string(),
+            n => format!("{} lines", n),
+        };
+        // the closing bracket of a JSON block
+        let last = self.lines[end].text();
+        let last = last.trim();
+        if last.starts_with(['}', ']']) {
+            Some(format!(" … {} ({})", last, hidden))
+        } else {
+            Some(format!(" … ({})", hidden))
+        }
+    }
+
+    // the line of the line cursor, or of the last click or search match when in view,
+    // the top line otherwise
+    fn cursor_line(&self) -> usize {
+        if let Some(line) = self.line_cursor {
+            return line;
+        }
+        let (real, _) = self
+            .size_ctx
+            .calculate_real_size(&self.reflowed_lines[self.scroll..]);
+        let view = &self.reflowed_lines[self.scroll..self.scroll + real];
+        match self.hint {
+            Some((line, _)) if view.iter().any(|row| row.line == line) => line,
+            _ => self.top_line(),
+        }
+    }
+
+    fn set_line_cursor(&mut self, on: bool) {
+        self.line_cursor = match on {
+            true => Some(self.cursor_line()),
+            false => None,
+        };
+        self.need_redraw = true;
+    }
+
+    // lines from the start of the selection to the line cursor, in order
+    fn selection(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        let (start, _) = self.visual?;
+        let line = self.line_cursor?;
+        Some(start.min(line)..=start.max(line))
+    }
+
+    fn is_selected(&self, line: usize) -> bool {
+        self.selection().is_some_and(|lines| lines.contains(&line))
+    }
+
+    fn start_visual(&mut self) {
+        let shown = self.line_cursor.is_some();
+        self.set_line_cursor(true);
+        self.visual = self.line_cursor.map(|line| (line, shown));
+        self.prompt_outdated = true;
+    }
+
+    // the selected lines, or every shown line without a selection, the line cursor
+    // going back to how it was
+    fn take_selection(&mut self) -> Vec<String> {
+        self.take_selected_lines()
+            .into_iter()
+            .map(|idx| self.lines[idx].text().into_owned())
+            .collect()
+    }
+
+    // indexes of the lines of [`take_selection`](Self::take_selection)
+    fn take_selected_lines(&mut self) -> Vec<usize> {
+        let lines = match self.selection() {
+            Some(range) => range
+                .filter(|idx| !self.is_hidden(*idx))
+                .collect::<Vec<_>>(),
+            None => self.visible_lines().collect(),
+        };
+        if let Some((_, shown)) = self.visual.take() {
+            self.set_line_cursor(shown);
+            self.prompt_outdated = true;
+        }
+        lines
+    }
+
+    // keys acting on the selection, true if `ke` was one
+    fn handle_visual_key(&mut self, ke: KeyEvent) -> bool {
+        if ke
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return false;
+        }
+
+        match ke.code {
+            KeyCode::Char('y') => self.copy_selection(),
+            KeyCode::Char('w') => self.command_prompt("write "),
+            KeyCode::Char('|') => self.command_prompt("pipe "),
+            KeyCode::Esc => {
+                self.take_selection();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn command_prompt(&mut self, text: &str) {
+        self.prompt_cursor = 0;
+        self.prompt_state = PromptState::Command(text.to_string());
+        self.prompt_outdated = true;
+    }
+
+    // to the clipboard of the terminal
+    fn copy_selection(&mut self) {
+        let lines = self.take_selection();
+        let text = crate::clipboard::osc52(&lines.join("\n"));
+        match self.output.write_all(text.as_bytes()) {
+            Ok(()) => self.set_message(format!("Copied {} lines", lines.len())),
+            Err(e) => self.set_message(format!("Can't copy: {}", e)),
+        }
+    }
+
+    /// `:pipe COMMAND`, the selection or every shown line goes to the stdin of `command`,
+    /// what it prints is shown over the view until a key closes it
+    fn pipe(&mut self, command: &str) {
+        if command.is_empty() {
+            return self.set_message("Usage: pipe COMMAND".into());
+        }
+
+        let mut input = self.take_selection().join("\n");
+        input.push('\n');
+        let output = match crate::process::pipe(command, input.into_bytes()) {
+            Ok(output) => output,
+            Err(e) => return self.set_message(format!("Can't run {}: {}", command, e)),
+        };
+
+        // the errors after the output, as they can't be told apart anymore
+        let mut printed = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !printed.is_empty() && !printed.ends_with('\n') {
+            printed.push('\n');
+        }
+        printed.push_str(&String::from_utf8_lossy(&output.stderr));
+        let mut printed = printed.lines().map(expand_tabs).collect::<Vec<_>>();
+        if output.truncated {
+            let max = convert::human_size(crate::process::MAX_PIPED as u64);
+            printed.push(format!("[{} printed more than {}, stopped]", command, max));
+        }
+        match (printed.is_empty(), output.status.success()) {
+            (true, true) => self.set_message(format!("{} printed nothing", command)),
+            (true, false) => self.set_message(format!("{} exited with {}", command, output.status)),
+            (false, _) => {
+                self.pipe_output = (command.to_string(), printed);
+                self.prompt_state = PromptState::Output(0);
+                self.need_redraw = true;
+                self.prompt_outdated = true;
+            }
+        }
+    }
+
+    // the raw mode is off while stopped, so Ctrl-Z is a key and the signal is raised here
+    #[cfg(unix)]
+    fn suspend(&mut self) -> Result<()> {
+        self.leave_screen();
+        disable_raw_mode()?;
+
+        // returns on SIGCONT
+        unsafe { libc::raise(libc::SIGTSTP) };
+
+        enable_raw_mode()?;
+        // the terminal may have been resized meanwhile
+        let (x, y) = self.output.size()?;
+        self.size_ctx.resize(x as usize, y as usize);
+        self.need_reflow = true;
+        self.enter_screen()
+    }
+
+    #[cfg(windows)]
+    fn suspend(&mut self) -> Result<()> {
+        self.set_message("Can't suspend on Windows".into());
+        Ok(())
+    }
+
+    // `lines` shown lines down or up, scrolling to keep the cursor in view
+    fn move_line_cursor(&mut self, lines: usize, down: bool) {
+        let line = match self.line_cursor {
+            Some(line) if !self.reflowed_lines.is_empty() => line,
+            _ => return,
+        };
+        let rows = &self.reflowed_lines;
+        let associations = &self.reflowed_lines_associations;
+        let mut row = associations
+            .get(line)
+            .map_or(0, |rows| rows.start)
+            .min(rows.len() - 1);
+
+        for _ in 0..lines {
+            // the hidden lines between have no rows
+            if down {
+                let end = associations[rows[row].line].end;
+                if end >= rows.len() {
+                    break;
+                }
+                row = end;
+            } else {
+                if row == 0 {
+                    break;
+                }
+                row = associations[rows[row - 1].line].start;
+            }
+        }
+
+        let line = rows[row].line;
+        let end = associations[line].end;
+        self.line_cursor = Some(line);
+        let height = self.size_ctx.terminal_line();
+        if row < self.scroll {
+            self.goto_scroll(row);
+        } else if end > self.scroll + height {
+            self.goto_scroll(end.saturating_sub(height));
+        }
+        self.need_redraw = true;
+    }
+
+    // the line cursor on the nearest shown line after the view moved without it
+    fn clamp_line_cursor(&mut self) {
+        let line = match self.line_cursor {
+            Some(line) if !self.reflowed_lines.is_empty() => line,
+            _ => return,
+        };
+        let (real, _) = self
+            .size_ctx
+            .calculate_real_size(&self.reflowed_lines[self.scroll..]);
+        let last = (self.scroll + real).max(self.scroll + 1) - 1;
+        let row = self
+            .reflowed_lines_associations
+            .get(line)
+            .map_or(0, |rows| rows.start);
+        if row < self.scroll {
+            self.line_cursor = self.reflowed_lines.get(self.scroll).map(|row| row.line);
+        } else if row > last {
+            self.line_cursor = self.reflowed_lines.get(last).map(|row| row.line);
+        }
+    }
+
+    // scroll to have the cursor line `row` rows below the top of the view
+    fn place_cursor_line(&mut self, row: usize) {
+        let line = self.cursor_line();
+        let first = self.reflowed_lines_associations[line].start;
+        self.goto_scroll(first.saturating_sub(row));
+    }
+
+    fn fold_region(&self, start: usize) -> Option<usize> {
+        let lines = &self.lines;
+        fold::region(|idx| lines[idx].text(), start, lines.len())
+    }
+
+    /// color the lines without colors of their own by log level
+    pub fn log_colors(mut self, colors: bool) -> Self {
+        self.log_colors = colors;
+        self
+    }
+
+    fn set_urls(&mut self, urls: bool) {
+        if self.urls != urls {
+            self.urls = urls;
+            self.clear_parsed();
+        }
+    }
+
+    /// underline the urls of the lines, the OSC 8 links always are
+    pub fn urls(mut self, urls: bool) -> Self {
+        self.set_urls(urls);
+        self
+    }
+
+    fn set_file_links(&mut self, file_links: bool) {
+        if self.file_links != file_links {
+            self.file_links = file_links;
+            self.clear_parsed();
+        }
+    }
+
+    /// underline the `path:line` positions of the lines, like the errors of a compiler
+    pub fn file_links(mut self, file_links: bool) -> Self {
+        self.set_file_links(file_links);
+        self
+    }
+
+    fn set_escapes(&mut self, escapes: bool) {
+        if self.escapes != escapes {
+            self.escapes = escapes;
+            // the lines reflowed next get the sizes of their caret text
+            for line in &mut self.lines {
+                line.escaped = escapes;
+            }
+            for (idx, (len, width)) in self.unescaped.drain() {
+                self.lines[idx].len = len;
+                self.lines[idx].width = width;
+            }
+            if !self.needle.is_empty() {
+                self.find_all();
+            }
+            self.resume = Some(Position::Line(self.top_line()));
+            self.need_reflow = true;
+            self.clear_parsed();
+        }
+    }
+
+    /// show the escape sequences and other control chars of the lines as caret text, like
+    /// `^[[31m`, to see what a program writes, instead of the colors
+    pub fn escapes(mut self, escapes: bool) -> Self {
+        self.escapes = escapes;
+        self
+    }
+
+    // the line `idx` shown as caret text gets its sizes, once, its own kept in `unescaped`
+    fn size_escaped(&mut self, idx: usize) {
+        let line = &mut self.lines[idx];
+        if !line.escaped || self.unescaped.contains_key(&idx) {
+            return;
+        }
+        self.unescaped.insert(idx, (line.len, line.width));
+        if let Some((len, width)) = line.escaped_size() {
+            line.len = len;
+            line.width = width;
+        }
+    }
+
+    // parsed again, like with or without the underline of the links
+    fn clear_parsed(&mut self) {
+        self.parsed_lines.clear();
+        self.parsed_memory = 0;
+        self.need_redraw = true;
+    }
+
+    /// patterns of the log levels and their colors, kept when saving the preferences
+    pub fn log_levels(mut self, levels: Vec<LogLevel>) -> Self {
+        self.log_levels = levels;
+        self
+    }
+
+    /// the bindings [`UiContext::bind`] changes
+    pub fn keymap(mut self, keymap: Keymap) -> Self {
+        (self.keymap, self.sequences) = preset_keymap(keymap);
+        self
+    }
+
+    /// bind each `(keys, action)`, like `("ctrl-g", "top")` or `("g g", "top")` for a sequence,
+    /// or unbind them with the action `none`; the first invalid one is shown in the prompt
+    pub fn bind(mut self, bindings: &[(String, String)]) -> Self {
+        let mut errors = Vec::new();
+        for (key, action) in bindings {
+            let keys = match keys::parse_keys(key) {
+                Ok(keys) => keys,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            match (keys::parse_action(action), &keys[..]) {
+                (Ok(Some(behavior)), [key]) => {
+                    for key in key {
+                        self.keymap.insert(*key, behavior);
+                    }
+                }
+                (Ok(None), [key]) => {
+                    for key in key {
+                        self.keymap.remove(key);
+                    }
+                }
+                (Ok(Some(behavior)), _) => {
+                    self.sequences.insert(sequence(&keys), behavior);
+                }
+                (Ok(None), _) => {
+                    self.sequences.remove(&sequence(&keys));
+                }
+                (Err(e), _) => errors.push(e),
+            }
+        }
+
+        match errors.len() {
+            0 => {}
+            1 => self.set_message(format!("Config: {}", errors[0])),
+            n => self.set_message(format!("Config: {} (and {} more)", errors[0], n - 1)),
+        }
+        self
+    }
+
+    /// keep the first `lines` of the input on top while the rest scrolls beneath them,
+    /// like `--header` in less
+    pub fn header(mut self, lines: usize) -> Self {
+        self.header = lines;
+        self.size_ctx.header = lines.min(self.lines.len());
+        self.need_reflow = true;
+        self
+    }
+
+    /// keep the start of the lines in view when panning them with Left and Right,
+    /// once wrapping is turned off
+    pub fn freeze(mut self, freeze: Option<Freeze>) -> Self {
+        self.set_freeze(freeze);
+        self
+    }
+
+    fn set_freeze(&mut self, freeze: Option<Freeze>) {
+        self.freeze = freeze;
+        self.frozen_width = match freeze {
+            Some(freeze) => {
+                let widest = self.lines.iter().map(|line| freeze.len(&line.text())).max();
+                widest.unwrap_or(0)
+            }
+            None => 0,
+        };
+        self.need_reflow = true;
+        self.need_redraw = true;
+    }
+
+    // columns left of the panned rows, at most half of the view
+    fn frozen_width(&self) -> usize {
+        if self.wrap {
+            return 0;
+        }
+        self.frozen_width.min(self.size_ctx.terminal_column() / 2)
+    }
+
+    // chars of the frozen part of the line, padded to the frozen width when drawn
+    fn frozen_len(&self, idx: usize) -> usize {
+        match self.freeze {
+            Some(freeze) if !self.wrap => {
+                freeze.len(&self.lines[idx].text()).min(self.frozen_width())
+            }
+            _ => 0,
+        }
+    }
+
+    // by half of the panned part, up to the end of the longest line
+    fn pan(&mut self, right: bool) {
+        let width = self
+            .size_ctx
+            .terminal_column()
+            .saturating_sub(self.frozen_width());
+        let step = (width / 2).max(1);
+        let shift = if right {
+            let longest = self.lines.iter().map(|line| line.len as usize).max();
+            let last = longest
+                .unwrap_or(0)
+                .saturating_sub(self.frozen_width + width / 2);
+            (self.shift + step).min(last.max(self.shift))
+        } else {
+            self.shift.saturating_sub(step)
+        };
+
+        if shift != self.shift {
+            self.shift = shift;
+            self.need_reflow = true;
+            self.need_redraw = true;
+            self.prompt_outdated = true;
+        }
+    }
+
+    // a thumb over the rows in view, dimmed elsewhere, and the cells holding rows with a
+    // search match marked
+    fn write_scrollbar(&mut self) -> Result<()> {
+        let height = self.size_ctx.terminal_line();
+        let rows = self.reflowed_lines.len();
+        if height == 0 || rows == 0 {
+            return Ok(());
+        }
+
+        let shown = self
+            .size_ctx
+            .calculate_real_size(&self.reflowed_lines[self.scroll..])
+            .0;
+        let len = (shown * height).div_ceil(rows).clamp(1, height);
+        // the thumb touches the bottom only at the end
+        let start = if self.scroll >= self.max_scroll() {
+            height - len
+        } else {
+            (self.scroll * height / rows).min(height - len)
+        };
+
+        let mut marks = vec![false; height];
+        for (row, positions) in self.reflowed_search_positions.iter().enumerate() {
+            if !positions.is_empty() {
+                marks[row * height / rows] = true;
+            }
+        }
+
+        let column = self.size_ctx.left() + self.size_ctx.terminal_column();
+        let column = column.saturating_sub(1) as u16;
+        for (cell, mark) in marks.into_iter().enumerate() {
+            let row = (self.size_ctx.top() + cell) as u16;
+            queue!(self.output_buf, MoveTo(column, row))?;
+            let thumb = (start..start + len).contains(&cell);
+            let ch = match (mark, thumb) {
+                (true, true) => '╋',
+                (true, false) => '━',
+                (false, true) => '┃',
+                (false, false) => '│',
+            };
+            if mark && self.theme == Theme::Colorful {
+                let color = colors::fit(self.ui().marks, self.color_depth);
+                queue!(self.output_buf, SetForegroundColor(color))?;
+            } else if !thumb {
+                queue!(self.output_buf, SetAttribute(Attribute::Dim))?;
+            }
+            write!(self.output_buf, "{}", ch)?;
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
+        Ok(())
+    }
+
+    // the column is the one of the scrollbar or the minimap
+    fn on_bar(&self, column: usize) -> bool {
+        let last = (self.size_ctx.left() + self.size_ctx.terminal_column()).saturating_sub(1);
+        let bars = self.scrollbar as usize + self.minimap as usize;
+        column <= last && column + bars > last
+    }
+
+    // the view centered on the part of the input the row of the bars stands for
+    fn jump_to_bar_row(&mut self, row: usize) {
+        let height = self.size_ctx.terminal_line();
+        if height == 0 {
+            return;
+        }
+        let cell = row.saturating_sub(self.size_ctx.top()).min(height - 1);
+        let target = cell * self.reflowed_lines.len() / height;
+        self.goto_scroll(target.saturating_sub(height / 2));
+    }
+
+    // each cell shaded by how many of its rows have a search match, in yellow, or come from
+    // stderr, in red, a diamond for the bookmarked ones; on a grey background for the rows in
+    // view, in reverse video without colors
+    fn write_minimap(&mut self) -> Result<()> {
+        let height = self.size_ctx.terminal_line();
+        let rows = self.reflowed_lines.len();
+        if height == 0 || rows == 0 {
+            return Ok(());
+        }
+        // short inputs get a cell per row
+        let cell_of = |row: usize| match rows <= height {
+            true => row,
+            false => row * height / rows,
+        };
+
+        // (rows, matches, stderr lines, bookmarks) of each cell
+        let mut cells = vec![(0usize, 0, 0, 0); height];
+        for (row, info) in self.reflowed_lines.iter().enumerate() {
+            let cell = &mut cells[cell_of(row)];
+            cell.0 += 1;
+            if self
+                .reflowed_search_positions
+                .get(row)
+                .is_some_and(|positions| !positions.is_empty())
+            {
+                cell.1 += 1;
+            }
+            // every line is from stderr when only they are shown
+            if !self.only_stderr && self.lines[info.line].stderr {
+                cell.2 += 1;
+            }
+        }
+        for bookmark in &self.bookmarks {
+            // on the first row of its line, unless it's folded away
+            let rows = self.reflowed_lines_associations.get(bookmark.line);
+            if let Some(rows) = rows.filter(|rows| !rows.is_empty()) {
+                cells[cell_of(rows.start)].3 += 1;
+            }
+        }
+
+        let shown = self
+            .size_ctx
+            .calculate_real_size(&self.reflowed_lines[self.scroll..])
+            .0;
+        let view = cell_of(self.scroll)..=cell_of((self.scroll + shown).max(1) - 1);
+        let colors = self.theme == Theme::Colorful;
+        let column = self.size_ctx.left() + self.size_ctx.terminal_column();
+        let column = column.saturating_sub(1 + self.scrollbar as usize) as u16;
+
+        for (cell, (total, matches, stderr, bookmarks)) in cells.into_iter().enumerate() {
+            let row = (self.size_ctx.top() + cell) as u16;
+            queue!(self.output_buf, MoveTo(column, row))?;
+            match (colors, view.contains(&cell)) {
+                (true, true) => queue!(self.output_buf, SetBackgroundColor(Color::AnsiValue(238)))?,
+                (false, true) => queue!(self.output_buf, SetAttribute(Attribute::Reverse))?,
+                _ => {}
+            }
+            let hits = matches.max(stderr);
+            if colors && hits > 0 {
+                let color = match matches >= stderr {
+                    true => colors::fit(self.ui().marks, self.color_depth),
+                    false => Color::DarkRed,
+                };
+                queue!(self.output_buf, SetForegroundColor(color))?;
+            }
+            // a cell with hits has rows
+            let ch = match (hits, hits * 4 / total.max(1)) {
+                _ if bookmarks > 0 => '◆',
+                (0, _) => ' ',
+                (_, 0) => '░',
+                (_, 1) => '▒',
+                (_, 2 | 3) => '▓',
+                _ => '█',
+            };
+            write!(self.output_buf, "{}", ch)?;
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
+        Ok(())
+    }
+
+    fn set_minimap(&mut self, minimap: bool) {
+        if self.minimap != minimap {
+            self.minimap = minimap;
+            // the lines make room for it
+            self.resume = Some(Position::Line(self.top_line()));
+            self.need_reflow = true;
+            self.need_redraw = true;
+        }
+    }
+
+    /// an overview of where the search matches, stderr lines and bookmarks are in the whole
+    /// input, a column left of the scrollbar
+    pub fn minimap(mut self, minimap: bool) -> Self {
+        self.set_minimap(minimap);
+        self
+    }
+
+    /// how failed actions are told, a flash of the prompt line by default
+    pub fn bell(mut self, bell: Bell) -> Self {
+        self.bell = bell;
+        self
+    }
+
+    /// the colors the terminal shows, found from `COLORTERM` and `TERM` unless given
+    pub fn color_depth(mut self, depth: ColorDepth) -> Self {
+        self.color_depth = depth;
+        self.need_redraw = true;
+        self
+    }
+
+    // an action did nothing, like a search without a match or a scroll past the end
+    fn ring_bell(&mut self) {
+        match self.bell {
+            Bell::Visual => {
+                self.bell_until = Some(Instant::now() + BELL_FLASH);
+                self.prompt_outdated = true;
+            }
+            Bell::Audible => {
+                self.output.write_all(b"\x07").ok();
+                self.output.flush().ok();
+            }
+            Bell::Off => {}
+        }
+    }
+
+    // the prompt back after the flash
+    fn expire_bell(&mut self) {
+        if self.bell_until.is_some_and(|until| Instant::now() >= until) {
+            self.bell_until = None;
+            self.prompt_outdated = true;
+        }
+    }
+
+    fn set_title(&mut self, title: bool) {
+        if self.title != title {
+            self.title = title;
+            if self.screen {
+                let code = if title { PUSH_TITLE } else { POP_TITLE };
+                self.output.write_all(code.as_bytes()).ok();
+                self.shown_title = None;
+                self.prompt_outdated = true;
+            }
+        }
+    }
+
+    /// the input name and the percentage in the title of the window, the previous title
+    /// restored on exit
+    pub fn title(mut self, title: bool) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    // OSC 2, only when it changed
+    fn write_title(&mut self) -> Result<()> {
+        if !self.title {
+            return Ok(());
+        }
+        let name = self.input_name();
+        let name = Path::new(&name)
+            .file_name()
+            .map_or(name.clone(), |n| n.to_string_lossy().into_owned());
+        let (bottom, rows) = self.view_bottom();
+        let title = match (bottom * 100).checked_div(rows) {
+            Some(percent) => format!("rp: {} {}%", name, percent),
+            None => format!("rp: {}", name),
+        };
+        // control chars would end the sequence early
+        let title = title.replace(|c: char| c.is_control(), " ");
+
+        if self.shown_title.as_ref() != Some(&title) {
+            write!(self.output_buf, "\x1b]2;{}\x07", title)?;
+            self.shown_title = Some(title);
+        }
+        Ok(())
+    }
+
+    /// a scrollbar in the last column of the view, with the search matches marked on it
+    pub fn scrollbar(mut self, scrollbar: bool) -> Self {
+        self.scrollbar = scrollbar;
+        self
+    }
+
+    // column of the line shown in each column of the view, from 0, the ones of the line
+    // numbers and the gutter left out
+    fn view_columns(&self) -> impl Iterator<Item = (usize, usize)> {
+        let gutter = self.gutter_width();
+        let frozen = self.frozen_width();
+        let shift = if self.wrap { 0 } else { self.shift };
+        (gutter..self.row_width() + gutter + frozen).map(move |x| {
+            let column = x - gutter;
+            match column < frozen {
+                true => (x, column),
+                false => (x, column + shift),
+            }
+        })
+    }
+
+    // columns of the view the guides are in
+    fn guide_columns(&self) -> Vec<usize> {
+        if self.guides.is_empty() {
+            return Vec::new();
+        }
+        self.view_columns()
+            .filter(|(_, column)| self.guides.contains(&(column + 1)))
+            .map(|(x, _)| x)
+            .collect()
+    }
+
+    // the column numbers above the header, `.` for each column, `+` every 5 and the number
+    // every 10, panned along with the lines
+    fn write_ruler(&mut self) -> Result<()> {
+        let mut ruler = vec![' '; self.size_ctx.terminal_column().saturating_sub(1)];
+        let columns = self.view_columns().collect::<Vec<_>>();
+        for (x, column) in columns.iter().copied() {
+            if let Some(cell) = ruler.get_mut(x) {
+                *cell = if (column + 1) % 5 == 0 { '+' } else { '.' };
+            }
+        }
+        for (x, column) in columns {
+            if (column + 1) % 10 == 0 {
+                let label = (column + 1).to_string();
+                for (i, ch) in label.chars().enumerate() {
+                    if let Some(cell) = ruler.get_mut(x + i) {
+                        *cell = ch;
+                    }
+                }
+            }
+        }
+
+        let mut ch_writer = ChWriter::new(&self.size_ctx);
+        ch_writer.row = (self.size_ctx.top() - self.size_ctx.header_rows() - 1) as u16;
+        ch_writer.guides = self.guide_columns();
+        queue!(
+            self.output_buf,
+            MoveTo(self.size_ctx.left() as u16, ch_writer.row)
+        )?;
+        ch_writer.clear_line(&mut self.output_buf)?;
+        ch_writer.write_note(&mut self.output_buf, &ruler.into_iter().collect::<String>())?;
+        queue!(self.output_buf, SetAttribute(Attribute::Reset))
+    }
+
+    fn set_ruler(&mut self, ruler: bool) {
+        if self.size_ctx.ruler != ruler {
+            self.size_ctx.ruler = ruler;
+            self.need_redraw = true;
+            self.prompt_outdated = true;
+        }
+    }
+
+    /// a row of column numbers on top of the view, like `:ruler`
+    pub fn ruler(mut self, ruler: bool) -> Self {
+        self.set_ruler(ruler);
+        self
+    }
+
+    fn set_guides(&mut self, columns: Vec<usize>) {
+        self.guides = columns;
+        self.need_redraw = true;
+    }
+
+    /// columns of the lines, from 1, given a background across the rows, like `:guides 80`
+    pub fn guides(mut self, columns: Vec<usize>) -> Self {
+        self.set_guides(columns);
+        self
+    }
+
+    // the header lines, one row each, above the scrolled ones
+    fn write_header(&mut self) -> Result<()> {
+        let rows = self.size_ctx.header_rows();
+        let top = self.size_ctx.top() - rows;
+        let frozen = self.frozen_width();
+        let numbers = self.number_width();
+        let decorated = self.gutter_width() - numbers;
+        let width = self
+            .size_ctx
+            .terminal_column()
+            .saturating_sub(frozen + numbers + decorated);
+        let styles = self.styles.clone();
+        let styles = styles.styles();
+        let mut row_buf = Vec::with_capacity(width);
+
+        for idx in 0..rows {
+            let line = self.lines[idx];
+            let parsed = line.parse(&self.styles);
+            let decoration = self.decoration(idx);
+            let mut ch_writer = self.ch_writer();
+            ch_writer.row = (top + idx) as u16;
+            queue!(
+                self.output_buf,
+                MoveTo(self.size_ctx.left() as u16, ch_writer.row)
+            )?;
+            ch_writer.clear_line(&mut self.output_buf)?;
+            if numbers > 0 {
+                ch_writer.write_number(&mut self.output_buf, Some(idx + 1), numbers)?;
+            }
+            if decorated > 0 {
+                ch_writer.write_decoration(&mut self.output_buf, decoration.as_ref(), decorated)?;
+            }
+
+            // panned along with the rows, to stay above their columns
+            let frozen_len = self.frozen_len(idx);
+            if frozen > 0 {
+                parsed
+                    .as_line()
+                    .expand(&styles, 0, frozen_len, &mut row_buf);
+                ch_writer.write_frozen(&mut self.output_buf, &row_buf, frozen)?;
+            }
+            let first = match self.wrap {
+                true => 0,
+                false => (frozen_len + self.shift).min(line.len as usize),
+            };
+            parsed
+                .as_line()
+                .expand(&styles, first, line.len as usize, &mut row_buf);
+            if let Some(decoration) = &decoration {
+                restyle(&mut row_buf, decoration.style);
+            }
+
+            // cut like a row without wrapping, wide chars don't go over
+            let mut used = 0;
+            let end = row_buf
+                .iter()
+                .position(|ch| {
+                    used += ch.ch.width().unwrap_or(0);
+                    used > width
+                })
+                .unwrap_or(row_buf.len());
+            ch_writer.write_slice(&mut self.output_buf, &row_buf[..end])?;
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
+        Ok(())
+    }
+
+    /// lines matching `pattern` start the sections `{` and `}` move between,
+    /// man page headings and diff files if None
+    pub fn section(mut self, pattern: Option<Regex>) -> Self {
+        if let Some(pattern) = pattern {
+            self.section = pattern;
+        }
+        self
+    }
+
+    // next or previous section header below or above the top row, in view order
+    fn goto_section(&mut self, forward: bool) {
+        let is_header =
+            |row: &Row| row.start == 0 && self.section.is_match(&self.lines[row.line].text());
+        let rows = &self.reflowed_lines;
+        let row = if forward {
+            rows.iter()
+                .enumerate()
+                .skip(self.scroll + 1)
+                .find(|(_, row)| is_header(row))
+        } else {
+            rows[..self.scroll.min(rows.len())]
+                .iter()
+                .enumerate()
+                .rfind(|(_, row)| is_header(row))
+        };
+
+        match row.map(|(i, _)| i) {
+            Some(row) => self.goto_scroll(row),
+            None if forward => self.set_message("No next section".into()),
+            None => self.set_message("No previous section".into()),
+        }
+    }
+
+    // blocks inside are collapsed along with it
+    fn collapse(&mut self, start: usize, end: usize) {
+        let inner = self.folds.range(start..=end).map(|(start, _)| *start);
+        for inner in inner.collect::<Vec<_>>() {
+            self.folds.remove(&inner);
+        }
+        self.folds.insert(start, end);
+    }
+
+    /// `za`, `zo` and `zc` on the block of the cursor line, `zM` and `zR` on all of them,
+    /// `zi` on every block more indented than the cursor line
+    fn fold(&mut self, action: char) {
+        let line = self.cursor_line();
+        match action {
+            'o' | 'a' if self.folds.contains_key(&line) => {
+                self.folds.remove(&line);
+            }
+            'c' | 'a' => match self.fold_region(line) {
+                Some(end) => self.collapse(line, end),
+                None => return self.set_message("Nothing to fold here".into()),
+            },
+            'i' => {
+                let lines = &self.lines;
+                let indent = fold::indent_of(&lines[line].text());
+                let blocks = fold::deeper_than(|idx| lines[idx].text(), lines.len(), indent);
+                for (start, end) in blocks {
+                    self.collapse(start, end);
+                }
+            }
+            'M' => {
+                self.folds.clear();
+                let mut idx = 0;
+                while idx < self.lines.len() {
+                    match self.fold_region(idx) {
+                        // the root of a JSON document, its children are folded instead
+                        Some(end) if idx == 0 && end + 1 == self.lines.len() => idx += 1,
+                        Some(end) => {
+                            self.collapse(idx, end);
+                            idx = end + 1;
+                        }
+                        None => idx += 1,
+                    }
+                }
+            }
+            'R' => self.folds.clear(),
+            _ => return,
+        }
+
+        // keep the top line in view, or the block it went into
+        let top = self.top_line();
+        let top = self.folded_in(top).unwrap_or(top);
+        self.resume = Some(Position::Line(top));
+        self.need_reflow = true;
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // same as the previous line, trusting the hash to not read spilled lines back
+    fn is_repeat(&self, idx: usize) -> bool {
+        idx > 0
+            && self.lines[idx].hash == self.lines[idx - 1].hash
+            && self.lines[idx].byte_len() == self.lines[idx - 1].byte_len()
+    }
+
+    // counter displayed after the last row of a collapsed line
+    fn repeat_count(&self, row: Row) -> Option<u32> {
+        if self.dedup && (row.end == self.lines[row.line].len || !self.wrap) {
+            self.repeats.get(&row.line).copied()
+        } else {
+            None
+        }
+    }
+
+    fn set_dedup(&mut self, dedup: bool) {
+        if self.dedup != dedup {
+            self.dedup = dedup;
+            self.keep_top_line();
+            self.need_reflow = true;
+            self.need_redraw = true;
+            self.prompt_outdated = true;
+        }
+    }
+
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.set_dedup(dedup);
+        self
+    }
+
+    fn set_only_stderr(&mut self, only_stderr: bool) {
+        if self.only_stderr != only_stderr {
+            self.only_stderr = only_stderr;
+            self.keep_top_line();
+            self.need_reflow = true;
+            self.need_redraw = true;
+            self.prompt_outdated = true;
+        }
+    }
+
+    /// give lines written to stderr a dark red background
+    pub fn tint_stderr(mut self, tint: bool) -> Self {
+        self.tint_stderr = tint;
+        self
+    }
+
+    /// color the lines of a diff without colors, green for added and red for removed
+    pub fn diff_colors(mut self, colors: bool) -> Self {
+        self.diff_colors = colors;
+        self
+    }
+
+    /// `]c` and `[c` for hunks, `]f` and `[f` for files
+    fn goto_diff(&mut self, forward: bool, files: bool) {
+        if !self.diff.is_diff() {
+            return self.set_message("Not a diff".into());
+        }
+
+        let targets = if files {
+            &self.diff.files
+        } else {
+            &self.diff.hunks
+        };
+        // rows follow the view, in reverse mode too
+        let rows = targets
+            .iter()
+            .filter(|line| !self.is_hidden(**line))
+            .filter_map(|line| self.reflowed_lines_associations.get(*line))
+            .map(|rows| rows.start);
+        let row = if forward {
+            rows.filter(|row| *row > self.scroll).min()
+        } else {
+            rows.filter(|row| *row < self.scroll).max()
+        };
+
+        match (row, files, forward) {
+            (Some(row), _, _) => self.goto_scroll(row),
+            (None, false, true) => self.set_message("No next hunk".into()),
+            (None, false, false) => self.set_message("No previous hunk".into()),
+            (None, true, true) => self.set_message("No next file in the diff".into()),
+            (None, true, false) => self.set_message("No previous file in the diff".into()),
+        }
+    }
+
+    // chars of a row, the line number and the frozen part left out
+    fn row_width(&self) -> usize {
+        let width = self.size_ctx.terminal_column().saturating_sub(1);
+        width
+            .saturating_sub(self.gutter_width() + self.frozen_width() + self.minimap as usize)
+            .max(1)
+    }
+
+    // columns before the rows: the line numbers and the gutter of the decorations
+    fn gutter_width(&self) -> usize {
+        self.number_width() + self.decorator.as_ref().map_or(0, |(width, _)| *width)
+    }
+
+    // columns of the line numbers and the space after them, at least 4 digits
+    fn number_width(&self) -> usize {
+        match self.numbers {
+            true => self.lines.len().to_string().len().max(4) + 1,
+            false => 0,
+        }
+    }
+
+    // the number is only written before the first row of a wrapped line, row `idx` of the
+    // reflowed lines
+    fn is_first_row(&self, idx: usize) -> bool {
+        self.reflowed_lines_associations[self.reflowed_lines[idx].line].start == idx
+    }
+
+    // rows of the image of line `idx`, when the terminal draws it
+    fn image_rows(&self, idx: usize) -> Option<usize> {
+        let image = self.lines[idx].image.filter(|_| !self.escapes)?;
+        if !self.images.contains(&image.protocol) {
+            return None;
+        }
+        image.rows(self.cell_height)
+    }
+
+    // the images of the rows `rows` of the view, drawn from row `top` of the terminal over the
+    // rows left blank for them; one cut by the edges of the view isn't drawn
+    fn write_images(&mut self, rows: Range<usize>, top: usize) -> Result<()> {
+        if self.kitty_placed {
+            self.output_buf.extend_from_slice(b"\x1b_Ga=d,q=2\x1b\\");
+            self.kitty_placed = false;
+        }
+        let column = self.size_ctx.left() + self.gutter_width() + self.frozen_width();
+
+        for (y, idx) in rows.clone().enumerate() {
+            let row = self.reflowed_lines[idx];
+            let line_rows = &self.reflowed_lines_associations[row.line];
+            let whole = line_rows.start == idx && line_rows.end <= rows.end;
+            if !whole || self.image_rows(row.line).is_none() {
+                continue;
+            }
+            let line = self.lines[row.line];
+            let protocol = line.image.unwrap().protocol;
+            queue!(self.output_buf, MoveTo(column as u16, (top + y) as u16))?;
+            self.output_buf
+                .extend_from_slice(&image::sequences(&line.bytes(), protocol));
+            self.kitty_placed |= protocol == Protocol::Kitty;
+        }
+        Ok(())
+    }
+
+    // split a line into terminal-wide pieces and append them to the reflowed lines
+    fn reflow_line(&mut self, idx: usize) {
+        self.size_escaped(idx);
+        let line = self.lines[idx];
+        let width = self.row_width();
+        // without wrapping only the piece panned to is kept, after the frozen part
+        let (first, len) = if self.wrap {
+            (0, line.len)
+        } else {
+            let first = (self.frozen_len(idx) + self.shift).min(line.len as usize) as u32;
+            (first, line.len.min(first + width as u32))
+        };
+        let start = self.reflowed_lines.len();
+
+        // if just line break
+        if len == first {
+            self.reflowed_lines.push(Row {
+                line: idx,
+                start: first,
+                end: first,
+                width: 0,
+            });
+        } else if line.is_narrow() {
+            self.reflowed_lines
+                .extend((first..len).step_by(width).map(|start| {
+                    let end = len.min(start + width as u32);
+                    Row {
+                        line: idx,
+                        start,
+                        end,
+                        width: end - start,
+                    }
+                }));
+        } else {
+            // wide chars, need to know the actual text to get the width of each piece
+            let text = line.text();
+            let mut chars = text.chars().skip(first as usize);
+            self.reflowed_lines
+                .extend((first..len).step_by(width).map(|start| {
+                    let end = len.min(start + width as u32);
+                    Row {
+                        line: idx,
+                        start,
+                        end,
+                        width: chars
+                            .by_ref()
+                            .take((end - start) as usize)
+                            .map(|c| c.width().unwrap_or(0) as u32)
+                            .sum(),
+                    }
+                }));
+        }
+
+        // the rows an image is drawn over, blank below the text of its line
+        let rows = self.reflowed_lines.len() - start;
+        if let Some(image_rows) = self.image_rows(idx).filter(|image_rows| *image_rows > rows) {
+            let blank = Row {
+                line: idx,
+                start: len,
+                end: len,
+                width: 0,
+            };
+            self.reflowed_lines
+                .extend(std::iter::repeat_n(blank, image_rows - rows));
+        }
+
+        if self.reflowed_lines_associations.len() <= idx {
+            self.reflowed_lines_associations.resize(idx + 1, 0..0);
+        }
+        self.reflowed_lines_associations[idx] = start..self.reflowed_lines.len();
+
+        if !self.needle.is_empty() {
+            self.reflowed_search_positions
+                .resize(self.reflowed_lines.len(), SmallVec::new());
+            self.reflow_line_search(idx);
+        }
+    }
+
+    fn set_reverse(&mut self, reverse: bool) {
+        if self.reverse != reverse {
+            self.reverse = reverse;
+            self.keep_top_line();
+            self.need_reflow = true;
+            self.need_redraw = true;
+            self.prompt_outdated = true;
+        }
+    }
+
+    fn set_follow(&mut self, follow: bool) {
+        if self.follow != follow {
+            self.follow = follow;
+            self.prompt_outdated = true;
+
+            if !self.new_lines.is_empty() {
+                self.new_lines.clear();
+                self.need_redraw = true;
+            }
+        }
+    }
+
+    // older batches are dropped as they expire so only the first one matters
+    fn row_background(&self, line: usize) -> Option<Color> {
+        if self.is_selected(line) {
+            Some(SELECTION_BACKGROUND)
+        } else if self.line_cursor == Some(line) {
+            Some(CURSOR_BACKGROUND)
+        } else if self.is_new(line) {
+            Some(self.ui().new_line)
+        } else if self.tint_stderr && self.lines[line].stderr {
+            Some(STDERR_BACKGROUND)
+        } else {
+            None
+        }
+    }
+
+    fn is_new(&self, line: usize) -> bool {
+        self.new_lines.front().is_some_and(|(first, _)| line >= *first)
+    }
+
+    fn expire_new_lines(&mut self) {
+        while let Some((_, time)) = self.new_lines.front() {
+            if time.elapsed() < NEW_LINE_HIGHLIGHT {
+                break;
+            }
+            self.new_lines.pop_front();
+            self.need_redraw = true;
+        }
+    }
+
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.set_reverse(reverse);
+        self
+    }
+
+    fn set_mouse(&mut self, mouse: bool) -> Result<()> {
+        if self.mouse != mouse {
+            self.mouse = mouse;
+            if !self.screen {
+                // captured once the screen is entered
+            } else if mouse {
+                self.output.write_all(ALTERNATE_SCROLL_OFF.as_bytes())?;
+                execute!(self.output, EnableMouseCapture)?;
+            } else {
+                self.output.write_all(ALTERNATE_SCROLL_ON.as_bytes())?;
+                execute!(self.output, DisableMouseCapture)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn mouse(mut self, mouse: bool) -> Result<Self> {
+        self.set_mouse(mouse)?;
+        Ok(self)
+    }
+
+    fn set_wrap(&mut self, wrap: bool) {
+        if self.wrap != wrap {
+            self.wrap = wrap;
+            self.keep_top_line();
+            self.need_reflow = true;
+            self.need_redraw = true;
+            self.prompt_outdated = true;
+        }
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.set_wrap(wrap);
+        self
+    }
+
+    /// toggle an option like less does with `-` then its letter, and tell its new state:
+    /// `S` to chop long lines, `N` for line numbers, `i` to ignore case in searches,
+    /// `s` to squeeze blank lines, and `r` or `R` to show the escape sequences as text or
+    /// interpret their colors
+    fn toggle_option(&mut self, letter: char) {
+        let (name, on) = match letter {
+            'S' => {
+                self.set_wrap(!self.wrap);
+                ("Chop long lines", !self.wrap)
+            }
+            'N' => {
+                self.set_numbers(!self.numbers);
+                ("Line numbers", self.numbers)
+            }
+            'i' => {
+                self.set_ignore_case(!self.ignore_case);
+                ("Ignore case in searches", self.ignore_case)
+            }
+            's' => {
+                self.set_squeeze(!self.squeeze);
+                ("Squeeze blank lines", self.squeeze)
+            }
+            'r' => {
+                self.set_escapes(true);
+                ("Show escapes as text", true)
+            }
+            'R' => {
+                self.set_escapes(false);
+                ("Show escapes as text", false)
+            }
+            _ => {
+                let expected = "S, N, i, s, r or R";
+                self.set_message(format!("No option -{}, expected {}", letter, expected));
+                return;
+            }
+        };
+
+        let state = if on { "on" } else { "off" };
+        self.set_message(format!("{}: {}", name, state));
+    }
+
+    // the top line in view again once reflowed, or the nearest one before it still shown
+    fn keep_top_line(&mut self) {
+        if self.lines.is_empty() {
+            self.scroll = 0;
+            return;
+        }
+
+        let top = self.top_line().min(self.lines.len() - 1);
+        let shown = (self.header..=top)
+            .rev()
+            .chain(top + 1..self.lines.len())
+            .find(|idx| !self.is_hidden(*idx));
+        self.resume = Some(Position::Line(shown.unwrap_or(top)));
+    }
+
+    fn set_numbers(&mut self, numbers: bool) {
+        if self.numbers != numbers {
+            self.numbers = numbers;
+            self.resume = Some(Position::Line(self.top_line()));
+            self.need_reflow = true;
+            self.need_redraw = true;
+        }
+    }
+
+    fn set_ignore_case(&mut self, ignore_case: bool) {
+        if self.ignore_case != ignore_case {
+            self.ignore_case = ignore_case;
+            if !self.needle.is_empty() {
+                self.find_all();
+                self.need_redraw = true;
+            }
+        }
+    }
+
+    fn set_squeeze(&mut self, squeeze: bool) {
+        if self.squeeze != squeeze {
+            self.squeeze = squeeze;
+            // keep the top line in view, or the blank line it's squeezed into
+            let top = self.top_line();
+            let top = (0..=top).rev().find(|idx| !self.is_squeezed(*idx));
+            self.resume = Some(Position::Line(top.unwrap_or(0)));
+            self.need_reflow = true;
+            self.need_redraw = true;
+        }
+    }
+
+    // a writer of rows keeping what the theme keeps of the input
+    fn ch_writer(&self) -> ChWriter {
+        let mut ch_writer = ChWriter::new(&self.size_ctx);
+        ch_writer.colors = self.theme == Theme::Colorful;
+        ch_writer.attributes = self.theme != Theme::Mono;
+        ch_writer.depth = self.color_depth;
+        ch_writer.highlight = match self.paint(self.ui().search) {
+            Paint::Colors(fg, bg) => Some((fg, bg)),
+            Paint::Reverse => None,
+        };
+        ch_writer.gutter = self.ui().gutter;
+        ch_writer
+    }
+
+    // the chosen theme, or the one for the background of the terminal when it's known
+    fn ui(&self) -> UiTheme {
+        match (self.ui_theme, self.light_background) {
+            (Some(theme), _) => theme,
+            (None, Some(light)) => UiTheme::for_background(light),
+            (None, None) => ui_theme::DEFAULT,
+        }
+    }
+
+    // `paint` as the terminal shows it, reverse video without colors
+    fn paint(&self, paint: Paint) -> Paint {
+        match paint {
+            Paint::Colors(fg, bg) if self.theme == Theme::Colorful => Paint::Colors(
+                colors::fit(fg, self.color_depth),
+                colors::fit(bg, self.color_depth),
+            ),
+            _ => Paint::Reverse,
+        }
+    }
+
+    /// the colors of the prompt, the search matches and the rest drawn by rp, picked for
+    /// the background of the terminal when None
+    pub fn ui_theme(mut self, theme: Option<UiTheme>) -> Self {
+        self.ui_theme = theme;
+        self.need_redraw = true;
+        self
+    }
+
+    /// draw the images of the input sent with `protocols`, in place of the terminal found, with
+    /// `cell_height` pixels a row for the images sized in pixels
+    pub fn images(mut self, protocols: &[Protocol], cell_height: Option<u16>) -> Self {
+        self.images = protocols.to_vec();
+        self.cell_height = cell_height;
+        self.need_reflow = true;
+        self
+    }
+
+    fn set_theme(&mut self, theme: Theme) {
+        if self.theme != theme {
+            self.theme = theme;
+            self.need_redraw = true;
+        }
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.set_theme(theme);
+        self
+    }
+
+    pub fn acceleration(mut self, acceleration: Vec<usize>) -> Self {
+        if !acceleration.is_empty() {
+            self.acceleration = acceleration;
+        }
+        self
+    }
+
+    /// how long a sequence waits for its next key, the keys typed so far run alone after it
+    pub fn key_timeout(mut self, timeout: Duration) -> Self {
+        self.key_timeout = timeout;
+        self
+    }
+
+    pub fn wheel_lines(mut self, lines: usize) -> Self {
+        self.wheel_lines = lines.max(1);
+        self
+    }
+
+    // lines to scroll for a notch of the wheel
+    fn wheel_step(&self, modifiers: KeyModifiers) -> usize {
+        if modifiers.contains(KeyModifiers::SHIFT) {
+            ScrollSize::HalfPage.calculate(self.size_ctx.terminal_line())
+        } else {
+            self.wheel_lines
+        }
+    }
+
+    // lines to scroll for the key, growing while a one-line scroll key is held
+    fn held_step(&mut self, size: ScrollSize, forward: bool) -> usize {
+        if !matches!(size, ScrollSize::One) {
+            self.held = None;
+            return 1;
+        }
+
+        let now = Instant::now();
+        let repeats = match self.held {
+            Some((dir, last, repeats)) if dir == forward && now - last < REPEAT_GAP => repeats + 1,
+            _ => 0,
+        };
+        self.held = Some((forward, now, repeats));
+
+        let step = (repeats / REPEATS_PER_STEP).min(self.acceleration.len() - 1);
+        self.acceleration[step]
+    }
+
+    /// draw the view in the `width`x`height` rectangle at `x`, `y` instead of the whole terminal,
+    /// the last line of the rectangle holds the prompt
+    pub fn set_rect(&mut self, x: u16, y: u16, width: u16, height: u16) {
+        self.size_ctx
+            .set_rect(x as usize, y as usize, width as usize, height as usize);
+        self.need_reflow = true;
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    pub fn rect(mut self, x: u16, y: u16, width: u16, height: u16) -> Self {
+        self.set_rect(x, y, width, height);
+        self
+    }
+
+    /// allow `R` to load the input again, the caller does it after `run` returns
+    /// when [`UiContext::reload_state`] gives a state to restore
+    pub fn reloadable(mut self, reloadable: bool) -> Self {
+        self.reloadable = reloadable;
+        self
+    }
+
+    /// reload when the watched file changes, implies [`UiContext::reloadable`]
+    pub fn watch(mut self, watcher: Option<Watcher>) -> Self {
+        if watcher.is_some() {
+            self.reloadable = true;
+        }
+        self.watcher = watcher;
+        self
+    }
+
+    /// the state to restore in a new `UiContext` if `run` returned for a reload
+    pub fn reload_state(&self) -> Option<ViewState> {
+        match self.reload {
+            true => Some(self.view_state()),
+            false => None,
+        }
+    }
+
+    /// what a session keeps of the input, see [`UiContext::restore_saved`]
+    pub fn saved_view(&self) -> SavedView {
+        self.view_state().saved()
+    }
+
+    fn view_state(&self) -> ViewState {
+        let position = match self.reflowed_lines.get(self.scroll) {
+            // lines of the other view don't match
+            _ if self.toggle_hex => Position::Line(0),
+            Some(row) if self.scroll < self.max_scroll() => Position::Line(row.line),
+            _ => Position::End,
+        };
+
+        ViewState {
+            position,
+            needle: self.needle.clone(),
+            reverse: self.reverse,
+            dedup: self.dedup,
+            squeeze: self.squeeze,
+            only_stderr: self.only_stderr,
+            ignore_case: self.ignore_case,
+            mouse: self.mouse,
+            wrap: self.wrap,
+            theme: self.theme,
+            escapes: self.escapes,
+            hex: Some(self.reader.hex.load(Ordering::Relaxed) != self.toggle_hex),
+        }
+    }
+
+    /// searches recalled with Up and Down in the search prompt
+    pub fn search_history(mut self, history: Vec<String>) -> Self {
+        self.search_history = history;
+        self
+    }
+
+    pub fn searches(&self) -> &[String] {
+        &self.search_history
+    }
+
+    fn remember_search(&mut self, needle: &str) {
+        self.search_history.retain(|s| s != needle);
+        self.search_history.push(needle.to_string());
+        let extra = self.search_history.len().saturating_sub(MAX_SEARCHES);
+        self.search_history.drain(..extra);
+    }
+
+    /// scroll to `line` once it arrives
+    pub fn start_at(mut self, line: Option<usize>) -> Self {
+        if let Some(line) = line {
+            self.resume = Some(Position::Line(line));
+        }
+        self
+    }
+
+    /// commands run once the first screenful is loaded, in order
+    pub fn startup(mut self, commands: Vec<StartupCommand>) -> Self {
+        self.startup = commands;
+        self
+    }
+
+    fn run_startup(&mut self, command: StartupCommand) {
+        match command {
+            StartupCommand::End => self.resume = Some(Position::End),
+            StartupCommand::Line(line) => {
+                self.resume = Some(Position::Line(line.saturating_sub(1)))
+            }
+            StartupCommand::Search(needle) => {
+                self.search(&needle);
+                // the first match may still be coming
+                self.search_pending = self.hint.is_none() && self.loading;
+            }
+        }
+    }
+
+    /// index of the line on top of the view
+    pub fn top_line(&self) -> usize {
+        self.reflowed_lines.get(self.scroll).map_or(0, |row| row.line)
+    }
+
+    pub fn restore(mut self, state: ViewState) -> Result<Self> {
+        self.set_mouse(state.mouse)?;
+        self.set_reverse(state.reverse);
+        self.set_dedup(state.dedup);
+        self.set_squeeze(state.squeeze);
+        self.set_only_stderr(state.only_stderr);
+        self.set_ignore_case(state.ignore_case);
+        self.set_wrap(state.wrap);
+        self.set_theme(state.theme);
+        self.set_escapes(state.escapes);
+        // new lines are searched as they arrive
+        self.search_char_len = state.needle.chars().count();
+        self.needle = state.needle;
+        self.resume = Some(state.position);
+        Ok(self)
+    }
+
+    /// the input as a session left it, the other settings as given
+    pub fn restore_saved(mut self, view: SavedView) -> Self {
+        self.set_reverse(view.reverse);
+        self.set_dedup(view.dedup);
+        self.set_squeeze(view.squeeze);
+        self.set_only_stderr(view.only_stderr);
+        self.set_ignore_case(view.ignore_case);
+        self.search_char_len = view.needle.chars().count();
+        self.needle = view.needle;
+        self.resume = Some(view.line.map_or(Position::End, Position::Line));
+        self
+    }
+
+    // go back to the position before the reload, stop once reached or everything was read
+    fn resume_position(&mut self, position: Position) {
+        if self.need_reflow {
+            return;
+        }
+
+        match position {
+            Position::Line(line) if line < self.lines.len() => {
+                let row = self.reflowed_lines_associations[line].start;
+                self.goto_scroll(row);
+                if self.scroll == row {
+                    self.resume = None;
+                }
+            }
+            Position::Line(_) => {}
+            Position::End => self.goto_scroll(usize::MAX),
+        }
+
+        if !self.loading {
+            self.resume = None;
+        }
+    }
+
+    /// show the `index`-th of the inputs, the caller switches to [`UiContext::next_input`]
+    /// like for a reload
+    pub fn inputs(mut self, index: usize, inputs: Vec<InputInfo>) -> Self {
+        self.input = index;
+        self.inputs = inputs;
+        self
+    }
+
+    pub fn next_input(&self) -> Option<usize> {
+        self.next_input
+    }
+
+    /// file opened with `:e` to show next, before [`UiContext::next_input`]
+    pub fn next_path(&self) -> Option<&Path> {
+        self.next_path.as_deref()
+    }
+
+    /// stay on this input when the next one can't be opened, `message` telling why, before
+    /// [`UiContext::run`] goes on
+    pub fn stay(&mut self, message: String) {
+        self.reload = false;
+        self.next_input = None;
+        self.next_path = None;
+        self.set_message(message);
+    }
+
+    // replace the search prompt with an older or newer search, an empty one after the newest
+    fn recall_search(&mut self, older: bool) {
+        let len = self.search_history.len();
+        self.history_index = match (self.history_index, older) {
+            (None, true) => len.checked_sub(1),
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => Some(i + 1).filter(|i| *i < len),
+            (None, false) => None,
+        };
+
+        let recalled = self
+            .history_index
+            .map(|i| self.search_history[i].clone())
+            .unwrap_or_default();
+        self.prompt_state = PromptState::Search(recalled);
+        self.prompt_cursor = 0;
+        self.prompt_outdated = true;
+    }
+
+    fn switch_input(&mut self, forward: bool) {
+        let next = if forward {
+            Some(self.input + 1).filter(|next| *next < self.inputs.len())
+        } else {
+            self.input.checked_sub(1)
+        };
+
+        match next {
+            Some(next) => self.open_input(next),
+            None if forward => self.set_message("No next file".into()),
+            None => self.set_message("No previous file".into()),
+        }
+    }
+
+    fn open_input(&mut self, index: usize) {
+        self.next_input = Some(index);
+        self.reload = true;
+    }
+
+    fn show_buffers(&mut self) {
+        self.prompt_state = PromptState::Buffers(self.input);
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // the input list drawn over the view
+    fn draw_buffers(&mut self, selected: usize) -> Result<()> {
+        self.output_buf.clear();
+
+        let left = self.size_ctx.left() as u16;
+        let top = self.size_ctx.top();
+        let width = self.size_ctx.terminal_column();
+        let height = self.size_ctx.terminal_line();
+        let first = selected.saturating_sub(height.saturating_sub(1));
+        let current_line = self.reflowed_lines.get(self.scroll).map(|r| r.line);
+
+        for row in 0..height {
+            let y = (top + row) as u16;
+            queue!(self.output_buf, MoveTo(left, y))?;
+            write!(self.output_buf, "{:1$}", "", width)?;
+            queue!(self.output_buf, MoveTo(left, y))?;
+
+            let idx = first + row;
+            let info = match self.inputs.get(idx) {
+                Some(info) => info,
+                None => continue,
+            };
+            let line = if idx == self.input {
+                current_line
+            } else {
+                info.line
+            };
+            let text = format!(
+                "{} {:>3}  {}  {}  {}",
+                if idx == self.input { '*' } else { ' ' },
+                idx + 1,
+                info.name,
+                info.size.map_or("-".into(), convert::human_size),
+                line.map_or(String::new(), |line| format!("line {}", line + 1)),
+            );
+
+            if idx == selected {
+                let paint = self.paint(self.ui().selected);
+                self.output_buf.extend_from_slice(paint.start().as_bytes());
+            }
+            self.output_buf
+                .extend_from_slice(truncate_to_width(&text, width).as_bytes());
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
+        self.update_prompt();
+        self.write_prompt()?;
+        self.output.write_all(&self.output_buf)?;
+        self.output.flush()?;
+        self.need_redraw = false;
+
+        Ok(())
+    }
+
+    // keys of the input list, returns true to leave `run` for the chosen input
+    fn handle_buffers_key(&mut self, selected: usize, ke: KeyEvent) -> bool {
+        let last = self.inputs.len().saturating_sub(1);
+        let choice = match ke.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.prompt_state = PromptState::Buffers(selected.saturating_sub(1));
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.prompt_state = PromptState::Buffers((selected + 1).min(last));
+                None
+            }
+            KeyCode::Enter => Some(selected),
+            KeyCode::Char(c @ '1'..='9') => Some(c as usize - '1' as usize).filter(|i| *i <= last),
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('B') => {
+                self.prompt_state = PromptState::Normal;
+                None
+            }
+            _ => None,
+        };
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+
+        match choice {
+            Some(idx) if idx != self.input => {
+                self.prompt_state = PromptState::Normal;
+                self.open_input(idx);
+                true
+            }
+            Some(_) => {
+                self.prompt_state = PromptState::Normal;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn show_matches(&mut self) {
+        if self.needle.is_empty() {
+            self.set_message("No search".into());
+            return self.ring_bell();
+        }
+        let positions = &self.search_positions;
+        let matched = |idx: &usize| positions.get(*idx).is_some_and(|p| !p.is_empty());
+        self.panel_lines = self.visible_lines().filter(matched).collect();
+        if self.panel_lines.is_empty() {
+            self.set_message("Pattern not found".into());
+            return self.ring_bell();
+        }
+        self.prompt_state = PromptState::Matches(self.panel_from_top());
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    fn show_outline(&mut self) {
+        let section = &self.section;
+        let lines = &self.lines;
+        let heading = |idx: &usize| section.is_match(&lines[*idx].text());
+        self.panel_lines = self.visible_lines().filter(heading).collect();
+        if self.panel_lines.is_empty() {
+            self.set_message("No sections".into());
+            return self.ring_bell();
+        }
+        // the section the top line is in
+        let top = self.top_line();
+        let selected = self.panel_lines.iter().rposition(|line| *line <= top);
+        self.prompt_state = PromptState::Outline(selected.unwrap_or(0));
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    /// bookmarks of the input, restored from an earlier run
+    pub fn bookmarks(mut self, mut bookmarks: Vec<Bookmark>) -> Self {
+        bookmarks.sort_by_key(|bookmark| bookmark.line);
+        self.bookmarks = bookmarks;
+        self
+    }
+
+    pub fn bookmark_list(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    // `:bookmark NAME NOTE` names the cursor or top line, a bookmark of the same name moves
+    fn add_bookmark(&mut self, arg: &str) {
+        let line = self.cursor_line();
+        let (name, note) = arg.split_once(' ').unwrap_or((arg, ""));
+        let name = match name {
+            "" => format!("line-{}", line + 1),
+            _ => name.to_string(),
+        };
+
+        self.bookmarks.retain(|bookmark| bookmark.name != name);
+        let at = self.bookmarks.partition_point(|b| b.line <= line);
+        let note = note.trim().to_string();
+        let message = format!("Bookmark {} on line {}", name, line + 1);
+        self.bookmarks.insert(at, Bookmark { line, name, note });
+        self.set_message(message);
+    }
+
+    fn show_bookmarks(&mut self) {
+        if self.bookmarks.is_empty() {
+            self.set_message("No bookmarks, add one with :bookmark NAME".into());
+            return self.ring_bell();
+        }
+        self.panel_lines = self.bookmarks.iter().map(|b| b.line).collect();
+        self.prompt_state = PromptState::Bookmarks(self.panel_from_top());
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // the first line of the panel from the top line on
+    fn panel_from_top(&self) -> usize {
+        let top = self.top_line();
+        let selected = self.panel_lines.iter().position(|line| *line >= top);
+        selected.unwrap_or(0)
+    }
+
+    // rows of the match or outline panel, at most half of the view
+    fn line_panel_height(&self) -> usize {
+        let half = (self.size_ctx.terminal_line() / 2).max(1);
+        self.panel_lines.len().min(half)
+    }
+
+    // the lines of the panel over the bottom of the view, with their numbers
+    fn write_line_panel(&mut self, selected: usize) -> Result<()> {
+        let left = self.size_ctx.left() as u16;
+        let width = self.size_ctx.terminal_column();
+        let height = self.line_panel_height();
+        let top = self.size_ctx.top() + self.size_ctx.terminal_line() - height;
+        let first = selected.saturating_sub(height.saturating_sub(1));
+        let last = self.panel_lines.last().copied().unwrap_or(0);
+        let digits = (last + 1).to_string().len();
+        let paint = self.paint(self.ui().selected);
+
+        for row in 0..height {
+            let y = (top + row) as u16;
+            queue!(self.output_buf, MoveTo(left, y))?;
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+            write!(self.output_buf, "{:1$}", "", width)?;
+            queue!(self.output_buf, MoveTo(left, y))?;
+
+            let idx = first + row;
+            let line = match self.panel_lines.get(idx) {
+                Some(line) => *line,
+                None => continue,
+            };
+            let text = format!(
+                "{:>digits$}  {}",
+                line + 1,
+                self.panel_text(idx),
+                digits = digits
+            );
+
+            let text = truncate_to_width(&text, width);
+            if idx == selected {
+                // across the whole row
+                let pad = width.saturating_sub(text_width(text));
+                self.output_buf.extend_from_slice(paint.start().as_bytes());
+                write!(self.output_buf, "{}{:2$}", text, "", pad)?;
+            } else {
+                self.output_buf.extend_from_slice(text.as_bytes());
+            }
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
+        Ok(())
+    }
+
+    // the name and note of a bookmark, the text of the line otherwise
+    fn panel_text(&self, idx: usize) -> String {
+        let line = self.panel_lines[idx];
+        let text = match self.lines.get(line) {
+            Some(line) => expand_tabs(line.text().trim_end()),
+            None => String::new(),
+        };
+        match (&self.prompt_state, self.bookmarks.get(idx)) {
+            (PromptState::Bookmarks(_), Some(bookmark)) if bookmark.note.is_empty() => {
+                format!("{}  {}", bookmark.name, text)
+            }
+            (PromptState::Bookmarks(_), Some(bookmark)) => {
+                format!("{}  {}", bookmark.name, bookmark.note)
+            }
+            _ => text,
+        }
+    }
+
+    // keys of the match, outline and bookmark panels, Enter jumps to the selected line
+    fn handle_panel_key(&mut self, selected: usize, ke: KeyEvent) {
+        let last = self.panel_lines.len().saturating_sub(1);
+        let page = self.line_panel_height();
+        let selected = match ke.code {
+            KeyCode::Up | KeyCode::Char('k') => selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => (selected + 1).min(last),
+            KeyCode::PageUp | KeyCode::Char('b') => selected.saturating_sub(page),
+            KeyCode::PageDown | KeyCode::Char(' ') => (selected + page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => 0,
+            KeyCode::End | KeyCode::Char('G') => last,
+            KeyCode::Char('d') if matches!(self.prompt_state, PromptState::Bookmarks(_)) => {
+                let bookmark = self.bookmarks.remove(selected);
+                self.set_message(format!("Deleted bookmark {}", bookmark.name));
+                self.panel_lines.remove(selected);
+                if self.panel_lines.is_empty() {
+                    self.prompt_state = PromptState::Normal;
+                    self.need_redraw = true;
+                    return;
+                }
+                selected.min(last - 1)
+            }
+            KeyCode::Enter => {
+                let line = self.panel_lines[selected];
+                self.prompt_state = PromptState::Normal;
+                self.jump_to_line(line);
+                return;
+            }
+            // Esc, q, L, T or any other key
+            _ => {
+                self.prompt_state = PromptState::Normal;
+                self.need_redraw = true;
+                self.prompt_outdated = true;
+                return;
+            }
+        };
+        self.prompt_state = match self.prompt_state {
+            PromptState::Outline(_) => PromptState::Outline(selected),
+            PromptState::Bookmarks(_) => PromptState::Bookmarks(selected),
+            _ => PromptState::Matches(selected),
+        };
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // `line` on top of the view, its first match hinted
+    fn jump_to_line(&mut self, line: usize) {
+        if let Some(rows) = self.reflowed_lines_associations.get(line) {
+            self.goto_scroll(rows.start);
+        }
+        let first = self.search_positions.get(line).and_then(|p| p.first());
+        self.hint = first.map(|p| (line, p.start as usize));
+        if self.line_cursor.is_some() {
+            self.line_cursor = Some(line);
+        }
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    fn show_help(&mut self) {
+        self.prompt_state = PromptState::Help(0);
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // the keys of the current keymap drawn over the view, from the `first` line of the help
+    fn draw_help(&mut self, first: usize) -> Result<()> {
+        let help = crate::keys::help(&self.keymap, &self.sequences);
+        self.draw_page(&help, first, true)
+    }
+
+    // `lines` drawn over the view from the `first` one, those not indented in bold with `headings`
+    fn draw_page(&mut self, lines: &[String], first: usize, headings: bool) -> Result<()> {
+        self.output_buf.clear();
+
+        let left = self.size_ctx.left() as u16;
+        let top = self.size_ctx.top();
+        let width = self.size_ctx.terminal_column();
+        let height = self.size_ctx.terminal_line();
+
+        for row in 0..height {
+            let y = (top + row) as u16;
+            queue!(self.output_buf, MoveTo(left, y))?;
+            write!(self.output_buf, "{:1$}", "", width)?;
+            queue!(self.output_buf, MoveTo(left, y))?;
+
+            let text = match lines.get(first + row) {
+                Some(text) => text,
+                None => continue,
+            };
+            // headings aren't indented
+            if headings && !text.starts_with(' ') {
+                queue!(self.output_buf, SetAttribute(Attribute::Bold))?;
+            }
+            self.output_buf
+                .extend_from_slice(truncate_to_width(text, width).as_bytes());
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
+        self.update_prompt();
+        self.write_prompt()?;
+        self.output.write_all(&self.output_buf)?;
+        self.output.flush()?;
+        self.need_redraw = false;
+
+        Ok(())
+    }
+
+    // keys scrolling and closing the help
+    fn handle_help_key(&mut self, first: usize, ke: KeyEvent) {
+        let len = crate::keys::help(&self.keymap, &self.sequences).len();
+        let first = self.scroll_page(first, len, ke);
+        self.prompt_state = first.map_or(PromptState::Normal, PromptState::Help);
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // keys scrolling and closing the output of `:pipe`
+    fn handle_output_key(&mut self, first: usize, ke: KeyEvent) {
+        let first = self.scroll_page(first, self.pipe_output.1.len(), ke);
+        self.prompt_state = first.map_or(PromptState::Normal, PromptState::Output);
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // first line of a page of `len` lines after `ke`, None when it closes the page
+    fn scroll_page(&self, first: usize, len: usize, ke: KeyEvent) -> Option<usize> {
+        let height = self.size_ctx.terminal_line();
+        let last = len.saturating_sub(height);
+        let first = match ke.code {
+            KeyCode::Up | KeyCode::Char('k') => first.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => first + 1,
+            KeyCode::PageUp | KeyCode::Char('b') => first.saturating_sub(height),
+            KeyCode::PageDown | KeyCode::Char(' ') | KeyCode::Char('f') => first + height,
+            KeyCode::Home | KeyCode::Char('g') => 0,
+            KeyCode::End | KeyCode::Char('G') => last,
+            // Esc, q, h or any other key
+            _ => return None,
+        };
+        Some(first.min(last))
+    }
+
+    fn show_palette(&mut self) {
+        self.prompt_state = PromptState::Palette(String::new(), 0);
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+    }
+
+    // the actions matching `query` drawn over the view, with their keys
+    fn draw_palette(&mut self, query: &str, selected: usize) -> Result<()> {
+        self.output_buf.clear();
+
+        let left = self.size_ctx.left() as u16;
+        let top = self.size_ctx.top();
+        let width = self.size_ctx.terminal_column();
+        let height = self.size_ctx.terminal_line();
+        let first = selected.saturating_sub(height.saturating_sub(1));
+        let actions = crate::keys::palette(query);
+
+        for row in 0..height {
+            let y = (top + row) as u16;
+            queue!(self.output_buf, MoveTo(left, y))?;
+            write!(self.output_buf, "{:1$}", "", width)?;
+            queue!(self.output_buf, MoveTo(left, y))?;
+
+            let idx = first + row;
+            let (name, description, behavior) = match actions.get(idx) {
+                Some(action) => *action,
+                None => continue,
+            };
+            let keys = crate::keys::keys_of(&self.keymap, &self.sequences, behavior);
+            let text = format!("{:<16} {}  {}", name, description, keys.join(", "));
+
+            if idx == selected {
+                let paint = self.paint(self.ui().selected);
+                self.output_buf.extend_from_slice(paint.start().as_bytes());
+            }
+            self.output_buf
+                .extend_from_slice(truncate_to_width(&text, width).as_bytes());
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+        }
+
+        self.update_prompt();
+        self.write_prompt()?;
+        self.output.write_all(&self.output_buf)?;
+        self.output.flush()?;
+        self.need_redraw = false;
+
+        Ok(())
+    }
+
+    // typing in the palette, returns true to quit like for the chosen action
+    fn handle_palette_key(
+        &mut self,
+        mut query: String,
+        selected: usize,
+        ke: KeyEvent,
+    ) -> Result<bool> {
+        let len = crate::keys::palette(&query).len();
+        let control = ke.modifiers.contains(KeyModifiers::CONTROL);
+        let selected = match ke.code {
+            KeyCode::Up => selected.saturating_sub(1),
+            KeyCode::Char('p') if control => selected.saturating_sub(1),
+            KeyCode::Down => selected + 1,
+            KeyCode::Char('n') if control => selected + 1,
+            KeyCode::Char(ch) if !control && !ke.modifiers.contains(KeyModifiers::ALT) => {
+                query.push(ch);
+                0
+            }
+            KeyCode::Backspace if query.pop().is_some() => 0,
+            KeyCode::Enter => {
+                self.prompt_state = PromptState::Normal;
+                self.need_redraw = true;
+                self.prompt_outdated = true;
+                return match crate::keys::palette(&query).get(selected) {
+                    Some((_, _, behavior)) => self.act(*behavior),
+                    None => Ok(false),
+                };
+            }
+            KeyCode::Esc | KeyCode::Backspace => {
+                self.prompt_state = PromptState::Normal;
+                self.need_redraw = true;
+                self.prompt_outdated = true;
+                return Ok(false);
+            }
+            _ => selected,
+        };
+
+        self.prompt_state = PromptState::Palette(query, selected.min(len.saturating_sub(1)));
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+        Ok(false)
+    }
+
+    /// call `hook` for every [`PagerEvent`], after the pager handled it
+    pub fn on_event(mut self, hook: impl FnMut(&PagerEvent) + 'b) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    fn emit(&mut self, event: PagerEvent) {
+        for hook in self.hooks.iter_mut() {
+            hook(&event);
+        }
+    }
+
+    /// give the keys to `filter` before the keymap, to drop them or to do something else
+    /// instead, in the order the filters were added. The keys typed in a prompt or a list
+    /// don't go through it
+    pub fn on_key(mut self, filter: impl FnMut(&KeyEvent) -> KeyAction + 'b) -> Self {
+        self.key_filters.push(Box::new(filter));
+        self
+    }
+
+    /// ask `decorator` for the [`Decoration`] of each line drawn, given its index and its text,
+    /// with a gutter `width` columns wide before the lines. It's asked again each time the
+    /// line is drawn
+    pub fn decorate(
+        mut self,
+        width: usize,
+        decorator: impl FnMut(usize, &str) -> Option<Decoration> + 'b,
+    ) -> Self {
+        self.decorator = Some((width, Box::new(decorator)));
+        self
+    }
+
+    fn decoration(&mut self, line: usize) -> Option<Decoration> {
+        let (_, decorator) = self.decorator.as_mut()?;
+        decorator(line, &self.lines[line].text())
+    }
+
+    // the decoration of the line of each row of `start..end`, asked once for a wrapped line
+    fn decorations(&mut self, start: usize, end: usize) -> Vec<Option<Decoration>> {
+        let mut decorations: Vec<Option<Decoration>> = Vec::with_capacity(end - start);
+        for idx in start..end {
+            let line = self.reflowed_lines[idx].line;
+            let decoration = match decorations.last() {
+                Some(prev) if self.reflowed_lines[idx - 1].line == line => prev.clone(),
+                _ => self.decoration(line),
+            };
+            decorations.push(decoration);
+        }
+        decorations
+    }
+
+    // the key left by the filters as a `Replace`, or what the one that took it does instead
+    fn filter_key(&mut self, mut ke: KeyEvent) -> KeyAction {
+        for filter in self.key_filters.iter_mut() {
+            match filter(&ke) {
+                KeyAction::Pass => {}
+                KeyAction::Replace(key) => ke = key,
+                action => return action,
+            }
+        }
+        KeyAction::Replace(ke)
+    }
+
+    /// ask the first-run questions before anything else
+    pub fn setup(mut self, setup: bool) -> Self {
+        if setup {
+            self.prompt_state = PromptState::Setup(0);
+            self.prompt_outdated = true;
+        }
+        self
+    }
+
+    // apply the answer right away so its effect is visible, save after the last one
+    fn answer_setup(&mut self, step: usize, answer: Option<bool>) -> Result<()> {
+        let yes = answer.unwrap_or(true);
+        match step {
+            0 => self.set_mouse(yes)?,
+            1 => self.set_wrap(yes),
+            _ => self.set_theme(if yes { Theme::Colorful } else { Theme::Plain }),
+        }
+
+        if answer.is_some() && step + 1 < SETUP_QUESTIONS.len() {
+            self.prompt_state = PromptState::Setup(step + 1);
+        } else {
+            self.prompt_state = PromptState::Normal;
+            self.save_config();
+        }
+        self.prompt_outdated = true;
+
+        Ok(())
+    }
+
+    fn save_config(&mut self) {
+        let config = Config {
+            mouse: self.mouse,
+            wrap: self.wrap,
+            theme: self.theme,
+            ui_theme: self.ui_theme,
+            acceleration: self.acceleration.clone(),
+            wheel_lines: self.wheel_lines,
+            log_colors: self.log_colors,
+            log_levels: self.log_levels.clone(),
+            ..Config::default()
+        };
+
+        match config.save() {
+            Ok(path) => self.set_message(format!("Saved preferences to {}", path.display())),
+            Err(e) => self.set_message(format!("Can't save preferences: {}", e)),
+        }
+    }
+
+    // true to quit
+    fn run_command(&mut self, command: &str) -> bool {
+        #[cfg(feature = "logging")]
+        log::debug!("Command: {:?}", command);
+
+        let command = command.trim();
+        let terminal = ["!", "w ", "write ", "export ", "pipe "];
+        if !self.output.is_terminal() && terminal.iter().any(|name| command.starts_with(name)) {
+            self.set_message("Not on a terminal".into());
+            return false;
+        }
+        if let Some(shell) = command.strip_prefix('!') {
+            self.shell_escape(shell.trim());
+            return false;
+        }
+        let (name, arg) = match command.find(' ') {
+            Some(i) => (&command[..i], command[i + 1..].trim()),
+            None => (command, ""),
+        };
+
+        match name {
+            "q" | "q!" | "quit" | "quit!" => {
+                self.emit(PagerEvent::Quit);
+                return true;
+            }
+            "$" => self.resume = Some(Position::End),
+            _ if name.parse::<usize>().is_ok() => {
+                let line = name.parse::<usize>().unwrap_or_default();
+                self.resume = Some(Position::Line(line.saturating_sub(1)));
+            }
+            "set" | "se" => self.set(arg),
+            "w" | "write" => self.export(arg),
+            "pipe" => self.pipe(arg),
+            "e" | "edit" => self.edit(arg),
+            "reverse" => self.set_reverse(!self.reverse),
+            "dedup" => self.set_dedup(!self.dedup),
+            "wrap" => self.set_wrap(!self.wrap),
+            "tint" => {
+                self.tint_stderr = !self.tint_stderr;
+                self.need_redraw = true;
+            }
+            "conv" if !arg.is_empty() => self.convert(arg),
+            "conv" => self.convert_hint(),
+            "export" => self.export(arg),
+            "mem" => self.set_message(self.memory_status()),
+            "ls" => self.show_buffers(),
+            "matches" => self.show_matches(),
+            "outline" => self.show_outline(),
+            "bookmark" | "mark" => self.add_bookmark(arg),
+            "bookmarks" | "marks" => self.show_bookmarks(),
+            "offset" => self.goto_offset(arg),
+            "time" => self.goto_time(arg),
+            "freeze" if arg.is_empty() || arg == "off" => self.set_freeze(None),
+            "freeze" => match Freeze::parse(arg) {
+                Some(freeze) => self.set_freeze(Some(freeze)),
+                None => self.set_message("Usage: freeze N|Nf|NfC|off".into()),
+            },
+            "ruler" => self.set_ruler(!self.size_ctx.ruler),
+            "guides" if arg.is_empty() || arg == "off" => self.set_guides(Vec::new()),
+            "guides" => match config::parse_columns(arg) {
+                Some(columns) => self.set_guides(columns),
+                None => self.set_message("Usage: guides COLUMN[,COLUMN...]|off".into()),
+            },
+            "section" if arg.is_empty() => {
+                self.set_message(format!("Sections start at {}", self.section))
+            }
+            "section" => match Regex::new(arg) {
+                Ok(pattern) => self.section = pattern,
+                Err(e) => self.set_message(format!("Bad pattern: {}", e)),
+            },
+            "n" => self.switch_input(true),
+            "p" => self.switch_input(false),
+            _ => self.set_message(format!("Unknown command: {}", name)),
+        }
+        false
+    }
+
+    // `:set wrap`, `:set nowrap`, `:set wrap!` to toggle it and `:set wrap?` to tell its state,
+    // for each option given, or for all of them with `:set` alone
+    fn set(&mut self, arg: &str) {
+        if arg.is_empty() {
+            let states = OPTIONS
+                .iter()
+                .map(|name| option_state(name, self.option(name)));
+            return self.set_message(states.collect::<Vec<_>>().join(" "));
+        }
+
+        let mut states = Vec::new();
+        for word in arg.split_whitespace() {
+            let (word, suffix) = match word.strip_suffix(['!', '?']) {
+                Some(rest) => (rest, word.chars().last()),
+                None => (word, None),
+            };
+            let (name, on) = match option_name(word) {
+                Some(name) => (name, true),
+                None => match word.strip_prefix("no").and_then(option_name) {
+                    Some(name) => (name, false),
+                    None => return self.set_message(format!("Unknown option: {}", word)),
+                },
+            };
+
+            match suffix {
+                Some('?') => {}
+                Some(_) => self.set_option(name, !self.option(name)),
+                None => self.set_option(name, on),
+            }
+            states.push(option_state(name, self.option(name)));
+        }
+        self.set_message(states.join(" "));
+    }
+
+    // state of one of the OPTIONS
+    fn option(&self, name: &str) -> bool {
+        match name {
+            "wrap" => self.wrap,
+            "number" => self.numbers,
+            "ignorecase" => self.ignore_case,
+            "squeeze" => self.squeeze,
+            "reverse" => self.reverse,
+            "dedup" => self.dedup,
+            "mouse" => self.mouse,
+            "tint" => self.tint_stderr,
+            "scrollbar" => self.scrollbar,
+            "minimap" => self.minimap,
+            "ruler" => self.size_ctx.ruler,
+            "cursorline" => self.line_cursor.is_some(),
+            "title" => self.title,
+            "mono" => self.theme == Theme::Mono,
+            "urls" => self.urls,
+            "filelinks" => self.file_links,
+            "escapes" => self.escapes,
+            "memory" => self.show_memory,
+            _ => false,
+        }
+    }
+
+    fn set_option(&mut self, name: &str, on: bool) {
+        match name {
+            "wrap" => self.set_wrap(on),
+            "number" => self.set_numbers(on),
+            "ignorecase" => self.set_ignore_case(on),
+            "squeeze" => self.set_squeeze(on),
+            "reverse" => self.set_reverse(on),
+            "dedup" => self.set_dedup(on),
+            "mouse" => {
+                if let Err(e) = self.set_mouse(on) {
+                    self.set_message(format!("Can't set the mouse capture: {}", e));
+                }
+            }
+            "tint" => {
+                self.tint_stderr = on;
+                self.need_redraw = true;
+            }
+            "scrollbar" => {
+                self.scrollbar = on;
+                self.need_redraw = true;
+            }
+            "minimap" => self.set_minimap(on),
+            "ruler" => self.set_ruler(on),
+            "cursorline" => self.set_line_cursor(on),
+            "title" => self.set_title(on),
+            "mono" => self.set_theme(if on { Theme::Mono } else { Theme::Colorful }),
+            "urls" => self.set_urls(on),
+            "filelinks" => self.set_file_links(on),
+            "escapes" => self.set_escapes(on),
+            "memory" => {
+                self.show_memory = on;
+                self.prompt_outdated = true;
+            }
+            _ => {}
+        }
+    }
+
+    // open another file in place of this one, or this one again
+    fn edit(&mut self, path: &str) {
+        if !self.reloadable {
+            return self.set_message("Can't leave piped input".into());
+        }
+
+        if path.is_empty() {
+            self.reload = true;
+            return;
+        }
+        match std::fs::metadata(path) {
+            Ok(meta) if meta.is_dir() => self.set_message(format!("{} is a directory", path)),
+            Ok(_) => {
+                self.next_path = Some(PathBuf::from(path));
+                self.reload = true;
+            }
+            Err(e) => self.set_message(format!("Can't open {}: {}", path, e)),
+        }
+    }
+
+    // note that this only knows about the lines already received
+    fn visible_lines(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        let all = 0..self.lines.len();
+        let ordered: Box<dyn Iterator<Item = usize>> = if self.reverse {
+            Box::new(all.rev())
+        } else {
+            Box::new(all)
+        };
+
+        Box::new(ordered.filter(move |idx| !self.is_hidden(*idx)))
+    }
+
+    /// `[-r] [-w COLUMN] PATH`, `-r` keeping the escape sequences of the input
+    fn export(&mut self, arg: &str) {
+        let (raw, arg) = match arg.strip_prefix("-r") {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => (true, rest.trim_start()),
+            _ => (false, arg),
+        };
+        let (column, path) = match arg.strip_prefix("-w") {
+            Some(rest) => {
+                let rest = rest.trim_start();
+                let (column, path) = rest.split_at(rest.find(' ').unwrap_or(rest.len()));
+                match column.parse::<usize>() {
+                    Ok(column) if column > 0 => (Some(column), path.trim()),
+                    _ => return self.set_message(format!("Invalid column: {}", column)),
+                }
+            }
+            None => (None, arg),
+        };
+
+        if path.is_empty() {
+            return self.set_message("Usage: export [-r] [-w COLUMN] PATH".into());
+        }
+        if raw && column.is_some() {
+            return self.set_message("Lines with escape sequences can't be wrapped".into());
+        }
+
+        // only the selected lines in visual mode
+        let selected = self.take_selected_lines();
+        let texts = match raw {
+            true => Vec::new(),
+            false => selected
+                .iter()
+                .map(|idx| self.lines[*idx].text().into_owned())
+                .collect(),
+        };
+        let lines = texts.iter().map(|t| t.as_str());
+
+        let ret = File::create(path).and_then(|file| {
+            let mut out = std::io::BufWriter::new(file);
+            match column {
+                _ if raw => {
+                    let lines = selected.iter().map(|idx| self.lines[*idx].bytes());
+                    crate::export::write_raw(&mut out, lines)?
+                }
+                Some(column) => crate::export::write_wrapped(&mut out, lines, column)?,
+                None => crate::export::write_plain(&mut out, lines)?,
+            }
+            out.flush()
+        });
+
+        match ret {
+            Ok(()) => self.set_message(format!("Exported {} lines to {}", selected.len(), path)),
+            Err(e) => self.set_message(format!("Can't export to {}: {}", path, e)),
+        }
+    }
+
+    /// `N` or `0xN`, in the hex dump
+    fn goto_offset(&mut self, arg: &str) {
+        if !self.reader.hex.load(Ordering::Relaxed) {
+            return self.set_message("Not a hex dump, H shows one".into());
+        }
+
+        let offset = match arg.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => arg.parse().ok(),
+        };
+        match offset {
+            // the line may still be coming
+            Some(offset) => self.resume = Some(Position::Line(offset as usize / HEX_ROW)),
+            None => self.set_message("Usage: offset N|0xN".into()),
+        }
+    }
+
+    /// `14:32`, `2024-05-01 14:32` or any format of [`timestamp::at_line_start`];
+    /// a time alone is on the day the top line is on
+    fn goto_time(&mut self, arg: &str) {
+        let mut target = match timestamp::parse_target(arg) {
+            Some(target) => target,
+            None => return self.set_message("Usage: time [YYYY-MM-DD] HH:MM[:SS]".into()),
+        };
+
+        let lines = &self.lines;
+        let line = |idx: usize| lines[idx].text();
+        if target.day.is_none() {
+            let day = timestamp::next_stamped(&line, self.top_line(), lines.len())
+                .or_else(|| timestamp::next_stamped(&line, 0, lines.len()))
+                .map(|(_, stamp)| stamp);
+            if let Some(day) = day {
+                target.year = day.year;
+                target.day = day.day;
+            }
+        }
+
+        match timestamp::first_at_or_after(line, lines.len(), &target) {
+            Some(idx) => self.resume = Some(Position::Line(idx)),
+            None => self.set_message(format!("No line at or after {}", arg)),
+        }
+    }
+
+    fn start_recording(&mut self, register: char) {
+        if register.is_alphanumeric() {
+            self.recording = Some((register, Vec::new()));
+        } else {
+            self.set_message(format!("Not a register: {}", register));
+        }
+    }
+
+    // the keys of `register` as if typed again, `count` times; true to quit
+    fn replay(&mut self, register: char, count: usize) -> Result<bool> {
+        let register = match register {
+            '@' => match self.last_macro {
+                Some(register) => register,
+                None => {
+                    self.set_message("No macro replayed yet".into());
+                    return Ok(false);
+                }
+            },
+            register => register,
+        };
+        let keys = match self.macros.get(&register) {
+            Some(keys) => keys.clone(),
+            None => {
+                self.set_message(format!("Nothing recorded in @{}", register));
+                return Ok(false);
+            }
+        };
+        if self.replaying >= MAX_MACRO_DEPTH {
+            self.set_message(format!("Macros nested too deep in @{}", register));
+            return Ok(false);
+        }
+
+        self.last_macro = Some(register);
+        self.replaying += 1;
+        let mut quit = false;
+        'replay: for _ in 0..count {
+            for key in &keys {
+                // the rows are used by the next moves
+                if self.need_reflow {
+                    self.update()?;
+                }
+                if self.handle_event(Event::Key(*key))? {
+                    quit = true;
+                    break 'replay;
+                }
+            }
+        }
+        self.replaying -= 1;
+
+        Ok(quit)
+    }
+
+    fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+        self.prompt_outdated = true;
+    }
+
+    fn convert(&mut self, token: &str) {
+        match crate::convert::describe(token) {
+            Some(conversions) => self.set_message(conversions),
+            None => self.set_message(format!("Can't convert {}", token)),
+        }
+    }
+
+    fn convert_hint(&mut self) {
+        let token = self.hint.and_then(|(line, idx)| {
+            let text = self.lines.get(line)?.text();
+            crate::convert::token_at(&text, idx)
+        });
+
+        match token {
+            Some(token) => self.convert(&token),
+            None => self.set_message("No token under the hint".into()),
+        }
+    }
+
+    // search the word under the last click, the view staying where it is
+    fn search_clicked_word(&mut self) {
+        let word = self.hint.and_then(|(line, idx)| {
+            let text = self.lines.get(line)?.text();
+            word_at(&text, idx)
+        });
+        let word = match word {
+            Some(word) => word,
+            None => return,
+        };
+
+        let (scroll, hint) = (self.scroll, self.hint);
+        self.search(&word);
+        self.goto_scroll(scroll);
+        self.hint = hint;
+        self.prompt_outdated = true;
+    }
+
+    // the link nearest to the hint, or the first one of the cursor line
+    fn open_link(&mut self) {
+        let line = self.cursor_line();
+        let idx = match self.hint {
+            Some((hint_line, idx)) if hint_line == line => Some(idx),
+            _ => None,
+        };
+
+        match self.link_near(line, idx) {
+            Some(target) => self.open_target(target),
+            None => {
+                self.set_message("No link on the line".into());
+                self.ring_bell();
+            }
+        }
+    }
+
+    // the OSC 8 link at char `idx` of the line, or the url or file position nearest to it,
+    // else the first link
+    fn link_near(&self, line: usize, idx: Option<usize>) -> Option<Target> {
+        let parsed = self.lines.get(line)?.parse(&self.styles);
+        let first = || Some(Target::Url(parsed.links.first()?.url.clone()));
+
+        match idx {
+            Some(idx) => parsed
+                .link_at(idx)
+                .map(|url| Target::Url(url.to_owned()))
+                .or_else(|| links::nearest(&parsed.text, idx))
+                .or_else(first),
+            None => first().or_else(|| links::nearest(&parsed.text, 0)),
+        }
+    }
+
+    fn open_target(&mut self, target: Target) {
+        match target {
+            Target::Url(url) => {
+                let message = match crate::process::open(&url) {
+                    Ok(()) => format!("Opening {}", url),
+                    Err(e) => format!("Can't open {}: {}", url, e),
+                };
+                self.set_message(message);
+            }
+            Target::File { path, line } => {
+                self.run_editor(Path::new(&path), line);
+            }
+        }
+    }
+
+    /// `:!COMMAND` run on the terminal, `%` replaced by the name of the file quoted for the shell
+    /// and `\%` by a `%`, or the shell itself without a command
+    fn shell_escape(&mut self, command: &str) {
+        let command = match command {
+            "" => std::env::var("SHELL").unwrap_or_else(|_| "sh".into()),
+            _ if command.contains('%') && !self.reloadable => {
+                return self.set_message("Piped input has no name for %".into());
+            }
+            _ => expand_file_name(command, &self.input_name()),
+        };
+
+        match self.run_shell(&command) {
+            Ok(status) if status.success() => {}
+            Ok(status) => self.set_message(format!("{} exited with {}", command, status)),
+            Err(e) => self.set_message(format!("Can't run {}: {}", command, e)),
+        }
+    }
+
+    // `command` run on the terminal, waiting for a key after it
+    fn run_shell(&mut self, command: &str) -> Result<std::process::ExitStatus> {
+        // before the screen is left, a missing terminal keeps the view as it is
+        let (input, output, errors) = (get_input()?, get_output()?, get_output()?);
+        self.leave_screen();
+        disable_raw_mode()?;
+
+        let ret = crate::process::shell(command)
+            .stdin(input)
+            .stdout(output)
+            .stderr(errors)
+            .status();
+
+        self.output.write_all(b"\n[press any key to go back]")?;
+        self.output.flush()?;
+        enable_raw_mode()?;
+        while !matches!(read()?, Event::Key(_)) {}
+        // the terminal may have been resized meanwhile
+        let (x, y) = self.output.size()?;
+        self.size_ctx.resize(x as usize, y as usize);
+        self.need_reflow = true;
+        self.enter_screen()?;
+
+        ret
+    }
+
+    // the terminal is the editor's until it exits, true if it exited fine
+    fn run_editor(&mut self, path: &Path, line: usize) -> bool {
+        let status = match (get_input(), get_output()) {
+            (Ok(input), Ok(output)) => {
+                self.leave_screen();
+                disable_raw_mode().ok();
+
+                let status = crate::process::edit(path, line, input, output);
+
+                enable_raw_mode().ok();
+                if let Ok((x, y)) = self.output.size() {
+                    self.size_ctx.resize(x as usize, y as usize);
+                    self.need_reflow = true;
+                }
+                self.enter_screen().ok();
+                status
+            }
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        };
+
+        match status {
+            Ok(status) if !status.success() => {
+                self.set_message(format!("The editor exited with {}", status));
+                false
+            }
+            Ok(_) => true,
+            Err(e) => {
+                self.set_message(format!("Can't run the editor: {}", e));
+                false
+            }
+        }
+    }
+
+    /// (line, char) displayed at the screen position
+    fn position_at(&self, column: usize, row: usize) -> Option<(usize, usize)> {
+        let rows = &self.reflowed_lines[self.scroll..];
+        let terminal_column = self.size_ctx.terminal_column().max(1);
+        let margin = self.gutter_width() + self.frozen_width();
+        let column = column.checked_sub(self.size_ctx.left() + margin)?;
+        let row = row.checked_sub(self.size_ctx.top())?;
+        if column >= terminal_column {
+            return None;
+        }
+        let (_, margin) = self.size_ctx.calculate_real_size(rows);
+        // the blank rows above a short input
+        if row < margin {
+            return None;
+        }
+        let mut y = margin;
+
+        for r in rows {
+            let height = line_line_size(r.width as usize, terminal_column);
+
+            if row < y + height {
+                // column counted from the start of the row
+                let target = (row - y) * terminal_column + column;
+                let text = self.lines[r.line].text();
+                let mut x = 0;
+
+                for (i, c) in text
+                    .chars()
+                    .enumerate()
+                    .take(r.end as usize)
+                    .skip(r.start as usize)
+                {
+                    x += c.width().unwrap_or(0);
+                    if x > target {
+                        return Some((r.line, i));
+                    }
+                }
+
+                return None;
+            }
+
+            y += height;
+        }
+
+        None
+    }
+
+    // end of the position shown on the left of the prompt, back to normal attributes
+    fn push_prompt_status(&mut self) {
+        use std::fmt::Write;
+
+        if let Some(progress) = self.loading_progress() {
+            self.prompt.push(' ');
+            self.prompt.push_str(&progress);
+        } else if self.scroll == self.max_scroll() {
+            // a followed file is only at its end for now
+            if self.reader.waiting.load(Ordering::Relaxed) {
+                self.prompt.push_str(" …");
+            } else {
+                self.prompt.push_str(" (END)");
+            }
+        }
+
+        if self.paused {
+            write!(self.prompt, " PAUSED (+{} pending)", self.pending).ok();
+        }
+
+        if self.only_stderr {
+            self.prompt.push_str(" STDERR");
+        }
+
+        if let Some(lines) = self.selection() {
+            write!(self.prompt, " VISUAL ({} lines)", lines.count()).ok();
+        }
+
+        if let Some((register, _)) = self.recording {
+            write!(self.prompt, " recording @{}", register).ok();
+        }
+
+        if self.show_memory {
+            write!(self.prompt, " mem {}", convert::human_size(self.memory_used() as u64)).ok();
+            if let Some(max) = self.max_memory {
+                write!(self.prompt, "/{}", convert::human_size(max as u64)).ok();
+            }
+        }
+
+        if self.inputs.len() > 1 {
+            write!(
+                self.prompt,
+                " (file {} of {})",
+                self.input + 1,
+                self.inputs.len()
+            )
+            .ok();
+        }
+
+        write!(self.prompt, "{}", SetAttribute(Attribute::Reset),).ok();
+    }
+
+    // the status on its own, like `(END)`, without the attributes around it
+    fn prompt_status(&mut self) -> String {
+        let prompt = std::mem::take(&mut self.prompt);
+        self.push_prompt_status();
+        let status = std::mem::replace(&mut self.prompt, prompt);
+        status
+            .trim_end_matches(&SetAttribute(Attribute::Reset).to_string())
+            .trim()
+            .to_string()
+    }
+
+    // (last row in view, rows), the header ones included
+    fn view_bottom(&self) -> (usize, usize) {
+        // the header lines come first, above the scrolled ones
+        let header = self.size_ctx.header;
+        let rows = header + self.reflowed_lines.len();
+        let bottom =
+            (header + self.scroll + self.size_ctx.terminal_line() - self.prev_wrap).min(rows);
+        (bottom, rows)
+    }
+
+    // the name of the input, `stdin` for the piped one
+    fn input_name(&self) -> String {
+        match self.inputs.get(self.input) {
+            Some(info) if info.name != "-" => info.name.clone(),
+            _ => "stdin".to_string(),
+        }
+    }
+
+    // the input name, filters and search on the left, the position and status on the right,
+    // filling the width; what doesn't fit is left out, the search first and the name last
+    fn status_bar(&mut self) -> String {
+        let header = self.size_ctx.header;
+        let (bottom, rows) = self.view_bottom();
+        let mut right = format!("lines {}-{}/{}", header + self.scroll + 1, bottom, rows);
+        if let Some(percent) = (bottom * 100).checked_div(rows) {
+            right.push_str(&format!(" {}%", percent));
+        }
+        let status = self.prompt_status();
+        if !status.is_empty() {
+            right.push(' ');
+            right.push_str(&status);
+        }
+
+        let name = self.input_name();
+        // a path shortened to its file name
+        let short = Path::new(&name)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .filter(|n| *n != name);
+        let filters = [
+            (self.dedup, "dedup"),
+            (self.reverse, "reverse"),
+            (self.squeeze, "squeeze"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+        let filters = Some(filters.join(" "))
+            .filter(|f| !f.is_empty())
+            .map(|f| format!("[{}]", f));
+        let search = Some(&self.needle)
+            .filter(|n| !n.is_empty())
+            .map(|n| format!("/{}", n));
+
+        let join = |parts: &[Option<&String>]| {
+            parts
+                .iter()
+                .flatten()
+                .map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let name = Some(&name);
+        let lefts = [
+            join(&[name, filters.as_ref(), search.as_ref()]),
+            join(&[name, filters.as_ref()]),
+            join(&[name]),
+            join(&[short.as_ref().or(name)]),
+        ];
+
+        let width = self.size_ctx.terminal_column();
+        let used = text_width(&right);
+        for left in lefts.iter() {
+            let left_width = text_width(left);
+            if left_width + 2 + used <= width {
+                let gap = width - left_width - used;
+                return format!("{}{:gap$}{}", left, "", right, gap = gap);
+            }
+        }
+        truncate_to_width(&right, width).to_string()
+    }
+
+    fn update_prompt(&mut self) {
+        if self.prompt_outdated {
+            use std::fmt::Write;
+            self.prompt.clear();
+            let paint = self.paint(self.ui().prompt);
+            let style = paint.start();
+
+            match self.prompt_state {
+                PromptState::Normal if self.follow => {
+                    write!(
+                        self.prompt,
+                        "{}Waiting for data… (interrupt to abort){}",
+                        style,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Normal if self.message.is_some() => {
+                    write!(
+                        self.prompt,
+                        "{}{}{}",
+                        style,
+                        self.message.as_deref().unwrap_or_default(),
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Normal if self.reader.hex.load(Ordering::Relaxed) => {
+                    write!(
+                        self.prompt,
+                        "{}offset {:08x}/{:08x}",
+                        style,
+                        self.top_line() * HEX_ROW,
+                        self.reader.read.load(Ordering::Relaxed),
+                    )
+                    .ok();
+                    self.push_prompt_status();
+                }
+                PromptState::Normal if self.prompt_format.is_some() => {
+                    let format = self.prompt_format.clone().unwrap_or_default();
+                    let text = self.expand_prompt(&format);
+                    write!(
+                        self.prompt,
+                        "{}{}{}",
+                        style,
+                        text,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Normal => {
+                    let bar = self.status_bar();
+                    write!(
+                        self.prompt,
+                        "{}{}{}",
+                        style,
+                        bar,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Number(n) => {
+                    write!(self.prompt, ":{}", n).ok();
+                }
+                PromptState::Keys(ref keys, _) => {
+                    for key in keys {
+                        match key.code {
+                            KeyCode::Char(ch) => self.prompt.push(ch),
+                            code => write!(self.prompt, "<{:?}>", code).unwrap_or_default(),
+                        }
+                    }
+                }
+                PromptState::Toggle => self.prompt.push('-'),
+                PromptState::Register(KeyBehavior::Record, _) => self.prompt.push('q'),
+                PromptState::Register(_, _) => self.prompt.push('@'),
+                PromptState::Search(ref s) | PromptState::Command(ref s) => {
+                    let lead = match self.prompt_state {
+                        PromptState::Search(_) => '/',
+                        _ => ':',
+                    };
+                    write!(self.prompt, "{}{}", style, lead).ok();
+                    push_edited(&mut self.prompt, s, self.prompt_cursor, paint);
+                    write!(self.prompt, "{}", SetAttribute(Attribute::Reset)).ok();
+                }
+                PromptState::Palette(ref query, _) => {
+                    write!(
+                        self.prompt,
+                        "{}> {}{}",
+                        style,
+                        query,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Help(_) => {
+                    write!(
+                        self.prompt,
+                        "{}HELP  j/k, space/b: scroll, any other key: close{}",
+                        style,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Output(_) => {
+                    write!(
+                        self.prompt,
+                        "{}| {}  j/k, space/b: scroll, any other key: close{}",
+                        style,
+                        self.pipe_output.0,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Buffers(_) => {
+                    write!(
+                        self.prompt,
+                        "{}j/k: select, Enter or 1-9: open, Esc: close{}",
+                        style,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Matches(selected) => {
+                    write!(
+                        self.prompt,
+                        "{}{}/{} lines matching {}  j/k: select, Enter: jump, Esc: close{}",
+                        style,
+                        selected + 1,
+                        self.panel_lines.len(),
+                        self.needle,
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Outline(selected) => {
+                    write!(
+                        self.prompt,
+                        "{}{}/{} sections  j/k: select, Enter: jump, Esc: close{}",
+                        style,
+                        selected + 1,
+                        self.panel_lines.len(),
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Bookmarks(selected) => {
+                    write!(
+                        self.prompt,
+                        "{}{}/{} bookmarks  j/k: select, Enter: jump, d: delete, Esc: close{}",
+                        style,
+                        selected + 1,
+                        self.panel_lines.len(),
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+                PromptState::Setup(step) => {
+                    write!(
+                        self.prompt,
+                        "{}{} [Y/n] (Esc: defaults){}",
+                        style,
+                        SETUP_QUESTIONS[step],
+                        SetAttribute(Attribute::Reset),
+                    )
+                    .ok();
+                }
+            }
+
+            self.prompt_outdated = false;
+        }
+    }
+
+    // lines are still coming while the reader runs or some are left in the queue,
+    // a followed file that was read to its end is done for now
+    fn is_loading(&self) -> bool {
+        let reading = !self.reader.done.load(Ordering::Acquire)
+            && !self.reader.waiting.load(Ordering::Relaxed);
+        reading || !self.rx.is_empty()
+    }
+
+    // lines a `LineSource` is asked for: a few pages past the view, all of them to be at the
+    // end, to show them backwards or to follow them
+    fn wanted_lines(&self) -> usize {
+        let at_end = self.pinned || matches!(self.resume, Some(Position::End));
+        match at_end || self.follow || self.reverse {
+            true => usize::MAX,
+            false => self.top_line() + 3 * self.size_ctx.terminal_line().max(1),
+        }
+    }
+
+    fn loading_progress(&self) -> Option<String> {
+        if !self.loading {
+            return None;
+        }
+
+        let read = self.reader.read.load(Ordering::Relaxed);
+        let progress = match self.reader.size.load(Ordering::Relaxed) {
+            0 => SPINNER[(read >> 16) as usize % SPINNER.len()].to_string(),
+            size => format!("{}%", read.min(size) * 100 / size),
+        };
+
+        Some(format!(
+            "loading… {} ({}, {}/s, {} lines/s)",
+            progress,
+            convert::human_size(read),
+            convert::human_size(self.rate as u64),
+            self.line_rate,
+        ))
+    }
+
+    fn sample_rate(&mut self) {
+        let (time, prev_read, prev_lines) = self.rate_sample;
+        let elapsed = time.elapsed();
+        if elapsed < RATE_INTERVAL {
+            return;
+        }
+
+        // the counter starts over on reload
+        let read = self.reader.read.load(Ordering::Relaxed);
+        let lines = self.lines.len();
+        let secs = elapsed.as_secs_f64();
+        self.rate = (read.saturating_sub(prev_read) as f64 / secs) as usize;
+        self.line_rate = ((lines - prev_lines) as f64 / secs) as usize;
+        self.rate_sample = (Instant::now(), read, lines);
+        if self.loading {
+            self.prompt_outdated = true;
+        }
+    }
+
+    fn goto_scroll(&mut self, idx: usize) {
+        let max_scroll = self.max_scroll();
+        let new_scroll = idx.min(max_scroll);
+        // in reverse mode the top already shows new lines
+        // the end of the lines fetched so far from a source isn't the end, unless sent there
+        let end = idx == usize::MAX || !self.reader.lazy.load(Ordering::Relaxed);
+        // while all the lines fit, a move down or to the end pins it too, not one to the top
+        let down = max_scroll > 0 || idx > 0;
+        self.pinned = !self.reverse && down && new_scroll == max_scroll && end;
+        if new_scroll != self.scroll {
+            self.scroll = new_scroll;
+            self.need_redraw = true;
+            self.prompt_outdated = true;
+
+            if self.loading && new_scroll == max_scroll {
+                self.emit(PagerEvent::ReachedEnd);
+            }
+        }
+    }
+
+    fn scroll_down(&mut self, idx: usize) {
+        self.goto_scroll(self.scroll.saturating_add(idx));
+    }
+
+    fn scroll_up(&mut self, idx: usize) {
+        self.goto_scroll(self.scroll.saturating_sub(idx));
+    }
+
+    fn move_search(&mut self, forward: bool) {
+        if self.reflowed_search_positions.is_empty() {
+            return self.ring_bell();
+        }
+
+        let next = self.reflowed_search_positions[self.scroll..]
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, p)| (i + self.scroll, p));
+
+        let prev = self.reflowed_search_positions[0..self.scroll].iter().enumerate();
+
+        let line = if forward {
+            next.chain(prev)
+                .find_map(|(line, p)| if !p.is_empty() { Some(line) } else { None })
+        } else {
+            prev.rev()
+                .chain(next.rev())
+                .find_map(|(line, p)| if !p.is_empty() { Some(line) } else { None })
+        };
+
+        if let Some(line) = line {
+            let row = self.reflowed_lines[line];
+            self.hint = self.reflowed_search_positions[line]
+                .first()
+                .map(|p| (row.line, (row.start + p.start) as usize));
+            self.goto_scroll(line);
+        } else {
+            self.ring_bell();
+        }
+    }
+
+    fn search(&mut self, needle: &str) {
+        self.search_pending = false;
+        let hex = match self.reader.hex.load(Ordering::Relaxed) {
+            true => hex_needle(needle),
+            false => None,
+        };
+        let needle = hex.as_deref().unwrap_or(needle);
+        if !self.search_positions.is_empty() {
+            self.need_redraw = true;
+            self.search_positions.clear();
+            self.reflowed_search_positions.clear();
+        }
+
+        self.search_char_len = needle.chars().count();
+        self.needle.clear();
+
+        if self.search_char_len == 0 {
+            return;
+        }
+
+        #[cfg(feature = "logging")]
+        log::debug!("Search: {:?}", needle);
+
+        self.needle.push_str(needle);
+        self.need_redraw = true;
+        self.remember_search(needle);
+
+        self.find_all();
+
+        let matches = self.search_positions.iter().filter(|p| !p.is_empty()).count();
+        self.emit(PagerEvent::Search { needle, matches });
+
+        // a match on the top row is already in view
+        match self.reflowed_search_positions.get(self.scroll) {
+            Some(positions) if !positions.is_empty() => {
+                let row = self.reflowed_lines[self.scroll];
+                self.hint = Some((row.line, (row.start + positions[0].start) as usize));
+            }
+            _ => self.move_search(true),
+        }
+    }
+
+    // matches of the needle in every line, again after the case was ignored or not
+    fn find_all(&mut self) {
+        let (needle, ignore_case) = (&self.needle, self.ignore_case);
         self.lines
             .par_iter()
-            .map(|chars| {
-                let mut arr = SearchPositionArr::new();
-
-                for i in 0..chars.len() {
-                    if chars[i..]
-                        .iter()
-                        .take(char_count)
-                        .map(|c| c.ch)
-                        .eq(needle.chars())
-                    {
-                        arr.push(SearchPosition { start: i as u32 });
+            .map(|line| find_matches(&line.text(), needle, ignore_case))
+            .collect_into_vec(&mut self.search_positions);
+
+        self.reflow_search();
+    }
+
+    // convert self.search_positions' indexes to match reflowed lines
+    fn reflow_search(&mut self) {
+        // clear, then initialize with same length as reflowed lines'
+        self.reflowed_search_positions.clear();
+        self.reflowed_search_positions
+            .resize(self.reflowed_lines.len(), SmallVec::new());
+
+        for index in 0..self.search_positions.len() {
+            self.reflow_line_search(index);
+        }
+    }
+
+    fn reflow_line_search(&mut self, index: usize) {
+        // get reflowed lines' indexes (original_line_index => [reflowed_line_index1, reflowed_line_index2, ...])
+        let linked_reflowed_lines = match self.reflowed_lines_associations.get(index) {
+            Some(linked) => linked.clone(),
+            None => return,
+        };
+        let search_positions = match self.search_positions.get(index) {
+            Some(positions) => positions,
+            None => return,
+        };
+        let width = self.row_width();
+        // after the frozen part and what's panned out of view when not wrapping
+        let first = match self.reflowed_lines.get(linked_reflowed_lines.start) {
+            Some(row) if !linked_reflowed_lines.is_empty() => row.start,
+            _ => 0,
+        };
+
+        for reflowed in linked_reflowed_lines.clone() {
+            self.reflowed_search_positions[reflowed].clear();
+        }
+
+        // push positions into their new reflowed line indexes
+        for position in search_positions {
+            let start = match position.start.checked_sub(first) {
+                Some(start) => start as usize,
+                None => continue,
+            };
+            let cut_index = start / width;
+            let index_in_cut = start % width;
+            if cut_index < linked_reflowed_lines.len() {
+                let reflowed = linked_reflowed_lines.start + cut_index;
+                self.reflowed_search_positions[reflowed].push(SearchPosition {
+                    start: index_in_cut as u32,
+                });
+            }
+        }
+    }
+
+    /// a key, a click or a resize of the terminal, true once the pager quits
+    pub fn handle_event(&mut self, mut event: Event) -> Result<bool> {
+        if let (Event::Key(ke), true) = (&event, self.prompt_state.is_keymap()) {
+            match self.filter_key(*ke) {
+                KeyAction::Replace(ke) => event = Event::Key(ke),
+                KeyAction::Command(command) => {
+                    self.prompt_outdated = true;
+                    return Ok(self.run_command(&command) || self.reload);
+                }
+                _ => return Ok(false),
+            }
+        }
+
+        match event {
+            // Shift+wheel pans the lines left and right when they aren't wrapped, like the
+            // horizontal wheel of the terminals sending it that way
+            Event::Mouse(MouseEvent {
+                kind: kind @ (MouseEventKind::ScrollUp | MouseEventKind::ScrollDown),
+                modifiers: KeyModifiers::SHIFT,
+                ..
+            }) if self.prompt_state == PromptState::Normal && !self.wrap => {
+                self.pan(kind == MouseEventKind::ScrollDown);
+            }
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                modifiers,
+                ..
+            }) if self.prompt_state == PromptState::Normal => {
+                self.set_follow(false);
+                self.scroll_up(self.wheel_step(modifiers));
+            }
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                modifiers,
+                ..
+            }) if self.prompt_state == PromptState::Normal => {
+                self.set_follow(false);
+                self.scroll_down(self.wheel_step(modifiers));
+            }
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) if self.prompt_state == PromptState::Normal && self.on_bar(column as usize) => {
+                self.dragging_bar = true;
+                self.set_follow(false);
+                self.jump_to_bar_row(row as usize);
+            }
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Drag(MouseButton::Left),
+                row,
+                ..
+            }) if self.dragging_bar && self.prompt_state == PromptState::Normal => {
+                self.jump_to_bar_row(row as usize)
+            }
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Up(MouseButton::Left),
+                ..
+            }) => self.dragging_bar = false,
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::CONTROL,
+            }) if self.prompt_state == PromptState::Normal => {
+                let position = self.position_at(column as usize, row as usize);
+                let target = position.and_then(|(line, idx)| self.link_near(line, Some(idx)));
+                if let Some(target) = target {
+                    self.open_target(target);
+                }
+            }
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) => {
+                self.hint = self.position_at(column as usize, row as usize);
+                if let (Some(_), Some((line, _))) = (self.line_cursor, self.hint) {
+                    self.line_cursor = Some(line);
+                    self.need_redraw = true;
+                }
+
+                let now = Instant::now();
+                let double = match self.last_click {
+                    Some((time, x, y)) => (x, y) == (column, row) && now - time < DOUBLE_CLICK,
+                    None => false,
+                };
+                // a third click starts over
+                self.last_click = Some((now, column, row)).filter(|_| !double);
+                if double && self.prompt_state == PromptState::Normal {
+                    self.search_clicked_word();
+                }
+            }
+            Event::Key(ke) => {
+                // the keys of a replayed macro are already in the one that replayed it
+                if let (Some((_, keys)), 0) = (&mut self.recording, self.replaying) {
+                    keys.push(ke);
+                }
+
+                if self.message.take().is_some() {
+                    self.prompt_outdated = true;
+                }
+
+                // any key only interrupts following
+                if self.follow {
+                    self.set_follow(false);
+                    return Ok(false);
+                }
+
+                if let PromptState::Buffers(selected) = self.prompt_state {
+                    return Ok(self.handle_buffers_key(selected, ke));
+                }
+
+                if let PromptState::Matches(selected)
+                | PromptState::Outline(selected)
+                | PromptState::Bookmarks(selected) = self.prompt_state
+                {
+                    self.handle_panel_key(selected, ke);
+                    return Ok(false);
+                }
+
+                if let PromptState::Help(first) = self.prompt_state {
+                    self.handle_help_key(first, ke);
+                    return Ok(false);
+                }
+
+                if let PromptState::Output(first) = self.prompt_state {
+                    self.handle_output_key(first, ke);
+                    return Ok(false);
+                }
+
+                if let PromptState::Palette(query, selected) = &self.prompt_state {
+                    let (query, selected) = (query.clone(), *selected);
+                    return self.handle_palette_key(query, selected, ke);
+                }
+
+                if self.prompt_state == PromptState::Toggle {
+                    self.prompt_state = PromptState::Normal;
+                    self.prompt_outdated = true;
+                    // Esc or any other key cancels
+                    if let KeyCode::Char(ch) = ke.code {
+                        self.toggle_option(ch);
                     }
+                    return Ok(false);
                 }
 
-                arr
-            })
-            .collect_into_vec(&mut self.search_positions);
-        
-        // remove duplicate matches
-        for search_positions in &mut self.search_positions {
-            let mut indexes_to_remove = vec!{} as Vec<usize>;
-            let mut previous_start = None as Option<u32>;
-            for (index, search_position) in search_positions.iter().enumerate() {
-                match previous_start {
-                    Some(start) => {
-                        // if the start of this match is still part of previous match, set to remove
-                        if (start + self.search_char_len as u32) > search_position.start {
-                            indexes_to_remove.push(index);
-                        } else {                        
-                            previous_start = Some(search_position.start);
+                if let PromptState::Register(b, count) = self.prompt_state {
+                    self.prompt_state = PromptState::Normal;
+                    self.prompt_outdated = true;
+                    return match ke.code {
+                        KeyCode::Char(ch) if b == KeyBehavior::Record => {
+                            self.start_recording(ch);
+                            Ok(false)
+                        }
+                        KeyCode::Char(ch) => self.replay(ch, count),
+                        // Esc or any other key cancels
+                        _ => Ok(false),
+                    };
+                }
+
+                if let PromptState::Setup(step) = self.prompt_state {
+                    let answer = match ke.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => Some(Some(true)),
+                        KeyCode::Char('n') | KeyCode::Char('N') => Some(Some(false)),
+                        KeyCode::Enter => Some(Some(true)),
+                        // keep the defaults for the remaining questions
+                        KeyCode::Esc => Some(None),
+                        // the other keys would replace or quit the questions
+                        _ => None,
+                    };
+
+                    if let Some(answer) = answer {
+                        self.answer_setup(step, answer)?;
+                    }
+                    return Ok(false);
+                }
+
+                if let PromptState::Search(ref mut s) | PromptState::Command(ref mut s) =
+                    self.prompt_state
+                {
+                    if ke.code == KeyCode::Backspace && s.is_empty() {
+                        self.prompt_state = PromptState::Normal;
+                        self.prompt_outdated = true;
+                        return Ok(false);
+                    }
+                    if edit_line(s, &mut self.prompt_cursor, ke) {
+                        self.prompt_outdated = true;
+                        return Ok(false);
+                    }
+
+                    if !ke
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                    {
+                        match ke.code {
+                            KeyCode::Up | KeyCode::Down
+                                if matches!(self.prompt_state, PromptState::Search(_)) =>
+                            {
+                                self.recall_search(ke.code == KeyCode::Up);
+                                return Ok(false);
+                            }
+                            KeyCode::Enter => {
+                                let quit = match self.prompt_state.take() {
+                                    PromptState::Search(needle) => {
+                                        self.search(&needle);
+                                        false
+                                    }
+                                    PromptState::Command(command) => self.run_command(&command),
+                                    _ => false,
+                                };
+                                self.prompt_outdated = true;
+                                return Ok(quit || self.reload);
+                            }
+                            _ => {}
                         }
-                    },
-                    None => {
-                        previous_start = Some(search_position.start);
-                        continue
                     }
                 }
+
+                if self.visual.is_some()
+                    && self.prompt_state == PromptState::Normal
+                    && self.handle_visual_key(ke)
+                {
+                    return Ok(false);
+                }
+
+                return self.handle_keys(ke);
+            }
+            Event::Resize(x, y) => {
+                self.size_ctx.resize(x as usize, y as usize);
+                self.need_reflow = true;
+                self.need_redraw = true;
+                self.prompt_outdated = true;
             }
+            _ => {}
+        };
+
+        Ok(false)
+    }
 
-            // remove index from behind because removing items reindexes the vec
-            for index in indexes_to_remove.iter().rev() {
-                search_positions.remove(*index);
+    // run the action of the key or of the keys before it, or wait for the next key of a sequence
+    fn handle_keys(&mut self, ke: KeyEvent) -> Result<bool> {
+        let mut keys = match self.prompt_state.take() {
+            PromptState::Keys(keys, _) => keys,
+            // a count is still waiting for its action
+            state => {
+                self.prompt_state = state;
+                Vec::new()
             }
+        };
+        keys.push(key_of_sequence(ke));
+
+        if let Some(b) = self.sequences.get(&keys).copied() {
+            self.prompt_outdated = true;
+            return self.act(b);
+        }
+        let longer = |seq: &Vec<KeyEvent>| seq.len() > keys.len() && seq.starts_with(&keys);
+        if self.sequences.keys().any(longer) {
+            self.prompt_state = PromptState::Keys(keys, Instant::now());
+            self.prompt_outdated = true;
+            return Ok(false);
         }
 
-        self.reflow_search();
+        // the key doesn't go on with the sequence, the one before it may do something alone
+        if keys.len() > 1 {
+            self.prompt_outdated = true;
+            if self.flush_keys(&keys[..keys.len() - 1])? {
+                return Ok(true);
+            }
+        }
 
-        self.move_search(true);
+        match self.keymap.get(&ke).copied() {
+            Some(b) => self.act(b),
+            None if keys.len() > 1 => {
+                self.ring_bell();
+                Ok(false)
+            }
+            None => {
+                self.emit(PagerEvent::UnhandledKey(ke));
+                Ok(false)
+            }
+        }
     }
 
-    // convert self.search_positions' indexes to match reflowed lines
-    fn reflow_search(&mut self) {
-        // clear, then initialize with same length as reflowed lines'
-        self.reflowed_search_positions.clear();
-        self.reflowed_search_positions.resize(self.reflowed_lines.len(), SmallVec::new());
-        
-        // iterate current self.search_positions
-        for (index, search_positions) in self.search_positions.iter().enumerate() {
-            // get reflowed lines' indexes (original_line_index => [reflowed_line_index1, reflowed_line_index2, ...])
-            let linked_reflowed_lines = self.reflowed_lines_associations.get(index);
-            if linked_reflowed_lines.is_none() { continue }
-            let linked_reflowed_lines = linked_reflowed_lines.unwrap();
+    // the action of a key typed alone that also starts a sequence
+    fn flush_keys(&mut self, keys: &[KeyEvent]) -> Result<bool> {
+        let single = match keys {
+            [key] => self
+                .keymap
+                .iter()
+                .find(|(k, _)| key_of_sequence(**k) == *key),
+            _ => None,
+        };
+        match single.map(|(_, b)| *b) {
+            Some(b) => self.act(b),
+            None => Ok(false),
+        }
+    }
+
+    /// run the keys of a sequence typed so far once the next one took too long, true to quit.
+    /// [`Self::run`] does it between the events, an embedder handling them does it too
+    pub fn expire_keys(&mut self) -> Result<bool> {
+        match &self.prompt_state {
+            PromptState::Keys(_, since) if since.elapsed() >= self.key_timeout => {}
+            _ => return Ok(false),
+        }
+
+        self.prompt_outdated = true;
+        match self.prompt_state.take() {
+            PromptState::Keys(keys, _) => self.flush_keys(&keys),
+            _ => Ok(false),
+        }
+    }
+
+    // true to quit
+    fn act(&mut self, b: KeyBehavior) -> Result<bool> {
+        let b = match b {
+            KeyBehavior::Left if self.wrap => KeyBehavior::Up(ScrollSize::HalfPage),
+            KeyBehavior::Right if self.wrap => KeyBehavior::Down(ScrollSize::HalfPage),
+            b => b,
+        };
+        match b {
+            KeyBehavior::NormalMode => {
+                self.prompt_state.take();
+                self.search("");
+                self.prompt_outdated = true;
+            }
+            KeyBehavior::Search => {
+                self.history_index = None;
+                self.prompt_state = PromptState::Search(String::new());
+                self.prompt_cursor = 0;
+                self.prompt_outdated = true;
+            }
+            KeyBehavior::Command => {
+                self.prompt_state = PromptState::Command(String::new());
+                self.prompt_cursor = 0;
+                self.prompt_outdated = true;
+            }
+            KeyBehavior::Convert => {
+                self.convert_hint();
+            }
+            KeyBehavior::Dedup => {
+                self.set_dedup(!self.dedup);
+            }
+            KeyBehavior::StderrOnly => {
+                self.set_only_stderr(!self.only_stderr);
+            }
+            KeyBehavior::Follow => {
+                self.prompt_state.take();
+                self.set_follow(true);
+            }
+            KeyBehavior::Buffers => {
+                self.show_buffers();
+            }
+            KeyBehavior::Help => {
+                self.show_help();
+            }
+            KeyBehavior::Palette => {
+                self.show_palette();
+            }
+            KeyBehavior::Record => match self.recording.take() {
+                Some((register, mut keys)) => {
+                    // the key that stopped it
+                    keys.pop();
+                    self.set_message(format!("Recorded @{} ({} keys)", register, keys.len()));
+                    self.macros.insert(register, keys);
+                }
+                None => {
+                    self.prompt_state = PromptState::Register(b, 1);
+                    self.prompt_outdated = true;
+                }
+            },
+            KeyBehavior::Replay => {
+                let count = match self.prompt_state.take() {
+                    PromptState::Number(n) => n.max(1),
+                    _ => 1,
+                };
+                self.prompt_state = PromptState::Register(b, count);
+                self.prompt_outdated = true;
+            }
+            KeyBehavior::Pause => {
+                self.paused = !self.paused;
+                self.prompt_outdated = true;
+            }
+            KeyBehavior::Reload | KeyBehavior::Hex if !self.reloadable => {
+                self.set_message("Can't reload piped input".into());
+            }
+            KeyBehavior::Reload => {
+                self.reload = true;
+                return Ok(true);
+            }
+            KeyBehavior::Hex => {
+                self.toggle_hex = true;
+                self.reload = true;
+                return Ok(true);
+            }
+            // a pane of another program has no terminal to give them
+            KeyBehavior::Save
+            | KeyBehavior::Pipe
+            | KeyBehavior::Shell
+            | KeyBehavior::Edit
+            | KeyBehavior::Suspend
+                if !self.output.is_terminal() =>
+            {
+                self.set_message("Not on a terminal".into());
+            }
+            KeyBehavior::Save => self.command_prompt("write "),
+            KeyBehavior::Pipe => self.command_prompt("pipe "),
+            KeyBehavior::Shell => self.command_prompt("!"),
+            KeyBehavior::Matches => self.show_matches(),
+            KeyBehavior::Outline => self.show_outline(),
+            KeyBehavior::Bookmark => self.command_prompt("bookmark "),
+            KeyBehavior::Bookmarks => self.show_bookmarks(),
+            KeyBehavior::Edit if !self.reloadable => {
+                self.set_message("Can't edit piped input".into());
+                self.ring_bell();
+            }
+            KeyBehavior::Edit => {
+                let name = self.input_name();
+                // the file likely changed, unless the editor failed and the reload
+                // would lose its message
+                if self.run_editor(Path::new(&name), self.cursor_line() + 1) {
+                    self.reload = true;
+                    return Ok(true);
+                }
+            }
+            KeyBehavior::SearchNext => {
+                self.move_search(true);
+            }
+            KeyBehavior::SearchPrev => {
+                self.move_search(false);
+            }
+            KeyBehavior::Left => {
+                self.pan(false);
+            }
+            KeyBehavior::Right => {
+                self.pan(true);
+            }
+            KeyBehavior::SectionNext => {
+                self.goto_section(true);
+            }
+            KeyBehavior::SectionPrev => {
+                self.goto_section(false);
+            }
+            KeyBehavior::Number(n) => match self.prompt_state {
+                PromptState::Number(ref mut pn) => {
+                    *pn = *pn * 10 + (n as usize);
+                    self.prompt_outdated = true;
+                }
+                _ => {
+                    self.prompt_state = PromptState::Number(n as usize);
+                    self.prompt_outdated = true;
+                }
+            },
+            KeyBehavior::Up(size) => {
+                let n = match self.prompt_state.take() {
+                    PromptState::Number(n) => n,
+                    _ => self.held_step(size, false),
+                };
+                if size == ScrollSize::One && self.line_cursor.is_some() {
+                    self.move_line_cursor(n, false);
+                    return Ok(false);
+                }
+                let size = size.calculate(self.size_ctx.terminal_line());
+                if self.scroll == 0 {
+                    self.ring_bell();
+                }
+                self.scroll_up(size.wrapping_mul(n));
+            }
+            KeyBehavior::Down(size) => {
+                let n = match self.prompt_state.take() {
+                    PromptState::Number(n) => n,
+                    _ => self.held_step(size, true),
+                };
+                if size == ScrollSize::One && self.line_cursor.is_some() {
+                    self.move_line_cursor(n, true);
+                    return Ok(false);
+                }
+                let size = size.calculate(self.size_ctx.terminal_line());
+                // the end isn't final while the input is still coming
+                if self.scroll >= self.max_scroll() && !self.loading {
+                    self.ring_bell();
+                }
+                self.scroll_down(size.wrapping_mul(n));
+                if self.count_end() {
+                    self.emit(PagerEvent::Quit);
+                    return Ok(true);
+                }
+            }
+            KeyBehavior::Quit => {
+                self.emit(PagerEvent::Quit);
+                return Ok(true);
+            }
+            KeyBehavior::HunkNext => self.goto_diff(true, false),
+            KeyBehavior::HunkPrev => self.goto_diff(false, false),
+            KeyBehavior::FileNext => self.goto_diff(true, true),
+            KeyBehavior::FilePrev => self.goto_diff(false, true),
+            KeyBehavior::Fold(action) => self.fold(action),
+            KeyBehavior::LineTop => self.place_cursor_line(0),
+            KeyBehavior::LineCenter => self.place_cursor_line(self.size_ctx.terminal_line() / 2),
+            KeyBehavior::LineBottom => {
+                self.place_cursor_line(self.size_ctx.terminal_line().saturating_sub(1))
+            }
+            KeyBehavior::Toggle => {
+                self.prompt_state = PromptState::Toggle;
+                self.prompt_outdated = true;
+            }
+            KeyBehavior::CursorLine => self.set_line_cursor(self.line_cursor.is_none()),
+            KeyBehavior::Visual if self.visual.is_some() => {
+                self.take_selection();
+            }
+            KeyBehavior::Visual => self.start_visual(),
+            KeyBehavior::Suspend => self.suspend()?,
+            KeyBehavior::OpenLink => self.open_link(),
+            KeyBehavior::Mouse => match self.set_mouse(!self.mouse) {
+                Ok(()) if self.mouse => self.set_message("Mouse captured".into()),
+                Ok(()) => self.set_message("Mouse released, the terminal selects text".into()),
+                Err(e) => self.set_message(format!("Can't set the mouse capture: {}", e)),
+            },
+            KeyBehavior::DumpQuit => {
+                self.dump.get_or_insert(Dump::Styled);
+                self.emit(PagerEvent::Quit);
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// with `true`, an input that fits in the view once fully read is printed
+    /// to stdout and [`UiContext::run`] returns right away
+    pub fn quit_if_one_screen(mut self, quit: bool) -> Self {
+        self.quit_if_one_screen = quit;
+        self
+    }
+
+    /// write the input on quit, the `O` key quits with [`Dump::Styled`] if not set
+    pub fn dump_on_quit(mut self, dump: Option<Dump>) -> Self {
+        self.dump = dump;
+        self
+    }
+
+    /// the way the input should be written after the last quit, if any
+    pub fn dump_mode(&self) -> Option<Dump> {
+        self.dump
+    }
+
+    /// leave the screen and write every line received, in input order
+    pub fn dump(&mut self, out: &mut impl Write, dump: Dump) -> Result<()> {
+        self.leave_screen();
+        // stdout may be the terminal too
+        disable_raw_mode()?;
 
-            // take note of positions and their new reflowed line indexes
-            let mut new_positions = vec!{} as Vec<Vec<usize>>;
-            new_positions.resize(linked_reflowed_lines.len(), vec!{});
-            for position in search_positions {
-                let cut_index = position.start as usize / (self.size_ctx.terminal_column() - 1);
-                let index_in_cut = position.start as usize % (self.size_ctx.terminal_column() - 1);
-                new_positions[cut_index].push(index_in_cut);
+        for line in self.lines.iter() {
+            match dump {
+                Dump::Styled => out.write_all(&line.bytes())?,
+                Dump::Plain => out.write_all(line.text().as_bytes())?,
             }
+            out.write_all(b"\n")?;
+        }
+
+        out.flush()
+    }
+
+    /// quit once forward scrolls reached the end of the fully read input `times` times,
+    /// like `-E` with 1 and `-e` with 2 in less
+    pub fn quit_at_end(mut self, times: usize) -> Self {
+        self.quit_at_end = times;
+        self
+    }
+
+    /// show `format` in the prompt instead of the line range, with the specs of
+    /// [`prompt::expand`] standing for:
+    ///
+    /// - `%f` the file name
+    /// - `%lt`, `%lb` the top and bottom line, `%L` the line count
+    /// - `%pt`, `%pb` how far the top and bottom line are in percent, `%p` being `%pt`
+    /// - `%bt` the byte offset of the top line, `%b` too, `%B` the bytes read
+    /// - `%s` the usual status, like `(END)`
+    pub fn prompt_format(mut self, format: Option<String>) -> Self {
+        self.prompt_format = format.filter(|f| !f.is_empty());
+        self
+    }
+
+    // the prompt template filled in, see `prompt_format`
+    fn expand_prompt(&mut self, format: &str) -> String {
+        let top = self.top_line();
+        let bottom_row = (self.scroll + self.size_ctx.terminal_line() - self.prev_wrap)
+            .min(self.reflowed_lines.len())
+            .saturating_sub(1);
+        let bottom = self
+            .reflowed_lines
+            .get(bottom_row)
+            .map_or(top, |row| row.line);
+        let len = self.lines.len();
+        let percent = |line: usize| (line + 1) * 100 / len.max(1);
+
+        let status = self.prompt_status();
+
+        let name = self.inputs.get(self.input).map(|info| info.name.as_str());
+        let lines = &self.lines;
+        prompt::expand(format, |spec| {
+            let text = match spec {
+                "f" => name.unwrap_or("-").to_string(),
+                "lt" => (top + 1).to_string(),
+                "lb" => (bottom + 1).to_string(),
+                "L" => len.to_string(),
+                "p" | "pt" => percent(top).to_string(),
+                "pb" => percent(bottom).to_string(),
+                // a newline after each line
+                "b" | "bt" => lines[..top.min(len)]
+                    .iter()
+                    .map(|line| line.byte_len() + 1)
+                    .sum::<usize>()
+                    .to_string(),
+                "B" => self.reader.read.load(Ordering::Relaxed).to_string(),
+                "s" => status.clone(),
+                _ => return None,
+            };
+            Some(text)
+        })
+    }
+
+    // true when the last forward scroll should quit
+    fn count_end(&mut self) -> bool {
+        if self.loading || self.scroll < self.max_scroll() {
+            self.ends_reached = 0;
+            return false;
+        }
+
+        self.ends_reached += 1;
+        self.quit_at_end > 0 && self.ends_reached >= self.quit_at_end
+    }
+
+    fn enter_screen(&mut self) -> Result<()> {
+        self.screen = true;
+        self.need_redraw = true;
+        self.prompt_outdated = true;
+        execute!(self.output, EnterAlternateScreen, DisableLineWrap, Hide)?;
+        if self.title {
+            self.output.write_all(PUSH_TITLE.as_bytes())?;
+            self.shown_title = None;
+        }
+        if self.mouse {
+            execute!(self.output, EnableMouseCapture)?;
+        } else {
+            self.output.write_all(ALTERNATE_SCROLL_ON.as_bytes())?;
+            self.output.flush()?;
+        }
+        Ok(())
+    }
 
-            // push into self.reflowed_search_positions
-            for (cut_index, start_indexes) in new_positions.iter().enumerate() {
-                let mut indexes = SmallVec::new();
-                for start_index in start_indexes { indexes.push(SearchPosition { start: *start_index as u32 }) }
-                self.reflowed_search_positions[linked_reflowed_lines[cut_index]] = indexes;
+    fn leave_screen(&mut self) {
+        if self.screen {
+            self.screen = false;
+            self.output.write_all(ALTERNATE_SCROLL_OFF.as_bytes()).ok();
+            if self.title {
+                self.output.write_all(POP_TITLE.as_bytes()).ok();
             }
-        };
+            execute!(
+                self.output,
+                Show,
+                EnableLineWrap,
+                DisableMouseCapture,
+                LeaveAlternateScreen
+            )
+            .ok();
+        }
+    }
+
+    fn fits_in_view(&mut self) -> bool {
+        if self.need_reflow {
+            self.reflow();
+            self.need_reflow = false;
+        }
+
+        let (real, _) = self.size_ctx.calculate_real_size(&self.reflowed_lines);
+        real == self.reflowed_lines.len()
     }
 
-    pub fn handle_event(&mut self, event: Event) -> Result<bool> {
-        match event {
-            Event::Mouse(MouseEvent {
-                kind: MouseEventKind::ScrollUp,
-                ..
-            }) => {
-                if self.prompt_state == PromptState::Normal {
-                    self.scroll_up(1);
-                }
-            }
-            Event::Mouse(MouseEvent {
-                kind: MouseEventKind::ScrollDown,
-                ..
-            }) => {
-                if self.prompt_state == PromptState::Normal {
-                    self.scroll_down(1);
-                }
-            }
-            Event::Key(ke) => {
-                if let PromptState::Search(ref mut s) = self.prompt_state {
-                    if !ke
-                        .modifiers
-                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
-                    {
-                        match ke.code {
-                            KeyCode::Char(c) => {
-                                s.push(c);
-                                self.prompt_outdated = true;
-                                return Ok(false);
-                            }
-                            KeyCode::Backspace => {
-                                if s.pop().is_none() {
-                                    self.prompt_state = PromptState::Normal;
-                                }
+    // write the input to stdout, paging isn't needed for a short one: the rows as drawn when
+    // stdout is the terminal, the lines as read when it's redirected, like `cat`
+    fn print_rows(&mut self) -> Result<()> {
+        let stdout = std::io::stdout();
+        if !stdout.is_tty() {
+            return self.dump(&mut stdout.lock(), Dump::Styled);
+        }
+        // the newlines return the carriage again
+        disable_raw_mode()?;
 
-                                self.prompt_outdated = true;
-                                return Ok(false);
-                            }
-                            KeyCode::Enter => {
-                                let needle = std::mem::take(s);
-                                self.search(&needle);
-                                self.prompt_state = PromptState::Normal;
-                                self.prompt_outdated = true;
-                                return Ok(false);
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+        self.output_buf.clear();
+        self.parse_rows(0, self.reflowed_lines.len());
+        let styles = self.styles.clone();
+        let styles = styles.styles();
+        let mut row_buf = Vec::new();
 
-                match self.keymap.get(&ke) {
-                    Some(b) => match b {
-                        KeyBehavior::NormalMode => {
-                            self.prompt_state.take();
-                            self.search("");
-                            self.prompt_outdated = true;
-                        }
-                        KeyBehavior::Search => {
-                            self.prompt_state = PromptState::Search(String::new());
-                            self.prompt_outdated = true;
-                        }
-                        KeyBehavior::SearchNext => {
-                            self.move_search(true);
-                        }
-                        KeyBehavior::SearchPrev => {
-                            self.move_search(false);
-                        }
-                        KeyBehavior::Number(n) => match self.prompt_state {
-                            PromptState::Number(ref mut pn) => {
-                                *pn = *pn * 10 + (*n as usize);
-                                self.prompt_outdated = true;
-                            }
-                            _ => {
-                                self.prompt_state = PromptState::Number(*n as usize);
-                                self.prompt_outdated = true;
-                            }
-                        },
-                        KeyBehavior::Up(size) => {
-                            let size = size.calculate(self.size_ctx.terminal_line());
-                            let n = match self.prompt_state.take() {
-                                PromptState::Number(n) => n,
-                                _ => 1,
-                            };
-                            self.scroll_up(size.wrapping_mul(n));
-                        }
-                        KeyBehavior::Down(size) => {
-                            let size = size.calculate(self.size_ctx.terminal_line());
-                            let n = match self.prompt_state.take() {
-                                PromptState::Number(n) => n,
-                                _ => 1,
-                            };
-                            self.scroll_down(size.wrapping_mul(n));
-                        }
-                        KeyBehavior::Quit => {
-                            return Ok(true);
-                        }
-                    },
-                    None => {}
-                }
+        for line in self.lines[..self.header.min(self.lines.len())].iter() {
+            let mut ch_writer = self.ch_writer();
+            line.parse(&self.styles)
+                .as_line()
+                .expand(&styles, 0, line.len as usize, &mut row_buf);
+            ch_writer.write_slice(&mut self.output_buf, &row_buf)?;
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+            self.output_buf.push(b'\n');
+        }
+
+        for row in self.reflowed_lines.iter() {
+            let mut ch_writer = self.ch_writer();
+            self.parsed_lines[&row.line].as_line().expand(
+                &styles,
+                row.start as usize,
+                row.end as usize,
+                &mut row_buf,
+            );
+            ch_writer.write_slice(&mut self.output_buf, &row_buf)?;
+            if let Some(count) = self.repeat_count(*row) {
+                ch_writer.write_count(&mut self.output_buf, count)?;
             }
-            Event::Resize(x, y) => {
-                self.size_ctx.resize(x as usize, y as usize);
-                self.need_reflow = true;
-                self.need_redraw = true;
-                self.prompt_outdated = true;
+            if let Some(summary) = self.fold_summary(*row) {
+                ch_writer.write_note(&mut self.output_buf, &summary)?;
             }
-            _ => {}
-        };
+            queue!(self.output_buf, SetAttribute(Attribute::Reset))?;
+            self.output_buf.push(b'\n');
+        }
 
-        Ok(false)
+        let mut out = stdout.lock();
+        out.write_all(&self.output_buf)?;
+        out.flush()
     }
 
     pub fn run(&mut self) -> Result<()> {
-        const BULK_LINE: usize = 5000;
+        const BULK_LINE: usize = 1024;
         const FPS: u64 = 30;
         const TICK: Duration = Duration::from_nanos(Duration::from_secs(1).as_nanos() as u64 / FPS);
 
         let mut prev_time = Instant::now();
+        // the first-run prompt needs the screen
+        let mut deferred =
+            self.quit_if_one_screen && !matches!(self.prompt_state, PromptState::Setup(_));
+        if !deferred && !self.screen {
+            self.enter_screen()?;
+        }
 
         loop {
-            if !crate::RUN.load(Ordering::Acquire) {
+            if !self.reader.run.load(Ordering::Acquire) {
                 return Ok(());
             }
 
@@ -709,25 +5813,82 @@ impl<'b> UiContext<'b> {
             while poll(Duration::from_nanos(0))? {
                 let e = read()?;
 
+                // a key before the end is read means paging after all
+                if deferred {
+                    deferred = false;
+                    self.enter_screen()?;
+                }
+
                 if self.handle_event(e)? {
                     return Ok(());
                 }
             }
 
             let mut line_count = 0;
+            let deadline = prev_time + TICK / 2;
+            let first_new = self.lines.len();
+            self.reader
+                .wanted
+                .store(self.wanted_lines(), Ordering::Relaxed);
 
-            // receive lines max BULK_LINE
-            while let Some(line) = self.rx.pop() {
+            // receive lines for at most half a frame, checking the time every BULK_LINE lines
+            while let Some(line) = if self.paused { None } else { self.rx.pop() } {
                 self.push_line(line);
 
                 line_count += 1;
 
-                if line_count >= BULK_LINE {
+                if line_count % BULK_LINE == 0 && Instant::now() >= deadline {
                     break;
                 }
             }
 
-            self.update()?;
+            if self.follow {
+                if self.lines.len() > first_new {
+                    self.new_lines.push_back((first_new, Instant::now()));
+                }
+                self.expire_new_lines();
+            }
+
+            if self.paused && self.pending != self.rx.len() {
+                self.pending = self.rx.len();
+                self.prompt_outdated = true;
+            }
+
+            self.sample_rate();
+            let loading = self.is_loading();
+            if loading != self.loading {
+                self.loading = loading;
+                self.prompt_outdated = true;
+            }
+
+            if let Some(watcher) = &mut self.watcher {
+                if watcher.changed() && self.reload_at.is_none() {
+                    self.reload_at = Some(Instant::now() + WATCH_DELAY);
+                }
+            }
+            if self.reload_at.is_some_and(|at| Instant::now() >= at) {
+                self.reload = true;
+                return Ok(());
+            }
+
+            if deferred {
+                let fits = self.fits_in_view();
+                if fits && !self.loading {
+                    return self.print_rows();
+                } else if !fits {
+                    deferred = false;
+                    self.enter_screen()?;
+                }
+            }
+
+            if self.expire_keys()? {
+                return Ok(());
+            }
+            self.expire_bell();
+
+            if !deferred {
+                self.update()?;
+            }
 
             if let Some(sleep) = TICK.checked_sub(prev_time.elapsed()) {
                 std::thread::sleep(sleep);
@@ -736,83 +5897,366 @@ impl<'b> UiContext<'b> {
             prev_time = Instant::now();
         }
     }
+
+    /// take the lines received and draw them, what [`Self::run`] does for each frame, without
+    /// waiting or reading the keys
+    pub fn render(&mut self) -> Result<()> {
+        if !self.screen {
+            self.enter_screen()?;
+        }
+
+        self.reader
+            .wanted
+            .store(self.wanted_lines(), Ordering::Relaxed);
+        while let Some(line) = self.rx.pop() {
+            self.push_line(line);
+        }
+        self.loading = self.is_loading();
+        self.prompt_outdated = true;
+        self.expire_bell();
+
+        self.update()
+    }
 }
 
 impl<'b> Drop for UiContext<'b> {
     fn drop(&mut self) {
-        execute!(
-            self.output,
-            Show,
-            EnableLineWrap,
-            DisableMouseCapture,
-            LeaveAlternateScreen
-        )
-        .ok();
-        disable_raw_mode().ok();
+        // even for a reload, the next UiContext may never come when the input is gone
+        self.leave_screen();
+        if self.raw_mode {
+            disable_raw_mode().ok();
+        }
+    }
+}
+
+// the colors of a decoration over those of `chars`, except the `Reset` ones, its attributes
+// added to theirs
+fn restyle(chars: &mut [RpChar], style: Style) {
+    for ch in chars {
+        if style.foreground != Color::Reset {
+            ch.foreground = style.foreground;
+        }
+        if style.background != Color::Reset {
+            ch.background = style.background;
+        }
+        ch.attribute.extend(style.attribute);
     }
 }
 
 struct ChWriter {
     terminal_column: usize,
+    left: u16,
+    row: u16,
+    /// the view spans the whole terminal width, lines can be cleared at once
+    full: bool,
     wrap: usize,
     pos: usize,
     current_color: Color,
     current_bgcolor: Color,
+    current_underline: Color,
     current_attribute: Attributes,
+    /// false to drop the colors of the input
+    colors: bool,
+    /// false to drop its attributes too
+    attributes: bool,
+    /// colors are fitted to it as they are written
+    depth: ColorDepth,
+    /// (foreground, background) of the search matches in place of reverse video
+    highlight: Option<(Color, Color)>,
+    /// writing a search match
+    marked: bool,
+    /// color of the line numbers
+    gutter: Color,
+    /// fills the row, for new lines and stderr
+    background: Option<Color>,
+    /// columns of the view given the guide background
+    guides: Vec<usize>,
 }
 
 impl ChWriter {
-    pub fn new(terminal_column: usize) -> Self {
+    pub fn new(size_ctx: &SizeContext) -> Self {
         Self {
-            terminal_column,
+            terminal_column: size_ctx.terminal_column(),
+            left: size_ctx.left() as u16,
+            row: size_ctx.top() as u16,
+            full: size_ctx.is_full_width(),
             wrap: 0,
             pos: 0,
             current_color: Color::Reset,
             current_bgcolor: Color::Reset,
+            current_underline: Color::Reset,
             current_attribute: Attributes::default(),
+            colors: true,
+            attributes: true,
+            depth: ColorDepth::True,
+            highlight: None,
+            marked: false,
+            gutter: Color::Reset,
+            background: None,
+            guides: Vec::new(),
         }
     }
 
     pub fn write_slice_reverse(&mut self, out: &mut Vec<u8>, chars: &[RpChar]) -> Result<()> {
-        chars.iter().copied().try_for_each(|mut ch| {
-            ch.attribute.set(Attribute::Reverse);
-            self.write(out, ch)
-        })?;
+        self.marked = true;
+        let ret = chars.iter().copied().try_for_each(|ch| {
+            let mut ch = self.input_style(ch);
+            match self.highlight {
+                Some((foreground, background)) => {
+                    ch.foreground = foreground;
+                    ch.background = background;
+                }
+                None => ch.attribute.set(Attribute::Reverse),
+            }
+            self.put(out, ch)
+        });
+        self.marked = false;
+        ret?;
         self.current_attribute.unset(Attribute::Reverse);
         queue!(out, SetAttribute(Attribute::NoReverse))
     }
 
+    /// the frozen part of a line, cut or padded to `width` columns
+    pub fn write_frozen(
+        &mut self,
+        out: &mut Vec<u8>,
+        chars: &[RpChar],
+        width: usize,
+    ) -> Result<()> {
+        let mut used = 0;
+        for ch in chars {
+            let w = ch.ch.width().unwrap_or(0);
+            if used + w > width {
+                break;
+            }
+            used += w;
+            self.write(out, *ch)?;
+        }
+
+        (used..width).try_for_each(|_| {
+            self.write(
+                out,
+                RpChar {
+                    ch: ' ',
+                    foreground: Color::Reset,
+                    background: Color::Reset,
+                    underline: Color::Reset,
+                    attribute: Attributes::default(),
+                },
+            )
+        })
+    }
+
+    /// `width` columns with the line number on the right and a space, blank without it
+    pub fn write_number(
+        &mut self,
+        out: &mut Vec<u8>,
+        number: Option<usize>,
+        width: usize,
+    ) -> Result<()> {
+        let text = match number {
+            Some(n) => format!("{:>1$} ", n, width - 1),
+            None => " ".repeat(width),
+        };
+        self.write_dim(out, &text, self.gutter)
+    }
+
+    /// `width` columns with the gutter text of `decoration`, blank without it
+    pub fn write_decoration(
+        &mut self,
+        out: &mut Vec<u8>,
+        decoration: Option<&Decoration>,
+        width: usize,
+    ) -> Result<()> {
+        let (text, style) = match decoration {
+            Some(decoration) => (decoration.gutter.as_str(), decoration.gutter_style),
+            None => ("", Style::default()),
+        };
+        let styled = |ch| RpChar {
+            ch,
+            foreground: style.foreground,
+            background: style.background,
+            underline: style.underline,
+            attribute: style.attribute,
+        };
+
+        let mut used = 0;
+        for ch in text.chars() {
+            let w = ch.width().unwrap_or(0);
+            if used + w > width {
+                break;
+            }
+            used += w;
+            self.put(out, styled(ch))?;
+        }
+        (used..width).try_for_each(|_| self.put(out, styled(' ')))
+    }
+
+    pub fn write_count(&mut self, out: &mut Vec<u8>, count: u32) -> Result<()> {
+        self.write_note(out, &format!(" (×{})", count))
+    }
+
+    /// dimmed text added by the pager after a line
+    pub fn write_note(&mut self, out: &mut Vec<u8>, note: &str) -> Result<()> {
+        self.write_dim(out, note, Color::Reset)
+    }
+
+    fn write_dim(&mut self, out: &mut Vec<u8>, text: &str, foreground: Color) -> Result<()> {
+        text.chars().try_for_each(|ch| {
+            self.write(
+                out,
+                RpChar {
+                    ch,
+                    foreground,
+                    background: Color::Reset,
+                    underline: Color::Reset,
+                    attribute: Attribute::Dim.into(),
+                },
+            )
+        })?;
+        self.current_attribute.unset(Attribute::Dim);
+        queue!(out, SetAttribute(Attribute::NormalIntensity))
+    }
+
+    /// clear the current row of the view, the cursor is at its start
+    pub fn clear_line(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        if self.full {
+            // erasing fills the line with the current background
+            if self.current_bgcolor != Color::Reset {
+                queue!(out, SetBackgroundColor(Color::Reset))?;
+                self.current_bgcolor = Color::Reset;
+            }
+            return queue!(out, Clear(ClearType::CurrentLine));
+        }
+
+        // other panes may be beside the view, only overwrite its own columns
+        queue!(out, SetAttribute(Attribute::Reset))?;
+        self.current_color = Color::Reset;
+        self.current_bgcolor = Color::Reset;
+        self.current_underline = Color::Reset;
+        self.current_attribute = Attributes::default();
+        write!(out, "{:1$}", "", self.terminal_column)?;
+        queue!(out, MoveTo(self.left, self.row))
+    }
+
+    /// the background up to the end of the row
+    pub fn fill_row(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let blank = RpChar {
+            ch: ' ',
+            foreground: Color::Reset,
+            background: Color::Reset,
+            underline: Color::Reset,
+            attribute: Attributes::default(),
+        };
+        // the last column is left for the scrollbar
+        while self.pos + 1 < self.terminal_column {
+            self.write(out, blank)?;
+        }
+        Ok(())
+    }
+
+    /// the guides past the end of the row, as thin lines
+    pub fn write_guides(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        if self.guides.is_empty() {
+            return Ok(());
+        }
+        for guide in self.guides.iter().filter(|guide| **guide >= self.pos) {
+            queue!(
+                out,
+                MoveTo(self.left + *guide as u16, self.row),
+                SetAttribute(Attribute::Reset),
+                SetAttribute(Attribute::Dim)
+            )?;
+            write!(out, "│")?;
+        }
+        queue!(out, SetAttribute(Attribute::Reset))?;
+        self.current_color = Color::Reset;
+        self.current_bgcolor = Color::Reset;
+        self.current_underline = Color::Reset;
+        self.current_attribute = Attributes::default();
+        Ok(())
+    }
+
+    pub fn next_line(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        self.row += 1;
+        self.pos = 0;
+        queue!(out, MoveTo(self.left, self.row))
+    }
+
     pub fn write_slice(&mut self, out: &mut Vec<u8>, chars: &[RpChar]) -> Result<()> {
         chars.iter().copied().try_for_each(|ch| self.write(out, ch))
     }
 
     pub fn write(&mut self, out: &mut Vec<u8>, ch: RpChar) -> Result<()> {
+        let ch = self.input_style(ch);
+        self.put(out, ch)
+    }
+
+    // the style of the input kept by the theme
+    fn input_style(&self, mut ch: RpChar) -> RpChar {
+        if !self.colors {
+            ch.foreground = Color::Reset;
+            ch.background = Color::Reset;
+            ch.underline = Color::Reset;
+        }
+        if !self.attributes {
+            ch.attribute = Attributes::default();
+        }
+        ch
+    }
+
+    // `ch` with the backgrounds of rp
+    fn put(&mut self, out: &mut Vec<u8>, mut ch: RpChar) -> Result<()> {
+        // the colors of a match win over the backgrounds of its row
+        let highlighted = self.marked && self.highlight.is_some();
+        if let Some(background) = self.background.filter(|_| !highlighted) {
+            ch.background = background;
+        }
+        if self.guides.contains(&self.pos) && !highlighted {
+            ch.background = GUIDE_BACKGROUND;
+        }
+
+        let width = ch.ch.width().unwrap_or(0);
+
+        if self.pos + width > self.terminal_column {
+            self.next_line(out)?;
+            self.clear_line(out)?;
+            self.wrap += 1;
+        }
+        self.pos += width;
+
         if self.current_attribute != ch.attribute {
+            // setting attributes doesn't turn off the others, like the bold of the row before
+            // the line numbers, a reset does
+            let current = drawn_attributes(self.current_attribute);
+            let drawn = drawn_attributes(ch.attribute);
+            let dropped = Attribute::iterator().any(|a| current.has(a) && !drawn.has(a));
+            if dropped && !ch.attribute.has(Attribute::Reset) {
+                queue!(out, SetAttribute(Attribute::Reset))?;
+            }
             queue!(out, SetAttributes(ch.attribute))?;
             // Reset attribute also reset colors
-            if ch.attribute.has(Attribute::Reset) {
+            if dropped || ch.attribute.has(Attribute::Reset) {
                 self.current_color = Color::Reset;
                 self.current_bgcolor = Color::Reset;
+                self.current_underline = Color::Reset;
             }
             self.current_attribute = ch.attribute;
         }
         if ch.foreground != self.current_color {
-            queue!(out, SetForegroundColor(ch.foreground))?;
+            let color = colors::fit(ch.foreground, self.depth);
+            queue!(out, SetForegroundColor(color))?;
             self.current_color = ch.foreground;
         }
         if ch.background != self.current_bgcolor {
-            queue!(out, SetBackgroundColor(ch.background))?;
+            let color = colors::fit(ch.background, self.depth);
+            queue!(out, SetBackgroundColor(color))?;
             self.current_bgcolor = ch.background;
         }
-
-        let width = ch.ch.width().unwrap_or(0);
-
-        if self.pos + width > self.terminal_column {
-            queue!(out, MoveToNextLine(1), Clear(ClearType::CurrentLine))?;
-            self.wrap += 1;
-            self.pos = width;
-        } else {
-            self.pos += width;
+        if ch.underline != self.current_underline {
+            let color = colors::fit(ch.underline, self.depth);
+            queue!(out, SetUnderlineColor(color))?;
+            self.current_underline = ch.underline;
         }
 
         write!(out, "{}", ch.ch)?;
@@ -821,10 +6265,21 @@ impl ChWriter {
     }
 }
 
+/// Position and size of the view, the whole terminal unless a rectangle was given
 #[derive(Default, Clone)]
 struct SizeContext {
+    left: usize,
+    top: usize,
     terminal_column: usize,
     terminal_line: usize,
+    /// width of the whole terminal
+    full_column: usize,
+    /// set by an embedder, terminal resizes don't move it
+    fixed: bool,
+    /// header lines received so far, on top of the rows
+    header: usize,
+    /// a row of column numbers above the header
+    ruler: bool,
 }
 
 impl SizeContext {
@@ -832,11 +6287,11 @@ impl SizeContext {
         Self::default()
     }
 
-    pub fn calculate_real_size(&self, lines: &[RpLine]) -> (usize, usize) {
+    fn calculate_real_size(&self, rows: &[Row]) -> (usize, usize) {
         let mut real = 0;
-        let mut left = self.terminal_line;
-        for line in lines.iter().rev() {
-            let size = line_line_size(line, self.terminal_column);
+        let mut left = self.terminal_line();
+        for row in rows.iter().rev() {
+            let size = line_line_size(row.width as usize, self.terminal_column);
             match left.checked_sub(size) {
                 Some(n) => {
                     real += 1;
@@ -851,37 +6306,370 @@ impl SizeContext {
     }
 
     pub fn resize(&mut self, terminal_column: usize, terminal_line: usize) {
-        self.terminal_column = terminal_column;
+        self.full_column = terminal_column;
+        if !self.fixed {
+            self.set_size(0, 0, terminal_column, terminal_line);
+        }
+    }
+
+    pub fn set_rect(&mut self, left: usize, top: usize, column: usize, line: usize) {
+        self.fixed = true;
+        self.set_size(left, top, column, line);
+    }
+
+    fn set_size(&mut self, left: usize, top: usize, column: usize, line: usize) {
+        self.left = left;
+        self.top = top;
+        // a column at least, the rows are wrapped to it
+        self.terminal_column = column.max(1);
         self.terminal_line = {
             // reduce by one on unix, keep on windows
             #[cfg(unix)]
-            { terminal_line - 1 }
+            { line.saturating_sub(1) }
             #[cfg(windows)]
-            { terminal_line }
+            { line }
         };
     }
 
+    pub fn left(&self) -> usize {
+        self.left
+    }
+
+    /// first row of the scrolled lines, below the ruler and the header
+    pub fn top(&self) -> usize {
+        self.top + self.ruler_rows() + self.header_rows()
+    }
+
+    /// the ruler row unless it would leave nothing to scroll
+    pub fn ruler_rows(&self) -> usize {
+        usize::from(self.ruler && self.terminal_line > 1)
+    }
+
+    /// rows taken by the header, at least one is left to scroll
+    pub fn header_rows(&self) -> usize {
+        self.header
+            .min(self.terminal_line.saturating_sub(1 + self.ruler_rows()))
+    }
+
+    pub fn is_full_width(&self) -> bool {
+        self.left == 0 && self.terminal_column >= self.full_column
+    }
+
     pub fn terminal_column(&self) -> usize {
         self.terminal_column
     }
 
+    /// rows of the scrolled lines
     pub fn terminal_line(&self) -> usize {
-        self.terminal_line
+        self.terminal_line - self.ruler_rows() - self.header_rows()
+    }
+}
+
+// colors for a line without any, the header lines only get bold
+fn color_diff_line(parsed: &mut ParsedLine, kind: DiffLine, styles: &StyleTable) {
+    if !is_plain(parsed) {
+        return;
+    }
+
+    // like after `\e[0m`, the bold of a header before doesn't carry over
+    let mut style = Style {
+        attribute: Attribute::Reset.into(),
+        ..Style::default()
+    };
+    match kind {
+        DiffLine::Added => style.foreground = Color::DarkGreen,
+        DiffLine::Removed => style.foreground = Color::DarkRed,
+        DiffLine::Hunk => style.foreground = Color::DarkCyan,
+        DiffLine::File | DiffLine::Header => style.attribute.set(Attribute::Bold),
+        DiffLine::Context | DiffLine::Other => return,
+    }
+    set_style(parsed, style, styles);
+}
+
+// the color of the first level matching a line without colors
+fn color_log_line(parsed: &mut ParsedLine, levels: &[LogLevel], styles: &StyleTable) {
+    if levels.is_empty() || !is_plain(parsed) {
+        return;
+    }
+
+    if let Some(level) = levels.iter().find(|l| l.pattern.is_match(&parsed.text)) {
+        let style = Style {
+            foreground: level.color,
+            ..Style::default()
+        };
+        set_style(parsed, style, styles);
+    }
+}
+
+// underline the urls and the file positions, or only one of them
+fn underline_links(parsed: &mut ParsedLine, (urls, files): (bool, bool), styles: &StyleTable) {
+    let links = links::targets(&parsed.text)
+        .into_iter()
+        .filter(|(_, target)| match target {
+            Target::Url(_) => urls,
+            Target::File { .. } => files,
+        })
+        .map(|(range, _)| range)
+        .collect::<Vec<_>>();
+    if links.is_empty() {
+        return;
+    }
+
+    // split the runs at the ends of the links, those inside them get underlined
+    let mut runs = Vec::with_capacity(parsed.runs.len() + links.len() * 2);
+    let mut start = 0;
+    for run in parsed.runs.iter() {
+        let mut cuts = vec![run.end];
+        for link in links.iter() {
+            cuts.extend([link.start as u32, link.end as u32]);
+        }
+        cuts.retain(|cut| (start + 1..=run.end).contains(cut));
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        for end in cuts {
+            let at = start as usize;
+            let mut style = styles.styles()[run.style as usize];
+            let after_link = links.iter().any(|link| link.end <= at);
+            if links.iter().any(|link| link.contains(&at)) {
+                style.attribute.unset(Attribute::NoUnderline);
+                style.attribute.set(Attribute::Underlined);
+            } else if after_link && !style.attribute.has(Attribute::Underlined) {
+                // the underline of the link before ends
+                style.attribute.set(Attribute::NoUnderline);
+            }
+            runs.push(StyleRun {
+                end,
+                style: styles.intern(style),
+            });
+            start = end;
+        }
+    }
+    parsed.runs = runs;
+}
+
+fn is_plain(parsed: &ParsedLine) -> bool {
+    parsed.len > 0 && parsed.runs.iter().all(|run| run.style == 0)
+}
+
+fn set_style(parsed: &mut ParsedLine, style: Style, styles: &StyleTable) {
+    parsed.runs = vec![StyleRun {
+        end: parsed.len,
+        style: styles.intern(style),
+    }];
+}
+
+// the first of the ways each key is given by [`keys::parse_keys`], as sequences compare them
+fn sequence(keys: &[Vec<KeyEvent>]) -> Vec<KeyEvent> {
+    keys.iter()
+        .filter_map(|key| key.first().copied().map(key_of_sequence))
+        .collect()
+}
+
+// one of the OPTIONS, or its short name like in vim
+fn option_name(word: &str) -> Option<&'static str> {
+    match word {
+        "nu" => Some("number"),
+        "ic" => Some("ignorecase"),
+        _ => OPTIONS.iter().find(|name| **name == word).copied(),
+    }
+}
+
+// `wrap` or `nowrap`
+fn option_state(name: &str, on: bool) -> String {
+    match on {
+        true => name.to_string(),
+        false => format!("no{}", name),
+    }
+}
+
+// bytes typed as hex digits, like `DEADbeef` or `de ad`, as written in the dump
+fn hex_needle(needle: &str) -> Option<String> {
+    let digits = needle
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+    if digits.is_empty() || digits.len() % 2 != 0 || !digits.iter().all(char::is_ascii_hexdigit) {
+        return None;
+    }
+
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| pair.iter().collect::<String>().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    Some(bytes.join(" "))
+}
+
+// find non-overlapping occurrences of needle, from left to right
+fn find_matches(text: &str, needle: &str, ignore_case: bool) -> SearchPositionArr {
+    let (text, needle) = match ignore_case {
+        true => (Cow::Owned(fold_case(text)), Cow::Owned(fold_case(needle))),
+        false => (Cow::Borrowed(text), Cow::Borrowed(needle)),
+    };
+    let needle = &*needle;
+    let mut arr = SearchPositionArr::new();
+    let mut chars = 0;
+    let mut prev = 0;
+
+    // convert byte offsets into char indexes
+    for (offset, _) in text.match_indices(needle) {
+        chars += text[prev..offset].chars().count();
+        prev = offset;
+        arr.push(SearchPosition { start: chars as u32 });
+    }
+
+    arr
+}
+
+// lowercase, one char for each char so that the matches are at the same indexes
+fn fold_case(text: &str) -> String {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+// edit `text` with the cursor `back` chars before its end like readline does, false for
+// the keys that don't edit it
+fn edit_line(text: &mut String, back: &mut usize, ke: KeyEvent) -> bool {
+    let len = text.chars().count();
+    let at = len - (*back).min(len);
+    // byte offset of the `n`-th char
+    let offset = |text: &str, n: usize| text.char_indices().nth(n).map_or(text.len(), |(i, _)| i);
+    let control = ke.modifiers.contains(KeyModifiers::CONTROL);
+
+    match ke.code {
+        KeyCode::Char('w') if control => {
+            // the spaces before the cursor, then the word before them
+            let chars = text.chars().take(at).collect::<Vec<_>>();
+            let spaces = chars.iter().rev().take_while(|c| c.is_whitespace()).count();
+            let word = chars[..at - spaces]
+                .iter()
+                .rev()
+                .take_while(|c| !c.is_whitespace())
+                .count();
+            let start = offset(text, at - spaces - word);
+            text.replace_range(start..offset(text, at), "");
+        }
+        KeyCode::Char('u') if control => {
+            text.replace_range(..offset(text, at), "");
+        }
+        KeyCode::Char(c) if !control && !ke.modifiers.contains(KeyModifiers::ALT) => {
+            text.insert(offset(text, at), c);
+        }
+        KeyCode::Backspace if at > 0 => {
+            text.replace_range(offset(text, at - 1)..offset(text, at), "");
+        }
+        KeyCode::Delete if *back > 0 => {
+            text.replace_range(offset(text, at)..offset(text, at + 1), "");
+            *back -= 1;
+        }
+        KeyCode::Left => *back = (*back + 1).min(len),
+        KeyCode::Right => *back = back.saturating_sub(1),
+        KeyCode::Home => *back = len,
+        KeyCode::End => *back = 0,
+        // nothing before the cursor to delete
+        KeyCode::Backspace | KeyCode::Delete => {}
+        _ => return false,
+    }
+    true
+}
+
+// the text of a prompt with the char under the cursor not reversed, a space at the end
+fn push_edited(prompt: &mut String, text: &str, back: usize, paint: Paint) {
+    let len = text.chars().count();
+    let at = len - back.min(len);
+    let mut chars = text.chars();
+
+    prompt.extend(chars.by_ref().take(at));
+    let cursor = chars.next().unwrap_or(' ');
+    prompt.push_str(&paint.inverted(&cursor.to_string()));
+    prompt.extend(chars);
+}
+
+// the letters, digits and `_` around the char `idx` of `text`, like a terminal selects
+// on a double click
+fn word_at(text: &str, idx: usize) -> Option<String> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let chars = text.chars().collect::<Vec<_>>();
+    if !chars.get(idx).copied().is_some_and(is_word_char) {
+        return None;
+    }
+
+    let start = chars[..idx]
+        .iter()
+        .rposition(|c| !is_word_char(*c))
+        .map_or(0, |i| i + 1);
+    let end = chars[idx..]
+        .iter()
+        .position(|c| !is_word_char(*c))
+        .map_or(chars.len(), |i| i + idx);
+    Some(chars[start..end].iter().collect())
+}
+
+// `%` of a shell command replaced by `name` quoted as a single word, `\%` by a `%`
+fn expand_file_name(command: &str, name: &str) -> String {
+    let name = crate::process::quote(name);
+    let mut out = String::with_capacity(command.len() + name.len());
+    let mut chars = command.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if chars.peek() == Some(&'%') => out.extend(chars.next()),
+            '%' => out.push_str(&name),
+            _ => out.push(ch),
+        }
     }
+    out
 }
 
-fn line_line_size(l: RpLine, column: usize) -> usize {
-    let width = line_width(l);
+// tabs as spaces up to the next multiple of 8 columns
+fn expand_tabs(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for ch in line.chars() {
+        if ch == '\t' {
+            let spaces = 8 - column % 8;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            out.push(ch);
+            column += ch.width().unwrap_or(0);
+        }
+    }
+    out
+}
+
+// columns taken by `s`, without escape sequences
+fn text_width(s: &str) -> usize {
+    s.chars().map(|c| c.width().unwrap_or(0)).sum()
+}
+
+// cut the prompt to `width` columns, escape sequences don't take any room
+fn truncate_to_width(s: &str, width: usize) -> &str {
+    let mut x = 0;
+    let mut escape = false;
+
+    for (i, c) in s.char_indices() {
+        if escape {
+            escape = !c.is_ascii_alphabetic();
+        } else if c == '\x1b' {
+            escape = true;
+        } else {
+            x += c.width().unwrap_or(0);
+            if x > width {
+                return &s[..i];
+            }
+        }
+    }
 
+    s
+}
+
+fn line_line_size(width: usize, column: usize) -> usize {
     if width == 0 {
         1
-    } else if width % column == 0 {
+    } else if width.is_multiple_of(column) {
         width / column
     } else {
         (width / column) + 1
     }
 }
-
-fn line_width(l: RpLine) -> usize {
-    l.iter().map(|c| c.ch.width().unwrap_or(0)).sum::<usize>()
-}
@@ -0,0 +1,235 @@
+use crossbeam_queue::ArrayQueue;
+use crossterm::style::{Attribute, Color};
+use rust_pager::diff::DiffLine;
+use rust_pager::image::{Height, Image, Protocol};
+use rust_pager::reader;
+use rust_pager::shared::{parse_ansi, RawLine, ReaderState, RpChar, StyleTable};
+use std::sync::Arc;
+
+// every line of `text` with the style of each char
+fn parse(text: &str) -> Vec<Vec<RpChar>> {
+    let styles = StyleTable::new();
+    let lines = parse_ansi(text, &styles);
+    lines.iter().map(|line| line.chars(&styles)).collect()
+}
+
+// the lines of `input` as the reader gives them to the view
+fn read_lines(input: &str) -> Vec<RawLine<'_>> {
+    let rx = Arc::new(ArrayQueue::new(1024));
+    let styles = Arc::new(StyleTable::new());
+    let state = Arc::new(ReaderState::new(0));
+    reader::read_from_mmap(input.as_bytes(), rx.clone(), styles, state).unwrap();
+    std::iter::from_fn(|| rx.pop()).collect()
+}
+
+fn text(chars: &[RpChar]) -> String {
+    chars.iter().map(|ch| ch.ch).collect()
+}
+
+#[test]
+fn escapes_are_left_out_of_the_text() {
+    let styles = StyleTable::new();
+    let lines = parse_ansi("\x1b[1mbold\x1b[0m plain\r\nnext\n", &styles);
+    let texts = lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(texts, ["bold plain", "next"]);
+}
+
+#[test]
+fn bold_ends_at_the_reset() {
+    let lines = parse("\x1b[1mab\x1b[0mc");
+    assert_eq!(text(&lines[0]), "abc");
+    assert!(lines[0][0].attribute.has(Attribute::Bold));
+    assert!(lines[0][1].attribute.has(Attribute::Bold));
+    assert!(!lines[0][2].attribute.has(Attribute::Bold));
+}
+
+#[test]
+fn styles_go_on_to_the_next_line() {
+    let lines = parse("\x1b[32mgreen\nstill\x1b[39m\nplain");
+    assert_eq!(lines[1][0].foreground, lines[0][0].foreground);
+    assert_ne!(lines[1][0].foreground, Color::Reset);
+    assert_eq!(lines[2][0].foreground, Color::Reset);
+}
+
+#[test]
+fn hyperlinks_are_kept() {
+    let styles = StyleTable::new();
+    let text = "see \x1b]8;;https://example.com\x1b\\the docs\x1b]8;;\x1b\\ here";
+    let lines = parse_ansi(text, &styles);
+    assert_eq!(lines[0].text, "see the docs here");
+    assert_eq!(lines[0].link_at(4), Some("https://example.com"));
+    assert_eq!(lines[0].link_at(13), None);
+}
+
+#[test]
+fn underline_kinds_and_colors() {
+    let lines =
+        parse("\x1b[4:3m\x1b[58:2::255:0:0merror\x1b[59m\x1b[4:0m ok \x1b[21;58;5;4mx\x1b[24mo");
+    let error = lines[0][0];
+    assert!(error.attribute.has(Attribute::Undercurled));
+    assert_eq!(error.underline, Color::Rgb { r: 255, g: 0, b: 0 });
+
+    let ok = lines[0][6];
+    assert!(!ok.attribute.has(Attribute::Undercurled));
+    assert_eq!(ok.underline, Color::Reset);
+
+    let double = lines[0][9];
+    assert!(double.attribute.has(Attribute::DoubleUnderlined));
+    assert_ne!(double.underline, Color::Reset);
+    assert!(!lines[0][10].attribute.has(Attribute::DoubleUnderlined));
+}
+
+#[test]
+fn every_attribute_is_kept() {
+    let attributes = [
+        (2, Attribute::Dim),
+        (3, Attribute::Italic),
+        (5, Attribute::SlowBlink),
+        (6, Attribute::RapidBlink),
+        (8, Attribute::Hidden),
+        (9, Attribute::CrossedOut),
+    ];
+    for (n, attribute) in attributes {
+        let lines = parse(&format!("\x1b[{}mon", n));
+        assert!(lines[0][0].attribute.has(attribute), "SGR {}", n);
+    }
+}
+
+#[test]
+fn attributes_end_with_their_own_reset() {
+    let lines = parse("\x1b[1;2;5;8;9ma\x1b[22mb\x1b[25mc\x1b[28md\x1b[29me");
+    let on = |idx: usize, attribute| lines[0][idx].attribute.has(attribute);
+    assert!(on(0, Attribute::Bold) && on(0, Attribute::Dim));
+    assert!(!on(1, Attribute::Bold) && !on(1, Attribute::Dim) && on(1, Attribute::SlowBlink));
+    assert!(!on(2, Attribute::SlowBlink) && on(2, Attribute::Hidden));
+    assert!(!on(3, Attribute::Hidden) && on(3, Attribute::CrossedOut));
+    assert!(!on(4, Attribute::CrossedOut));
+    // one kind of blink replaces the other
+    let lines = parse("\x1b[5;6mx");
+    assert!(!lines[0][0].attribute.has(Attribute::SlowBlink));
+}
+
+#[test]
+fn reset_clears_the_colors() {
+    for reset in ["\x1b[0m", "\x1b[m"] {
+        let lines = parse(&format!("\x1b[31;44;3mred{}plain", reset));
+        let plain = lines[0][3];
+        assert_eq!(
+            (plain.foreground, plain.background),
+            (Color::Reset, Color::Reset)
+        );
+        assert!(!plain.attribute.has(Attribute::Italic));
+    }
+}
+
+#[test]
+fn osc_sequences_are_left_out() {
+    let sequences = [
+        "\x1b]0;a title\x07",
+        "\x1b]2;a title\x1b\\",
+        "\x1b]4;1;rgb:ff/00/00\x1b\\",
+        "\x1b]52;c;aGVsbG8=\x07",
+        "\x1b]133;A\x07",
+    ];
+    for sequence in sequences {
+        let lines = parse(&format!("a{}b", sequence));
+        assert_eq!(text(&lines[0]), "ab", "{:?}", sequence);
+    }
+}
+
+#[test]
+fn open_sequences_end_with_their_line() {
+    let input = "a\x1b]0;cut short\nnext\n\x1bPq#0\nlast\n";
+    let lines = read_lines(input)
+        .iter()
+        .map(|line| line.text().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(lines, ["a", "next", "", "last"]);
+}
+
+#[test]
+fn images_are_found_with_their_height() {
+    let input = concat!(
+        "\x1bPq\"1;1;8;40#0~~~~-~~~~\x1b\\\n",
+        "\x1bPq#0~~~~-~~~~-~~\x1b\\\n",
+        "\x1b]1337;File=inline=1;height=3:AAAA\x07\n",
+        "\x1b]1337;File=inline=1:iVBORw0KGgoAAAANSUhEUgAAAGQAAAA8\x07\n",
+        "\x1b]1337;File=name=eA==:AAAA\x07\n",
+        "\x1b_Ga=T,f=100,r=4,m=1;AAAA\x1b\\\x1b_Gm=0;AAAA\x1b\\\n",
+        "\x1b_Ga=t,f=100;AAAA\x1b\\\n",
+        "text\n",
+    );
+    let images = read_lines(input)
+        .iter()
+        .map(|line| line.image)
+        .collect::<Vec<_>>();
+
+    let image = |protocol, height| Some(Image { protocol, height });
+    assert_eq!(
+        images,
+        [
+            image(Protocol::Sixel, Height::Pixels(40)),
+            image(Protocol::Sixel, Height::Pixels(18)),
+            image(Protocol::Iterm, Height::Rows(3)),
+            image(Protocol::Iterm, Height::Pixels(60)),
+            // a file to download and one only sent, not placed
+            None,
+            image(Protocol::Kitty, Height::Rows(4)),
+            None,
+            None,
+        ]
+    );
+}
+
+#[test]
+fn dec_graphics_are_lines() {
+    let lines = parse("\x1b(0lqqk\x1b(B ok\n\x1b)0a\x0exqx\x0fx\n");
+    assert_eq!(text(&lines[0]), "┌──┐ ok");
+    assert_eq!(text(&lines[1]), "a│─│x");
+}
+
+#[test]
+fn long_lines_are_kept_whole() {
+    let input = format!("{}\n", "x".repeat(5000)).repeat(20);
+    let lens = read_lines(&input)
+        .iter()
+        .map(|line| line.len)
+        .collect::<Vec<_>>();
+    assert_eq!(lens, [5000; 20]);
+}
+
+#[test]
+fn diff_lines_are_told_apart_through_their_colors() {
+    let input = concat!(
+        "commit 1234\n",
+        "\x1b[1mdiff --git a/lib.rs b/lib.rs\x1b[m\n",
+        "\x1b[1m--- a/lib.rs\x1b[m\n",
+        "\x1b[1m+++ b/lib.rs\x1b[m\n",
+        "\x1b[36m@@ -1,2 +1,2 @@\x1b[m\n",
+        " same\n",
+        "\x1b[31m--- removed\x1b[m\n",
+        "\x1b[32m+added\x1b[m\n",
+        "after\n",
+    );
+    let kinds = read_lines(input)
+        .iter()
+        .map(|line| line.diff)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            DiffLine::Other,
+            DiffLine::File,
+            DiffLine::Header,
+            DiffLine::Header,
+            DiffLine::Hunk,
+            DiffLine::Context,
+            DiffLine::Removed,
+            DiffLine::Added,
+            DiffLine::Other,
+        ]
+    );
+}
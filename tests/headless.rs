@@ -0,0 +1,682 @@
+use crossbeam_queue::ArrayQueue;
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::style::{Attribute, Color};
+use rust_pager::backend::{Backend, Headless};
+use rust_pager::config::Theme;
+use rust_pager::history::Bookmark;
+use rust_pager::hook::{Decoration, KeyAction, PagerEvent};
+use rust_pager::image::Protocol;
+use rust_pager::reader;
+use rust_pager::shared::{RawLine, ReaderState, Style, StyleTable};
+use rust_pager::writer::{InputInfo, UiContext};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::sync::{atomic::Ordering, Arc};
+use std::time::Duration;
+
+type Lines = (
+    Arc<ArrayQueue<RawLine<'static>>>,
+    Arc<StyleTable>,
+    Arc<ReaderState>,
+);
+
+// the lines of `text` queued for the view by a reader that may go on, those starting with
+// `stderr` as if written to stderr unless it's empty
+fn read_lines(text: &'static [u8], stderr: &str) -> Lines {
+    let rx = Arc::new(ArrayQueue::new(1024));
+    let styles = Arc::new(StyleTable::new());
+    let state = Arc::new(ReaderState::new(0));
+    reader::read_from_mmap(text, rx.clone(), styles.clone(), state.clone()).unwrap();
+    for _ in 0..rx.len() {
+        let mut line = rx.pop().unwrap();
+        line.stderr = !stderr.is_empty() && line.text().starts_with(stderr);
+        rx.push(line).ok();
+    }
+    (rx, styles, state)
+}
+
+// the pager drawing the whole of `text` to `backend`, with the stderr lines of `read_lines`
+fn pager_on(text: &'static str, stderr: &str, backend: impl Backend + 'static) -> UiContext<'static> {
+    let (rx, styles, state) = read_lines(text.as_bytes(), stderr);
+    state.done.store(true, Ordering::Release);
+    UiContext::with_backend(rx, styles, state, backend).unwrap()
+}
+
+// the pager drawing `text` to a screen of `width` and `height`
+fn pager(text: &'static str, width: u16, height: u16) -> (UiContext<'static>, Headless) {
+    let screen = Headless::new(width, height);
+    (pager_on(text, "", screen.clone()), screen)
+}
+
+fn type_keys(ui: &mut UiContext, keys: &str) {
+    ui.render().unwrap();
+    for ch in keys.chars() {
+        let code = if ch == '\n' {
+            KeyCode::Enter
+        } else {
+            KeyCode::Char(ch)
+        };
+        let key = KeyEvent::new(code, KeyModifiers::NONE);
+        ui.handle_event(Event::Key(key)).unwrap();
+        ui.render().unwrap();
+    }
+}
+
+// the screen drawn for `text`, after the keys `keys`
+fn render(text: &'static str, width: u16, height: u16, keys: &str) -> Headless {
+    let (mut ui, screen) = pager(text, width, height);
+    type_keys(&mut ui, keys);
+    screen
+}
+
+#[test]
+fn long_lines_wrap() {
+    // the last column is the scrollbar's
+    let screen = render("one two three four five six\nseven\n", 10, 5, "");
+    let rows = (0..4).map(|y| screen.row(y)).collect::<Vec<_>>();
+    assert_eq!(rows, ["one two t", "hree four", " five six", "seven"]);
+}
+
+#[test]
+fn search_matches_are_highlighted() {
+    let screen = render("a needle\nnone\nneedles\n", 20, 5, "/needle\n");
+    // the match jumped to is a row below the top, like on the terminal
+    assert_eq!(screen.row(1), "a needle");
+    assert_eq!(
+        screen.with_attribute(1, Attribute::Reverse),
+        (2..8).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        screen.with_attribute(2, Attribute::Reverse),
+        Vec::<usize>::new()
+    );
+    assert_eq!(
+        screen.with_attribute(3, Attribute::Reverse),
+        (0..6).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn rows_with_matches_keep_their_gutter() {
+    let (mut ui, screen) = pager("a needle\nnone\n", 20, 5);
+    type_keys(&mut ui, "-N/needle\n");
+    // numbered like the rows without a match
+    assert_eq!(screen.row(2), "   1 a needle");
+    assert_eq!(screen.row(3), "   2 none");
+    assert_eq!(
+        screen.with_attribute(2, Attribute::Reverse),
+        (7..13).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn prompt_shows_the_search() {
+    let screen = render("text\n", 30, 4, "/tex");
+    assert_eq!(screen.row(3), "/tex");
+}
+
+#[test]
+fn key_filters_see_the_keys_first() {
+    let tops = Rc::new(RefCell::new(Vec::new()));
+    let (ui, screen) = pager("1\n2\n3\n4\n5\n6\n", 10, 4);
+    let seen = tops.clone();
+    let mut ui = ui
+        .on_key(|key| match key.code {
+            KeyCode::Char('x') => KeyAction::Consume,
+            KeyCode::Char('n') => KeyAction::Replace(KeyEvent::from(KeyCode::Char('j'))),
+            _ => KeyAction::Pass,
+        })
+        .on_event(move |event| {
+            if let PagerEvent::Scroll { top } = event {
+                seen.borrow_mut().push(*top);
+            }
+        });
+
+    type_keys(&mut ui, "nnxj");
+    assert_eq!(screen.row(0), "4");
+    assert_eq!(*tops.borrow(), [1, 2, 3]);
+}
+
+#[test]
+fn decorations_have_a_gutter() {
+    let (ui, screen) = pager("ok one\nfailed two three\n", 12, 4);
+    let mut ui = ui.decorate(2, |_, text| {
+        let failed = text.starts_with("failed");
+        let style = Style {
+            foreground: Color::Red,
+            ..Style::default()
+        };
+        Some(Decoration {
+            gutter: if failed { "✘" } else { "✔" }.to_string(),
+            style: if failed { style } else { Style::default() },
+            ..Decoration::default()
+        })
+    });
+
+    type_keys(&mut ui, "");
+    let rows = (0..3).map(|y| screen.row(y)).collect::<Vec<_>>();
+    assert_eq!(rows, ["✔ ok one", "✘ failed tw", "  o three"]);
+    assert!(screen.cell(2, 1).style.foreground == Color::Red);
+    assert!(screen.cell(2, 0).style.foreground == Color::Reset);
+}
+
+#[test]
+fn attributes_go_on_past_the_line_numbers() {
+    let screen = render("\x1b[1;9mbold and struck\x1b[0m\n", 12, 4, "-N");
+    assert_eq!(screen.row(1), "     nd str");
+    // the numbers are dim only, the row after them is bold again
+    assert!(!screen.cell(0, 1).style.attribute.has(Attribute::Bold));
+    assert!(screen.cell(0, 1).style.attribute.has(Attribute::Dim));
+    for attribute in [Attribute::Bold, Attribute::CrossedOut] {
+        assert_eq!(
+            screen.with_attribute(0, attribute),
+            (5..11).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            screen.with_attribute(1, attribute),
+            (5..11).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            screen.with_attribute(2, attribute),
+            (5..8).collect::<Vec<_>>()
+        );
+    }
+}
+
+#[test]
+fn header_lines_fit_a_narrow_screen() {
+    let (ui, screen) = pager("head\nbody\n", 4, 4);
+    let mut ui = ui.header(1);
+    // the line numbers are wider than the screen
+    type_keys(&mut ui, "-N");
+    assert_eq!(screen.row(3), "Line");
+}
+
+// lines numbered from 0
+const NUMBERED: &str = "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n14\n15\n16\n17\n18\n19\n";
+
+#[test]
+fn options_toggle_and_keep_the_top_line() {
+    for (keys, message) in [
+        ("-S", "Chop long lines: on"),
+        ("-N", "Line numbers: on"),
+        ("-s", "Squeeze blank lines: on"),
+        ("-i", "Ignore case in searches: on"),
+    ] {
+        let (mut ui, screen) = pager(NUMBERED, 40, 5);
+        type_keys(&mut ui, "jjjjjjjjjj");
+        assert_eq!(ui.top_line(), 10);
+        type_keys(&mut ui, keys);
+        assert_eq!(ui.top_line(), 10, "{}", keys);
+        assert_eq!(screen.row(4), message);
+    }
+}
+
+#[test]
+fn unknown_options_are_told() {
+    let screen = render("text\n", 60, 3, "-x");
+    assert_eq!(screen.row(2), "No option -x, expected S, N, i, s, r or R");
+}
+
+#[test]
+fn the_memory_used_is_in_the_status() {
+    let (ui, screen) = pager(NUMBERED, 80, 5);
+    let mut ui = ui.max_memory(Some(64 << 20));
+    type_keys(&mut ui, ":set memory\nj");
+    assert!(screen.row(4).contains(" mem "), "{}", screen.row(4));
+    assert!(screen.row(4).contains("/64.0 MiB"), "{}", screen.row(4));
+}
+
+#[test]
+fn dedup_collapses_repeats_and_keeps_the_top_line() {
+    let text = "0\n1\n2\n3\n4\nx\nx\nx\n5\n6\n7\n8\n9\n10\n";
+    let (mut ui, screen) = pager(text, 20, 5);
+    type_keys(&mut ui, "jjjjjjj");
+    assert_eq!(ui.top_line(), 7);
+
+    // on its first line once collapsed
+    type_keys(&mut ui, "D");
+    assert_eq!(ui.top_line(), 5);
+    assert_eq!(screen.row(0), "x (×3)");
+    assert_eq!(screen.row(1), "5");
+    type_keys(&mut ui, "jD");
+    assert_eq!(ui.top_line(), 8);
+    assert_eq!(screen.row(0), "5");
+}
+
+#[test]
+fn bookmark_notes_keep_their_tabs() {
+    let (mut ui, _screen) = pager(NUMBERED, 40, 6);
+    type_keys(&mut ui, "mcols a\tb\n");
+    assert_eq!(ui.bookmark_list()[0].note, "a\tb");
+}
+
+#[test]
+fn bookmarks_are_added_and_jumped_to() {
+    let (mut ui, screen) = pager(NUMBERED, 40, 6);
+    type_keys(&mut ui, "jjjmstart the run\n");
+    assert_eq!(screen.row(5), "Bookmark start on line 4");
+    let bookmark = Bookmark {
+        line: 3,
+        name: "start".into(),
+        note: "the run".into(),
+    };
+    assert_eq!(ui.bookmark_list(), std::slice::from_ref(&bookmark));
+
+    type_keys(&mut ui, "G'");
+    // the line number, name and note of each above the keys of the panel
+    assert_eq!(screen.row(4), "4  start  the run");
+    assert!(screen.row(5).starts_with("1/1 bookmarks"));
+    type_keys(&mut ui, "\n");
+    assert!(ui.top_line() <= 3);
+    assert!((0..5).any(|y| screen.row(y) == "3"));
+
+    // given back by the next run, and deleted from the panel
+    let (ui, screen) = pager(NUMBERED, 40, 6);
+    let mut ui = ui.bookmarks(vec![bookmark]);
+    type_keys(&mut ui, "'d");
+    assert!(ui.bookmark_list().is_empty());
+    assert_eq!(screen.row(5), "Deleted bookmark start");
+}
+
+#[test]
+fn sessions_keep_the_search_and_the_filters() {
+    let (mut ui, _) = pager(NUMBERED, 20, 5);
+    type_keys(&mut ui, "/1\nD-s-iE");
+    let view = ui.saved_view();
+    assert_eq!(view.needle, "1");
+    assert!(view.dedup && view.squeeze && view.ignore_case && view.only_stderr);
+    assert!(!view.reverse);
+
+    let (ui, _) = pager(NUMBERED, 20, 5);
+    let mut ui = ui.restore_saved(view.clone());
+    type_keys(&mut ui, "");
+    assert_eq!(ui.saved_view(), view);
+}
+
+// the screen, keeping the bytes written to it
+struct Recorder(Headless, Rc<RefCell<Vec<u8>>>);
+
+impl Write for Recorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.1.borrow_mut().extend_from_slice(buf);
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend for Recorder {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.0.size()
+    }
+}
+
+#[test]
+fn images_are_drawn_over_their_rows() {
+    const SIXEL: &str = "\x1bPq\"1;1;8;40#0~~~~-~~~~\x1b\\";
+    let text = "before\n\x1bPq\"1;1;8;40#0~~~~-~~~~\x1b\\\nafter\n";
+    let (screen, written) = (Headless::new(20, 5), Rc::new(RefCell::new(Vec::new())));
+    let recorder = Recorder(screen.clone(), written.clone());
+    let mut ui = pager_on(text, "", recorder).images(&[Protocol::Sixel], Some(20));
+    type_keys(&mut ui, "");
+
+    // 40 pixels over rows of 20, on the second and third row
+    let rows = (0..4).map(|y| screen.row(y)).collect::<Vec<_>>();
+    assert_eq!(rows, ["before", "", "", "after"]);
+    let written = String::from_utf8_lossy(&written.borrow()).into_owned();
+    assert!(written.contains(&format!("\x1b[2;1H{}", SIXEL)));
+}
+
+#[test]
+fn dec_graphics_go_on_to_the_next_lines() {
+    let screen = render("\x1b(0lqk\nx x\nmqj\x1b(B\nplain q\n", 12, 5, "");
+    let rows = (0..4).map(|y| screen.row(y)).collect::<Vec<_>>();
+    assert_eq!(rows, ["┌─┐", "│ │", "└─┘", "plain q"]);
+}
+
+#[test]
+fn escapes_are_shown_as_text() {
+    let (mut ui, screen) = pager("\x1b[31mred\x1b[0m plain\n", 12, 4);
+    type_keys(&mut ui, "-r");
+    // the rows are as wide as the caret text
+    assert_eq!(screen.row(1), "^[[31mred^[");
+    assert_eq!(screen.row(2), "[0m plain");
+    assert_eq!(
+        screen.with_attribute(1, Attribute::Reverse),
+        vec![0, 1, 9, 10]
+    );
+    assert_eq!(screen.cell(6, 1).style.foreground, Color::Reset);
+
+    // a second -r keeps them
+    type_keys(&mut ui, "-r");
+    assert_eq!(screen.row(1), "^[[31mred^[");
+
+    type_keys(&mut ui, "-R");
+    assert_eq!(screen.row(2), "red plain");
+    assert_eq!(screen.cell(0, 2).style.foreground, Color::DarkRed);
+    assert!(screen.row(3).starts_with("Show escapes"));
+}
+
+#[test]
+fn reverse_mode_puts_new_lines_on_top() {
+    let (rx, styles, state) = read_lines(b"first\nsecond\n", "");
+    let screen = Headless::new(10, 5);
+    let mut ui = UiContext::with_backend(rx.clone(), styles.clone(), state.clone(), screen.clone())
+        .unwrap()
+        .reverse(true);
+    ui.render().unwrap();
+    assert_eq!(
+        (screen.row(2), screen.row(3)),
+        ("second".into(), "first".into())
+    );
+
+    reader::read_from_mmap(b"third is long\n", rx, styles, state.clone()).unwrap();
+    state.done.store(true, Ordering::Release);
+    ui.render().unwrap();
+    let rows = (0..4).map(|y| screen.row(y)).collect::<Vec<_>>();
+    assert_eq!(rows, ["third is", "long", "second", "first"]);
+}
+
+#[test]
+fn reverse_mode_keeps_the_top_line() {
+    let (mut ui, screen) = pager(NUMBERED, 20, 5);
+    type_keys(&mut ui, "jjj");
+    type_keys(&mut ui, ":reverse\n");
+    assert_eq!(ui.top_line(), 3);
+    let rows = (0..4).map(|y| screen.row(y)).collect::<Vec<_>>();
+    assert_eq!(rows, ["3", "2", "1", "0"]);
+
+    type_keys(&mut ui, "k:reverse\n");
+    assert_eq!(ui.top_line(), 4);
+    assert_eq!(screen.row(0), "4");
+}
+
+#[test]
+fn stderr_only_keeps_the_nearest_line_in_view() {
+    // the odd lines as if written to stderr
+    let text = "o0\ne1\no2\ne3\no4\ne5\no6\ne7\no8\ne9\n";
+    let screen = Headless::new(20, 4);
+    let mut ui = pager_on(text, "e", screen.clone());
+    type_keys(&mut ui, "jjjj");
+    assert_eq!(ui.top_line(), 4);
+    type_keys(&mut ui, "E");
+    assert_eq!(ui.top_line(), 3);
+    let rows = (0..3).map(|y| screen.row(y)).collect::<Vec<_>>();
+    assert_eq!(rows, ["e3", "e5", "e7"]);
+    type_keys(&mut ui, "E");
+    assert_eq!(ui.top_line(), 3);
+}
+
+#[test]
+fn programs_need_a_terminal() {
+    for keys in ["!", "|", "V", "s", ":!true\n", ":pipe cat\n"] {
+        let screen = render("text\n", 30, 4, keys);
+        assert_eq!(screen.row(3), "Not on a terminal", "{:?}", keys);
+    }
+}
+
+#[test]
+fn each_pager_has_its_own_reader() {
+    let (mut done, done_screen) = pager("one\n", 40, 3);
+    let (rx, styles, state) = read_lines(b"two\n", "");
+    state.size.store(10, Ordering::Release);
+    let screen = Headless::new(40, 3);
+    let mut loading = UiContext::with_backend(rx, styles, state, screen.clone()).unwrap();
+
+    type_keys(&mut loading, "");
+    type_keys(&mut done, "");
+    assert!(screen.row(2).contains("loading… 40%"), "{}", screen.row(2));
+    assert!(!done_screen.row(2).contains("loading"));
+}
+
+#[test]
+fn a_switch_to_another_input_can_be_called_off() {
+    let (ui, screen) = pager("one\n", 40, 3);
+    let input = |name: &str| InputInfo {
+        name: name.into(),
+        size: None,
+        line: None,
+    };
+    let mut ui = ui.reloadable(true).inputs(0, vec![input("a"), input("b")]);
+    type_keys(&mut ui, ":n\n");
+    assert_eq!(ui.next_input(), Some(1));
+
+    ui.stay("Can't open b".into());
+    type_keys(&mut ui, "");
+    assert!(ui.reload_state().is_none() && ui.next_input().is_none());
+    assert_eq!(screen.row(2), "Can't open b");
+}
+
+// a terminal the shell and the editor can take over
+#[derive(Clone)]
+struct Console(Headless);
+
+impl Write for Console {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Backend for Console {
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.0.size()
+    }
+
+    fn is_terminal(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn a_failed_editor_leaves_its_message_and_no_reload() {
+    // without a terminal it can't run, with one `false` exits with 1
+    std::env::set_var("VISUAL", "false");
+    let screen = Headless::new(60, 3);
+    let input = InputInfo {
+        name: "a".into(),
+        size: None,
+        line: None,
+    };
+    let ui = pager_on("one\n", "", Console(screen.clone()));
+    let mut ui = ui.reloadable(true).inputs(0, vec![input]);
+    type_keys(&mut ui, "V");
+
+    assert!(ui.reload_state().is_none());
+    let message = screen.row(2);
+    assert!(
+        message.starts_with("The editor exited with")
+            || message.starts_with("Can't run the editor"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn setup_questions_take_every_key() {
+    let (ui, screen) = pager("one\n", 60, 3);
+    let mut ui = ui.setup(true);
+    ui.render().unwrap();
+    let question = screen.row(2);
+    for ch in ['q', '/', ':'] {
+        let key = KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE);
+        assert!(!ui.handle_event(Event::Key(key)).unwrap());
+        ui.render().unwrap();
+        assert_eq!(screen.row(2), question);
+    }
+}
+
+#[cfg(feature = "ratatui")]
+#[test]
+fn the_widget_draws_nothing_in_an_empty_area() {
+    use ratatui::{buffer::Buffer, layout::Rect, widgets::StatefulWidget};
+    use rust_pager::widget::{PagerState, PagerView};
+
+    let (rx, styles, state) = read_lines(b"one\ntwo\n", "");
+    state.done.store(true, Ordering::Release);
+    let mut pane = PagerState::new(rx, styles, state).unwrap();
+
+    for area in [Rect::new(0, 0, 0, 5), Rect::new(0, 0, 5, 0)] {
+        let mut buf = Buffer::empty(area);
+        PagerView::new().render(area, &mut buf, &mut pane);
+    }
+    // still drawn once the area has room again
+    let area = Rect::new(0, 0, 10, 3);
+    let mut buf = Buffer::empty(area);
+    PagerView::new().render(area, &mut buf, &mut pane);
+    assert_eq!(buf[(0, 0)].symbol(), "o");
+}
+
+#[test]
+fn a_rect_without_columns_draws_nothing_past_it() {
+    let (ui, screen) = pager("one\ntwo\n", 20, 5);
+    let mut ui = ui.rect(0, 0, 0, 5);
+    type_keys(&mut ui, "j");
+    // the single column it's widened to
+    assert!(screen.row(0).chars().count() <= 1);
+}
+
+#[test]
+fn a_screen_without_rows_or_columns_takes_the_drawing() {
+    for (width, height) in [(5, 0), (0, 5)] {
+        let screen = render("one\ntwo\n", width, height, "j");
+        assert_eq!(screen.text(), "");
+    }
+}
+
+#[test]
+fn sequences_run_their_action() {
+    let (mut ui, screen) = pager(NUMBERED, 20, 10);
+    type_keys(&mut ui, "Ggg");
+    assert_eq!(ui.top_line(), 0);
+    assert_ne!(screen.row(9), "g");
+}
+
+#[test]
+fn sequences_wait_for_their_next_key() {
+    let (mut ui, screen) = pager(NUMBERED, 20, 10);
+    type_keys(&mut ui, "Gg");
+    assert_eq!(screen.row(9), "g");
+    // not yet too long
+    assert!(!ui.expire_keys().unwrap());
+    type_keys(&mut ui, "");
+    assert_eq!(screen.row(9), "g");
+    assert_ne!(ui.top_line(), 0);
+}
+
+#[test]
+fn a_key_alone_runs_once_the_sequence_times_out() {
+    let (ui, screen) = pager(NUMBERED, 20, 10);
+    let mut ui = ui.key_timeout(Duration::ZERO);
+    type_keys(&mut ui, "Gg");
+    ui.expire_keys().unwrap();
+    type_keys(&mut ui, "");
+    assert_ne!(screen.row(9), "g");
+    assert_eq!(ui.top_line(), 0);
+}
+
+#[test]
+fn a_key_not_going_on_with_the_sequence_runs_after_the_one_before() {
+    let (mut ui, screen) = pager(NUMBERED, 20, 10);
+    // `g` alone to the top, then `j`
+    type_keys(&mut ui, "Ggj");
+    assert_eq!(ui.top_line(), 1);
+    assert_ne!(screen.row(9), "g");
+}
+
+#[test]
+fn sequences_bound_from_the_config() {
+    let (ui, _) = pager(NUMBERED, 20, 10);
+    let bindings = [("q w".to_string(), "top".to_string())];
+    let mut ui = ui.bind(&bindings);
+    type_keys(&mut ui, "Gqw");
+    assert_eq!(ui.top_line(), 0);
+}
+
+#[test]
+fn a_short_stream_scrolled_to_its_end_keeps_up_with_it() {
+    let (rx, styles, state) = read_lines(b"0\n1\n", "");
+    let screen = Headless::new(20, 5);
+    let mut ui = UiContext::with_backend(rx.clone(), styles.clone(), state.clone(), screen.clone())
+        .unwrap();
+    // all of it fits, there's nothing to scroll yet
+    type_keys(&mut ui, "G");
+
+    reader::read_from_mmap(&NUMBERED.as_bytes()[4..], rx, styles, state).unwrap();
+    type_keys(&mut ui, "");
+    assert_eq!(screen.row(3), "19");
+}
+
+#[test]
+fn a_short_stream_scrolled_to_its_top_stays_there() {
+    let (rx, styles, state) = read_lines(b"0\n1\n", "");
+    let screen = Headless::new(20, 5);
+    let mut ui = UiContext::with_backend(rx.clone(), styles.clone(), state.clone(), screen.clone())
+        .unwrap();
+    type_keys(&mut ui, "k");
+
+    reader::read_from_mmap(&NUMBERED.as_bytes()[4..], rx, styles, state).unwrap();
+    type_keys(&mut ui, "");
+    assert_eq!(screen.row(0), "0");
+}
+
+#[test]
+fn the_help_tells_the_editor_is_on_v_capital() {
+    let (mut ui, screen) = pager("text\n", 80, 100);
+    type_keys(&mut ui, "h");
+    let text = screen.text();
+    let edit = text.lines().find(|line| line.contains("$EDITOR")).unwrap();
+    assert!(edit.trim_start().starts_with("V "), "{}", edit);
+    assert!(edit.ends_with("v in less"), "{}", edit);
+}
+
+#[test]
+fn the_minimap_shows_bookmarks_and_the_view_without_colors() {
+    let (ui, screen) = pager(NUMBERED, 20, 5);
+    let bookmark = Bookmark {
+        line: 15,
+        name: "b".into(),
+        note: String::new(),
+    };
+    let mut ui = ui.minimap(true).theme(Theme::Plain).bookmarks(vec![bookmark]);
+    type_keys(&mut ui, "");
+
+    // 20 lines over 4 cells, the first one in view
+    assert_eq!(screen.row(3).chars().last(), Some('◆'));
+    assert_eq!(screen.with_attribute(0, Attribute::Reverse), vec![19]);
+    assert_eq!(
+        screen.with_attribute(1, Attribute::Reverse),
+        Vec::<usize>::new()
+    );
+}
+
+#[test]
+fn clicks_on_the_scrollbar_leave_an_open_prompt_alone() {
+    let (ui, _screen) = pager(NUMBERED, 20, 5);
+    let mut ui = ui.scrollbar(true);
+    let click = |ui: &mut UiContext, row| {
+        let mouse = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: 19,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        ui.handle_event(Event::Mouse(mouse)).unwrap();
+    };
+    type_keys(&mut ui, ":");
+    click(&mut ui, 3);
+    assert_eq!(ui.top_line(), 0);
+
+    type_keys(&mut ui, "\n");
+    click(&mut ui, 3);
+    assert!(ui.top_line() > 0);
+}
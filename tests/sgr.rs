@@ -0,0 +1,174 @@
+// Colors of the output of common tools, as they write it with `--color=always`
+
+use crossterm::style::{Attribute, Color};
+use rust_pager::shared::{parse_ansi, RpChar, StyleTable};
+
+fn parse(text: &str) -> Vec<Vec<RpChar>> {
+    let styles = StyleTable::new();
+    let lines = parse_ansi(text, &styles);
+    lines.iter().map(|line| line.chars(&styles)).collect()
+}
+
+// the first char of `word` in `line`
+fn find(line: &[RpChar], word: &str) -> RpChar {
+    let text = line.iter().map(|ch| ch.ch).collect::<String>();
+    let idx = text.find(word).expect(word);
+    line[text[..idx].chars().count()]
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+#[test]
+fn every_form_of_the_colors() {
+    let forms = [
+        ("\x1b[31m", Color::DarkRed, Color::Reset),
+        ("\x1b[91;44m", Color::Red, Color::DarkBlue),
+        ("\x1b[38;5;208m", Color::AnsiValue(208), Color::Reset),
+        ("\x1b[38;5;1m", Color::DarkRed, Color::Reset),
+        ("\x1b[48;5;236m", Color::Reset, Color::AnsiValue(236)),
+        ("\x1b[38:5:208m", Color::AnsiValue(208), Color::Reset),
+        ("\x1b[48:5:236m", Color::Reset, Color::AnsiValue(236)),
+        ("\x1b[38;2;1;2;3m", rgb(1, 2, 3), Color::Reset),
+        ("\x1b[48;2;1;2;3m", Color::Reset, rgb(1, 2, 3)),
+        ("\x1b[38:2:1:2:3m", rgb(1, 2, 3), Color::Reset),
+        ("\x1b[38:2::1:2:3m", rgb(1, 2, 3), Color::Reset),
+        ("\x1b[48:2:0:1:2:3m", Color::Reset, rgb(1, 2, 3)),
+        (
+            "\x1b[1;38;2;255;0;127;48;5;17m",
+            rgb(255, 0, 127),
+            Color::AnsiValue(17),
+        ),
+    ];
+    for (sgr, foreground, background) in forms {
+        let ch = parse(&format!("{}x", sgr))[0][0];
+        assert_eq!(
+            (ch.foreground, ch.background),
+            (foreground, background),
+            "{:?}",
+            sgr
+        );
+    }
+}
+
+#[test]
+fn colors_out_of_range_are_left_out() {
+    let ch = parse("\x1b[32m\x1b[38;2;300;0;0mx")[0][0];
+    assert_eq!(ch.foreground, Color::DarkGreen);
+    let ch = parse("\x1b[38;5mx")[0][0];
+    assert_eq!(ch.foreground, Color::Reset);
+}
+
+#[test]
+fn git_diff() {
+    let lines = parse(concat!(
+        "\x1b[1mdiff --git a/src/lib.rs b/src/lib.rs\x1b[m\n",
+        "\x1b[36m@@ -1,2 +1,2 @@\x1b[m fn main() {\n",
+        "\x1b[31m-    old();\x1b[m\n",
+        "\x1b[32m+\x1b[m\x1b[32m    new();\x1b[m\x1b[41m \x1b[m\n",
+    ));
+    assert!(find(&lines[0], "diff").attribute.has(Attribute::Bold));
+    assert_eq!(find(&lines[1], "@@").foreground, Color::DarkCyan);
+    assert_eq!(find(&lines[1], "fn").foreground, Color::Reset);
+    assert_eq!(find(&lines[2], "old").foreground, Color::DarkRed);
+    assert_eq!(find(&lines[3], "new").foreground, Color::DarkGreen);
+    // the whitespace error at the end
+    let last = lines[3][lines[3].len() - 1];
+    assert_eq!(
+        (last.foreground, last.background),
+        (Color::Reset, Color::DarkRed)
+    );
+}
+
+#[test]
+fn git_log_decorations() {
+    let lines = parse(concat!(
+        "\x1b[33mcommit 0123abc\x1b[m\x1b[33m (\x1b[m\x1b[1;36mHEAD\x1b[m\x1b[33m -> \x1b[m",
+        "\x1b[1;32mmain\x1b[m\x1b[33m, \x1b[m\x1b[1;31morigin/main\x1b[m\x1b[33m)\x1b[m\n",
+    ));
+    let line = &lines[0];
+    assert_eq!(find(line, "commit").foreground, Color::DarkYellow);
+    assert_eq!(find(line, "HEAD").foreground, Color::DarkCyan);
+    assert!(find(line, "HEAD").attribute.has(Attribute::Bold));
+    assert_eq!(find(line, "main").foreground, Color::DarkGreen);
+    assert_eq!(find(line, "origin").foreground, Color::DarkRed);
+    assert!(!find(line, ")").attribute.has(Attribute::Bold));
+}
+
+#[test]
+fn ls_colors() {
+    let lines = parse(concat!(
+        "\x1b[0m\x1b[01;34msrc\x1b[0m  \x1b[01;32mbuild.sh\x1b[0m  ",
+        "\x1b[38;5;208mdata.tar\x1b[0m  \x1b[40;31;01mbroken\x1b[0m  ",
+        "\x1b[38;2;255;180;0mnotes.md\x1b[0m\n",
+    ));
+    let line = &lines[0];
+    assert_eq!(find(line, "src").foreground, Color::DarkBlue);
+    assert!(find(line, "src").attribute.has(Attribute::Bold));
+    assert_eq!(find(line, "build").foreground, Color::DarkGreen);
+    assert_eq!(find(line, "data").foreground, Color::AnsiValue(208));
+    assert!(!find(line, "data").attribute.has(Attribute::Bold));
+    let broken = find(line, "broken");
+    assert_eq!(
+        (broken.foreground, broken.background),
+        (Color::DarkRed, Color::Black)
+    );
+    assert_eq!(find(line, "notes").foreground, rgb(255, 180, 0));
+    assert_eq!(find(line, "  ").foreground, Color::Reset);
+}
+
+#[test]
+fn cargo_diagnostics() {
+    let lines = parse(concat!(
+        "\x1b[0m\x1b[1m\x1b[38;5;9merror[E0308]\x1b[0m\x1b[0m\x1b[1m: mismatched types\x1b[0m\n",
+        "\x1b[0m \x1b[0m\x1b[0m\x1b[1m\x1b[38;5;12m--> \x1b[0m\x1b[0msrc/main.rs:2:5\x1b[0m\n",
+        "\x1b[1m\x1b[32m   Compiling\x1b[0m rust-pager v0.2.4\n",
+    ));
+    let error = find(&lines[0], "error");
+    assert_eq!(error.foreground, Color::Red);
+    assert!(error.attribute.has(Attribute::Bold));
+    let message = find(&lines[0], "mismatched");
+    assert_eq!(message.foreground, Color::Reset);
+    assert!(message.attribute.has(Attribute::Bold));
+    assert_eq!(find(&lines[1], "-->").foreground, Color::Blue);
+    assert!(!find(&lines[1], "src").attribute.has(Attribute::Bold));
+    assert_eq!(find(&lines[2], "Compiling").foreground, Color::DarkGreen);
+    assert_eq!(find(&lines[2], "rust").foreground, Color::Reset);
+}
+
+#[test]
+fn grep_matches() {
+    let lines = parse(concat!(
+        "\x1b[35m\x1b[Ksrc/main.rs\x1b[m\x1b[K\x1b[36m\x1b[K:\x1b[m\x1b[K",
+        "\x1b[32m\x1b[K12\x1b[m\x1b[K\x1b[36m\x1b[K:\x1b[m\x1b[Kfn ",
+        "\x1b[01;31m\x1b[Kmain\x1b[m\x1b[K() {\n",
+    ));
+    let line = &lines[0];
+    assert_eq!(
+        line.iter().map(|ch| ch.ch).collect::<String>(),
+        "src/main.rs:12:fn main() {"
+    );
+    assert_eq!(find(line, "src").foreground, Color::DarkMagenta);
+    assert_eq!(find(line, ":").foreground, Color::DarkCyan);
+    assert_eq!(find(line, "12").foreground, Color::DarkGreen);
+    assert_eq!(find(line, "fn").foreground, Color::Reset);
+    assert_eq!(find(line, "main(").foreground, Color::DarkRed);
+    assert!(find(line, "main(").attribute.has(Attribute::Bold));
+    assert_eq!(find(line, "()").foreground, Color::Reset);
+}
+
+#[test]
+fn syntax_highlighters_in_truecolor() {
+    // bat and delta
+    let lines = parse(concat!(
+        "\x1b[48;2;63;0;1m\x1b[38;2;249;38;114mlet\x1b[0m\x1b[48;2;63;0;1m\x1b[38;2;248;248;242m x\x1b[0m\n",
+        "\x1b[38:2::102:217:239mfn\x1b[39m plain\n",
+    ));
+    let keyword = find(&lines[0], "let");
+    assert_eq!(keyword.foreground, rgb(249, 38, 114));
+    assert_eq!(keyword.background, rgb(63, 0, 1));
+    assert_eq!(find(&lines[0], "x").foreground, rgb(248, 248, 242));
+    assert_eq!(find(&lines[1], "fn").foreground, rgb(102, 217, 239));
+    assert_eq!(find(&lines[1], "plain").foreground, Color::Reset);
+}